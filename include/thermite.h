@@ -0,0 +1,101 @@
+#ifndef THERMITE_H
+#define THERMITE_H
+
+/* Generated with cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define THERMITE_OK 0
+
+#define THERMITE_ERROR -1
+
+#define THERMITE_CANCELLED -2
+
+typedef struct ThermiteModManager ThermiteModManager;
+
+/**
+ * Called with the bytes downloaded so far and the total size, which is 0 if unknown
+ */
+typedef void (*ThermiteProgressCallback)(uint64_t current, uint64_t total, void *user_data);
+
+/**
+ * Called with a human-readable status line, only valid for the duration of the call
+ */
+typedef void (*ThermiteStatusCallback)(const char *line, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the message of the last error on this thread, or NULL if there wasn't one
+ *
+ * The string is owned by thermite and valid until the next failing call on the same thread
+ */
+const char *thermite_last_error(void);
+
+/**
+ * Frees a string returned by thermite
+ */
+void thermite_string_free(char *s);
+
+/**
+ * Sets the callback that receives status lines from every operation. Passing NULL removes it
+ */
+void thermite_set_status_callback(ThermiteStatusCallback cb, void *user_data);
+
+/**
+ * Fetches the package index as a JSON array of mods, or NULL on error
+ */
+char *thermite_get_package_index_json(void);
+
+/**
+ * Downloads `url` to the file at `output_path`, calling `progress` after every chunk if it isn't NULL
+ */
+int thermite_download(const char *url,
+                      const char *output_path,
+                      ThermiteProgressCallback progress,
+                      void *user_data);
+
+/**
+ * Installs the mod archive at `zip_path` into `target_dir` as `mod_string` (`author-name-X.Y.Z`)
+ */
+int thermite_install_mod(const char *mod_string, const char *zip_path, const char *target_dir);
+
+/**
+ * Creates a [`ModManager`] for `profile` of the game at `game_dir`, or NULL on error
+ *
+ * `profile` may be NULL to use the default profile. Free the manager with [`thermite_manager_free`]
+ */
+ThermiteModManager *thermite_manager_new(const char *game_dir, const char *profile);
+
+void thermite_manager_free(ThermiteModManager *manager);
+
+/**
+ * Installs a package (`author-name` or `author-name-X.Y.Z`) and its dependencies
+ */
+int thermite_manager_install(ThermiteModManager *manager, const char *name);
+
+/**
+ * Removes every installed version of a package
+ */
+int thermite_manager_remove(ThermiteModManager *manager, const char *name);
+
+/**
+ * Returns the available updates as a JSON array of `{name, installed, latest}`, or NULL on error
+ */
+char *thermite_manager_check_updates_json(ThermiteModManager *manager);
+
+/**
+ * Updates every outdated package
+ */
+int thermite_manager_update_all(ThermiteModManager *manager);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* THERMITE_H */
@@ -0,0 +1,254 @@
+//! Community-maintained lists of known mod conflicts and broken versions
+//!
+//! A list is a JSON document like:
+//!
+//! ```json
+//! {
+//!     "conflicts": [
+//!         { "packages": ["Foo-Hud", "Bar-Hud"], "reason": "Both replace the same HUD scripts" }
+//!     ],
+//!     "broken": [
+//!         { "package": "Foo-Bar", "versions": ["1.0.0"], "reason": "Crashes on map load", "fixed_in": "1.0.1" }
+//!     ]
+//! }
+//! ```
+//!
+//! Checking an install against it lets launchers warn about bad combinations before they break in game.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    cancel, config,
+    error::ThermiteError,
+    http::{self, HttpRequest},
+    model::InstalledMod,
+    time::Instant,
+};
+
+/// Packages that don't work together
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Thunderstore `author-name` of every package involved
+    pub packages: Vec<String>,
+    pub reason: String,
+}
+
+/// Versions of a package that are known not to work
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BrokenVersions {
+    /// Thunderstore `author-name` of the package
+    pub package: String,
+    pub versions: Vec<String>,
+    pub reason: String,
+    #[serde(default)]
+    pub fixed_in: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatibilityList {
+    #[serde(default)]
+    pub conflicts: Vec<Conflict>,
+    #[serde(default)]
+    pub broken: Vec<BrokenVersions>,
+}
+
+/// A problem [`CompatibilityList::check`] found in an install
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// All of `packages` are installed
+    Conflict {
+        packages: Vec<String>,
+        reason: String,
+    },
+    /// `version` of `package` is installed
+    Broken {
+        package: String,
+        version: String,
+        reason: String,
+        fixed_in: Option<String>,
+    },
+}
+
+impl Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Conflict { packages, reason } => {
+                write!(f, "{} don't work together: {reason}", packages.join(", "))
+            }
+            Self::Broken {
+                package,
+                version,
+                reason,
+                fixed_in,
+            } => {
+                write!(f, "{package} {version} is broken: {reason}")?;
+                if let Some(fixed) = fixed_in {
+                    write!(f, ", update to {fixed}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CompatibilityList {
+    /// Finds every known conflict and broken version among `installed`
+    #[must_use]
+    pub fn check(&self, installed: &[InstalledMod]) -> Vec<Incompatibility> {
+        // packages with several mods show up once per mod
+        let packages = installed
+            .iter()
+            .map(|m| {
+                (
+                    format!("{}-{}", m.author, m.manifest.name).to_lowercase(),
+                    m.manifest.version_number.as_str(),
+                )
+            })
+            .collect::<BTreeSet<_>>();
+        let is_installed = |package: &str| {
+            packages
+                .iter()
+                .any(|(p, _)| p.eq_ignore_ascii_case(package))
+        };
+
+        let mut found = vec![];
+        for conflict in &self.conflicts {
+            if conflict.packages.len() > 1 && conflict.packages.iter().all(|p| is_installed(p)) {
+                found.push(Incompatibility::Conflict {
+                    packages: conflict.packages.clone(),
+                    reason: conflict.reason.clone(),
+                });
+            }
+        }
+        for broken in &self.broken {
+            for (package, version) in &packages {
+                if package.eq_ignore_ascii_case(&broken.package)
+                    && broken.versions.iter().any(|v| v == version)
+                {
+                    found.push(Incompatibility::Broken {
+                        package: broken.package.clone(),
+                        version: (*version).to_owned(),
+                        reason: broken.reason.clone(),
+                        fixed_in: broken.fixed_in.clone(),
+                    });
+                }
+            }
+        }
+
+        for problem in &found {
+            warn!("{problem}");
+        }
+        found
+    }
+}
+
+/// Checks `installed` against the list at the global config's `compatibility_url`
+///
+/// Finds nothing if no URL is configured.
+///
+/// # Errors
+/// * IO and network errors
+/// * Unexpected response format
+pub fn check_compatibility(
+    installed: &[InstalledMod],
+) -> Result<Vec<Incompatibility>, ThermiteError> {
+    Ok(get_compatibility_list()?.check(installed))
+}
+
+/// Fetches the list from the global config's `compatibility_url`, or returns an empty list if there isn't one
+///
+/// # Errors
+/// * IO and network errors
+/// * Unexpected response format
+pub fn get_compatibility_list() -> Result<CompatibilityList, ThermiteError> {
+    let config = config::config();
+    match &config.compatibility_url {
+        Some(url) => fetch_compatibility_list(url, config.timeout),
+        None => Ok(CompatibilityList::default()),
+    }
+}
+
+pub(crate) fn fetch_compatibility_list(
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<CompatibilityList, ThermiteError> {
+    const OPERATION: &str = "fetching the compatibility list";
+    cancel::checkpoint(OPERATION)?;
+    let started = Instant::now();
+    let req = HttpRequest::get(url).timeout(timeout);
+    let body = http::get(&req, OPERATION)?
+        .into_string()
+        .map_err(|e| ThermiteError::from_io(e, OPERATION, started, timeout))?;
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{
+        core::{find_mods, manage::install_mod, utils::TempDir},
+        test_util::{mod_archive, MockServer},
+    };
+
+    use super::{fetch_compatibility_list, Incompatibility};
+
+    const LIST: &str = r#"{
+        "conflicts": [
+            { "packages": ["Foo-Hud", "Bar-Hud"], "reason": "Both replace the HUD" },
+            { "packages": ["Foo-Hud", "Baz-Hud"], "reason": "Baz isn't installed" }
+        ],
+        "broken": [
+            { "package": "foo-hud", "versions": ["1.0.0"], "reason": "Crashes", "fixed_in": "1.0.1" },
+            { "package": "Bar-Hud", "versions": ["0.9.0"], "reason": "Old" }
+        ]
+    }"#;
+
+    #[test]
+    fn check_installed() {
+        let server = MockServer::start().expect("start mock server");
+        server.serve("/compat.json", LIST);
+        let list = fetch_compatibility_list(&format!("{}/compat.json", server.url()), None)
+            .expect("compatibility list");
+
+        let dir = TempDir::create("./test_compatibility").expect("Unable to create temp dir");
+        for (author, version) in [("Foo", "1.0.0"), ("Bar", "1.0.0")] {
+            install_mod(
+                format!("{author}-Hud-{version}"),
+                Cursor::new(mod_archive("Hud", version)),
+                &dir,
+            )
+            .expect("install");
+        }
+        let installed = find_mods(&dir).unwrap();
+
+        let found = list.check(&installed);
+        assert_eq!(
+            found,
+            [
+                Incompatibility::Conflict {
+                    packages: vec!["Foo-Hud".into(), "Bar-Hud".into()],
+                    reason: "Both replace the HUD".into(),
+                },
+                Incompatibility::Broken {
+                    package: "foo-hud".into(),
+                    version: "1.0.0".into(),
+                    reason: "Crashes".into(),
+                    fixed_in: Some("1.0.1".into()),
+                }
+            ]
+        );
+        assert_eq!(
+            found[1].to_string(),
+            "foo-hud 1.0.0 is broken: Crashes, update to 1.0.1"
+        );
+    }
+}
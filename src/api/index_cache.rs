@@ -0,0 +1,225 @@
+//! Cache of the package index on disk
+//!
+//! The index is stored as `<dir>/index.json`, exactly as the server sent it, next to `<dir>/meta.json` which
+//! records the URL it came from and the `ETag` and `Last-Modified` headers it was sent with. Later fetches
+//! send those back, so an unchanged index costs a `304 Not Modified` instead of a full download.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    cancel,
+    config::{self, ThermiteConfig},
+    error::ThermiteError,
+    game::GameSpec,
+    http::{self, HttpRequest},
+    model::Mod,
+    time::Instant,
+};
+
+const INDEX_FILE: &str = "index.json";
+const META_FILE: &str = "meta.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+struct CacheMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// A package index parsed from the cache, and the header values it was stored with
+#[derive(Debug, Clone)]
+struct Parsed {
+    meta: CacheMeta,
+    index: Vec<Mod>,
+}
+
+#[derive(Debug)]
+pub struct IndexCache {
+    dir: PathBuf,
+    /// The last index returned, so an unchanged index isn't parsed again
+    parsed: Mutex<Option<Parsed>>,
+}
+
+impl IndexCache {
+    /// Uses `dir` directly to store the index
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            parsed: Mutex::new(None),
+        }
+    }
+
+    /// The index cache inside `config.cache_dir`, if caching is enabled
+    #[must_use]
+    pub fn from_config(config: &ThermiteConfig) -> Option<Self> {
+        config
+            .cache_dir
+            .as_ref()
+            .map(|dir| Self::new(dir.join("index")))
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Fetches the index from the global config's `index_url`, only downloading it if it changed since it was
+    /// cached
+    ///
+    /// # Errors
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn get_index(&self) -> Result<Vec<Mod>, ThermiteError> {
+        let config = config::config();
+        self.fetch(&config.index_url, config.timeout, &config.game)
+    }
+
+    /// Removes the cached index
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn clear(&self) -> Result<(), ThermiteError> {
+        *self.lock() = None;
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn fetch(
+        &self,
+        url: &str,
+        timeout: Option<Duration>,
+        game: &GameSpec,
+    ) -> Result<Vec<Mod>, ThermiteError> {
+        const OPERATION: &str = "fetching the package index";
+        cancel::checkpoint(OPERATION)?;
+        let started = Instant::now();
+
+        let cached = self.cached(url, game);
+        let mut req = HttpRequest::get(url)
+            .header("accept", "application/json")
+            .timeout(timeout);
+        if let Some(Parsed { meta, .. }) = &cached {
+            if let Some(etag) = &meta.etag {
+                req = req.header("If-None-Match", etag);
+            }
+            if let Some(modified) = &meta.last_modified {
+                req = req.header("If-Modified-Since", modified);
+            }
+        }
+
+        let res = match (http::get(&req, OPERATION), cached) {
+            (Err(ThermiteError::HttpStatus { status: 304, .. }), Some(cached)) => {
+                debug!("Package index at {url} hasn't changed");
+                let index = cached.index.clone();
+                *self.lock() = Some(cached);
+                return Ok(index);
+            }
+            (res, _) => res?,
+        };
+
+        let meta = CacheMeta {
+            url: url.into(),
+            etag: res.header("ETag").map(Into::into),
+            last_modified: res.header("Last-Modified").map(Into::into),
+        };
+        let body = res
+            .into_string()
+            .map_err(|e| ThermiteError::from_io(e, OPERATION, started, timeout))?;
+        cancel::checkpoint(OPERATION)?;
+        let index = super::parse_index(body.as_bytes(), game)?;
+
+        if let Err(e) = self.store(&body, &meta) {
+            warn!("Unable to cache the package index: {e}");
+        }
+        *self.lock() = Some(Parsed {
+            meta,
+            index: index.clone(),
+        });
+
+        Ok(index)
+    }
+
+    /// The cached index for `url`, from memory if it was parsed already
+    fn cached(&self, url: &str, game: &GameSpec) -> Option<Parsed> {
+        if let Some(parsed) = self.lock().as_ref().filter(|p| p.meta.url == url) {
+            return Some(parsed.clone());
+        }
+
+        let meta: CacheMeta = serde_json::from_slice(&fs::read(self.dir.join(META_FILE)).ok()?)
+            .inspect_err(|e| warn!("Ignoring invalid package index cache: {e}"))
+            .ok()?;
+        if meta.url != url {
+            return None;
+        }
+        let body = fs::read_to_string(self.dir.join(INDEX_FILE)).ok()?;
+        let index = super::parse_index(body.as_bytes(), game)
+            .inspect_err(|e| warn!("Ignoring invalid package index cache: {e}"))
+            .ok()?;
+        Some(Parsed { meta, index })
+    }
+
+    fn store(&self, body: &str, meta: &CacheMeta) -> Result<(), ThermiteError> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(INDEX_FILE), body)?;
+        fs::write(self.dir.join(META_FILE), serde_json::to_vec(meta)?)?;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Parsed>> {
+        self.parsed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::utils::TempDir,
+        test_util::{MockPackage, MockServer},
+    };
+
+    use super::IndexCache;
+
+    #[test]
+    fn conditional_requests() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        let dir = TempDir::create("./test_index_cache").expect("Unable to create temp dir");
+        let fetch = |cache: &IndexCache| {
+            cache
+                .fetch(&server.index_url(), None, &Default::default())
+                .expect("index")
+        };
+
+        let cache = IndexCache::new(dir.join("index"));
+        assert_eq!(fetch(&cache).len(), 1);
+        assert!(!server.requests()[0].headers.contains_key("if-none-match"));
+
+        // a new cache sends the stored ETag and reads the unchanged index from disk
+        let stored = dir.join("index/index.json");
+        let edited = std::fs::read_to_string(&stored)
+            .unwrap()
+            .replace("\"Bar\"", "\"Cached\"");
+        std::fs::write(&stored, edited).unwrap();
+        let cache = IndexCache::new(dir.join("index"));
+        assert_eq!(fetch(&cache)[0].name, "Cached");
+        assert!(server.requests()[1].headers.contains_key("if-none-match"));
+
+        server.add_package(MockPackage::new("Foo", "Baz", "1.0.0"));
+        assert_eq!(fetch(&cache).len(), 2);
+        assert_eq!(fetch(&cache).len(), 2);
+
+        cache.clear().expect("clear");
+        assert!(!dir.join("index").exists());
+    }
+}
@@ -0,0 +1,326 @@
+//! Packages from a directory instead of Thunderstore, for installing mods without a network connection
+//!
+//! A repository is a directory of package archives named `author-name-X.Y.Z.zip` next to a `repository.json`
+//! listing them. [`LocalRepository::add`] keeps both up to date, [`LocalRepository::rebuild`] writes the
+//! listing for archives that were copied in by hand.
+
+use std::{
+    collections::BTreeMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use zip::ZipArchive;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::verify;
+use crate::{
+    config,
+    core::{
+        manage::install_from_source,
+        report::InstallReport,
+        utils::{is_newer, parse_modstring},
+        vfs,
+    },
+    error::ThermiteError,
+    model::{Manifest, Mod, ModVersion},
+};
+
+const MANIFEST_FILE: &str = "repository.json";
+
+/// The contents of `repository.json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepositoryManifest {
+    /// Packages keyed by `author-name-X.Y.Z`
+    pub packages: BTreeMap<String, RepositoryPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepositoryPackage {
+    /// Path of the archive, relative to the repository
+    pub file: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub file_size: u64,
+    /// Hex encoded SHA-256 of the archive
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalRepository {
+    dir: PathBuf,
+    manifest: RepositoryManifest,
+}
+
+impl LocalRepository {
+    /// Opens the repository in `dir`, which is empty if it doesn't have a `repository.json` yet
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * `repository.json` isn't formatted properly
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, ThermiteError> {
+        let dir = dir.into();
+        let fs = vfs::current();
+        let path = dir.join(MANIFEST_FILE);
+        let manifest = if fs.exists(&path)? {
+            serde_json::from_str(&fs.read_to_string(&path)?)
+                .map_err(|e| ThermiteError::invalid_json(&path, e))?
+        } else {
+            RepositoryManifest::default()
+        };
+        Ok(Self { dir, manifest })
+    }
+
+    /// Lists every `author-name-X.Y.Z.zip` in `dir`, replacing its `repository.json`
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * An archive isn't a package
+    pub fn rebuild(dir: impl Into<PathBuf>) -> Result<Self, ThermiteError> {
+        let mut repo = Self {
+            dir: dir.into(),
+            manifest: RepositoryManifest::default(),
+        };
+        let fs = vfs::current();
+        for entry in fs.read_dir(&repo.dir)? {
+            let Some(full_name) = entry.file_name().strip_suffix(".zip") else {
+                continue;
+            };
+            if entry.is_dir || parse_modstring(full_name).is_err() {
+                debug!("Skipping {}", entry.path.display());
+                continue;
+            }
+            let archive = fs.read(&entry.path)?;
+            let package = describe(entry.file_name(), &archive)?;
+            repo.manifest.packages.insert(full_name.to_owned(), package);
+        }
+        repo.save()?;
+        Ok(repo)
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    #[must_use]
+    pub const fn manifest(&self) -> &RepositoryManifest {
+        &self.manifest
+    }
+
+    /// Stores the archive of `full_name`, e.g. one downloaded on a machine with a network connection
+    ///
+    /// # Errors
+    /// * `ThermiteError::NameError` if `full_name` isn't `author-name-X.Y.Z`
+    /// * The archive isn't a package
+    /// * IO errors
+    pub fn add(&mut self, full_name: impl AsRef<str>, archive: &[u8]) -> Result<(), ThermiteError> {
+        let full_name = full_name.as_ref();
+        parse_modstring(full_name)?;
+        let file = format!("{full_name}.zip");
+        let package = describe(&file, archive)?;
+
+        let fs = vfs::current();
+        fs.create_dir_all(&self.dir)?;
+        fs.write_atomic(&self.dir.join(&file), archive)?;
+        self.manifest.packages.insert(full_name.to_owned(), package);
+        self.save()
+    }
+
+    /// Every package in the repository in the same form as the Thunderstore index, so it can be used with
+    /// [`resolve_deps`](crate::core::utils::resolve_deps) and the like
+    ///
+    /// Dependencies on the global config's loader package are left out, like they are from the index. The
+    /// `url` of each version is the path of its archive.
+    #[must_use]
+    pub fn index(&self) -> Vec<Mod> {
+        let game = config::config().game.clone();
+        let mut index: BTreeMap<(String, String), Mod> = BTreeMap::new();
+        for (full_name, package) in &self.manifest.packages {
+            let Ok((author, name, version)) = parse_modstring(full_name) else {
+                continue;
+            };
+            let entry = index
+                .entry((author.clone(), name.clone()))
+                .or_insert_with(|| Mod {
+                    name: name.clone(),
+                    latest: version.clone(),
+                    installed: false,
+                    upgradable: false,
+                    global: false,
+                    versions: BTreeMap::new(),
+                    author,
+                    ..Default::default()
+                });
+            if is_newer(&version, &entry.latest) {
+                entry.latest.clone_from(&version);
+            }
+            entry.versions.insert(
+                version.as_str().into(),
+                ModVersion {
+                    name,
+                    full_name: full_name.clone(),
+                    version,
+                    url: self.dir.join(&package.file).display().to_string(),
+                    desc: package.description.clone(),
+                    deps: package
+                        .dependencies
+                        .iter()
+                        .filter(|d| !game.is_loader_package(d))
+                        .cloned()
+                        .collect(),
+                    installed: false,
+                    global: false,
+                    file_size: package.file_size,
+                    sha256: package.sha256.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+        index.into_values().collect()
+    }
+
+    /// Reads the archive of `version`, checking it against the checksum it was stored with
+    ///
+    /// # Errors
+    /// * `ThermiteError::DepError` if the repository doesn't have the version
+    /// * `ThermiteError::ChecksumMismatch` if the archive changed since it was added
+    /// * IO errors
+    pub fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>, ThermiteError> {
+        let package = self
+            .manifest
+            .packages
+            .get(&version.full_name)
+            .ok_or_else(|| ThermiteError::DepError {
+                name: version.full_name.clone(),
+                suggestions: vec![],
+            })?;
+        let path = self.dir.join(&package.file);
+        let archive = vfs::current().read(&path)?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(expected) = &package.sha256 {
+            let actual = verify::encode_hash(&verify::sha256(&archive));
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(ThermiteError::ChecksumMismatch {
+                    url: path.display().to_string(),
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+
+        Ok(archive)
+    }
+
+    /// Installs a package and its dependencies from the repository to `target_dir`, see
+    /// [`install_from_source`]
+    ///
+    /// # Errors
+    /// * `ThermiteError::DepError` if the package or one of its dependencies isn't in the repository
+    /// * IO errors
+    /// * Misformatted mod files
+    pub fn install(
+        &self,
+        name: impl AsRef<str>,
+        target_dir: impl AsRef<Path>,
+    ) -> Result<Vec<InstallReport>, ThermiteError> {
+        install_from_source(self, name, target_dir)
+    }
+
+    fn save(&self) -> Result<(), ThermiteError> {
+        let fs = vfs::current();
+        fs.create_dir_all(&self.dir)?;
+        fs.write_atomic(
+            &self.dir.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&self.manifest)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+/// The repository entry for an archive stored as `file`
+fn describe(file: &str, archive: &[u8]) -> Result<RepositoryPackage, ThermiteError> {
+    let mut zip = ZipArchive::new(Cursor::new(archive))?;
+    let manifest: Manifest = match zip.by_name("manifest.json") {
+        Ok(raw) => serde_json::from_reader(raw)?,
+        Err(_) => return Err(ThermiteError::MissingFile(Box::new(file.into()))),
+    };
+
+    Ok(RepositoryPackage {
+        file: file.to_owned(),
+        description: manifest.description,
+        dependencies: manifest.dependencies,
+        file_size: archive.len() as u64,
+        #[cfg(not(target_arch = "wasm32"))]
+        sha256: Some(verify::encode_hash(&verify::sha256(archive))),
+        #[cfg(target_arch = "wasm32")]
+        sha256: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::utils::{resolve_deps, TempDir},
+        error::ThermiteError,
+        test_util::mod_archive,
+    };
+
+    use super::LocalRepository;
+
+    #[test]
+    fn install_offline() {
+        let dir = TempDir::create("./test_local_repository").expect("Unable to create temp dir");
+        let repo_dir = dir.join("repo");
+        let mut repo = LocalRepository::open(&repo_dir).expect("open");
+        assert!(repo.index().is_empty());
+        repo.add("Foo-Dep-1.0.0", &mod_archive("Dep", "1.0.0"))
+            .expect("add");
+        repo.add("Foo-Dep-1.1.0", &mod_archive("Dep", "1.1.0"))
+            .expect("add");
+        std::fs::write(repo_dir.join("notes.txt"), "not a package").unwrap();
+
+        // packages copied in by hand are picked up by a rebuild
+        std::fs::write(
+            repo_dir.join("Foo-Bar-1.0.0.zip"),
+            mod_archive("Bar", "1.0.0"),
+        )
+        .unwrap();
+        assert_eq!(LocalRepository::open(&repo_dir).unwrap().index().len(), 1);
+        let repo = LocalRepository::rebuild(&repo_dir).expect("rebuild");
+        let index = repo.index();
+        assert_eq!(index.len(), 2);
+        let dep = index.iter().find(|m| m.name == "Dep").unwrap();
+        assert_eq!(dep.latest, "1.1.0");
+        assert_eq!(
+            resolve_deps(&["Foo-Dep-1.0.0"], &index).unwrap()[0].name,
+            "Dep"
+        );
+
+        let packages = dir.join("packages");
+        let reports = repo.install("Foo-Dep", &packages).expect("install");
+        assert_eq!(reports[0].name, "Foo-Dep-1.1.0");
+        assert!(packages.join("Foo-Dep-1.1.0/manifest.json").exists());
+        assert!(matches!(
+            repo.install("Foo-Missing", &packages),
+            Err(ThermiteError::DepError { .. })
+        ));
+
+        std::fs::write(repo_dir.join("Foo-Bar-1.0.0.zip"), "tampered").unwrap();
+        let err = repo
+            .install("Foo-Bar-1.0.0", &packages)
+            .expect_err("tampered archive");
+        assert!(matches!(err.root(), ThermiteError::ChecksumMismatch { .. }));
+        assert_eq!(
+            err.context().and_then(|c| c.package.as_deref()),
+            Some("Foo-Bar-1.0.0")
+        );
+    }
+}
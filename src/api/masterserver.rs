@@ -0,0 +1,323 @@
+//! Client for the Northstar master server's public server list
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    cancel, config,
+    core::utils::find_mods,
+    error::ThermiteError,
+    http::{self, HttpRequest},
+    model::{Mod, ModVersion},
+    time::Instant,
+};
+
+/// A server listed on the master server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Server {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub map: String,
+    /// The game mode, e.g. `aitdm`
+    pub playlist: String,
+    #[serde(default)]
+    pub region: Option<String>,
+    pub player_count: u32,
+    pub max_players: u32,
+    #[serde(default)]
+    pub has_password: bool,
+    #[serde(default)]
+    pub mod_info: ModInfo,
+    #[serde(flatten)]
+    pub _extra: HashMap<String, Value>,
+}
+
+impl Server {
+    /// The mods a client needs to join this server
+    pub fn required_mods(&self) -> impl Iterator<Item = &ServerMod> {
+        self.mod_info.mods.iter().filter(|m| m.required_on_client)
+    }
+
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.player_count >= self.max_players
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModInfo {
+    #[serde(rename = "Mods", default)]
+    pub mods: Vec<ServerMod>,
+}
+
+/// A mod running on a server, named as in its `mod.json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServerMod {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub required_on_client: bool,
+}
+
+/// Fetches the public server list from the master server in the global config
+///
+/// # Errors
+/// * IO and network errors
+/// * Unexpected response format from the master server
+pub fn get_servers() -> Result<Vec<Server>, ThermiteError> {
+    let config = config::config();
+    fetch_servers(&config.masterserver_url, config.timeout)
+}
+
+pub(crate) fn fetch_servers(
+    base_url: &str,
+    timeout: Option<Duration>,
+) -> Result<Vec<Server>, ThermiteError> {
+    const OPERATION: &str = "fetching the server list";
+    cancel::checkpoint(OPERATION)?;
+    let started = Instant::now();
+    let url = format!("{}/client/servers", base_url.trim_end_matches('/'));
+    let req = HttpRequest::get(url)
+        .header("accept", "application/json")
+        .timeout(timeout);
+    let body = http::get(&req, OPERATION)?
+        .into_string()
+        .map_err(|e| ThermiteError::from_io(e, OPERATION, started, timeout))?;
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Something that has to change locally before a server can be joined
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncStep {
+    /// The mod isn't installed, installing `package` provides it
+    Install {
+        required: ServerMod,
+        package: ModVersion,
+    },
+    /// The mod is installed at a different version than the server's
+    Update {
+        required: ServerMod,
+        installed: String,
+        package: ModVersion,
+    },
+    /// The mod isn't installed and no package providing it was found in the index
+    Missing(ServerMod),
+}
+
+/// The steps needed to make the local install compatible with a server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncPlan {
+    pub steps: Vec<SyncStep>,
+}
+
+impl SyncPlan {
+    /// Returns `true` if the server can be joined without changes
+    #[must_use]
+    pub fn is_compatible(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// The packages to install for every step that can be done automatically
+    pub fn packages(&self) -> impl Iterator<Item = &ModVersion> {
+        self.steps.iter().filter_map(|s| match s {
+            SyncStep::Install { package, .. } | SyncStep::Update { package, .. } => Some(package),
+            SyncStep::Missing(_) => None,
+        })
+    }
+}
+
+/// Compares the mods `server` requires with the ones installed in `packages_dir`
+///
+/// Core mods are skipped since they come with Northstar itself. Packages are looked up in `index`, preferring the
+/// exact version the server runs. Mods that aren't installed are matched to packages by name, so a mod called
+/// `Author.ModName` is expected to be published as `ModName`.
+///
+/// # Errors
+/// * IO errors while searching `packages_dir`
+/// * Improperly formatted JSON files
+pub fn plan_sync(
+    server: &Server,
+    packages_dir: impl AsRef<Path>,
+    index: &[Mod],
+) -> Result<SyncPlan, ThermiteError> {
+    let installed = find_mods(packages_dir)?;
+    let config = config::config();
+
+    let mut plan = SyncPlan::default();
+    for required in server.required_mods() {
+        if config.game.is_core_mod(&required.name) {
+            continue;
+        }
+
+        let local = installed
+            .iter()
+            .find(|m| m.mod_json.name.eq_ignore_ascii_case(&required.name));
+        let step = match local {
+            Some(m) if m.mod_json.version == required.version => continue,
+            Some(m) => index
+                .iter()
+                .find(|p| p.author == m.author && p.name == m.manifest.name)
+                .and_then(|p| pick_version(p, &required.version))
+                .map_or_else(
+                    || SyncStep::Missing(required.clone()),
+                    |package| SyncStep::Update {
+                        required: required.clone(),
+                        installed: m.mod_json.version.clone(),
+                        package,
+                    },
+                ),
+            None => index
+                .iter()
+                .find(|p| provides(p, &required.name))
+                .and_then(|p| pick_version(p, &required.version))
+                .map_or_else(
+                    || SyncStep::Missing(required.clone()),
+                    |package| SyncStep::Install {
+                        required: required.clone(),
+                        package,
+                    },
+                ),
+        };
+        plan.steps.push(step);
+    }
+
+    Ok(plan)
+}
+
+/// The package version matching `version`, or the latest one if the index doesn't have it
+fn pick_version(package: &Mod, version: &str) -> Option<ModVersion> {
+    package
+        .get_version(version)
+        .or_else(|| package.get_latest())
+        .cloned()
+}
+
+/// Guesses if `package` provides the mod called `mod_name`
+fn provides(package: &Mod, mod_name: &str) -> bool {
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let package_name = normalize(&package.name);
+    let last = mod_name.rsplit('.').next().unwrap_or(mod_name);
+
+    package_name == normalize(mod_name) || package_name == normalize(last)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{
+        api::fetch_index,
+        core::{manage::install_mod, utils::TempDir},
+        game::GameSpec,
+        test_util::{mod_archive, MockPackage, MockServer},
+    };
+
+    use super::{fetch_servers, plan_sync, ModInfo, Server, ServerMod, SyncStep};
+
+    const SERVERS: &str = r#"[
+        {
+            "lastHeartbeat": 1700000000000,
+            "id": "1234",
+            "name": "Test server",
+            "description": "For testing",
+            "playerCount": 4,
+            "maxPlayers": 16,
+            "map": "mp_forwardbase_kodai",
+            "playlist": "aitdm",
+            "region": "EU",
+            "hasPassword": false,
+            "modInfo": {
+                "Mods": [
+                    { "Name": "Northstar.Custom", "Version": "1.20.0", "RequiredOnClient": true, "Pdiff": "" },
+                    { "Name": "Server.Utils", "Version": "0.1.0", "RequiredOnClient": false, "Pdiff": "" }
+                ]
+            }
+        }
+    ]"#;
+
+    #[test]
+    fn get_servers_from_mock() {
+        let server = MockServer::start().expect("start mock server");
+        server.serve("/client/servers", SERVERS);
+
+        let servers = fetch_servers(&server.url(), None).expect("server list");
+        assert_eq!(servers.len(), 1);
+
+        let s = &servers[0];
+        assert_eq!(s.name, "Test server");
+        assert_eq!((s.player_count, s.max_players), (4, 16));
+        assert!(!s.is_full());
+        assert_eq!(
+            s.required_mods()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Northstar.Custom"]
+        );
+    }
+
+    #[test]
+    fn plan_required_mods() {
+        let mock = MockServer::start().expect("start mock server");
+        mock.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        mock.add_package(MockPackage::new("Foo", "Bar", "1.1.0"));
+        mock.add_package(MockPackage::new("Foo", "Baz", "0.1.0"));
+        let index = fetch_index(&mock.index_url(), None, &GameSpec::default()).expect("index");
+
+        let dir = TempDir::create("./test_plan_sync").expect("Unable to create temp dir");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &dir,
+        )
+        .expect("install");
+
+        let required = |name: &str, version: &str| ServerMod {
+            name: name.into(),
+            version: version.into(),
+            required_on_client: true,
+        };
+        let mut server = serde_json::from_str::<Vec<Server>>(SERVERS)
+            .unwrap()
+            .remove(0);
+        server.mod_info = ModInfo {
+            mods: vec![
+                required("Northstar.Custom", "1.20.0"),
+                required("Mock.Bar", "1.1.0"),
+                required("Mock.Baz", "0.1.0"),
+                required("Other.Thing", "1.0.0"),
+            ],
+        };
+
+        let plan = plan_sync(&server, &dir, &index).expect("plan");
+        assert!(!plan.is_compatible());
+        assert!(
+            matches!(
+                &plan.steps[..],
+                [
+                    SyncStep::Update { installed, package, .. },
+                    SyncStep::Install { .. },
+                    SyncStep::Missing(missing),
+                ] if installed == "1.0.0" && package.version == "1.1.0" && missing.name == "Other.Thing"
+            ),
+            "{plan:#?}"
+        );
+        assert_eq!(
+            plan.packages()
+                .map(|p| p.full_name.as_str())
+                .collect::<Vec<_>>(),
+            ["Foo-Bar-1.1.0", "Foo-Baz-0.1.0"]
+        );
+    }
+}
@@ -0,0 +1,823 @@
+pub mod compat;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod index_cache;
+pub mod local;
+pub mod masterserver;
+pub mod search;
+pub mod source;
+pub mod thunderstore;
+pub mod verified;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::BufReader,
+    time::Duration,
+};
+
+use serde::{
+    de::{DeserializeSeed, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use serde_json::Value;
+use tracing::warn;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use index_cache::IndexCache;
+pub use local::LocalRepository;
+pub use search::{search, SearchFilter};
+pub use source::{GithubReleases, PackageSource, Thunderstore};
+pub use thunderstore::ThunderstoreClient;
+
+#[cfg(feature = "async")]
+use crate::blocking;
+use crate::{
+    cancel,
+    config::{self, ThermiteConfig},
+    error::ThermiteError,
+    game::GameSpec,
+    http::{self, HttpRequest},
+    model::{Mod, ModVersion},
+    time::Instant,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct PackageListing {
+    name: String,
+    owner: String,
+    versions: Vec<PackageVersion>,
+    #[serde(default)]
+    categories: Vec<String>,
+    #[serde(default)]
+    has_nsfw_content: bool,
+    #[serde(default)]
+    is_deprecated: bool,
+    #[serde(default)]
+    rating_score: i64,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    package_url: String,
+    #[serde(default)]
+    donation_link: Option<String>,
+    #[serde(default)]
+    date_created: String,
+    #[serde(default)]
+    date_updated: String,
+    #[serde(flatten)]
+    _extra: HashMap<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct PackageVersion {
+    dependencies: Vec<String>,
+    description: String,
+    download_url: String,
+    file_size: u64,
+    version_number: String,
+    full_name: String,
+    #[serde(default)]
+    sha256: Option<String>,
+    #[serde(default)]
+    downloads: u64,
+    #[serde(default)]
+    date_created: String,
+    #[serde(default)]
+    icon: String,
+    #[serde(default)]
+    website_url: String,
+    #[serde(default = "default_active")]
+    is_active: bool,
+
+    #[serde(flatten)]
+    _extra: HashMap<String, Value>,
+}
+
+const fn default_active() -> bool {
+    true
+}
+
+/// A package index and where it was fetched from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcedIndex {
+    /// The config's `index_url` or whichever of its `index_mirrors` worked
+    pub url: String,
+    pub packages: Vec<Mod>,
+}
+
+/// Goes through an [`IndexCache`] in the global config's `cache_dir` if one is set
+///
+/// If the index can't be fetched from the config's `index_url` because of a network error, each of its
+/// `index_mirrors` is tried in turn.
+///
+/// # Errors
+/// * IO Erros
+/// * Unexpected response format from thunderstore
+pub fn get_package_index() -> Result<Vec<Mod>, ThermiteError> {
+    get_package_index_with_source().map(|index| index.packages)
+}
+
+/// Like [`get_package_index`], also reporting which of the config's `index_url` and `index_mirrors` the index
+/// came from
+///
+/// # Errors
+/// * IO Erros
+/// * Unexpected response format from thunderstore
+pub fn get_package_index_with_source() -> Result<SourcedIndex, ThermiteError> {
+    let config = config::config();
+    fetch_index_sourced(&config)
+}
+
+/// Like [`get_package_index`], but only keeps the packages `predicate` returns `true` for
+///
+/// Packages are dropped as soon as they're parsed, so the whole index is never held in memory. This skips the
+/// [`IndexCache`] since it stores the full index.
+///
+/// # Errors
+/// * IO Erros
+/// * Unexpected response format from thunderstore
+pub fn get_package_index_filtered(
+    predicate: impl FnMut(&Mod) -> bool,
+) -> Result<Vec<Mod>, ThermiteError> {
+    let config = config::config();
+    fetch_index_filtered_sourced(&config, predicate).map(|index| index.packages)
+}
+
+/// Async version of [`get_package_index`], fetched with the current
+/// [`AsyncHttpBackend`](crate::http::AsyncHttpBackend)
+///
+/// Like the sync version it tries each of the config's `index_mirrors` when the `index_url` fails with a network
+/// error, but it doesn't go through the [`IndexCache`]. The index is parsed on a background thread so a large one
+/// doesn't hold up the runtime. Dropping the future cancels the request.
+///
+/// # Errors
+/// * IO Erros
+/// * Unexpected response format from thunderstore
+#[cfg(feature = "async")]
+pub async fn get_package_index_async() -> Result<Vec<Mod>, ThermiteError> {
+    let config = config::config();
+    fetch_index_sourced_async(&config)
+        .await
+        .map(|index| index.packages)
+}
+
+/// The package index of any Thunderstore community, for using thermite with other games or against a staging
+/// instance
+///
+/// ```no_run
+/// # use thermite::api::PackageIndex;
+/// let index = PackageIndex::for_community("northstar")
+///     .base_url("https://thunderstore.dev")
+///     .get()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageIndex {
+    base_url: String,
+    community: String,
+    timeout: Option<Duration>,
+    game: GameSpec,
+}
+
+impl PackageIndex {
+    /// The index of `community`, e.g. `northstar`, on the global config's `thunderstore_url`, filtered for the
+    /// global config's game
+    #[must_use]
+    pub fn for_community(community: impl Into<String>) -> Self {
+        let config = config::config();
+        Self {
+            base_url: config.thunderstore_url.clone(),
+            community: community.into(),
+            timeout: config.timeout,
+            game: config.game.clone(),
+        }
+    }
+
+    /// Base URL of the Thunderstore instance, e.g. `https://thunderstore.io`
+    #[must_use]
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Time limit for fetching the index. `None` means no limit
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The game whose loader package is left out of dependency lists
+    #[must_use]
+    pub fn game(mut self, game: GameSpec) -> Self {
+        self.game = game;
+        self
+    }
+
+    #[must_use]
+    pub fn community(&self) -> &str {
+        &self.community
+    }
+
+    /// The URL the index is fetched from, e.g. `https://thunderstore.io/c/northstar/api/v1/package/`
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!(
+            "{}/c/{}/api/v1/package/",
+            self.base_url.trim_end_matches('/'),
+            self.community
+        )
+    }
+
+    /// Fetches the whole index. This skips the [`IndexCache`], which only holds one index
+    ///
+    /// # Errors
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn get(&self) -> Result<Vec<Mod>, ThermiteError> {
+        fetch_index(&self.url(), self.timeout, &self.game)
+    }
+
+    /// Like [`PackageIndex::get`], but only keeps the packages `predicate` returns `true` for, see
+    /// [`get_package_index_filtered`]
+    ///
+    /// # Errors
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn get_filtered(
+        &self,
+        predicate: impl FnMut(&Mod) -> bool,
+    ) -> Result<Vec<Mod>, ThermiteError> {
+        fetch_index_filtered(&self.url(), self.timeout, &self.game, predicate)
+    }
+}
+
+/// The latest version of Northstar, or whichever loader the global config's game uses, on Thunderstore
+///
+/// Compare it with [`northstar_version`](crate::core::northstar_version) to tell whether an update is available.
+///
+/// # Errors
+/// * IO and network errors
+/// * `ThermiteError::DepError` if the index doesn't have the loader's package
+pub fn latest_northstar_version() -> Result<String, ThermiteError> {
+    let config = config::config();
+    latest_loader_version(&config)
+}
+
+pub(crate) fn latest_loader_version(config: &ThermiteConfig) -> Result<String, ThermiteError> {
+    let is_loader = |m: &Mod| {
+        config
+            .game
+            .is_loader_package(format!("{}-{}", m.author, m.name))
+    };
+    // a cached index is cheap to search, otherwise only the loader's listing is kept
+    let index = if config.cache_dir.is_some() {
+        fetch_index_cached(config)?
+    } else {
+        fetch_index_filtered_sourced(config, is_loader)?.packages
+    };
+    index
+        .into_iter()
+        .find(is_loader)
+        .map(|m| m.latest)
+        .ok_or_else(|| ThermiteError::DepError {
+            name: config.game.loader_package.clone(),
+            suggestions: vec![],
+        })
+}
+
+/// Fetches the index from `url`, leaving `game`'s loader package out of every dependency list
+pub(crate) fn fetch_index(
+    url: &str,
+    timeout: Option<Duration>,
+    game: &GameSpec,
+) -> Result<Vec<Mod>, ThermiteError> {
+    const OPERATION: &str = "fetching the package index";
+    cancel::checkpoint(OPERATION)?;
+    let started = Instant::now();
+    let req = HttpRequest::get(url)
+        .header("accept", "application/json")
+        .timeout(timeout);
+    let body = http::get(&req, OPERATION)?
+        .into_string()
+        .map_err(|e| ThermiteError::from_io(e, OPERATION, started, timeout))?;
+    cancel::checkpoint(OPERATION)?;
+    parse_index(body.as_bytes(), game)
+}
+
+/// [`with_mirrors`] for [`fetch_index_async`]
+#[cfg(feature = "async")]
+async fn fetch_index_sourced_async(config: &ThermiteConfig) -> Result<SourcedIndex, ThermiteError> {
+    let sourced = |url: &String, packages| SourcedIndex {
+        url: url.clone(),
+        packages,
+    };
+    let mut res = fetch_index_async(&config.index_url, config.timeout, &config.game)
+        .await
+        .map(|p| sourced(&config.index_url, p));
+    for mirror in &config.index_mirrors {
+        match &res {
+            Err(e) if is_network_error(e) => {
+                warn!("Unable to fetch the package index, trying {mirror}: {e}");
+            }
+            _ => break,
+        }
+        res = fetch_index_async(mirror, config.timeout, &config.game)
+            .await
+            .map(|p| sourced(mirror, p));
+    }
+    res
+}
+
+/// [`fetch_index`] with the current [`AsyncHttpBackend`](crate::http::AsyncHttpBackend)
+#[cfg(feature = "async")]
+async fn fetch_index_async(
+    url: &str,
+    timeout: Option<Duration>,
+    game: &GameSpec,
+) -> Result<Vec<Mod>, ThermiteError> {
+    const OPERATION: &str = "fetching the package index";
+    let started = Instant::now();
+    let req = HttpRequest::get(url)
+        .header("accept", "application/json")
+        .timeout(timeout);
+    let body = http::get_async(&req, OPERATION)
+        .await?
+        .into_bytes()
+        .await
+        .map_err(|e| ThermiteError::from_io(e, OPERATION, started, timeout))?;
+    let game = game.clone();
+    blocking::spawn(move || {
+        cancel::checkpoint(OPERATION)?;
+        parse_index(&body, &game)
+    })
+    .await
+}
+
+pub(crate) fn fetch_index_filtered(
+    url: &str,
+    timeout: Option<Duration>,
+    game: &GameSpec,
+    predicate: impl FnMut(&Mod) -> bool,
+) -> Result<Vec<Mod>, ThermiteError> {
+    const OPERATION: &str = "fetching the package index";
+    cancel::checkpoint(OPERATION)?;
+    let req = HttpRequest::get(url)
+        .header("accept", "application/json")
+        .timeout(timeout);
+    let body = BufReader::new(http::get(&req, OPERATION)?.into_reader());
+
+    let mut de = serde_json::Deserializer::from_reader(body);
+    let index = FilteredIndex { game, predicate }.deserialize(&mut de)?;
+    de.end()?;
+    Ok(index)
+}
+
+/// [`fetch_index`] with `config`'s settings, through its [`IndexCache`] if it has one
+pub(crate) fn fetch_index_cached(config: &ThermiteConfig) -> Result<Vec<Mod>, ThermiteError> {
+    fetch_index_sourced(config).map(|index| index.packages)
+}
+
+fn fetch_index_sourced(config: &ThermiteConfig) -> Result<SourcedIndex, ThermiteError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(cache) = IndexCache::from_config(config) {
+        return with_mirrors(config, |url| cache.fetch(url, config.timeout, &config.game));
+    }
+    with_mirrors(config, |url| fetch_index(url, config.timeout, &config.game))
+}
+
+fn fetch_index_filtered_sourced(
+    config: &ThermiteConfig,
+    mut predicate: impl FnMut(&Mod) -> bool,
+) -> Result<SourcedIndex, ThermiteError> {
+    with_mirrors(config, |url| {
+        fetch_index_filtered(url, config.timeout, &config.game, &mut predicate)
+    })
+}
+
+/// Calls `fetch` with `config.index_url`, then with each of `config.index_mirrors` for as long as it fails with
+/// a network error
+fn with_mirrors(
+    config: &ThermiteConfig,
+    mut fetch: impl FnMut(&str) -> Result<Vec<Mod>, ThermiteError>,
+) -> Result<SourcedIndex, ThermiteError> {
+    let sourced = |url: &String, packages| SourcedIndex {
+        url: url.clone(),
+        packages,
+    };
+    let mut res = fetch(&config.index_url).map(|p| sourced(&config.index_url, p));
+    for mirror in &config.index_mirrors {
+        match &res {
+            Err(e) if is_network_error(e) => {
+                warn!("Unable to fetch the package index, trying {mirror}: {e}");
+            }
+            _ => break,
+        }
+        res = fetch(mirror).map(|p| sourced(mirror, p));
+    }
+    res
+}
+
+/// Errors a mirror might not run into, unlike ones from the response itself
+fn is_network_error(err: &ThermiteError) -> bool {
+    match err {
+        #[cfg(not(target_arch = "wasm32"))]
+        ThermiteError::NetworkError(_) => true,
+        ThermiteError::IoError(_) | ThermiteError::Timeout { .. } => true,
+        ThermiteError::HttpStatus { status, .. } => *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+fn parse_index(body: &[u8], game: &GameSpec) -> Result<Vec<Mod>, ThermiteError> {
+    let mut de = serde_json::Deserializer::from_slice(body);
+    let index = FilteredIndex {
+        game,
+        predicate: |_: &Mod| true,
+    }
+    .deserialize(&mut de)?;
+    de.end()?;
+    Ok(index)
+}
+
+/// Deserializes the index one listing at a time, only keeping the packages `predicate` accepts
+struct FilteredIndex<'a, F> {
+    game: &'a GameSpec,
+    predicate: F,
+}
+
+impl<'de, F: FnMut(&Mod) -> bool> DeserializeSeed<'de> for FilteredIndex<'_, F> {
+    type Value = Vec<Mod>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(&Mod) -> bool> Visitor<'de> for FilteredIndex<'_, F> {
+    type Value = Vec<Mod>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of packages")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut index = vec![];
+        while let Some(listing) = seq.next_element::<PackageListing>()? {
+            let package = map_listing(&listing, self.game);
+            if (self.predicate)(&package) {
+                index.push(package);
+            }
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+fn map_response(res: &[PackageListing], game: &GameSpec) -> Vec<Mod> {
+    res.iter().map(|e| map_listing(e, game)).collect()
+}
+
+fn map_listing(e: &PackageListing, game: &GameSpec) -> Mod {
+    let versions = &e.versions;
+    let latest = versions[0].clone();
+    let mut urls = BTreeMap::new();
+
+    for v in versions {
+        urls.insert(
+            v.version_number.as_str().into(),
+            ModVersion {
+                name: e.name.clone(),
+                full_name: v.full_name.clone(),
+                version: v.version_number.clone(),
+                desc: v.description.clone(),
+                file_size: v.file_size,
+                deps: v
+                    .dependencies
+                    .iter()
+                    .filter(|e| !game.is_loader_package(e))
+                    .cloned()
+                    .collect::<Vec<String>>(),
+                installed: false,
+                global: false,
+                url: v.download_url.clone(),
+                sha256: v.sha256.clone(),
+                downloads: v.downloads,
+                date_created: v.date_created.clone(),
+                icon: v.icon.clone(),
+                website_url: v.website_url.clone(),
+                deprecated: !v.is_active,
+            },
+        );
+    }
+
+    Mod {
+        name: e.name.clone(),
+        author: e.owner.clone(),
+        latest: latest.version_number,
+        versions: urls,
+        installed: false,
+        global: false,
+        upgradable: false,
+        categories: e.categories.clone(),
+        nsfw: e.has_nsfw_content,
+        deprecated: e.is_deprecated,
+        rating: e.rating_score,
+        downloads: versions.iter().map(|v| v.downloads).sum(),
+        pinned: e.is_pinned,
+        package_url: e.package_url.clone(),
+        donation_link: e.donation_link.clone(),
+        date_created: e.date_created.clone(),
+        date_updated: e.date_updated.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::BTreeMap,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        cancel::CancelToken,
+        config::{NetworkConfig, ThermiteConfig},
+        error::ThermiteError,
+        game::GameSpec,
+        model::{Mod, ModVersion},
+        test_util::{Failure, MockPackage, MockServer, INDEX_PATH},
+    };
+
+    use super::{
+        fetch_index, fetch_index_filtered, fetch_index_sourced, get_package_index,
+        latest_loader_version, map_response, PackageIndex, PackageListing,
+    };
+
+    #[test]
+    fn get_packages_from_tstore() {
+        let index = get_package_index();
+        assert!(index.is_ok());
+        let index = index.unwrap();
+        assert!(!index.is_empty());
+        let mut deps = 0;
+        for f in index {
+            for d in f.get_version(&f.latest).unwrap().deps.iter() {
+                assert_ne!(d, "northstar-Northstar");
+                deps += 1;
+            }
+        }
+
+        assert_ne!(0, deps);
+    }
+
+    #[test]
+    fn get_packages_from_mock() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(
+            MockPackage::new("Foo", "Bar", "1.0.0").with_dependencies(["Foo-Baz-0.1.0"]),
+        );
+        server.add_package(MockPackage::new("Foo", "Baz", "0.1.0"));
+
+        let index =
+            fetch_index(&server.index_url(), None, &GameSpec::default()).expect("fetch index");
+        assert_eq!(index.len(), 2);
+        let bar = index
+            .iter()
+            .find(|m| m.name == "Bar")
+            .expect("Bar in index");
+        assert_eq!(bar.get_latest().unwrap().deps, ["Foo-Baz-0.1.0"]);
+    }
+
+    #[test]
+    fn index_of_community() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        let index = PackageIndex::for_community("northstar")
+            .base_url(format!("{}/", server.url()))
+            .timeout(None);
+        assert_eq!(index.url(), server.index_url());
+        assert_eq!(index.get().expect("index").len(), 1);
+        assert!(index.get_filtered(|_| false).expect("index").is_empty());
+
+        assert!(matches!(
+            PackageIndex::for_community("other")
+                .base_url(server.url())
+                .get(),
+            Err(ThermiteError::HttpStatus { status: 404, .. })
+        ));
+    }
+
+    #[test]
+    fn filter_packages_while_parsing() {
+        let server = MockServer::start().expect("start mock server");
+        for name in ["Bar", "Baz", "Qux"] {
+            server.add_package(MockPackage::new("Foo", name, "1.0.0"));
+        }
+
+        let index = fetch_index_filtered(&server.index_url(), None, &GameSpec::default(), |m| {
+            m.name != "Baz"
+        })
+        .expect("fetch index");
+        let mut names = index.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["Bar", "Qux"]);
+    }
+
+    #[test]
+    fn latest_northstar_from_index() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Northstar", "9.0.0"));
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            latest_loader_version(&config),
+            Err(ThermiteError::DepError { .. })
+        ));
+
+        server.add_package(MockPackage::new("northstar", "Northstar", "1.22.0"));
+        assert_eq!(latest_loader_version(&config).expect("latest"), "1.22.0");
+    }
+
+    #[test]
+    fn fall_back_to_mirrors() {
+        let server = MockServer::start().expect("start mock server");
+        let mirror = MockServer::start().expect("start mock server");
+        mirror.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            index_mirrors: vec![format!("{}/broken", mirror.url()), mirror.index_url()],
+            ..Default::default()
+        };
+
+        server.fail_next(INDEX_PATH, Failure::Disconnect);
+        for _ in 0..=NetworkConfig::default().retries {
+            mirror.fail_next("/broken", Failure::Status(503));
+        }
+        let index = fetch_index_sourced(&config).expect("index from mirror");
+        assert_eq!(index.url, mirror.index_url());
+        assert_eq!(index.packages.len(), 1);
+
+        // only network errors fall back, an index that isn't there is the server's answer
+        server.fail_next(INDEX_PATH, Failure::Status(404));
+        assert!(matches!(
+            fetch_index_sourced(&config),
+            Err(ThermiteError::HttpStatus { status: 404, .. })
+        ));
+        let index = fetch_index_sourced(&config).expect("index");
+        assert_eq!(index.url, server.index_url());
+    }
+
+    #[test]
+    fn retry_server_errors() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        server.fail_next(INDEX_PATH, Failure::Status(503));
+        server.fail_next(INDEX_PATH, Failure::Status(429));
+
+        let index = fetch_index(&server.index_url(), None, &GameSpec::default()).expect("index");
+        assert_eq!(index.len(), 1);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn fetch_index_async() {
+        let server = MockServer::start().expect("start mock server");
+        let mirror = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        mirror.add_package(MockPackage::new("Foo", "Baz", "1.0.0"));
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            index_mirrors: vec![mirror.index_url()],
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime");
+
+        // retried like sync requests
+        server.fail_next(INDEX_PATH, Failure::Status(503));
+        let index = runtime
+            .block_on(super::fetch_index_sourced_async(&config))
+            .expect("index");
+        assert_eq!(index.url, server.index_url());
+        assert_eq!(
+            index.packages,
+            fetch_index(&server.index_url(), None, &GameSpec::default()).expect("sync index")
+        );
+
+        server.fail_next(INDEX_PATH, Failure::Disconnect);
+        let index = runtime
+            .block_on(super::fetch_index_sourced_async(&config))
+            .expect("index from mirror");
+        assert_eq!(index.url, mirror.index_url());
+        assert_eq!(index.packages[0].name, "Baz");
+    }
+
+    #[test]
+    fn cancel_during_backoff() {
+        let server = MockServer::start().expect("start mock server");
+        server.fail_next(INDEX_PATH, Failure::Status(503));
+
+        let token = CancelToken::with_timeout(Duration::from_millis(20));
+        let started = Instant::now();
+        let res = token.run(|| fetch_index(&server.index_url(), None, &GameSpec::default()));
+        assert!(matches!(res, Err(ThermiteError::Timeout { .. })), "{res:?}");
+        assert!(started.elapsed() < NetworkConfig::default().backoff);
+    }
+
+    #[test]
+    fn fail_get_packages_on_server_error() {
+        let server = MockServer::start().expect("start mock server");
+        for _ in 0..=NetworkConfig::default().retries {
+            server.fail_next(INDEX_PATH, Failure::Status(500));
+        }
+
+        match fetch_index(&server.index_url(), None, &GameSpec::default()) {
+            Err(ThermiteError::HttpStatus { status, .. }) => assert_eq!(status, 500),
+            res => panic!("Expected an HTTP status error, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn map_thunderstore_response() {
+        let test_data: [PackageListing; 1] = serde_json::from_str(
+            r#"[{
+                "name": "Foo",
+                "full_name": "Bar-Foo",
+                "owner": "Bar",
+                "package_url": "https://thunderstore.io/c/northstar/p/Bar/Foo/",
+                "donation_link": null,
+                "date_created": "2024-01-01T00:00:00Z",
+                "date_updated": "2024-02-01T00:00:00Z",
+                "rating_score": 3,
+                "is_pinned": true,
+                "is_deprecated": true,
+                "has_nsfw_content": false,
+                "categories": ["Mods"],
+                "versions": [{
+                    "name": "Foo",
+                    "full_name": "Bar-Foo-0.1.0",
+                    "description": "Test",
+                    "icon": "https://example.com/icon.png",
+                    "version_number": "0.1.0",
+                    "dependencies": ["something"],
+                    "download_url": "localhost",
+                    "downloads": 12,
+                    "date_created": "2024-02-01T00:00:00Z",
+                    "website_url": "https://example.com",
+                    "is_active": false,
+                    "file_size": 420
+                }]
+            }]"#,
+        )
+        .unwrap();
+
+        let expected = [Mod {
+            name: "Foo".into(),
+            author: "Bar".into(),
+            latest: "0.1.0".into(),
+            installed: false,
+            upgradable: false,
+            global: false,
+            versions: BTreeMap::from([(
+                "0.1.0".into(),
+                ModVersion {
+                    name: "Foo".into(),
+                    full_name: "Bar-Foo-0.1.0".into(),
+                    version: "0.1.0".into(),
+                    url: "localhost".into(),
+                    desc: "Test".into(),
+                    deps: vec!["something".into()],
+                    installed: false,
+                    global: false,
+                    file_size: 420,
+                    sha256: None,
+                    downloads: 12,
+                    date_created: "2024-02-01T00:00:00Z".into(),
+                    icon: "https://example.com/icon.png".into(),
+                    website_url: "https://example.com".into(),
+                    deprecated: true,
+                },
+            )]),
+            categories: vec!["Mods".into()],
+            nsfw: false,
+            deprecated: true,
+            rating: 3,
+            downloads: 12,
+            pinned: true,
+            package_url: "https://thunderstore.io/c/northstar/p/Bar/Foo/".into(),
+            donation_link: None,
+            date_created: "2024-01-01T00:00:00Z".into(),
+            date_updated: "2024-02-01T00:00:00Z".into(),
+        }];
+
+        let res = map_response(&test_data, &GameSpec::default());
+        assert!(!res.is_empty());
+        assert_eq!(res[0], expected[0]);
+        assert_eq!(res[0].description(), "Test");
+    }
+}
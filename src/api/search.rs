@@ -0,0 +1,243 @@
+//! Searching and filtering the package index, so frontends don't each have to reimplement Thunderstore's
+//!
+//! [`search`] ranks packages by how well their name, author and description match a query, allowing for the
+//! odd typo. [`SearchFilter`] narrows the index down by category, content flags, rating and downloads. Its
+//! [`matches`](SearchFilter::matches) also works as the predicate of
+//! [`get_package_index_filtered`](super::get_package_index_filtered).
+
+use std::cmp::Reverse;
+
+use crate::{core::utils::edit_distance, model::Mod};
+
+/// Which packages [`SearchFilter::search`] and [`SearchFilter::apply`] keep, every package by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    categories: Vec<String>,
+    hide_nsfw: bool,
+    hide_deprecated: bool,
+    min_rating: i64,
+    min_downloads: u64,
+}
+
+impl SearchFilter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only keeps packages in `category`, e.g. `Mods`, `Skins` or `Server-side`, ignoring case. Packages have
+    /// to be in every category added
+    #[must_use]
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.categories.push(category.into());
+        self
+    }
+
+    /// Leaves out packages marked as having NSFW content
+    #[must_use]
+    pub const fn hide_nsfw(mut self, hide: bool) -> Self {
+        self.hide_nsfw = hide;
+        self
+    }
+
+    /// Leaves out packages their authors deprecated
+    #[must_use]
+    pub const fn hide_deprecated(mut self, hide: bool) -> Self {
+        self.hide_deprecated = hide;
+        self
+    }
+
+    #[must_use]
+    pub const fn min_rating(mut self, rating: i64) -> Self {
+        self.min_rating = rating;
+        self
+    }
+
+    /// Only keeps packages downloaded at least `downloads` times, counting every version
+    #[must_use]
+    pub const fn min_downloads(mut self, downloads: u64) -> Self {
+        self.min_downloads = downloads;
+        self
+    }
+
+    #[must_use]
+    pub fn matches(&self, package: &Mod) -> bool {
+        (!self.hide_nsfw || !package.nsfw)
+            && (!self.hide_deprecated || !package.deprecated)
+            && package.rating >= self.min_rating
+            && package.downloads >= self.min_downloads
+            && self.categories.iter().all(|wanted| {
+                package
+                    .categories
+                    .iter()
+                    .any(|c| c.eq_ignore_ascii_case(wanted))
+            })
+    }
+
+    /// The packages of `index` the filter keeps, in the same order
+    #[must_use]
+    pub fn apply<'a>(&self, index: &'a [Mod]) -> Vec<&'a Mod> {
+        index.iter().filter(|m| self.matches(m)).collect()
+    }
+
+    /// Like [`search`], only returning packages the filter keeps
+    #[must_use]
+    pub fn search<'a>(&self, index: &'a [Mod], query: impl AsRef<str>) -> Vec<&'a Mod> {
+        let query = query.as_ref().to_lowercase();
+        let terms = query
+            .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>();
+
+        let mut found = index
+            .iter()
+            .filter(|m| self.matches(m))
+            .filter_map(|m| score(m, &terms).map(|score| (score, m)))
+            .collect::<Vec<_>>();
+        // stable, so packages matching equally well with as many downloads stay in index order
+        found.sort_by_key(|(score, m)| (Reverse(*score), Reverse(m.downloads)));
+        found.into_iter().map(|(_, m)| m).collect()
+    }
+}
+
+/// The packages of `index` matching every word of `query`, best matches first
+///
+/// Words are matched against the package's name and author, then against the description of its latest
+/// version. Package names differing from a word by about one typo for every three characters still match,
+/// just ranked lower. Equally good matches are ordered by downloads. An empty query matches every package.
+#[must_use]
+pub fn search(index: &[Mod], query: impl AsRef<str>) -> Vec<&Mod> {
+    SearchFilter::default().search(index, query)
+}
+
+/// The sum of how well each of `terms` matches `package`, `None` if any of them doesn't
+fn score(package: &Mod, terms: &[&str]) -> Option<u32> {
+    let name = package.name.to_lowercase();
+    let author = package.author.to_lowercase();
+    let desc = package
+        .get_latest()
+        .map(|v| v.desc.to_lowercase())
+        .unwrap_or_default();
+    let words = name.split('_').collect::<Vec<_>>();
+
+    terms
+        .iter()
+        .map(|term| {
+            if words.contains(term) {
+                Some(100)
+            } else if words.iter().any(|w| w.starts_with(term)) {
+                Some(60)
+            } else if name.contains(term) {
+                Some(40)
+            } else if author.contains(term) {
+                Some(30)
+            } else if desc.contains(term) {
+                Some(10)
+            } else {
+                let limit = (term.chars().count() / 3).max(1);
+                // a single typo turns any one or two letter word into any other
+                let fuzzy = term.chars().count() > 2
+                    && words.iter().any(|w| edit_distance(term, w) <= limit);
+                fuzzy.then_some(5)
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::model::{Mod, ModVersion};
+
+    use super::{search, SearchFilter};
+
+    fn package(author: &str, name: &str, desc: &str, downloads: u64) -> Mod {
+        let version = ModVersion {
+            name: name.into(),
+            full_name: format!("{author}-{name}-1.0.0"),
+            version: "1.0.0".into(),
+            url: String::new(),
+            desc: desc.into(),
+            deps: vec![],
+            installed: false,
+            global: false,
+            file_size: 0,
+            sha256: None,
+            ..Default::default()
+        };
+        Mod {
+            name: name.into(),
+            latest: "1.0.0".into(),
+            versions: BTreeMap::from([("1.0.0".into(), version)]),
+            author: author.into(),
+            downloads,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn search_index() {
+        let index = [
+            package("Fifty", "Server_Utilities", "Commands for servers", 10),
+            package("Foo", "Utilities", "Handy things", 5),
+            package("Foo", "Skin", "A skin for utility servers", 50),
+            Mod {
+                categories: vec!["Skins".into()],
+                nsfw: true,
+                rating: 4,
+                ..package("Bar", "OtherSkin", "Another skin", 1)
+            },
+            Mod {
+                deprecated: true,
+                categories: vec!["Mods".into(), "Server-side".into()],
+                ..package("Bar", "OldServer", "Deprecated", 100)
+            },
+        ];
+        let names = |found: Vec<&Mod>| found.iter().map(|m| m.name.clone()).collect::<Vec<_>>();
+
+        assert_eq!(
+            names(search(&index, "utilities")),
+            ["Server_Utilities", "Utilities"]
+        );
+        assert_eq!(names(search(&index, "Fifty-Server")), ["Server_Utilities"]);
+        assert_eq!(
+            names(search(&index, "server")),
+            ["Server_Utilities", "OldServer", "Skin"]
+        );
+        assert_eq!(
+            names(search(&index, "utilites")),
+            ["Server_Utilities", "Utilities"]
+        );
+        assert!(search(&index, "nothing like it").is_empty());
+        assert_eq!(search(&index, " ").len(), index.len());
+
+        let filter = SearchFilter::new().hide_nsfw(true).hide_deprecated(true);
+        assert_eq!(
+            names(filter.search(&index, "server")),
+            ["Server_Utilities", "Skin"]
+        );
+        assert_eq!(filter.apply(&index).len(), 3);
+        assert_eq!(
+            names(SearchFilter::new().category("skins").apply(&index)),
+            ["OtherSkin"]
+        );
+        assert_eq!(
+            names(
+                SearchFilter::new()
+                    .category("mods")
+                    .category("server-side")
+                    .apply(&index)
+            ),
+            ["OldServer"]
+        );
+        assert_eq!(
+            names(SearchFilter::new().min_rating(1).apply(&index)),
+            ["OtherSkin"]
+        );
+        assert_eq!(
+            names(SearchFilter::new().min_downloads(50).apply(&index)),
+            ["Skin", "OldServer"]
+        );
+    }
+}
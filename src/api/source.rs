@@ -0,0 +1,222 @@
+//! Where packages come from, so frontends can add their own sources next to Thunderstore
+//!
+//! A [`PackageSource`] lists its packages in the same form as the Thunderstore index and hands out their
+//! archives. [`install_from_source`](crate::core::manage::install_from_source) installs from any of them.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{
+    config,
+    core::{github, manage},
+    error::ThermiteError,
+    model::{Mod, ModVersion},
+};
+
+use super::{get_package_index, LocalRepository, PackageIndex};
+
+/// A source of packages and their archives
+///
+/// Implementations are called on whichever thread does the install, so they have to be `Send + Sync`.
+pub trait PackageSource: Send + Sync {
+    /// Every package the source has
+    ///
+    /// # Errors
+    /// * Whatever kept the source from listing its packages
+    fn index(&self) -> Result<Vec<Mod>, ThermiteError>;
+
+    /// The archive of a version from [`PackageSource::index`]
+    ///
+    /// # Errors
+    /// * Whatever kept the source from providing the archive
+    fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>, ThermiteError>;
+}
+
+/// The Thunderstore index from the global config, see [`get_package_index`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Thunderstore;
+
+impl PackageSource for Thunderstore {
+    fn index(&self) -> Result<Vec<Mod>, ThermiteError> {
+        get_package_index()
+    }
+
+    fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>, ThermiteError> {
+        download(version)
+    }
+}
+
+impl PackageSource for PackageIndex {
+    fn index(&self) -> Result<Vec<Mod>, ThermiteError> {
+        self.get()
+    }
+
+    fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>, ThermiteError> {
+        download(version)
+    }
+}
+
+impl PackageSource for LocalRepository {
+    fn index(&self) -> Result<Vec<Mod>, ThermiteError> {
+        Ok(LocalRepository::index(self))
+    }
+
+    fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>, ThermiteError> {
+        LocalRepository::fetch(self, version)
+    }
+}
+
+/// Downloads a version, checking it against the index's checksum where that's supported
+fn download(version: &ModVersion) -> Result<Vec<u8>, ThermiteError> {
+    let mut archive = vec![];
+    #[cfg(not(target_arch = "wasm32"))]
+    manage::download_verified(&mut archive, version)?;
+    #[cfg(target_arch = "wasm32")]
+    manage::download(&mut archive, &version.url)?;
+    Ok(archive)
+}
+
+/// A package published as GitHub releases, each release with a matching asset being one version
+///
+/// Versions are the release tags without a leading `v`. Drafts and prereleases are skipped and, since GitHub
+/// doesn't know about Thunderstore dependencies, the versions don't have any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GithubReleases {
+    api_url: String,
+    timeout: Option<Duration>,
+    owner: String,
+    repo: String,
+    author: String,
+    name: String,
+    pattern: String,
+}
+
+impl GithubReleases {
+    /// The releases of `owner/repo` on the global config's `github_api_url`, listed as the package
+    /// `author-name`
+    #[must_use]
+    pub fn new(
+        owner: impl Into<String>,
+        repo: impl Into<String>,
+        author: impl Into<String>,
+        name: impl Into<String>,
+    ) -> Self {
+        let config = config::config();
+        Self {
+            api_url: config.github_api_url.clone(),
+            timeout: config.timeout,
+            owner: owner.into(),
+            repo: repo.into(),
+            author: author.into(),
+            name: name.into(),
+            pattern: "*.zip".into(),
+        }
+    }
+
+    /// Base URL of the GitHub API, e.g. for GitHub Enterprise
+    #[must_use]
+    pub fn api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// Which asset of a release is the package's archive, see [`Release::asset`](github::Release::asset).
+    /// Defaults to `*.zip`
+    #[must_use]
+    pub fn asset(mut self, pattern: impl Into<String>) -> Self {
+        self.pattern = pattern.into();
+        self
+    }
+}
+
+impl PackageSource for GithubReleases {
+    fn index(&self) -> Result<Vec<Mod>, ThermiteError> {
+        let releases =
+            github::fetch_releases(&self.api_url, self.timeout, &self.owner, &self.repo)?;
+
+        let mut versions = BTreeMap::new();
+        let mut latest = None;
+        // newest first, so the first one found is the latest
+        for release in releases.iter().filter(|r| !r.prerelease) {
+            let Some(asset) = release.asset(&self.pattern) else {
+                continue;
+            };
+            let version = release.tag.trim_start_matches('v').to_owned();
+            latest.get_or_insert_with(|| version.clone());
+            versions.insert(
+                version.as_str().into(),
+                ModVersion {
+                    name: self.name.clone(),
+                    full_name: format!("{}-{}-{version}", self.author, self.name),
+                    version,
+                    url: asset.url.clone(),
+                    desc: release.name.clone().unwrap_or_default(),
+                    deps: vec![],
+                    installed: false,
+                    global: false,
+                    file_size: asset.size,
+                    sha256: None,
+                    ..Default::default()
+                },
+            );
+        }
+
+        Ok(latest
+            .map(|latest| Mod {
+                name: self.name.clone(),
+                latest,
+                installed: false,
+                upgradable: false,
+                global: false,
+                versions,
+                author: self.author.clone(),
+                ..Default::default()
+            })
+            .into_iter()
+            .collect())
+    }
+
+    fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>, ThermiteError> {
+        let mut archive = vec![];
+        manage::download(&mut archive, &version.url)?;
+        Ok(archive)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{manage::install_from_source, utils::TempDir},
+        test_util::{mod_archive, MockServer},
+    };
+
+    use super::{GithubReleases, PackageSource};
+
+    #[test]
+    fn install_from_github_releases() {
+        let server = MockServer::start().expect("start mock server");
+        let releases = r#"[
+            {"tag_name": "v2.0.0", "prerelease": true, "assets": [
+                {"name": "Bar.zip", "browser_download_url": "URL/2.0.0.zip", "size": 1}
+            ]},
+            {"tag_name": "v1.1.0", "name": "Bar 1.1", "assets": [
+                {"name": "Bar.zip", "browser_download_url": "URL/1.1.0.zip", "size": 1}
+            ]},
+            {"tag_name": "v1.0.0", "assets": []}
+        ]"#
+        .replace("URL", &server.url());
+        server.serve("/repos/Foo/bar-mod/releases", releases);
+        server.serve("/1.1.0.zip", mod_archive("Bar", "1.1.0"));
+
+        let source = GithubReleases::new("Foo", "bar-mod", "Foo", "Bar").api_url(server.url());
+        let index = source.index().expect("index");
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].latest, "1.1.0");
+        assert_eq!(index[0].versions.len(), 1);
+
+        let dir = TempDir::create("./test_github_source").expect("Unable to create temp dir");
+        let source: &dyn PackageSource = &source;
+        let reports = install_from_source(source, "Foo-Bar", &dir).expect("install");
+        assert_eq!(reports[0].name, "Foo-Bar-1.1.0");
+        assert!(dir.join("Foo-Bar-1.1.0/manifest.json").exists());
+    }
+}
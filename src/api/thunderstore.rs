@@ -0,0 +1,313 @@
+//! Client for Thunderstore's experimental API, for looking up single packages without the whole index
+//!
+//! Packages are addressed by namespace, the author in `author-name-X.Y.Z`, and name. The experimental API
+//! isn't versioned and Thunderstore may change it, so unknown fields are kept in `_extra`.
+
+use std::{collections::HashMap, time::Duration};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    cancel,
+    config::{self, ThermiteConfig},
+    error::ThermiteError,
+    http::{self, HttpRequest},
+    time::Instant,
+};
+
+/// A package and its latest version
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PackageInfo {
+    pub namespace: String,
+    pub name: String,
+    /// `namespace-name`
+    pub full_name: String,
+    pub owner: String,
+    pub package_url: String,
+    pub date_created: String,
+    pub date_updated: String,
+    #[serde(default)]
+    pub rating_score: i64,
+    #[serde(default)]
+    pub is_pinned: bool,
+    #[serde(default)]
+    pub is_deprecated: bool,
+    #[serde(default)]
+    pub total_downloads: u64,
+    pub latest: VersionInfo,
+    #[serde(flatten)]
+    pub _extra: HashMap<String, Value>,
+}
+
+/// A single version of a package
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VersionInfo {
+    pub namespace: String,
+    pub name: String,
+    pub version_number: String,
+    /// `namespace-name-X.Y.Z`
+    pub full_name: String,
+    #[serde(default)]
+    pub description: String,
+    /// URL of the package's icon
+    #[serde(default)]
+    pub icon: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    pub download_url: String,
+    #[serde(default)]
+    pub downloads: u64,
+    pub date_created: String,
+    #[serde(default)]
+    pub website_url: String,
+    #[serde(default)]
+    pub is_active: bool,
+    #[serde(flatten)]
+    pub _extra: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct Markdown {
+    markdown: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThunderstoreClient {
+    base_url: String,
+    timeout: Option<Duration>,
+}
+
+impl Default for ThunderstoreClient {
+    fn default() -> Self {
+        Self::from_config(&config::config())
+    }
+}
+
+impl ThunderstoreClient {
+    /// A client for the Thunderstore at `base_url`, e.g. `https://thunderstore.io`, with the global config's
+    /// timeout
+    #[must_use]
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            timeout: config::config().timeout,
+        }
+    }
+
+    /// A client using `config.thunderstore_url` and `config.timeout`
+    #[must_use]
+    pub fn from_config(config: &ThermiteConfig) -> Self {
+        Self {
+            base_url: config.thunderstore_url.clone(),
+            timeout: config.timeout,
+        }
+    }
+
+    /// Time limit for every request. `None` means no limit
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The package `namespace-name`, with its latest version
+    ///
+    /// # Errors
+    /// * `ThermiteError::HttpStatus` with status 404 if there is no such package
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn package(
+        &self,
+        namespace: impl AsRef<str>,
+        name: impl AsRef<str>,
+    ) -> Result<PackageInfo, ThermiteError> {
+        let (namespace, name) = (namespace.as_ref(), name.as_ref());
+        self.fetch(
+            &format!("package/{namespace}/{name}/"),
+            &format!("fetching {namespace}-{name}"),
+        )
+    }
+
+    /// Version `version` of the package `namespace-name`
+    ///
+    /// # Errors
+    /// * `ThermiteError::HttpStatus` with status 404 if there is no such version
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn version(
+        &self,
+        namespace: impl AsRef<str>,
+        name: impl AsRef<str>,
+        version: impl AsRef<str>,
+    ) -> Result<VersionInfo, ThermiteError> {
+        let (namespace, name, version) = (namespace.as_ref(), name.as_ref(), version.as_ref());
+        self.fetch(
+            &format!("package/{namespace}/{name}/{version}/"),
+            &format!("fetching {namespace}-{name}-{version}"),
+        )
+    }
+
+    /// The README of a version of a package, as markdown
+    ///
+    /// # Errors
+    /// * `ThermiteError::HttpStatus` with status 404 if there is no such version
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn readme(
+        &self,
+        namespace: impl AsRef<str>,
+        name: impl AsRef<str>,
+        version: impl AsRef<str>,
+    ) -> Result<String, ThermiteError> {
+        let (namespace, name, version) = (namespace.as_ref(), name.as_ref(), version.as_ref());
+        let res: Markdown = self.fetch(
+            &format!("package/{namespace}/{name}/{version}/readme/"),
+            &format!("fetching the README of {namespace}-{name}-{version}"),
+        )?;
+        Ok(res.markdown.unwrap_or_default())
+    }
+
+    /// The changelog of a version of a package, as markdown, or `None` if it doesn't have one
+    ///
+    /// # Errors
+    /// * `ThermiteError::HttpStatus` with status 404 if there is no such version
+    /// * IO and network errors
+    /// * Unexpected response format from thunderstore
+    pub fn changelog(
+        &self,
+        namespace: impl AsRef<str>,
+        name: impl AsRef<str>,
+        version: impl AsRef<str>,
+    ) -> Result<Option<String>, ThermiteError> {
+        let (namespace, name, version) = (namespace.as_ref(), name.as_ref(), version.as_ref());
+        let res = self.fetch::<Markdown>(
+            &format!("package/{namespace}/{name}/{version}/changelog/"),
+            &format!("fetching the changelog of {namespace}-{name}-{version}"),
+        );
+        match res {
+            Ok(res) => Ok(res.markdown.filter(|m| !m.is_empty())),
+            // versions without a changelog are a 404 as well, tell them apart by looking the version up
+            Err(ThermiteError::HttpStatus { status: 404, .. }) => {
+                self.version(namespace, name, version).map(|_| None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetches any endpoint of the experimental API, `path` being relative to `/api/experimental/`
+    ///
+    /// # Errors
+    /// * `ThermiteError::HttpStatus` for error statuses
+    /// * IO and network errors
+    /// * The response doesn't deserialize to `T`
+    pub fn experimental<T: DeserializeOwned>(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<T, ThermiteError> {
+        let path = path.as_ref().trim_start_matches('/');
+        self.fetch(path, &format!("fetching /api/experimental/{path}"))
+    }
+
+    fn fetch<T: DeserializeOwned>(&self, path: &str, operation: &str) -> Result<T, ThermiteError> {
+        cancel::checkpoint(operation)?;
+        let started = Instant::now();
+        let url = format!(
+            "{}/api/experimental/{path}",
+            self.base_url.trim_end_matches('/')
+        );
+        let req = HttpRequest::get(url)
+            .header("accept", "application/json")
+            .timeout(self.timeout);
+        let body = http::get(&req, operation)?
+            .into_string()
+            .map_err(|e| ThermiteError::from_io(e, operation, started, self.timeout))?;
+
+        Ok(serde_json::from_str(&body)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{error::ThermiteError, test_util::MockServer};
+
+    use super::ThunderstoreClient;
+
+    const VERSION: &str = r#"{
+        "namespace": "Foo",
+        "name": "Bar",
+        "version_number": "1.0.0",
+        "full_name": "Foo-Bar-1.0.0",
+        "description": "A mod",
+        "icon": "https://example.com/icon.png",
+        "dependencies": ["northstar-Northstar-1.22.0"],
+        "download_url": "https://example.com/Foo-Bar-1.0.0.zip",
+        "downloads": 12,
+        "date_created": "2024-01-01T00:00:00Z",
+        "website_url": "",
+        "is_active": true,
+        "new_field": 1
+    }"#;
+
+    #[test]
+    fn package_details() {
+        let server = MockServer::start().expect("start mock server");
+        let package = format!(
+            r#"{{
+                "namespace": "Foo",
+                "name": "Bar",
+                "full_name": "Foo-Bar",
+                "owner": "Foo",
+                "package_url": "https://example.com/Foo/Bar/",
+                "date_created": "2024-01-01T00:00:00Z",
+                "date_updated": "2024-01-01T00:00:00Z",
+                "rating_score": 3,
+                "is_pinned": false,
+                "is_deprecated": false,
+                "total_downloads": 12,
+                "latest": {VERSION}
+            }}"#
+        );
+        server.serve("/api/experimental/package/Foo/Bar/", package);
+        server.serve("/api/experimental/package/Foo/Bar/1.0.0/", VERSION);
+        server.serve(
+            "/api/experimental/package/Foo/Bar/1.0.0/readme/",
+            r##"{"markdown": "# Bar"}"##,
+        );
+        let client = ThunderstoreClient::new(server.url()).timeout(None);
+
+        let info = client.package("Foo", "Bar").expect("package");
+        assert_eq!(info.latest.version_number, "1.0.0");
+        assert!(info.latest._extra.contains_key("new_field"));
+        let raw: serde_json::Value = client.experimental("/package/Foo/Bar/").expect("raw");
+        assert_eq!(raw["owner"], "Foo");
+        let version = client.version("Foo", "Bar", "1.0.0").expect("version");
+        assert_eq!(version, info.latest);
+        assert_eq!(
+            client.readme("Foo", "Bar", "1.0.0").expect("readme"),
+            "# Bar"
+        );
+        assert_eq!(
+            client.changelog("Foo", "Bar", "1.0.0").expect("changelog"),
+            None
+        );
+
+        server.serve(
+            "/api/experimental/package/Foo/Bar/1.0.0/changelog/",
+            r#"{"markdown": "Fixed things"}"#,
+        );
+        assert_eq!(
+            client.changelog("Foo", "Bar", "1.0.0").expect("changelog"),
+            Some("Fixed things".into())
+        );
+        assert!(matches!(
+            client.changelog("Foo", "Bar", "2.0.0"),
+            Err(ThermiteError::HttpStatus { status: 404, .. })
+        ));
+    }
+}
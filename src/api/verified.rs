@@ -0,0 +1,100 @@
+//! Northstar's list of verified mods, the only packages allowed to ship native plugins by default
+
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cancel, config,
+    error::ThermiteError,
+    http::{self, HttpRequest},
+    time::Instant,
+};
+
+/// A verified mod, as listed by Northstar
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct VerifiedMod {
+    /// Thunderstore `author-name` of the package
+    pub dependency_prefix: String,
+    pub versions: Vec<VerifiedVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct VerifiedVersion {
+    pub version: String,
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+/// The verified mods list, keyed by display name
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct VerifiedMods {
+    pub mods: BTreeMap<String, VerifiedMod>,
+}
+
+impl VerifiedMods {
+    /// Returns `true` if `version` of the package `author-name` is on the list
+    #[must_use]
+    pub fn is_verified(&self, package: impl AsRef<str>, version: impl AsRef<str>) -> bool {
+        self.mods.values().any(|m| {
+            m.dependency_prefix.eq_ignore_ascii_case(package.as_ref())
+                && m.versions.iter().any(|v| v.version == version.as_ref())
+        })
+    }
+}
+
+/// Fetches the verified mods list from the URL in the global config
+///
+/// # Errors
+/// * IO and network errors
+/// * Unexpected response format
+pub fn get_verified_mods() -> Result<VerifiedMods, ThermiteError> {
+    let config = config::config();
+    fetch_verified_mods(&config.verified_mods_url, config.timeout)
+}
+
+pub(crate) fn fetch_verified_mods(
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<VerifiedMods, ThermiteError> {
+    const OPERATION: &str = "fetching the verified mods list";
+    cancel::checkpoint(OPERATION)?;
+    let started = Instant::now();
+    let req = HttpRequest::get(url).timeout(timeout);
+    let body = http::get(&req, OPERATION)?
+        .into_string()
+        .map_err(|e| ThermiteError::from_io(e, OPERATION, started, timeout))?;
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util::MockServer;
+
+    use super::fetch_verified_mods;
+
+    const VERIFIED: &str = r#"{
+        "Foo's Bar": {
+            "DependencyPrefix": "Foo-Bar",
+            "Versions": [
+                { "Version": "1.0.0", "Checksum": "abc", "DownloadLink": "https://example.com" }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn verified_mods_from_mock() {
+        let server = MockServer::start().expect("start mock server");
+        server.serve("/verified-mods.json", VERIFIED);
+
+        let verified = fetch_verified_mods(&format!("{}/verified-mods.json", server.url()), None)
+            .expect("verified mods");
+        assert!(verified.is_verified("Foo-Bar", "1.0.0"));
+        assert!(!verified.is_verified("Foo-Bar", "1.0.1"));
+        assert!(!verified.is_verified("Foo-Baz", "1.0.0"));
+    }
+}
@@ -0,0 +1,287 @@
+use std::{fs, io::Cursor, path::PathBuf, process::ExitCode};
+
+use clap::{Arg, ArgMatches, Command};
+use thermite::{
+    api,
+    core::{manage::download_verified, set_status_sink, StatusEvent},
+    http::{set_backend, ProxyConfig, UreqBackend},
+    manager::{Lockfile, ModManager, DEFAULT_PROFILE},
+    prelude::*,
+};
+
+fn cli() -> Command<'static> {
+    let names = || {
+        Arg::new("name")
+            .required(true)
+            .multiple_values(true)
+            .help("Packages as author-name or author-name-X.Y.Z")
+    };
+    let dry_run = || {
+        Arg::new("dry-run")
+            .long("dry-run")
+            .help("Only list the files that would change")
+    };
+
+    Command::new("thermite")
+        .about("Manage Northstar and mods from Thunderstore")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("game-dir")
+                .long("game-dir")
+                .short('g')
+                .takes_value(true)
+                .global(true)
+                .help("Titanfall 2 install directory. Found automatically with the steam feature"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .short('p')
+                .takes_value(true)
+                .global(true)
+                .default_value(DEFAULT_PROFILE)
+                .help("Northstar profile to manage"),
+        )
+        .arg(
+            Arg::new("index-url")
+                .long("index-url")
+                .takes_value(true)
+                .global(true)
+                .help("Use a different Thunderstore package index"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .takes_value(true)
+                .global(true)
+                .help("Connect through this proxy instead of the one from HTTPS_PROXY"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .global(true)
+                .help("Don't print progress to stderr"),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Search the package index")
+                .arg(Arg::new("query").required(true)),
+        )
+        .subcommand(
+            Command::new("install")
+                .about("Install packages and their dependencies")
+                .arg(names())
+                .arg(dry_run()),
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("Remove installed packages")
+                .arg(names())
+                .arg(dry_run()),
+        )
+        .subcommand(
+            Command::new("update")
+                .about("Update outdated packages")
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Only list available updates"),
+                )
+                .arg(dry_run()),
+        )
+        .subcommand(Command::new("list").about("List installed mods"))
+        .subcommand(
+            Command::new("install-northstar").about("Install the latest Northstar into the game"),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the profile's lockfile, to stdout if no file is given")
+                .arg(Arg::new("file")),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Install every package recorded in an exported lockfile")
+                .arg(Arg::new("file").required(true)),
+        )
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    if let Some(url) = matches.value_of("index-url") {
+        set_config(ThermiteConfig {
+            index_url: url.into(),
+            ..Default::default()
+        });
+    }
+    if let Some(url) = matches.value_of("proxy") {
+        let proxy = ProxyConfig {
+            url: Some(url.into()),
+            ..ProxyConfig::from_env()
+        };
+        match UreqBackend::with_proxy(&proxy) {
+            Ok(backend) => set_backend(backend),
+            Err(e) => {
+                eprintln!("error: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if !matches.is_present("quiet") {
+        set_status_sink(|event: &StatusEvent| eprintln!("{event}"));
+    }
+
+    match run(&matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            if matches!(e, ThermiteError::GameNotFound(_)) {
+                eprintln!("pass its directory with --game-dir");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn game_dir(matches: &ArgMatches) -> Result<PathBuf, ThermiteError> {
+    if let Some(dir) = matches.value_of("game-dir") {
+        return Ok(dir.into());
+    }
+
+    #[cfg(feature = "steam")]
+    if let Some(dir) = titanfall() {
+        return Ok(dir);
+    }
+
+    Err(ThermiteError::GameNotFound(
+        thermite::config::config().game.name.clone(),
+    ))
+}
+
+fn run(matches: &ArgMatches) -> Result<(), ThermiteError> {
+    let (command, sub) = matches.subcommand().expect("clap requires a subcommand");
+
+    if command == "search" {
+        return search(sub.value_of("query").unwrap_or_default());
+    }
+
+    let profile = matches.value_of("profile").unwrap_or(DEFAULT_PROFILE);
+    let mut manager = ModManager::with_config(
+        game_dir(matches)?,
+        profile,
+        thermite::config::config().as_ref().clone(),
+    )?;
+
+    match command {
+        "install" if sub.is_present("dry-run") => {
+            for name in sub.values_of("name").into_iter().flatten() {
+                print!("{}", manager.plan_install(name)?);
+            }
+        }
+        "install" => {
+            for name in sub.values_of("name").into_iter().flatten() {
+                for report in manager.install(name)? {
+                    println!("{report}");
+                }
+            }
+        }
+        "remove" if sub.is_present("dry-run") => {
+            for name in sub.values_of("name").into_iter().flatten() {
+                print!("{}", manager.plan_remove(name)?);
+            }
+        }
+        "remove" => {
+            for name in sub.values_of("name").into_iter().flatten() {
+                manager.remove(name)?;
+                println!("Removed {name}");
+            }
+        }
+        "update" if sub.is_present("check") => {
+            for update in manager.check_updates()? {
+                let deprecated = if update.deprecated {
+                    " (deprecated)"
+                } else {
+                    ""
+                };
+                println!(
+                    "{} {} -> {}{deprecated}",
+                    update.name, update.installed, update.latest
+                );
+            }
+        }
+        "update" if sub.is_present("dry-run") => print!("{}", manager.plan_update_all()?),
+        "update" => {
+            for report in manager.update_all()? {
+                println!("{report}");
+            }
+        }
+        "list" => {
+            for m in manager.list()? {
+                println!(
+                    "{}-{} {} ({})",
+                    m.author, m.manifest.name, m.manifest.version_number, m.mod_json.name
+                );
+            }
+        }
+        "install-northstar" => install_northstar_latest(&mut manager)?,
+        "export" => {
+            let lockfile = serde_json::to_string_pretty(manager.lockfile())?;
+            match sub.value_of("file") {
+                Some(path) => fs::write(path, lockfile)?,
+                None => println!("{lockfile}"),
+            }
+        }
+        "import" => {
+            let raw = fs::read_to_string(sub.value_of("file").unwrap_or_default())?;
+            let lockfile: Lockfile = serde_json::from_str(&raw)?;
+            // dependencies are pulled in by the packages that need them
+            for (name, package) in lockfile.packages.iter().filter(|(_, p)| p.explicit) {
+                for report in manager.install(format!("{name}-{}", package.version))? {
+                    println!("{report}");
+                }
+            }
+        }
+        _ => unreachable!("clap only accepts known subcommands"),
+    }
+
+    Ok(())
+}
+
+fn search(query: &str) -> Result<(), ThermiteError> {
+    let index = get_package_index()?;
+    for m in api::search(&index, query) {
+        let full_name = format!("{}-{}", m.author, m.name);
+        let desc = m.get_latest().map(|v| v.desc.as_str()).unwrap_or_default();
+        println!("{full_name} {} - {desc}", m.latest);
+    }
+    Ok(())
+}
+
+fn install_northstar_latest(manager: &mut ModManager) -> Result<(), ThermiteError> {
+    let config = thermite::config::config();
+    let northstar = manager
+        .index()?
+        .iter()
+        .filter(|m| {
+            config
+                .game
+                .is_loader_package(format!("{}-{}", m.author, m.name))
+        })
+        .find_map(|m| m.get_latest().cloned())
+        .ok_or_else(|| ThermiteError::DepError {
+            name: config.game.loader_package.clone(),
+            suggestions: vec![],
+        })?;
+
+    let mut archive = vec![];
+    download_verified(&mut archive, &northstar)?;
+    if let Some(verifier) = &config.verifier {
+        verifier.verify(&northstar.full_name, &archive)?;
+    }
+    let report = install_northstar(Cursor::new(archive), manager.game_dir())?;
+    println!("{report}");
+
+    Ok(())
+}
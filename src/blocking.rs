@@ -0,0 +1,141 @@
+//! Running local, blocking work like installs as futures for the `async` feature
+//!
+//! The requests of the `*_async` functions go through an [`AsyncHttpBackend`](crate::http::AsyncHttpBackend)
+//! and don't need this. What's left is disk IO and the CPU bound work around it, which has no runtime-agnostic
+//! async API, so it runs on the shared thread pool of the [`blocking`](::blocking) crate, the one smol and
+//! async-std use too. The work inherits the caller's cancel tokens and filesystem like pool workers do.
+//!
+//! Dropping a future before it resolves cancels its call at the next cancellation checkpoint, the same as
+//! cancelling a [`CancelToken`] around it. Work done until then, like files already written, isn't undone.
+
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    task::{Context, Poll},
+    thread,
+};
+
+use crate::{
+    cancel::{self, CancelToken},
+    core::vfs,
+};
+
+/// Resolves to what the closure passed to [`spawn`] returned, cancels it when dropped before that
+pub(crate) struct BlockingTask<T> {
+    task: ::blocking::Task<thread::Result<T>>,
+    token: CancelToken,
+}
+
+/// Queues `f` to run on a background thread right away
+pub(crate) fn spawn<T, F>(f: F) -> BlockingTask<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let token = CancelToken::new();
+    let mut tokens = cancel::current();
+    tokens.push(token.clone());
+    let fs = vfs::current();
+
+    let task = ::blocking::unblock(move || {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            vfs::with(fs, || cancel::run_with(&tokens, f))
+        }))
+    });
+    BlockingTask { task, token }
+}
+
+impl<T> Future for BlockingTask<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match Pin::new(&mut self.task).poll(cx) {
+            Poll::Ready(Ok(value)) => Poll::Ready(value),
+            // panics carry over to whoever awaits the task, like with `JoinHandle::join`
+            Poll::Ready(Err(payload)) => panic::resume_unwind(payload),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for BlockingTask<T> {
+    fn drop(&mut self) {
+        // nobody is waiting for the result anymore
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::{mpsc, Arc},
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+        time::Duration,
+    };
+
+    use crate::{cancel::CancelToken, error::ThermiteError};
+
+    use super::spawn;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor so the tests don't need a runtime
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(value) => return value,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn spawned_work_resolves() {
+        assert_eq!(block_on(spawn(|| 2 + 2)), 4);
+
+        let token = CancelToken::new();
+        token.cancel();
+        let res = token.run(|| block_on(spawn(|| crate::cancel::checkpoint("testing"))));
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+
+        let tasks = (0..64).map(|i| spawn(move || i)).collect::<Vec<_>>();
+        let results = tasks.into_iter().map(block_on).collect::<Vec<_>>();
+        assert_eq!(results, (0..64).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn dropping_cancels() {
+        let (started_tx, started) = mpsc::channel();
+        let (done_tx, done) = mpsc::channel();
+        let task = spawn(move || {
+            started_tx.send(()).unwrap();
+            // stands in for a long install
+            let res = loop {
+                if let Err(e) = crate::cancel::checkpoint("testing") {
+                    break e;
+                }
+                thread::sleep(Duration::from_millis(5));
+            };
+            done_tx.send(res).unwrap();
+        });
+        started.recv().unwrap();
+        drop(task);
+        let res = done
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the work should stop");
+        assert!(matches!(res, ThermiteError::Cancelled));
+    }
+}
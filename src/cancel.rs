@@ -0,0 +1,254 @@
+//! Cancellation and deadlines for long-running operations
+//!
+//! Operations don't take a token directly. Instead, run them inside [`CancelToken::run`] and every
+//! checkpoint they pass (each downloaded chunk, extracted file, resolved dependency...) will check it:
+//!
+//! ```no_run
+//! use thermite::{cancel::CancelToken, prelude::*};
+//!
+//! let token = CancelToken::new();
+//! let worker = token.clone();
+//! std::thread::spawn(move || worker.run(get_package_index));
+//!
+//! // later, from the UI thread
+//! token.cancel();
+//! ```
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    error::{Result, ThermiteError},
+    time::{self, Instant},
+};
+
+/// Longest a [`sleep`] goes without checking its tokens
+const CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+struct Inner {
+    cancelled: AtomicBool,
+    created: Instant,
+    deadline: Option<Instant>,
+}
+
+/// A shared flag that aborts operations when set or when its deadline passes
+///
+/// Clones share the same state, so cancelling any clone cancels all of them
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                created: Instant::now(),
+                deadline: None,
+            }),
+        }
+    }
+
+    /// Creates a token that expires once `limit` has passed
+    #[must_use]
+    pub fn with_timeout(limit: Duration) -> Self {
+        let created = Instant::now();
+        Self {
+            inner: Arc::new(Inner {
+                cancelled: AtomicBool::new(false),
+                created,
+                deadline: created.checked_add(limit),
+            }),
+        }
+    }
+
+    /// Cancels every operation using this token
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the token was cancelled or its deadline has passed
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Relaxed)
+            || self.inner.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Returns an error if the token was cancelled or its deadline has passed
+    ///
+    /// # Errors
+    /// * `ThermiteError::Cancelled` if `cancel` was called
+    /// * `ThermiteError::Timeout` if the deadline passed
+    pub fn check(&self, operation: impl AsRef<str>) -> Result<()> {
+        if self.inner.cancelled.load(Ordering::Relaxed) {
+            return Err(ThermiteError::Cancelled);
+        }
+
+        if let Some(deadline) = self.inner.deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ThermiteError::Timeout {
+                    operation: operation.as_ref().into(),
+                    elapsed: now - self.inner.created,
+                    limit: Some(deadline - self.inner.created),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Time left until the deadline, `None` if there is none
+    fn remaining(&self) -> Option<Duration> {
+        self.inner.deadline.map(|d| d - Instant::now())
+    }
+
+    /// Runs `f` with this token as the current thread's token
+    ///
+    /// Tokens can be nested, the innermost token is checked first but outer tokens still apply
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        CURRENT.with(|c| c.borrow_mut().push(self.clone()));
+
+        // pop the token even if `f` panics
+        struct Guard;
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                CURRENT.with(|c| c.borrow_mut().pop());
+            }
+        }
+        let _guard = Guard;
+
+        f()
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Vec<CancelToken>> = const { RefCell::new(vec![]) };
+}
+
+/// Returns the tokens active on the current thread, innermost last
+///
+/// Used to carry the tokens over to worker threads
+pub(crate) fn current() -> Vec<CancelToken> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+/// Runs `f` with all of `tokens` active, used on worker threads
+pub(crate) fn run_with<T>(tokens: &[CancelToken], f: impl FnOnce() -> T) -> T {
+    match tokens.split_first() {
+        Some((first, rest)) => first.run(|| run_with(rest, f)),
+        None => f(),
+    }
+}
+
+/// Checks every token active on the current thread
+///
+/// # Errors
+/// * If any token was cancelled or has expired
+pub(crate) fn checkpoint(operation: impl AsRef<str>) -> Result<()> {
+    CURRENT.with(|c| {
+        c.borrow()
+            .iter()
+            .rev()
+            .try_for_each(|t| t.check(operation.as_ref()))
+    })
+}
+
+/// Blocks the current thread for `duration` like [`std::thread::sleep`], but returns as soon as a token active
+/// on it is cancelled or expires
+///
+/// # Errors
+/// * If any token was cancelled or has expired
+pub(crate) fn sleep(duration: Duration, operation: impl AsRef<str>) -> Result<()> {
+    let operation = operation.as_ref();
+    let started = Instant::now();
+    loop {
+        checkpoint(operation)?;
+        let slept = started.elapsed();
+        // without a clock no time ever passes
+        if slept >= duration || cfg!(target_arch = "wasm32") {
+            return Ok(());
+        }
+        let left = CURRENT.with(|c| c.borrow().iter().filter_map(CancelToken::remaining).min());
+        let step = (duration - slept)
+            .min(CHECK_INTERVAL)
+            .min(left.unwrap_or(Duration::MAX));
+        time::sleep(step);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{checkpoint, sleep, CancelToken};
+    use crate::error::ThermiteError;
+
+    #[test]
+    fn no_token_passes() {
+        assert!(checkpoint("testing").is_ok());
+    }
+
+    #[test]
+    fn cancelled_token_fails_checkpoint() {
+        let token = CancelToken::new();
+        token.clone().cancel();
+
+        let res = token.run(|| checkpoint("testing"));
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+        // the token is no longer active outside of `run`
+        assert!(checkpoint("testing").is_ok());
+    }
+
+    #[test]
+    fn expired_token_times_out() {
+        let token = CancelToken::with_timeout(Duration::ZERO);
+
+        match token.run(|| checkpoint("testing")) {
+            Err(ThermiteError::Timeout {
+                operation, limit, ..
+            }) => {
+                assert_eq!(operation, "testing");
+                assert_eq!(limit, Some(Duration::ZERO));
+            }
+            res => panic!("Expected a timeout, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn outer_token_applies_to_nested_runs() {
+        let outer = CancelToken::new();
+        outer.cancel();
+
+        let res = outer.run(|| CancelToken::new().run(|| checkpoint("testing")));
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+    }
+
+    #[test]
+    fn sleep_wakes_at_deadline() {
+        let started = Instant::now();
+        assert!(sleep(Duration::from_millis(20), "testing").is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(20));
+
+        let token = CancelToken::with_timeout(Duration::from_millis(30));
+        let started = Instant::now();
+        let res = token.run(|| sleep(Duration::from_secs(10), "testing"));
+        assert!(matches!(res, Err(ThermiteError::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,201 @@
+//! Crate-wide settings
+//!
+//! The free functions in `api` and `core` read the global config set with [`set_config`].
+//! Types that own their own settings take a [`ThermiteConfig`] directly.
+
+use std::{
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+
+use crate::api::verified::VerifiedMods;
+use crate::core::utils::PackageLayout;
+use crate::game::GameSpec;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::verify::Verifier;
+
+/// The Thunderstore package index for the Northstar community
+pub const DEFAULT_INDEX_URL: &str = "https://northstar.thunderstore.io/c/northstar/api/v1/package/";
+/// The official Northstar master server
+pub const DEFAULT_MASTERSERVER_URL: &str = "https://northstar.tf";
+/// Northstar's list of verified mods
+pub const DEFAULT_VERIFIED_MODS_URL: &str =
+    "https://raw.githubusercontent.com/R2Northstar/VerifiedMods/main/verified-mods.json";
+/// Thunderstore, for the API beyond the package index
+pub const DEFAULT_THUNDERSTORE_URL: &str = "https://thunderstore.io";
+/// The GitHub REST API
+pub const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+/// What to do when installing a package whose target directory already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Return `ThermiteError::AlreadyInstalled`
+    Fail,
+    /// Replace the existing directory once the new install is extracted
+    #[default]
+    Replace,
+    /// Ask the [`Resolver`](crate::core::resolve::Resolver), failing like [`OverwritePolicy::Fail`] if none
+    /// is set
+    Ask,
+    /// Replace the existing directory, but keep the config files (`.cfg`, `.ini`, `.json` and `.txt`) the user
+    /// edited in it. Without checksums from when it was installed every config file counts as edited
+    MergePreservingConfigs,
+}
+
+/// What to do when mods are installed or removed while the game is running, see
+/// [`is_game_running`](crate::core::utils::is_game_running)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunningGamePolicy {
+    /// Change the mods anyway
+    #[default]
+    Ignore,
+    /// Return `ThermiteError::GameRunning`
+    Refuse,
+    /// Wait for the game to close, failing with `ThermiteError::Timeout` once the limit passes. `None` waits
+    /// until it closes
+    Wait(Option<Duration>),
+}
+
+/// How HTTP requests are retried and throttled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// How many times a request is retried after a 5xx or 429 response
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every retry after it
+    pub backoff: Duration,
+    /// Time limit for each attempt of a request. The lower of this and the operation's own limit, like
+    /// `ThermiteConfig::timeout`, is used. `None` leaves it to the operation
+    pub timeout: Option<Duration>,
+    /// Download speed limit. `None` means no limit
+    pub throttle_bytes_per_sec: Option<u64>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            backoff: Duration::from_millis(250),
+            timeout: None,
+            throttle_bytes_per_sec: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThermiteConfig {
+    /// Directory for cached data such as the package index. `None` disables caching
+    pub cache_dir: Option<PathBuf>,
+    /// Size in bytes the package cache is pruned to after every download. `None` means no limit
+    pub cache_limit: Option<u64>,
+    /// Directory downloaded archives are written to. `None` keeps them in memory
+    pub download_dir: Option<PathBuf>,
+    /// URL of the Thunderstore package index
+    pub index_url: String,
+    /// Copies of the package index tried in order when fetching it from `index_url` fails with a network error
+    pub index_mirrors: Vec<String>,
+    /// Base URL of Thunderstore, used by [`ThunderstoreClient`](crate::api::ThunderstoreClient)
+    pub thunderstore_url: String,
+    /// Base URL of the Northstar master server
+    pub masterserver_url: String,
+    /// URL of the verified mods list
+    pub verified_mods_url: String,
+    /// URL of a list of known mod conflicts and broken versions. `None` skips compatibility checks
+    pub compatibility_url: Option<String>,
+    /// Base URL of the GitHub API that releases of components like NorthstarProton are fetched from
+    pub github_api_url: String,
+    /// Time limit for small requests like the package index. `None` means no limit
+    pub timeout: Option<Duration>,
+    /// Time limit for downloading a single file. `None` means no limit
+    pub download_timeout: Option<Duration>,
+    pub network: NetworkConfig,
+    /// Maximum number of worker threads for parallel work
+    pub parallelism: NonZeroUsize,
+    pub overwrite: OverwritePolicy,
+    /// Whether installs and removals go ahead while the game is running
+    pub running_game: RunningGamePolicy,
+    /// How packages are installed into the target directory
+    pub layout: PackageLayout,
+    /// Install packages containing native plugins (`.dll` files). They still have to be verified or
+    /// `allow_unverified_plugins` has to be set
+    pub install_plugins: bool,
+    /// Packages allowed to contain native plugins (`.dll` files). `None` means no package is allowed
+    pub verified_mods: Option<Arc<VerifiedMods>>,
+    /// Install packages containing plugins even if they aren't on the verified list
+    pub allow_unverified_plugins: bool,
+    /// Skip files only clients load, like textures, audio and client scripts, when installing packages. Meant
+    /// for dedicated servers
+    pub strip_client_assets: bool,
+    /// Most bytes a package may extract to, so a zip bomb can't fill the disk. `None` means no limit
+    pub max_extracted_bytes: Option<u64>,
+    /// Most files a package may contain. `None` means no limit
+    pub max_extracted_files: Option<usize>,
+    /// File to append a JSON line to for every install, update and uninstall. `None` disables the log
+    pub audit_log: Option<PathBuf>,
+    /// Record every install, update and uninstall in `.thermite/journal.json` in the directory it happened in,
+    /// see [`history`](crate::core::utils::history)
+    pub journal: bool,
+    /// The game and mod loader being managed
+    pub game: GameSpec,
+    /// Trusted hashes every archive must match before `ModManager` installs it. `None` disables verification
+    #[cfg(not(target_arch = "wasm32"))]
+    pub verifier: Option<Arc<Verifier>>,
+}
+
+impl Default for ThermiteConfig {
+    fn default() -> Self {
+        Self {
+            cache_dir: None,
+            cache_limit: None,
+            download_dir: None,
+            index_url: DEFAULT_INDEX_URL.into(),
+            index_mirrors: vec![],
+            thunderstore_url: DEFAULT_THUNDERSTORE_URL.into(),
+            masterserver_url: DEFAULT_MASTERSERVER_URL.into(),
+            verified_mods_url: DEFAULT_VERIFIED_MODS_URL.into(),
+            compatibility_url: None,
+            github_api_url: DEFAULT_GITHUB_API_URL.into(),
+            timeout: Some(Duration::from_secs(60)),
+            download_timeout: None,
+            network: NetworkConfig::default(),
+            parallelism: std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            overwrite: OverwritePolicy::default(),
+            running_game: RunningGamePolicy::default(),
+            layout: PackageLayout::default(),
+            install_plugins: false,
+            verified_mods: None,
+            allow_unverified_plugins: false,
+            strip_client_assets: false,
+            max_extracted_bytes: Some(4 << 30),
+            max_extracted_files: Some(50_000),
+            audit_log: None,
+            journal: true,
+            game: GameSpec::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            verifier: None,
+        }
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<Arc<ThermiteConfig>> = RwLock::new(Arc::default());
+}
+
+/// Replaces the global config
+pub fn set_config(config: ThermiteConfig) {
+    match CONFIG.write() {
+        Ok(mut lock) => *lock = Arc::new(config),
+        Err(poisoned) => *poisoned.into_inner() = Arc::new(config),
+    }
+}
+
+/// Returns the global config
+pub fn config() -> Arc<ThermiteConfig> {
+    match CONFIG.read() {
+        Ok(lock) => lock.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
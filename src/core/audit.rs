@@ -0,0 +1,159 @@
+//! Append-only log of the operations performed by `manage`
+//!
+//! When `ThermiteConfig::audit_log` is set, every install, update, uninstall and Northstar install
+//! appends one JSON object per line to that file, whether it succeeded or not.
+//! Use [`read_log`] to load it back, e.g. when helping a user work out what their manager did.
+
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    config,
+    error::{Result, ThermiteError},
+};
+
+use super::{
+    status::{self, StatusEvent},
+    vfs,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    Install,
+    /// An install that replaced an existing copy of the package
+    Update,
+    Uninstall,
+    InstallNorthstar,
+    /// Files of a Northstar install restored from its release
+    RepairNorthstar,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure { error: String },
+}
+
+/// A single line of the audit log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub operation: AuditOperation,
+    /// Mod string of the package, or the path for uninstalls
+    pub name: String,
+    /// Version from the package's manifest, if it had one
+    #[serde(default)]
+    pub version: Option<String>,
+    pub target: PathBuf,
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(
+        operation: AuditOperation,
+        name: impl Into<String>,
+        target: impl Into<PathBuf>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            timestamp,
+            operation,
+            name: name.into(),
+            version: None,
+            target: target.into(),
+            outcome: AuditOutcome::Success,
+        }
+    }
+}
+
+/// Records the outcome of an operation to the configured audit log, if there is one
+///
+/// Failing to write the log never fails the operation itself, a warning is emitted instead
+pub(crate) fn record<T, E: Display>(mut entry: AuditEntry, result: &Result<T, E>) {
+    let Some(path) = config::config().audit_log.clone() else {
+        return;
+    };
+    if vfs::is_dry_run() {
+        return;
+    }
+
+    if let Err(e) = result {
+        entry.outcome = AuditOutcome::Failure {
+            error: e.to_string(),
+        };
+    }
+
+    if let Err(e) = append(&path, &entry) {
+        warn!("Unable to write audit log at {}: {e}", path.display());
+        status::emit(StatusEvent::Warning(format!(
+            "Unable to write audit log at {}: {e}",
+            path.display()
+        )));
+    }
+}
+
+fn append(path: &Path, entry: &AuditEntry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // a single write keeps lines intact if several processes share the log
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)
+}
+
+/// Reads every entry from an audit log
+///
+/// # Errors
+/// * IO errors
+/// * A line isn't a valid entry
+pub fn read_log(path: impl AsRef<Path>) -> Result<Vec<AuditEntry>> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(ThermiteError::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{append, read_log, AuditEntry, AuditOperation, AuditOutcome};
+    use crate::core::utils::TempDir;
+
+    #[test]
+    fn log_roundtrip() {
+        let dir = TempDir::create("./test_audit_log").unwrap();
+        let path = dir.join("logs").join("audit.jsonl");
+
+        let mut install = AuditEntry::new(AuditOperation::Install, "foo-bar", "packages");
+        install.version = Some("1.0.0".into());
+        let mut failed = AuditEntry::new(AuditOperation::InstallNorthstar, "Northstar", "game");
+        failed.outcome = AuditOutcome::Failure {
+            error: "IO Error".into(),
+        };
+
+        append(&path, &install).unwrap();
+        append(&path, &failed).unwrap();
+
+        assert_eq!(read_log(&path).unwrap(), [install, failed]);
+    }
+}
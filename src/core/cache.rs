@@ -0,0 +1,256 @@
+//! Cache of downloaded package archives
+//!
+//! Archives are stored as `<cache_dir>/packages/<author-name-X.Y.Z>.zip`. A file's modification time is its
+//! last-used time, so reading an entry through [`PackageCache::get`] keeps it from being pruned.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tracing::debug;
+
+use crate::{config::ThermiteConfig, error::Result};
+
+use super::events::{self, Event};
+
+const EXTENSION: &str = "zip";
+
+/// A cached package archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// `author-name-X.Y.Z`
+    pub full_name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub total_bytes: u64,
+}
+
+/// What pruning removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// `author-name-X.Y.Z` of every removed archive
+    pub removed: Vec<String>,
+    pub bytes_freed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageCache {
+    dir: PathBuf,
+}
+
+impl PackageCache {
+    /// Uses `dir` directly to store archives
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The package cache inside `config.cache_dir`, if caching is enabled
+    #[must_use]
+    pub fn from_config(config: &ThermiteConfig) -> Option<Self> {
+        config
+            .cache_dir
+            .as_ref()
+            .map(|dir| Self::new(dir.join("packages")))
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, full_name: &str) -> PathBuf {
+        self.dir.join(format!("{full_name}.{EXTENSION}"))
+    }
+
+    /// Reads a cached archive and marks it as used
+    ///
+    /// # Errors
+    /// * IO errors other than the archive not being cached
+    pub fn get(&self, full_name: impl AsRef<str>) -> Result<Option<Vec<u8>>> {
+        let path = self.entry_path(full_name.as_ref());
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(SystemTime::now())?;
+
+        debug!("Using cached archive {}", path.display());
+        Ok(Some(data))
+    }
+
+    /// Stores an archive, replacing any cached copy
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn put(&self, full_name: impl AsRef<str>, archive: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.entry_path(full_name.as_ref()), archive)?;
+        Ok(())
+    }
+
+    /// Every cached archive, least recently used first
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = vec![];
+        for file in dir {
+            let path = file?.path();
+            if path.extension().is_none_or(|ext| ext != EXTENSION) {
+                continue;
+            }
+            let Some(full_name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let meta = fs::metadata(&path)?;
+            entries.push(CacheEntry {
+                full_name: full_name.to_owned(),
+                size: meta.len(),
+                last_used: meta.modified()?,
+                path,
+            });
+        }
+        entries.sort_by_key(|e| e.last_used);
+
+        Ok(entries)
+    }
+
+    /// # Errors
+    /// * IO errors
+    pub fn stats(&self) -> Result<CacheStats> {
+        let entries = self.entries()?;
+        Ok(CacheStats {
+            entries: entries.len(),
+            total_bytes: entries.iter().map(|e| e.size).sum(),
+        })
+    }
+
+    /// Removes archives that haven't been used for longer than `max_age`
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn prune_older_than(&self, max_age: Duration) -> Result<PruneReport> {
+        let now = SystemTime::now();
+        let old = self.entries()?.into_iter().filter(|e| {
+            now.duration_since(e.last_used)
+                .is_ok_and(|age| age > max_age)
+        });
+        remove(old)
+    }
+
+    /// Removes the least recently used archives until the cache is at most `max_bytes`
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn prune_to_size(&self, max_bytes: u64) -> Result<PruneReport> {
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|e| e.size).sum();
+        let excess = entries.into_iter().take_while(|e| {
+            let over = total > max_bytes;
+            total = total.saturating_sub(e.size);
+            over
+        });
+        remove(excess)
+    }
+
+    /// Removes every cached archive
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn clear(&self) -> Result<PruneReport> {
+        remove(self.entries()?)
+    }
+}
+
+fn remove(entries: impl IntoIterator<Item = CacheEntry>) -> Result<PruneReport> {
+    let mut report = PruneReport::default();
+    for entry in entries {
+        debug!("Pruning cached archive {}", entry.path.display());
+        fs::remove_file(&entry.path)?;
+        report.bytes_freed += entry.size;
+        events::emit(Event::CacheEvicted {
+            full_name: entry.full_name.clone(),
+            bytes: entry.size,
+        });
+        report.removed.push(entry.full_name);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::File,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::core::utils::TempDir;
+
+    use super::{CacheStats, PackageCache};
+
+    #[test]
+    fn prune_cache() {
+        let dir = TempDir::create("./test_package_cache").expect("Unable to create temp dir");
+        let cache = PackageCache::new(dir.join("packages"));
+        assert_eq!(cache.stats().unwrap(), CacheStats::default());
+
+        cache.put("Foo-Old-1.0.0", &[0; 100]).unwrap();
+        cache.put("Foo-Bar-1.0.0", &[0; 10]).unwrap();
+        cache.put("Foo-Baz-1.0.0", &[0; 20]).unwrap();
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(cache.dir().join("Foo-Old-1.0.0.zip"))
+            .and_then(|f| f.set_modified(week_ago))
+            .unwrap();
+
+        assert_eq!(
+            cache.stats().unwrap(),
+            CacheStats {
+                entries: 3,
+                total_bytes: 130
+            }
+        );
+        assert_eq!(
+            cache.get("Foo-Bar-1.0.0").unwrap().as_deref(),
+            Some(&[0; 10][..])
+        );
+        assert_eq!(cache.get("Foo-Missing-1.0.0").unwrap(), None);
+        assert_eq!(cache.entries().unwrap()[0].full_name, "Foo-Old-1.0.0");
+
+        let report = cache
+            .prune_older_than(Duration::from_secs(24 * 60 * 60))
+            .unwrap();
+        assert_eq!(report.removed, ["Foo-Old-1.0.0"]);
+        assert_eq!(report.bytes_freed, 100);
+
+        // Foo-Bar was used last, so Foo-Baz goes first
+        let report = cache.prune_to_size(15).unwrap();
+        assert_eq!(report.removed, ["Foo-Baz-1.0.0"]);
+        assert!(cache.get("Foo-Bar-1.0.0").unwrap().is_some());
+
+        cache.clear().unwrap();
+        assert_eq!(cache.stats().unwrap().entries, 0);
+    }
+}
@@ -0,0 +1,123 @@
+//! Typed notifications about state thermite changed on disk
+//!
+//! Unlike the single [`StatusSink`](super::StatusSink), any number of subscribers can listen for [`Event`]s,
+//! so several UI components or plugins can react to installs and removals without polling the filesystem.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use lazy_static::lazy_static;
+
+use super::vfs;
+
+/// Something thermite changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// A package was installed or replaced at `path`
+    ModInstalled {
+        name: String,
+        version: Option<String>,
+        path: PathBuf,
+    },
+    /// A package was removed from `path`
+    ModRemoved { name: String, path: PathBuf },
+    /// Northstar was installed or updated in `target`
+    NorthstarUpdated {
+        target: PathBuf,
+        version: Option<String>,
+    },
+    /// A [`ModManager`](crate::manager::ModManager) switched to another profile
+    ProfileSwitched { from: String, to: String },
+    /// A cached archive was pruned from the package cache
+    CacheEvicted { full_name: String, bytes: u64 },
+}
+
+/// Returned by [`subscribe`], pass it to [`unsubscribe`] to stop receiving events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+type Subscriber = Arc<dyn Fn(&Event) + Send + Sync>;
+
+lazy_static! {
+    static ref SUBSCRIBERS: RwLock<Vec<(SubscriptionId, Subscriber)>> = RwLock::new(vec![]);
+}
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Calls `f` with every event emitted from now on, from whichever thread caused it
+pub fn subscribe(f: impl Fn(&Event) + Send + Sync + 'static) -> SubscriptionId {
+    let id = SubscriptionId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    if let Ok(mut lock) = SUBSCRIBERS.write() {
+        lock.push((id, Arc::new(f)));
+    }
+    id
+}
+
+/// Removes a subscriber. Does nothing if it was already removed
+pub fn unsubscribe(id: SubscriptionId) {
+    if let Ok(mut lock) = SUBSCRIBERS.write() {
+        lock.retain(|(i, _)| *i != id);
+    }
+}
+
+/// Sends an event to every subscriber
+pub(crate) fn emit(event: Event) {
+    if vfs::is_dry_run() {
+        return;
+    }
+    // clone the subscribers so they can subscribe or unsubscribe from inside the callback
+    let subscribers = SUBSCRIBERS
+        .read()
+        .map(|lock| lock.iter().map(|(_, s)| s.clone()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    for subscriber in subscribers {
+        subscriber(&event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{emit, subscribe, unsubscribe, Event};
+
+    #[test]
+    fn multiple_subscribers() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let event = Event::ProfileSwitched {
+            from: "events_test".into(),
+            to: "other".into(),
+        };
+        let ours =
+            |e: &Event| matches!(e, Event::ProfileSwitched { from, .. } if from == "events_test");
+
+        let first = {
+            let seen = seen.clone();
+            subscribe(move |e| {
+                if ours(e) {
+                    seen.lock().unwrap().push(1);
+                }
+            })
+        };
+        let second = {
+            let seen = seen.clone();
+            subscribe(move |e| {
+                if ours(e) {
+                    seen.lock().unwrap().push(2);
+                }
+            })
+        };
+
+        emit(event.clone());
+        unsubscribe(first);
+        emit(event);
+        unsubscribe(second);
+
+        assert_eq!(*seen.lock().unwrap(), [1, 2, 2]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Fetching releases of components published on GitHub, like NorthstarProton
+//!
+//! Releases are looked up through the GitHub REST API at `ThermiteConfig::github_api_url`. Unauthenticated
+//! requests are rate limited by GitHub, so avoid polling.
+
+use std::{io::Write, time::Duration};
+
+use serde::Deserialize;
+
+use crate::{
+    cancel, config,
+    error::{Result, ThermiteError},
+    http::{self, HttpRequest},
+    time::Instant,
+};
+
+use super::{manage::download, report::DownloadReport};
+
+/// A published release of a repository
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    #[serde(rename = "tag_name")]
+    pub tag: String,
+    /// The release title, GitHub falls back to the tag if it's empty
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// A file attached to a release
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    pub name: String,
+    #[serde(rename = "browser_download_url")]
+    pub url: String,
+    pub size: u64,
+}
+
+impl Release {
+    /// The first asset whose name matches `pattern`, where `*` matches any number of characters, e.g.
+    /// `NorthstarProton*.tar.gz`
+    #[must_use]
+    pub fn asset(&self, pattern: &str) -> Option<&Asset> {
+        self.assets
+            .iter()
+            .find(|a| matches_pattern(pattern, &a.name))
+    }
+}
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// The latest release of `owner/repo`, ignoring drafts and prereleases
+///
+/// # Errors
+/// * IO and network errors
+/// * Unexpected response format from GitHub
+pub fn latest_release(owner: impl AsRef<str>, repo: impl AsRef<str>) -> Result<Release> {
+    let config = config::config();
+    fetch_latest_release(
+        &config.github_api_url,
+        config.timeout,
+        owner.as_ref(),
+        repo.as_ref(),
+    )
+}
+
+/// The most recent releases of `owner/repo`, newest first
+///
+/// Only the first page GitHub returns, up to 30 releases, is fetched.
+///
+/// # Errors
+/// * IO and network errors
+/// * Unexpected response format from GitHub
+pub fn list_releases(owner: impl AsRef<str>, repo: impl AsRef<str>) -> Result<Vec<Release>> {
+    let config = config::config();
+    fetch_releases(
+        &config.github_api_url,
+        config.timeout,
+        owner.as_ref(),
+        repo.as_ref(),
+    )
+}
+
+/// Downloads the first asset of `release` matching `pattern` to `output`, see [`Release::asset`]
+///
+/// # Errors
+/// * `ThermiteError::MissingAsset` if no asset matches
+/// * IO and network errors
+pub fn download_asset(
+    release: &Release,
+    pattern: impl AsRef<str>,
+    output: impl Write,
+) -> Result<DownloadReport> {
+    let pattern = pattern.as_ref();
+    let asset = release
+        .asset(pattern)
+        .ok_or_else(|| ThermiteError::MissingAsset {
+            release: release.tag.clone(),
+            pattern: pattern.into(),
+        })?;
+    download(output, &asset.url)
+}
+
+pub(crate) fn fetch_latest_release(
+    api_url: &str,
+    timeout: Option<Duration>,
+    owner: &str,
+    repo: &str,
+) -> Result<Release> {
+    let url = format!(
+        "{}/repos/{owner}/{repo}/releases/latest",
+        api_url.trim_end_matches('/')
+    );
+    fetch_json(
+        &url,
+        timeout,
+        &format!("finding the latest release of {owner}/{repo}"),
+    )
+}
+
+pub(crate) fn fetch_releases(
+    api_url: &str,
+    timeout: Option<Duration>,
+    owner: &str,
+    repo: &str,
+) -> Result<Vec<Release>> {
+    let url = format!(
+        "{}/repos/{owner}/{repo}/releases",
+        api_url.trim_end_matches('/')
+    );
+    fetch_json(
+        &url,
+        timeout,
+        &format!("listing the releases of {owner}/{repo}"),
+    )
+}
+
+fn fetch_json<T: for<'de> Deserialize<'de>>(
+    url: &str,
+    timeout: Option<Duration>,
+    operation: &str,
+) -> Result<T> {
+    cancel::checkpoint(operation)?;
+    let started = Instant::now();
+    let req = HttpRequest::get(url)
+        .header("accept", "application/vnd.github+json")
+        .timeout(timeout);
+    let body = http::get(&req, operation)?
+        .into_string()
+        .map_err(|e| ThermiteError::from_io(e, operation, started, timeout))?;
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{error::ThermiteError, test_util::MockServer};
+
+    use super::{download_asset, fetch_latest_release, fetch_releases, matches_pattern};
+
+    const RELEASES: &str = r#"[
+        {
+            "tag_name": "v9-1",
+            "name": "NorthstarProton 9-1",
+            "prerelease": false,
+            "assets": [
+                {"name": "NorthstarProton9-1.tar.gz.sha512sum", "browser_download_url": "URL/sum", "size": 128},
+                {"name": "NorthstarProton9-1.tar.gz", "browser_download_url": "URL/archive", "size": 4}
+            ]
+        },
+        {"tag_name": "v8-28", "name": null, "assets": []}
+    ]"#;
+
+    #[test]
+    fn match_asset_names() {
+        assert!(matches_pattern(
+            "NorthstarProton*.tar.gz",
+            "NorthstarProton9-1.tar.gz"
+        ));
+        assert!(!matches_pattern(
+            "NorthstarProton*.tar.gz",
+            "NorthstarProton9-1.tar.gz.sha512sum"
+        ));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("a*b*c", "abc"));
+        assert!(!matches_pattern("a*a", "a"));
+        assert!(matches_pattern("exact", "exact"));
+        assert!(!matches_pattern("exact", "exactly"));
+    }
+
+    #[test]
+    fn releases_from_api() {
+        let server = MockServer::start().expect("start mock server");
+        let releases = RELEASES.replace("URL", &server.url());
+        server.serve("/repos/Foo/Bar/releases", releases.clone());
+        let latest = serde_json::to_string(
+            &serde_json::from_str::<serde_json::Value>(&releases).unwrap()[0],
+        )
+        .unwrap();
+        server.serve("/repos/Foo/Bar/releases/latest", latest);
+        server.serve("/archive", "data");
+
+        let list = fetch_releases(&server.url(), None, "Foo", "Bar").expect("list releases");
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].tag, "v8-28");
+
+        let latest = fetch_latest_release(&server.url(), None, "Foo", "Bar").expect("latest");
+        assert_eq!(latest, list[0]);
+        let mut archive = vec![];
+        download_asset(&latest, "NorthstarProton*.tar.gz", &mut archive).expect("download");
+        assert_eq!(archive, b"data");
+        assert!(matches!(
+            download_asset(&latest, "*.zip", vec![]),
+            Err(ThermiteError::MissingAsset { .. })
+        ));
+
+        assert!(matches!(
+            fetch_releases(&server.url(), None, "Foo", "Missing"),
+            Err(ThermiteError::HttpStatus { status: 404, .. })
+        ));
+    }
+}
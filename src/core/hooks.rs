@@ -0,0 +1,343 @@
+//! Closures and external commands run before and after operations
+//!
+//! Hooks registered with [`add_hook`] run around every install and update. Frontends that launch the game can
+//! call [`run_hooks`] with [`HookOperation::Launch`] so the same hooks apply there.
+//!
+//! A failing `Before` hook aborts the operation with `ThermiteError::HookFailed`. `After` hooks run whether the
+//! operation succeeded or not, and their failures are only logged.
+//!
+//! Commands get the context as environment variables:
+//! * `THERMITE_OPERATION` - `install`, `update` or `launch`
+//! * `THERMITE_STAGE` - `before` or `after`
+//! * `THERMITE_NAME` - the package's `author-name-X.Y.Z`, or the game for `launch`
+//! * `THERMITE_VERSION` - the installed version, only set after a successful install or update
+//! * `THERMITE_PATH` - the directory being installed into, or the game directory
+//! * `THERMITE_ERROR` - why the operation failed, only set after a failed operation
+
+use std::{
+    fmt::{self, Debug, Display},
+    path::PathBuf,
+    process::Command,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use lazy_static::lazy_static;
+use tracing::{debug, warn};
+
+use crate::error::{Result, ThermiteError};
+
+use super::{
+    status::{self, StatusEvent},
+    vfs,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookOperation {
+    Install,
+    Update,
+    Launch,
+}
+
+impl Display for HookOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Install => "install",
+            Self::Update => "update",
+            Self::Launch => "launch",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookStage {
+    Before,
+    After,
+}
+
+impl Display for HookStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Before => "before",
+            Self::After => "after",
+        })
+    }
+}
+
+/// What a hook is being run for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookContext {
+    pub operation: HookOperation,
+    pub stage: HookStage,
+    pub name: String,
+    pub version: Option<String>,
+    pub path: PathBuf,
+    /// Why the operation failed, only set for `After` hooks
+    pub error: Option<String>,
+}
+
+impl HookContext {
+    #[must_use]
+    pub fn new(
+        operation: HookOperation,
+        stage: HookStage,
+        name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            operation,
+            stage,
+            name: name.into(),
+            version: None,
+            path: path.into(),
+            error: None,
+        }
+    }
+
+    /// The environment variables commands are run with
+    #[must_use]
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("THERMITE_OPERATION", self.operation.to_string()),
+            ("THERMITE_STAGE", self.stage.to_string()),
+            ("THERMITE_NAME", self.name.clone()),
+            ("THERMITE_PATH", self.path.display().to_string()),
+        ];
+        if let Some(version) = &self.version {
+            env.push(("THERMITE_VERSION", version.clone()));
+        }
+        if let Some(error) = &self.error {
+            env.push(("THERMITE_ERROR", error.clone()));
+        }
+        env
+    }
+}
+
+type HookFn = dyn Fn(&HookContext) -> Result<(), String> + Send + Sync;
+
+/// Something to run around an operation
+#[derive(Clone)]
+pub enum Hook {
+    Closure(Arc<HookFn>),
+    /// An external program, which fails the hook if it exits unsuccessfully
+    Command {
+        program: String,
+        args: Vec<String>,
+    },
+}
+
+impl Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closure(_) => f.write_str("Closure"),
+            Self::Command { program, args } => f
+                .debug_struct("Command")
+                .field("program", program)
+                .field("args", args)
+                .finish(),
+        }
+    }
+}
+
+impl Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closure(_) => f.write_str("closure"),
+            Self::Command { program, .. } => f.write_str(program),
+        }
+    }
+}
+
+impl Hook {
+    pub fn closure(f: impl Fn(&HookContext) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Self::Closure(Arc::new(f))
+    }
+
+    pub fn command(
+        program: impl Into<String>,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self::Command {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn run(&self, ctx: &HookContext) -> Result<(), String> {
+        match self {
+            Self::Closure(f) => f(ctx),
+            Self::Command { program, args } => {
+                let status = Command::new(program)
+                    .args(args)
+                    .envs(ctx.env())
+                    .status()
+                    .map_err(|e| e.to_string())?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("exited with {status}"))
+                }
+            }
+        }
+    }
+}
+
+/// Returned by [`add_hook`], pass it to [`remove_hook`] to unregister the hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookId(u64);
+
+#[derive(Debug)]
+struct Registered {
+    id: HookId,
+    /// `None` runs the hook for every operation
+    operation: Option<HookOperation>,
+    stage: HookStage,
+    hook: Hook,
+}
+
+lazy_static! {
+    static ref HOOKS: RwLock<Vec<Registered>> = RwLock::new(vec![]);
+}
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Registers a hook for one operation, or every operation if `operation` is `None`
+///
+/// Hooks run in the order they were added
+pub fn add_hook(operation: Option<HookOperation>, stage: HookStage, hook: Hook) -> HookId {
+    let id = HookId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    if let Ok(mut lock) = HOOKS.write() {
+        lock.push(Registered {
+            id,
+            operation,
+            stage,
+            hook,
+        });
+    }
+    id
+}
+
+/// Unregisters a hook. Does nothing if it was already removed
+pub fn remove_hook(id: HookId) {
+    if let Ok(mut lock) = HOOKS.write() {
+        lock.retain(|h| h.id != id);
+    }
+}
+
+/// Unregisters every hook
+pub fn clear_hooks() {
+    if let Ok(mut lock) = HOOKS.write() {
+        lock.clear();
+    }
+}
+
+/// Runs every hook registered for `ctx`'s operation and stage
+///
+/// # Errors
+/// * `ThermiteError::HookFailed` if a `Before` hook fails. Later hooks don't run
+pub fn run_hooks(ctx: &HookContext) -> Result<()> {
+    if vfs::is_dry_run() {
+        return Ok(());
+    }
+    // clone the hooks so they can register or remove hooks themselves
+    let hooks = HOOKS
+        .read()
+        .map(|lock| {
+            lock.iter()
+                .filter(|h| h.stage == ctx.stage && h.operation.is_none_or(|o| o == ctx.operation))
+                .map(|h| h.hook.clone())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    for hook in hooks {
+        debug!("Running {} {} hook {hook}", ctx.stage, ctx.operation);
+        let Err(reason) = hook.run(ctx) else {
+            continue;
+        };
+
+        match ctx.stage {
+            HookStage::Before => {
+                return Err(ThermiteError::HookFailed {
+                    hook: hook.to_string(),
+                    reason,
+                })
+            }
+            HookStage::After => {
+                let msg = format!("{} hook {hook} failed: {reason}", ctx.operation);
+                warn!("{msg}");
+                status::emit(StatusEvent::Warning(msg));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{add_hook, remove_hook, run_hooks, Hook, HookContext, HookOperation, HookStage};
+    use crate::error::ThermiteError;
+
+    #[test]
+    fn closure_hooks() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let recorder = seen.clone();
+        let record = add_hook(
+            None,
+            HookStage::After,
+            Hook::closure(move |ctx| {
+                if ctx.name == "closure_hooks_test" {
+                    recorder.lock().unwrap().push(ctx.operation);
+                }
+                Ok(())
+            }),
+        );
+        let veto = add_hook(
+            Some(HookOperation::Update),
+            HookStage::Before,
+            Hook::closure(|ctx| match ctx.name.as_str() {
+                "closure_hooks_test" => Err("not today".into()),
+                _ => Ok(()),
+            }),
+        );
+
+        let ctx = |operation, stage| HookContext::new(operation, stage, "closure_hooks_test", ".");
+        run_hooks(&ctx(HookOperation::Install, HookStage::Before)).expect("no install veto");
+        assert!(matches!(
+            run_hooks(&ctx(HookOperation::Update, HookStage::Before)),
+            Err(ThermiteError::HookFailed { reason, .. }) if reason == "not today"
+        ));
+        run_hooks(&ctx(HookOperation::Launch, HookStage::After)).unwrap();
+
+        remove_hook(record);
+        remove_hook(veto);
+        run_hooks(&ctx(HookOperation::Install, HookStage::After)).unwrap();
+        assert_eq!(*seen.lock().unwrap(), [HookOperation::Launch]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_hooks() {
+        let hook = Hook::command(
+            "sh",
+            [
+                "-c",
+                r#"test "$THERMITE_OPERATION-$THERMITE_VERSION" = "update-1.0.0""#,
+            ],
+        );
+        let mut ctx = HookContext::new(
+            HookOperation::Update,
+            HookStage::After,
+            "Foo-Bar-1.0.0",
+            ".",
+        );
+        assert!(hook.run(&ctx).is_err());
+
+        ctx.version = Some("1.0.0".into());
+        hook.run(&ctx).expect("command should see the context");
+    }
+}
@@ -0,0 +1,259 @@
+//! Journal of the packages installed into and removed from a directory
+//!
+//! Unlike the audit log, which is opt-in and shared by every directory, each directory mods are installed
+//! into keeps its own journal in `.thermite/journal.json`, listing the files every successful install, update
+//! and uninstall touched. [`history`](super::utils::history) reads it back, e.g. to see what a user's manager
+//! did to their mods, and [`undo_last`](super::manage::undo_last) reverses the latest operation. Set
+//! `ThermiteConfig::journal` to `false` to stop recording.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    config,
+    error::{Result, ThermiteError},
+};
+
+use super::{
+    audit::AuditOperation,
+    status::{self, StatusEvent},
+    vfs,
+};
+
+/// Directory the journal is kept in, which [`find_mods`](super::utils::find_mods) ignores
+pub(crate) const JOURNAL_DIR: &str = ".thermite";
+const JOURNAL_FILE: &str = "journal.json";
+
+/// Keeps concurrent installs into the same directory from losing each other's entries
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// A single operation in the journal
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub operation: AuditOperation,
+    /// Mod string of the package, or the directory name of a mod installed by hand
+    pub name: String,
+    /// Version from the package's manifest, if it had one
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Directory the package was installed to or removed from
+    pub path: PathBuf,
+    /// Every file the operation created or removed
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// `author-name-X.Y.Z` of the package an update replaced
+    #[serde(default)]
+    pub previous: Option<String>,
+    /// State of the package's mods in the profile's `enabledmods.json` before the operation changed it, `None`
+    /// for mods that weren't listed
+    #[serde(default)]
+    pub enabled: BTreeMap<String, Option<bool>>,
+}
+
+impl JournalEntry {
+    pub(crate) fn new(
+        operation: AuditOperation,
+        name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            timestamp,
+            operation,
+            name: name.into(),
+            version: None,
+            path: path.into(),
+            files: vec![],
+            previous: None,
+            enabled: BTreeMap::new(),
+        }
+    }
+
+    /// `author-name-X.Y.Z` of the package, the name its archive is cached under
+    #[must_use]
+    pub fn full_name(&self) -> String {
+        match &self.version {
+            Some(version) if !self.name.ends_with(&format!("-{version}")) => {
+                format!("{}-{version}", self.name)
+            }
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// Path of the journal of `dir`
+#[must_use]
+pub fn journal_path(dir: impl AsRef<Path>) -> PathBuf {
+    dir.as_ref().join(JOURNAL_DIR).join(JOURNAL_FILE)
+}
+
+/// Appends `entry` to the journal of `dir`, unless the journal is disabled
+///
+/// Failing to write the journal never fails the operation itself, a warning is emitted instead
+pub(crate) fn record(dir: &Path, entry: JournalEntry) {
+    if !config::config().journal {
+        return;
+    }
+    if let Err(e) = append(dir, entry) {
+        warn_unwritten(dir, &e);
+    }
+}
+
+fn warn_unwritten(dir: &Path, e: &ThermiteError) {
+    let path = journal_path(dir);
+    warn!("Unable to write journal at {}: {e}", path.display());
+    status::emit(StatusEvent::Warning(format!(
+        "Unable to write journal at {}: {e}",
+        path.display()
+    )));
+}
+
+/// Adds the removal of `path` to the journal of the directory it was in
+pub(crate) fn record_removal(
+    path: &Path,
+    name: impl Into<String>,
+    version: Option<String>,
+    files: Vec<PathBuf>,
+    enabled: BTreeMap<String, Option<bool>>,
+) {
+    let mut entry = JournalEntry::new(AuditOperation::Uninstall, name, path);
+    entry.version = version;
+    entry.files = files;
+    entry.enabled = enabled;
+    record(path.parent().unwrap_or(path), entry);
+}
+
+/// Turns the latest install of `path` in the journal of `dir` into an update of `previous`
+pub(crate) fn record_update(
+    dir: &Path,
+    path: &Path,
+    previous: String,
+    enabled: BTreeMap<String, Option<bool>>,
+) {
+    if !config::config().journal {
+        return;
+    }
+    let res = modify(dir, |entries| {
+        if let Some(entry) = entries
+            .iter_mut()
+            .rev()
+            .find(|e| e.operation != AuditOperation::Uninstall && e.path == path)
+        {
+            entry.operation = AuditOperation::Update;
+            entry.previous = Some(previous);
+            entry.enabled = enabled;
+        }
+    });
+    if let Err(e) = res {
+        warn_unwritten(dir, &e);
+    }
+}
+
+/// Drops every entry in the journal of `dir` after the first `len`
+pub(crate) fn truncate(dir: &Path, len: usize) -> Result<()> {
+    modify(dir, |entries| entries.truncate(len))
+}
+
+fn append(dir: &Path, entry: JournalEntry) -> Result<()> {
+    modify(dir, |entries| entries.push(entry))
+}
+
+fn modify(dir: &Path, f: impl FnOnce(&mut Vec<JournalEntry>)) -> Result<()> {
+    let _lock = LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let mut entries = read(dir)?;
+    f(&mut entries);
+
+    let fs = vfs::current();
+    let path = journal_path(dir);
+    fs.create_dir_all(&dir.join(JOURNAL_DIR))?;
+    fs.write_atomic(&path, serde_json::to_string_pretty(&entries)?.as_bytes())?;
+    Ok(())
+}
+
+/// Every entry in the journal of `dir`, oldest first. Directories without a journal have an empty one
+pub(crate) fn read(dir: &Path) -> Result<Vec<JournalEntry>> {
+    let fs = vfs::current();
+    let path = journal_path(dir);
+    if !fs.exists(&path)? {
+        return Ok(vec![]);
+    }
+    serde_json::from_str(&fs.read_to_string(&path)?)
+        .map_err(|e| ThermiteError::invalid_json(&path, e))
+}
+
+/// Every file in `path`, or `path` itself if it's a file
+pub(crate) fn files_in(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let fs = vfs::current();
+    if !fs.is_dir(path) {
+        return Ok(vec![path.into()]);
+    }
+    let mut files = vec![];
+    for entry in fs.read_dir(path)? {
+        if entry.is_dir {
+            files.append(&mut files_in(&entry.path)?);
+        } else {
+            files.push(entry.path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{
+        core::{
+            audit::AuditOperation,
+            manage::{install_mod, remove_mod},
+            utils::{find_mods, history, TempDir},
+        },
+        test_util::mod_archive,
+    };
+
+    #[test]
+    fn record_installs_and_removals() {
+        let dir = TempDir::create("./test_journal").expect("Unable to create temp dir");
+        assert!(history(&dir).unwrap().is_empty());
+
+        let archive = mod_archive("Bar", "1.0.0");
+        install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &dir).expect("install");
+        install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &dir).expect("reinstall");
+        let installed = find_mods(&dir).expect("find mods");
+        assert_eq!(installed.len(), 1, "the journal isn't a package");
+        remove_mod(&installed[0], false).expect("remove");
+
+        let entries = history(&dir).unwrap();
+        let operations = entries.iter().map(|e| e.operation).collect::<Vec<_>>();
+        assert_eq!(
+            operations,
+            [
+                AuditOperation::Install,
+                AuditOperation::Update,
+                AuditOperation::Uninstall
+            ]
+        );
+        assert_eq!(entries[0].version.as_deref(), Some("1.0.0"));
+        assert!(entries[0]
+            .files
+            .contains(&dir.join("Foo-Bar-1.0.0/mods/Bar/mod.json")));
+        assert_eq!(entries[2].name, "Foo-Bar-1.0.0");
+        assert_eq!(entries[2].files.len(), entries[0].files.len());
+    }
+}
@@ -0,0 +1,347 @@
+//! Detecting and switching how Northstar gets launched
+//!
+//! Northstar can be started two ways:
+//! * running `NorthstarLauncher.exe`, which injects `Northstar.dll` into the game
+//! * launching the game normally, e.g. through the EA App, with Northstar's `wsock32.dll` proxy in
+//!   `bin/x64_retail` loading it instead
+//!
+//! Switching to the launcher never deletes the proxy, it's renamed so switching back doesn't need a reinstall.
+//!
+//! [`launch_northstar`] starts the game with Northstar, directly on Windows and through Steam elsewhere, or
+//! with a Proton install on Linux.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Child, Command},
+};
+
+use tracing::debug;
+
+use crate::{
+    config,
+    error::{Result, ThermiteError},
+};
+
+use super::{profiles::Profile, vfs};
+
+/// Where Northstar's proxy DLL goes, relative to the game directory. Other loaders set
+/// [`GameSpec::proxy_dll`](crate::game::GameSpec::proxy_dll)
+pub const PROXY_DLL: &str = "bin/x64_retail/wsock32.dll";
+const DISABLED_SUFFIX: &str = ".thermite-disabled";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMethod {
+    /// `NorthstarLauncher.exe`
+    Launcher,
+    /// The `wsock32.dll` proxy
+    Proxy,
+}
+
+/// The Northstar files found in a game directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NorthstarSetup {
+    /// `Northstar.dll`, needed by both methods
+    pub core: bool,
+    pub launcher: bool,
+    pub proxy: bool,
+    /// The proxy was disabled by [`set_launch_method`]
+    pub disabled_proxy: bool,
+}
+
+impl NorthstarSetup {
+    /// The method Northstar will be started with. The proxy takes effect whenever it's in place, even if the
+    /// launcher is there too
+    #[must_use]
+    pub const fn method(&self) -> Option<LaunchMethod> {
+        match self {
+            Self { core: false, .. } => None,
+            Self { proxy: true, .. } => Some(LaunchMethod::Proxy),
+            Self { launcher: true, .. } => Some(LaunchMethod::Launcher),
+            _ => None,
+        }
+    }
+}
+
+fn proxy_path(game_dir: &Path) -> PathBuf {
+    game_dir.join(&config::config().game.proxy_dll)
+}
+
+pub(crate) fn disabled_proxy_path(game_dir: &Path) -> PathBuf {
+    game_dir.join(format!(
+        "{}{DISABLED_SUFFIX}",
+        config::config().game.proxy_dll
+    ))
+}
+
+fn launcher_path(game_dir: &Path) -> PathBuf {
+    game_dir.join(&config::config().game.launcher)
+}
+
+/// Checks which Northstar files are in `game_dir`
+///
+/// # Errors
+/// * IO errors
+pub fn detect(game_dir: impl AsRef<Path>) -> Result<NorthstarSetup> {
+    let game_dir = game_dir.as_ref();
+    let fs = vfs::current();
+    Ok(NorthstarSetup {
+        core: fs.exists(&game_dir.join(&config::config().game.loader_dll))?,
+        launcher: fs.exists(&launcher_path(game_dir))?,
+        proxy: fs.exists(&proxy_path(game_dir))?,
+        disabled_proxy: fs.exists(&disabled_proxy_path(game_dir))?,
+    })
+}
+
+/// Makes `method` the way Northstar is started
+///
+/// # Errors
+/// * `ThermiteError::NotInstalled` if Northstar isn't installed
+/// * `ThermiteError::MissingFile` if the launcher or proxy the method needs is missing. Reinstalling Northstar
+///   restores both
+/// * IO errors
+pub fn set_launch_method(game_dir: impl AsRef<Path>, method: LaunchMethod) -> Result<()> {
+    let game_dir = game_dir.as_ref();
+    let setup = detect(game_dir)?;
+    if !setup.core {
+        return Err(ThermiteError::NotInstalled("Northstar".into()));
+    }
+
+    let fs = vfs::current();
+    let (proxy, disabled) = (proxy_path(game_dir), disabled_proxy_path(game_dir));
+    match method {
+        LaunchMethod::Proxy if setup.proxy => {}
+        LaunchMethod::Proxy if setup.disabled_proxy => {
+            debug!("Enabling {}", proxy.display());
+            fs.rename(&disabled, &proxy)?;
+        }
+        LaunchMethod::Proxy => return Err(ThermiteError::MissingFile(Box::new(proxy))),
+        LaunchMethod::Launcher if !setup.launcher => {
+            return Err(ThermiteError::MissingFile(Box::new(launcher_path(
+                game_dir,
+            ))))
+        }
+        LaunchMethod::Launcher => {
+            if setup.proxy {
+                debug!("Disabling {}", proxy.display());
+                fs.rename(&proxy, &disabled)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps the proxy disabled after an install wrote a new one
+pub(crate) fn keep_proxy_disabled(game_dir: &Path) -> Result<()> {
+    let setup = detect(game_dir)?;
+    if setup.proxy && setup.disabled_proxy {
+        vfs::current().rename(&proxy_path(game_dir), &disabled_proxy_path(game_dir))?;
+    }
+    Ok(())
+}
+
+/// How [`launch_northstar_with`] starts the game
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LaunchVia {
+    /// [`LaunchVia::Direct`] on Windows and [`LaunchVia::Steam`] everywhere else
+    #[default]
+    Auto,
+    /// Run the launcher executable, which only works on Windows
+    Direct,
+    /// Open Steam's `steam://run` URL, so the game starts with its Steam launch options and compatibility tool
+    Steam,
+    /// Run the launcher with a Proton install, without going through Steam
+    Proton(ProtonLaunch),
+}
+
+/// The Proton install and prefix [`LaunchVia::Proton`] runs the launcher with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtonLaunch {
+    /// Directory with the `proton` script, e.g. a NorthstarProton release in `compatibilitytools.d`
+    pub proton_dir: PathBuf,
+    /// The game's compatdata directory, Proton keeps its prefix in there
+    pub compat_data: PathBuf,
+    /// The Steam installation Proton uses
+    pub steam_dir: PathBuf,
+}
+
+/// Starts the game in `game_dir` with Northstar, directly on Windows and through Steam everywhere else
+///
+/// `profile` is passed as `-profile=`, before `args`. Returns the started process, which for Steam is the
+/// program that opened the `steam://` URL rather than the game.
+///
+/// # Errors
+/// * `ThermiteError::NotInstalled` if Northstar isn't installed
+/// * `ThermiteError::MissingFile` if the launcher is missing when running it directly
+/// * IO errors starting the process
+pub fn launch_northstar(
+    game_dir: impl AsRef<Path>,
+    args: &[impl AsRef<str>],
+    profile: Option<&Profile>,
+) -> Result<Child> {
+    launch_northstar_with(game_dir, args, profile, &LaunchVia::Auto)
+}
+
+/// Like [`launch_northstar`], starting the game the way `via` says
+///
+/// # Errors
+/// * The same as [`launch_northstar`]
+pub fn launch_northstar_with(
+    game_dir: impl AsRef<Path>,
+    args: &[impl AsRef<str>],
+    profile: Option<&Profile>,
+    via: &LaunchVia,
+) -> Result<Child> {
+    let game_dir = game_dir.as_ref();
+    if !detect(game_dir)?.core {
+        return Err(ThermiteError::NotInstalled("Northstar".into()));
+    }
+    let args: Vec<String> = profile
+        .map(Profile::launch_arg)
+        .into_iter()
+        .chain(args.iter().map(|a| a.as_ref().to_owned()))
+        .collect();
+
+    let mut command = match via {
+        LaunchVia::Auto if cfg!(windows) => direct_command(game_dir, &args)?,
+        LaunchVia::Direct => direct_command(game_dir, &args)?,
+        LaunchVia::Auto | LaunchVia::Steam => {
+            // the proxy only loads Northstar when asked to
+            let args = [String::from("-northstar")]
+                .into_iter()
+                .chain(args)
+                .collect::<Vec<_>>();
+            open_url(&steam_url(config::config().game.steam_id, &args))
+        }
+        LaunchVia::Proton(proton) => {
+            let mut command = Command::new(proton.proton_dir.join("proton"));
+            command
+                .arg("run")
+                .arg(existing_launcher(game_dir)?)
+                .args(&args)
+                .current_dir(game_dir)
+                .env("STEAM_COMPAT_DATA_PATH", &proton.compat_data)
+                .env("STEAM_COMPAT_CLIENT_INSTALL_PATH", &proton.steam_dir)
+                .env("SteamAppId", config::config().game.steam_id.to_string())
+                .env("WINEDLLOVERRIDES", "wsock32=n,b");
+            command
+        }
+    };
+    debug!("Launching {command:?}");
+    Ok(command.spawn()?)
+}
+
+fn direct_command(game_dir: &Path, args: &[String]) -> Result<Command> {
+    let mut command = Command::new(existing_launcher(game_dir)?);
+    command.args(args).current_dir(game_dir);
+    Ok(command)
+}
+
+fn existing_launcher(game_dir: &Path) -> Result<PathBuf> {
+    let launcher = launcher_path(game_dir);
+    if !vfs::current().exists(&launcher)? {
+        return Err(ThermiteError::MissingFile(Box::new(launcher)));
+    }
+    Ok(launcher)
+}
+
+/// Steam's URL for starting the game with `args`
+fn steam_url(steam_id: u32, args: &[String]) -> String {
+    let mut encoded = String::new();
+    for byte in args.join(" ").bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~=".contains(&byte) {
+            encoded.push(byte.into());
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    format!("steam://run/{steam_id}//{encoded}/")
+}
+
+/// A command that opens `url` with the platform's URL handler
+fn open_url(url: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "", url]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(url);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(url);
+        command
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor};
+
+    use crate::{
+        core::{manage::install_northstar, profiles::Profile, utils::TempDir},
+        error::ThermiteError,
+    };
+
+    use super::{
+        detect, launch_northstar_with, set_launch_method, steam_url, LaunchMethod, LaunchVia,
+        PROXY_DLL,
+    };
+
+    const NORTHSTAR: &[u8] = include_bytes!("test_media/northstar.zip");
+
+    #[test]
+    fn switch_methods() {
+        let dir = TempDir::create("./test_launch_method").expect("Unable to create temp dir");
+        assert!(matches!(
+            set_launch_method(&dir, LaunchMethod::Proxy),
+            Err(ThermiteError::NotInstalled(_))
+        ));
+
+        install_northstar(Cursor::new(NORTHSTAR), &dir).expect("install Northstar");
+        assert_eq!(detect(&dir).unwrap().method(), Some(LaunchMethod::Proxy));
+
+        set_launch_method(&dir, LaunchMethod::Launcher).expect("switch to launcher");
+        let setup = detect(&dir).unwrap();
+        assert_eq!(setup.method(), Some(LaunchMethod::Launcher));
+        assert!(setup.disabled_proxy);
+
+        // updating Northstar keeps the proxy disabled
+        install_northstar(Cursor::new(NORTHSTAR), &dir).expect("update Northstar");
+        assert_eq!(detect(&dir).unwrap().method(), Some(LaunchMethod::Launcher));
+
+        set_launch_method(&dir, LaunchMethod::Proxy).expect("switch to proxy");
+        assert_eq!(detect(&dir).unwrap().method(), Some(LaunchMethod::Proxy));
+
+        fs::remove_file(dir.join(PROXY_DLL)).unwrap();
+        set_launch_method(&dir, LaunchMethod::Launcher).unwrap();
+        assert!(matches!(
+            set_launch_method(&dir, LaunchMethod::Proxy),
+            Err(ThermiteError::MissingFile(_))
+        ));
+    }
+
+    #[test]
+    fn launch_args() {
+        let args = ["-profile=Test Profile".to_owned(), "+map mp_glitch".into()];
+        assert_eq!(
+            steam_url(1_237_970, &args),
+            "steam://run/1237970//-profile=Test%20Profile%20%2Bmap%20mp_glitch/"
+        );
+
+        let dir = TempDir::create("./test_launch_northstar").expect("Unable to create temp dir");
+        let profile = Profile::default_profile(dir.to_path_buf());
+        let launch = |via| launch_northstar_with(&dir, &["-dev"], Some(&profile), &via);
+        assert!(matches!(
+            launch(LaunchVia::Direct),
+            Err(ThermiteError::NotInstalled(_))
+        ));
+
+        fs::write(dir.join("Northstar.dll"), "").unwrap();
+        assert!(matches!(
+            launch(LaunchVia::Direct),
+            Err(ThermiteError::MissingFile(_))
+        ));
+    }
+}
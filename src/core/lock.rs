@@ -0,0 +1,360 @@
+//! Advisory lock on a directory mods are installed into
+//!
+//! Installs, updates and removals hold `.thermite/lock` in the directory they change, holding the id of the
+//! process that created it, so two tools or two instances working on the same profile can't interleave their
+//! changes. The lock is only advisory: anything that doesn't use thermite ignores it. A lock left behind by a
+//! process that no longer runs is taken over, and of several processes taking over the same lock only one
+//! gets it.
+//!
+//! The lock is re-entrant on the thread holding it, so an update that installs the new version, or a
+//! transaction staging several packages, only creates the lock file once and removes it when the last holder
+//! is dropped. Other threads of the same process wait for it to be released, checking their
+//! [`CancelToken`](crate::cancel::CancelToken)s while they do.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex, PoisonError,
+    },
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use tracing::debug;
+
+use crate::{
+    cancel,
+    error::{Result, ThermiteError},
+    time,
+};
+
+use super::{journal::JOURNAL_DIR, vfs};
+
+const LOCK_FILE: &str = "lock";
+/// How often a thread waiting for another one's lock checks if it was cancelled
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The thread holding each lock file in this process, and how many times it took it
+static HELD: Mutex<BTreeMap<PathBuf, (ThreadId, usize)>> = Mutex::new(BTreeMap::new());
+/// Notified whenever a lock in `HELD` is released
+static RELEASED: Condvar = Condvar::new();
+
+/// A held lock on a directory, released when dropped
+#[derive(Debug)]
+#[must_use = "the directory is unlocked as soon as this is dropped"]
+pub struct DirLock {
+    path: PathBuf,
+    /// `false` for the stand-in returned while planning, which holds nothing
+    held: bool,
+}
+
+impl DirLock {
+    /// The lock file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+        let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some((_, count)) = held.get_mut(&self.path) else {
+            return;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        // the entry stays until the file is gone, so no other thread creates it in the meantime
+        drop(held);
+        if let Err(e) = vfs::current().remove_file(&self.path) {
+            debug!("Unable to remove {}: {e}", self.path.display());
+        }
+        HELD.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.path);
+        RELEASED.notify_all();
+    }
+}
+
+/// Locks `dir` until the returned [`DirLock`] is dropped
+///
+/// If another thread of this process holds the lock this waits until it's released.
+///
+/// # Errors
+/// * `ThermiteError::DirectoryLocked` if another running process holds the lock
+/// * `ThermiteError::Cancelled` or `ThermiteError::Timeout` if a token was cancelled while waiting
+/// * IO errors
+pub fn lock_dir(dir: impl AsRef<Path>) -> Result<DirLock> {
+    let dir = dir.as_ref();
+    let lock_dir = dir.join(JOURNAL_DIR);
+    // planning doesn't touch the disk, so it needs no lock
+    if vfs::is_dry_run() {
+        return Ok(DirLock {
+            path: lock_dir.join(LOCK_FILE),
+            held: false,
+        });
+    }
+    let fs = vfs::current();
+    fs.create_dir_all(&lock_dir)?;
+    let path = fs
+        .canonicalize(&lock_dir)
+        .unwrap_or(lock_dir)
+        .join(LOCK_FILE);
+
+    let this = thread::current().id();
+    {
+        let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match held.get_mut(&path) {
+                Some((owner, count)) if *owner == this => {
+                    *count += 1;
+                    return Ok(DirLock { path, held: true });
+                }
+                Some(_) => {
+                    cancel::checkpoint(format!("waiting for the lock on {}", dir.display()))?;
+                    held = RELEASED
+                        .wait_timeout(held, WAIT_INTERVAL)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+                None => {
+                    // claimed with no holders yet, so other threads wait while the file is created below
+                    held.insert(path.clone(), (this, 0));
+                    break;
+                }
+            }
+        }
+    }
+
+    let res = create_lock(&path, dir);
+    let mut held = HELD.lock().unwrap_or_else(PoisonError::into_inner);
+    match res {
+        Ok(()) => {
+            held.insert(path.clone(), (this, 1));
+            Ok(DirLock { path, held: true })
+        }
+        Err(e) => {
+            held.remove(&path);
+            RELEASED.notify_all();
+            Err(e)
+        }
+    }
+}
+
+/// Creates the lock file, taking it over if the process that created it no longer runs
+fn create_lock(path: &Path, dir: &Path) -> Result<()> {
+    let fs = vfs::current();
+    let pid = current_pid().to_string();
+    // more tries in case other processes are taking over the same stale lock
+    for _ in 0..3 {
+        match fs.write_new(path, pid.as_bytes()) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        match stale_contents(path) {
+            Some(stale) => take_over(path, &stale)?,
+            None => return Err(ThermiteError::DirectoryLocked(dir.into())),
+        }
+    }
+    Err(ThermiteError::DirectoryLocked(dir.into()))
+}
+
+/// The contents of the lock file if the process that created it no longer runs, `None` if it still does
+fn stale_contents(path: &Path) -> Option<String> {
+    let fs = vfs::current();
+    let mut contents = fs.read_to_string(path).unwrap_or_default();
+    if contents.is_empty() {
+        // the process that created it may not have written its id yet
+        time::sleep(WAIT_INTERVAL);
+        contents = fs.read_to_string(path).unwrap_or_default();
+    }
+    match contents.trim().parse::<u32>() {
+        // the lock isn't in `HELD`, so one with our id was left by an earlier process with the same id
+        Ok(owner) if owner != current_pid() && is_alive(owner) => None,
+        _ => Some(contents),
+    }
+}
+
+/// Removes a stale lock file, unless another process replaced it with its own since it was read as `stale`
+///
+/// The file is renamed out of the way first, which is atomic, so what's checked is exactly what was removed.
+fn take_over(path: &Path, stale: &str) -> Result<()> {
+    static TAKEOVERS: AtomicUsize = AtomicUsize::new(0);
+
+    let fs = vfs::current();
+    let aside = path.with_file_name(format!(
+        "{LOCK_FILE}.stale-{}-{}",
+        current_pid(),
+        TAKEOVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    match fs.rename(path, &aside) {
+        Ok(()) => {}
+        // another process removed it first
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    if fs.read_to_string(&aside).is_ok_and(|c| c == stale) {
+        debug!("Taking over stale lock {}", path.display());
+        if let Err(e) = fs.remove_file(&aside) {
+            debug!("Unable to remove {}: {e}", aside.display());
+        }
+    } else {
+        debug!("{} was taken over by another process", path.display());
+        fs.rename(&aside, path)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn current_pid() -> u32 {
+    std::process::id()
+}
+
+// there's only ever one process on the web
+#[cfg(target_arch = "wasm32")]
+const fn current_pid() -> u32 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+fn is_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(windows)]
+fn is_alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/FO", "CSV", "/NH"])
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).contains(&format!("\"{pid}\"")))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn is_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+#[cfg(not(any(unix, windows)))]
+const fn is_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        cancel::CancelToken,
+        core::{journal::JOURNAL_DIR, utils::TempDir},
+        error::ThermiteError,
+    };
+
+    use super::{lock_dir, take_over, LOCK_FILE};
+
+    #[test]
+    fn shared_within_thread() {
+        let dir = TempDir::create("./test_lock_shared").expect("Unable to create temp dir");
+        let first = lock_dir(&dir).expect("lock");
+        let second = lock_dir(&dir).expect("the lock should be re-entrant on its thread");
+        assert!(first.path().exists());
+
+        drop(first);
+        assert!(second.path().exists(), "the lock has another holder");
+        let path = second.path().to_path_buf();
+        drop(second);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn exclusive_between_threads() {
+        let dir = TempDir::create("./test_lock_threads").expect("Unable to create temp dir");
+        let lock = lock_dir(&dir).expect("lock");
+        let released = AtomicBool::new(false);
+        let (locked_tx, locked) = mpsc::channel();
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                let token = CancelToken::with_timeout(Duration::from_millis(100));
+                assert!(matches!(
+                    token.run(|| lock_dir(&dir)),
+                    Err(ThermiteError::Timeout { .. })
+                ));
+
+                let lock = lock_dir(&dir).expect("lock once released");
+                assert!(
+                    released.load(Ordering::SeqCst),
+                    "only one thread at a time should hold the lock"
+                );
+                locked_tx.send(lock.path().exists()).unwrap();
+            });
+
+            thread::sleep(Duration::from_millis(300));
+            assert!(locked.try_recv().is_err(), "the other thread should wait");
+            released.store(true, Ordering::SeqCst);
+            drop(lock);
+            assert!(locked.recv().unwrap());
+        });
+        assert!(!dir.join(JOURNAL_DIR).join(LOCK_FILE).exists());
+    }
+
+    #[test]
+    fn take_over_replaced_lock() {
+        let dir = TempDir::create("./test_lock_take_over").expect("Unable to create temp dir");
+        let file = dir.join(JOURNAL_DIR).join(LOCK_FILE);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+
+        // another process took over the stale lock after it was read
+        fs::write(&file, "1").unwrap();
+        take_over(&file, &u32::MAX.to_string()).expect("take over");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "1");
+
+        fs::write(&file, u32::MAX.to_string()).unwrap();
+        take_over(&file, &u32::MAX.to_string()).expect("take over");
+        assert!(!file.exists());
+        assert_eq!(fs::read_dir(file.parent().unwrap()).unwrap().count(), 0);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn held_by_other_process() {
+        let dir = TempDir::create("./test_lock_other").expect("Unable to create temp dir");
+        let file = dir.join(JOURNAL_DIR).join(LOCK_FILE);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+
+        // init always runs
+        fs::write(&file, "1").unwrap();
+        assert!(matches!(
+            lock_dir(&dir),
+            Err(ThermiteError::DirectoryLocked(p)) if p == *dir
+        ));
+
+        fs::write(&file, u32::MAX.to_string()).unwrap();
+        let lock = lock_dir(&dir).expect("a stale lock should be taken over");
+        assert_eq!(
+            fs::read_to_string(lock.path()).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+}
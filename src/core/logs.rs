@@ -0,0 +1,395 @@
+//! Finding out what went wrong from Northstar's logs
+//!
+//! Northstar writes an `nslog<timestamp>.txt` to the profile's `logs` directory every time the game starts.
+//! [`parse_log`] pulls script errors, mods that failed to load and crashes out of one, and [`attribute`] works
+//! out which installed mod each one came from, so a launcher can tell users "Mod X failed to load" instead of
+//! sending them the raw log.
+
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{config, error::Result, model::InstalledMod};
+
+use super::{utils::find_mods, vfs};
+
+lazy_static! {
+    /// `[12:34:56] [SCRIPT UI] [error] message`, older versions leave out the context
+    static ref LINE: Regex =
+        Regex::new(r"^\[[\d:.]+\] (?:\[([^\]]+)\] )?\[(\w+)\] ?(.*)$").unwrap();
+    static ref SCRIPT_ERROR: Regex = Regex::new(r"^SCRIPT ERROR: (?:\[\w+\] )?(.*)$").unwrap();
+    /// `*FUNCTION [CodeCallback_Foo()] ui/menu_foo.nut line [42]`
+    static ref CALLSTACK: Regex = Regex::new(r"\*FUNCTION \[.*\] (\S+\.nut) line \[(\d+)\]").unwrap();
+    /// `ui/menu_foo.nut line = (42) column = (7)`
+    static ref COMPILE_LOCATION: Regex = Regex::new(r"(\S+\.nut) line = \((\d+)\)").unwrap();
+    static ref MOD_DIR: Regex = Regex::new(r#"mods[/\\]([^/\\\s:'"]+)"#).unwrap();
+    static ref QUOTED: Regex = Regex::new(r#"['"]([^'"]+)['"]"#).unwrap();
+    static ref DLL: Regex = Regex::new(r"([\w.\-]+\.dll)").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// A script threw at runtime
+    ScriptError,
+    /// A script failed to compile, which stops the VM from starting
+    CompileError,
+    /// A mod wasn't loaded
+    ModLoadFailed,
+    /// A plugin couldn't be loaded
+    PluginFailed,
+    /// The game crashed
+    Crash,
+}
+
+/// Something that went wrong, as found in a log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogIssue {
+    pub kind: IssueKind,
+    pub message: String,
+    /// Line of the log the issue starts on, starting at 1
+    pub line: usize,
+    /// The script involved, relative to `scripts/vscripts`
+    pub script: Option<String>,
+    pub script_line: Option<u32>,
+    /// The DLL a crash happened in or a plugin that failed to load
+    pub module: Option<String>,
+    /// The name of the mod responsible, from its `mod.json`. Set by [`attribute`]
+    pub mod_name: Option<String>,
+    /// The `author-name` of the package providing the mod. Set by [`attribute`]
+    pub package: Option<String>,
+}
+
+impl LogIssue {
+    fn new(kind: IssueKind, message: impl Into<String>, line: usize) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            line,
+            script: None,
+            script_line: None,
+            module: None,
+            mod_name: None,
+            package: None,
+        }
+    }
+}
+
+impl Display for LogIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let culprit = self
+            .mod_name
+            .as_deref()
+            .or(self.module.as_deref())
+            .unwrap_or("Unknown mod");
+        match self.kind {
+            IssueKind::ScriptError => write!(f, "{culprit} had a script error")?,
+            IssueKind::CompileError => write!(f, "{culprit} has a script that doesn't compile")?,
+            IssueKind::ModLoadFailed => write!(f, "{culprit} failed to load")?,
+            IssueKind::PluginFailed => write!(f, "Plugin {culprit} failed to load")?,
+            IssueKind::Crash => write!(f, "The game crashed in {culprit}")?,
+        }
+        if let (Some(script), Some(line)) = (&self.script, self.script_line) {
+            write!(f, " ({script} line {line})")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Extracts the issues from the contents of an `nslog` file, in the order they happened
+#[must_use]
+pub fn parse_log(raw: &str) -> Vec<LogIssue> {
+    let mut issues: Vec<LogIssue> = vec![];
+    // the issue later lines like callstacks and crash details belong to
+    let mut open: Option<usize> = None;
+
+    for (i, line) in raw.lines().enumerate() {
+        let Some(caps) = LINE.captures(line.trim_end()) else {
+            continue;
+        };
+        let context = caps.get(1).map_or("", |c| c.as_str());
+        let level = caps[2].to_ascii_lowercase();
+        let message = caps[3].trim();
+        let line = i + 1;
+
+        if let Some(script) = SCRIPT_ERROR.captures(message) {
+            issues.push(LogIssue::new(IssueKind::ScriptError, &script[1], line));
+            open = Some(issues.len() - 1);
+            continue;
+        }
+        if message.contains("COMPILE ERROR") {
+            let message = message.replace("COMPILE ERROR", "");
+            let mut issue = LogIssue::new(IssueKind::CompileError, message.trim(), line);
+            set_location(&mut issue, &COMPILE_LOCATION, &message);
+            issues.push(issue);
+            open = Some(issues.len() - 1);
+            continue;
+        }
+        if message.contains("has crashed") {
+            issues.push(LogIssue::new(IssueKind::Crash, "", line));
+            open = Some(issues.len() - 1);
+            continue;
+        }
+        if let Some(issue) = open.and_then(|i| issues.get_mut(i)) {
+            if add_detail(issue, context, message) {
+                continue;
+            }
+            open = None;
+        }
+
+        if !matches!(level.as_str(), "error" | "warning" | "critical")
+            || context.starts_with("SCRIPT")
+        {
+            continue;
+        }
+        let lower = message.to_lowercase();
+        if lower.contains("plugin") || (lower.contains("failed") && DLL.is_match(message)) {
+            let mut issue = LogIssue::new(IssueKind::PluginFailed, message, line);
+            issue.module = DLL.captures(message).map(|c| c[1].to_owned());
+            issues.push(issue);
+        } else if lower.contains("mod") && (lower.contains("fail") || lower.contains("error")) {
+            let mut issue = LogIssue::new(IssueKind::ModLoadFailed, message, line);
+            issue.mod_name = MOD_DIR
+                .captures(message)
+                .or_else(|| QUOTED.captures(message))
+                .map(|c| c[1].to_owned());
+            issues.push(issue);
+        }
+    }
+
+    issues
+}
+
+/// Adds a follow-up line to the issue it belongs to. Returns `false` if the line isn't part of it
+fn add_detail(issue: &mut LogIssue, context: &str, message: &str) -> bool {
+    match issue.kind {
+        IssueKind::ScriptError | IssueKind::CompileError if context.starts_with("SCRIPT") => {
+            if issue.script.is_none() {
+                set_location(issue, &CALLSTACK, message);
+                set_location(issue, &COMPILE_LOCATION, message);
+            }
+            true
+        }
+        // nothing runs after a crash, the rest of the log is the crash dump
+        IssueKind::Crash => {
+            if let Some(cause) = message.strip_prefix("Cause:") {
+                issue.message = cause.trim().to_owned();
+            } else if let Some(at) = message.strip_prefix("At:") {
+                issue.module = DLL.captures(at).map(|c| c[1].to_owned());
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+fn set_location(issue: &mut LogIssue, re: &Regex, message: &str) {
+    if let Some(caps) = re.captures(message) {
+        issue.script = Some(caps[1].replace('\\', "/"));
+        issue.script_line = caps[2].parse().ok();
+    }
+}
+
+/// Fills in which of the `installed` mods each issue came from, where that can be worked out
+///
+/// * script errors go to the mod that lists or ships the script, preferring mods other than the loader's core
+///   mods since those override a lot of the game's own scripts
+/// * crashes and plugin failures go to the package that ships the DLL in its `plugins` directory
+/// * mod load failures go to the mod whose directory or name the message mentions
+pub fn attribute(issues: &mut [LogIssue], installed: &[InstalledMod]) {
+    let config = config::config();
+    let fs = vfs::current();
+    let mut by_priority = installed.iter().collect::<Vec<_>>();
+    by_priority.sort_by_key(|m| config.game.is_core_mod(&m.mod_json.name));
+
+    for issue in issues.iter_mut() {
+        let culprit = match (&issue.script, &issue.module, &issue.mod_name) {
+            (Some(script), ..) => by_priority.iter().copied().find(|m| {
+                lists_script(m, script)
+                    || fs
+                        .exists(&m.path.join("mod/scripts/vscripts").join(script))
+                        .unwrap_or(false)
+            }),
+            (None, Some(module), _) => installed.iter().find(|m| {
+                package_dir(m).is_some_and(|dir| {
+                    fs.exists(&dir.join("plugins").join(module))
+                        .unwrap_or(false)
+                })
+            }),
+            (None, None, Some(name)) => installed.iter().find(|m| {
+                m.mod_json.name.eq_ignore_ascii_case(name)
+                    || m.path
+                        .file_name()
+                        .is_some_and(|dir| dir.eq_ignore_ascii_case(name))
+            }),
+            (None, None, None) => None,
+        };
+
+        if let Some(m) = culprit {
+            issue.mod_name = Some(m.mod_json.name.clone());
+            issue.package = Some(format!("{}-{}", m.author, m.manifest.name));
+        }
+    }
+}
+
+fn lists_script(m: &InstalledMod, script: &str) -> bool {
+    m.mod_json.scripts.iter().any(|s| {
+        s.get("Path")
+            .and_then(Value::as_str)
+            .is_some_and(|p| p.replace('\\', "/").eq_ignore_ascii_case(script))
+    })
+}
+
+/// `packages/<package>` for a mod at `packages/<package>/mods/<mod>`
+fn package_dir(m: &InstalledMod) -> Option<&Path> {
+    m.path.parent()?.parent()
+}
+
+/// The newest `nslog` file in a profile's `logs` directory
+///
+/// # Errors
+/// * IO errors
+pub fn latest_log(profile_dir: impl AsRef<Path>) -> Result<Option<PathBuf>> {
+    let fs = vfs::current();
+    let dir = profile_dir.as_ref().join("logs");
+    if !fs.exists(&dir)? {
+        return Ok(None);
+    }
+
+    // the timestamps in the names sort chronologically
+    Ok(fs
+        .read_dir(&dir)?
+        .into_iter()
+        .filter(|e| !e.is_dir && e.file_name().starts_with("nslog"))
+        .map(|e| e.path)
+        .max())
+}
+
+/// Parses the profile's newest log and attributes its issues to the mods installed in the profile
+///
+/// Returns an empty list if there's no log yet.
+///
+/// # Errors
+/// * IO errors
+/// * Improperly formatted JSON files
+pub fn analyze_latest(profile_dir: impl AsRef<Path>) -> Result<Vec<LogIssue>> {
+    let profile_dir = profile_dir.as_ref();
+    let Some(log) = latest_log(profile_dir)? else {
+        return Ok(vec![]);
+    };
+
+    let fs = vfs::current();
+    let mut issues = parse_log(&String::from_utf8_lossy(&fs.read(&log)?));
+    let mut installed = vec![];
+    for dir in ["packages", "mods"].map(|d| profile_dir.join(d)) {
+        if fs.exists(&dir)? {
+            installed.append(&mut find_mods(dir)?);
+        }
+    }
+    attribute(&mut issues, &installed);
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor};
+
+    use crate::{
+        config::ThermiteConfig,
+        core::{manage::install_with_config, utils::TempDir},
+        test_util::mod_archive_with,
+    };
+
+    use super::{analyze_latest, parse_log, IssueKind};
+
+    const LOG: &str = r"[20:01:02] [NORTHSTAR] [info] NorthstarLauncher version: 1.20.0.0
+[20:01:03] [NORTHSTAR] [info] Loading mods...
+[20:01:03] [NORTHSTAR] [error] Failed reading mod file R2Northstar/packages/Foo-Broken-1.0.0/mods/Broken/mod.json: invalid json
+[20:01:04] [NORTHSTAR] [error] Failed to load library 'Overlay.dll'
+[20:01:10] [SCRIPT UI] [error] SCRIPT ERROR: [UI] the index 'count' does not exist
+[20:01:10] [SCRIPT UI] [error]  -> local x = data.count
+[20:01:10] [SCRIPT UI] [info] CALLSTACK
+[20:01:10] [SCRIPT UI] [info] *FUNCTION [UpdateHud()] ui/hud_bar.nut line [12]
+[20:01:10] [SCRIPT UI] [info] *FUNCTION [CodeCallback_Frame()] _menus.nut line [400]
+[20:01:11] [NORTHSTAR] [info] Loaded mod Mock.Bar
+[20:02:00] [NORTHSTAR] [error] Northstar has crashed! Crash info can be found at R2Northstar/logs!
+[20:02:00] [NORTHSTAR] [error] Cause: Access Violation
+[20:02:00] [NORTHSTAR] [error] At: Bar.dll + 0x1a2b
+";
+
+    #[test]
+    fn parse_issues() {
+        let issues = parse_log(LOG);
+        let kinds = issues.iter().map(|i| i.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                IssueKind::ModLoadFailed,
+                IssueKind::PluginFailed,
+                IssueKind::ScriptError,
+                IssueKind::Crash
+            ]
+        );
+
+        assert_eq!(issues[0].mod_name.as_deref(), Some("Broken"));
+        assert_eq!(issues[1].module.as_deref(), Some("Overlay.dll"));
+        assert_eq!(issues[2].message, "the index 'count' does not exist");
+        assert_eq!(issues[2].script.as_deref(), Some("ui/hud_bar.nut"));
+        assert_eq!(issues[2].script_line, Some(12));
+        assert_eq!(issues[2].line, 5);
+        assert_eq!(issues[3].message, "Access Violation");
+        assert_eq!(issues[3].module.as_deref(), Some("Bar.dll"));
+    }
+
+    #[test]
+    fn attribute_to_mods() {
+        let dir = TempDir::create("./test_log_analysis").expect("Unable to create temp dir");
+        let profile = dir.join("R2Northstar");
+        let config = ThermiteConfig {
+            install_plugins: true,
+            allow_unverified_plugins: true,
+            ..Default::default()
+        };
+        install_with_config(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive_with(
+                "Bar",
+                "1.0.0",
+                &[
+                    ("mods/Bar/mod/scripts/vscripts/ui/hud_bar.nut", ""),
+                    ("plugins/Bar.dll", ""),
+                ],
+            )),
+            profile.join("packages"),
+            |_| Ok(()),
+            &config,
+        )
+        .expect("install");
+
+        let logs = profile.join("logs");
+        fs::create_dir_all(&logs).unwrap();
+        fs::write(logs.join("nslog2024-01-01 10-00-00.txt"), "").unwrap();
+        fs::write(logs.join("nslog2024-01-02 10-00-00.txt"), LOG).unwrap();
+
+        let issues = analyze_latest(&profile).expect("analyze");
+        assert_eq!(issues.len(), 4);
+        let script = &issues[2];
+        assert_eq!(script.mod_name.as_deref(), Some("Mock.Bar"));
+        assert_eq!(script.package.as_deref(), Some("Foo-Bar"));
+        assert_eq!(
+            script.to_string(),
+            "Mock.Bar had a script error (ui/hud_bar.nut line 12): the index 'count' does not exist"
+        );
+        assert_eq!(issues[3].package.as_deref(), Some("Foo-Bar"));
+        assert_eq!(
+            issues[3].to_string(),
+            "The game crashed in Mock.Bar: Access Violation"
+        );
+        // not installed, so nothing to attribute it to
+        assert_eq!(issues[1].package, None);
+    }
+}
@@ -1,18 +1,66 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     error::Error,
-    ffi::OsString,
-    fs::{self, OpenOptions},
+    fmt::Debug,
     io::{self, Read, Seek, Write},
-    path::{Path, PathBuf},
+    num::NonZeroUsize,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use crate::error::{Result, ThermiteError};
+#[cfg(feature = "async")]
+use std::future::Future;
 
-use zip::ZipArchive;
+#[cfg(feature = "async")]
+use crate::blocking;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::verify::{self, HashingWriter};
+use crate::{
+    api::source::PackageSource,
+    cancel::{self, CancelToken},
+    config::{self, OverwritePolicy, ThermiteConfig},
+    error::{ErrorContext, Result, ThermiteError},
+    http::{self, HttpRequest, HttpResponse},
+    metrics,
+    model::{EnabledMods, InstalledMod, Mod, ModList, ModPack, ModVersion},
+    pool::{self, ThreadPool},
+    server,
+    time::{self, Instant},
+};
+
+use serde::Serialize;
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 
 use tracing::{debug, trace, warn};
 
-use super::utils::validate_modstring;
+use super::{
+    audit::{self, AuditEntry, AuditOperation},
+    events::{self, Event},
+    hooks::{self, HookContext, HookOperation, HookStage},
+    journal::{self, JournalEntry, JOURNAL_DIR},
+    launch,
+    lock::{lock_dir, DirLock},
+    report::{self, DownloadReport, InstallReport, NorthstarReport},
+    resolve::{self, InstallConflict, Resolution},
+    status::{self, StatusEvent},
+    utils::{
+        check_game_running, check_space, check_writable, deprecation_warning, find_package_mods,
+        get_enabled_mods, long_path, package_dir, package_files, parse_modstring,
+        resolve_install_order, set_mod_enabled, suggest_packages, unchanged_files,
+        validate_modstring, OutdatedPackage, PackageKind, PackageLayout, AUTHOR_FILE,
+    },
+    vfs::{self, DirEntry},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{
+    cache::{PackageCache, PruneReport},
+    northstar,
+    utils::write_checksums,
+};
 
 const CHUNK_SIZE: usize = 1024;
 
@@ -23,69 +71,870 @@ const CHUNK_SIZE: usize = 1024;
 /// * `cb` - Callback to call with every chunk read. Params are |`delta_bytes`: u64, `current_bytes`: u64, `total_size`: u64|
 ///
 /// # Returns
-/// * total bytes downloaded & written
+/// * a [`DownloadReport`] with the total bytes downloaded & written
+///
+/// # Errors
+/// * IO Errors
+pub fn download_with_progress<F>(
+    output: impl Write,
+    url: impl AsRef<str>,
+    cb: F,
+) -> Result<DownloadReport>
+where
+    F: Fn(u64, u64, u64),
+{
+    download_with_limit(output, url, config::config().download_timeout, cb)
+}
+
+/// A step of a download or install, so frontends can show every stage with one progress bar or several
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    /// A download started. `total` is its size in bytes, 0 if the server didn't report one
+    Started { total: u64 },
+    /// `bytes` more bytes were downloaded
+    Chunk { bytes: u64 },
+    /// Entry `current` of the `total` entries in the archive is being extracted
+    ExtractingFile {
+        name: String,
+        current: usize,
+        total: usize,
+    },
+    /// The download or install is done
+    Finished,
+}
+
+/// [`download_with_progress`] reporting [`ProgressEvent`]s instead of raw byte counts
+///
+/// `cb` can forward the events to another thread, e.g. a UI:
+///
+/// ```no_run
+/// # use std::{sync::mpsc, thread};
+/// # use thermite::core::manage::{download_with_events, ProgressEvent};
+/// let (tx, rx) = mpsc::channel();
+/// let download = thread::spawn(move || {
+///     let mut archive = vec![];
+///     download_with_events(&mut archive, "https://example.com/Foo-Bar-1.0.0.zip", move |e| {
+///         let _ = tx.send(e);
+///     })
+///     .map(|_| archive)
+/// });
+/// for event in rx {
+///     if let ProgressEvent::Chunk { bytes } = event {
+///         println!("+{bytes} bytes");
+///     }
+/// }
+/// # let _ = download.join();
+/// ```
 ///
 /// # Errors
 /// * IO Errors
-pub fn download_with_progress<F>(mut output: impl Write, url: impl AsRef<str>, cb: F) -> Result<u64>
+pub fn download_with_events(
+    output: impl Write,
+    url: impl AsRef<str>,
+    cb: impl Fn(ProgressEvent),
+) -> Result<DownloadReport> {
+    let started = std::cell::Cell::new(false);
+    let report = download_with_progress(output, url, |delta, _, total| {
+        if !started.replace(true) {
+            cb(ProgressEvent::Started { total });
+        }
+        if delta > 0 {
+            cb(ProgressEvent::Chunk { bytes: delta });
+        }
+    })?;
+    cb(ProgressEvent::Finished);
+    Ok(report)
+}
+
+/// `download_with_progress` with an explicit time limit instead of the global config's
+pub(crate) fn download_with_limit<F>(
+    mut output: impl Write,
+    url: impl AsRef<str>,
+    limit: Option<Duration>,
+    cb: F,
+) -> Result<DownloadReport>
 where
     F: Fn(u64, u64, u64),
 {
+    let started = Instant::now();
+    let operation = || format!("downloading {}", url.as_ref());
+
     //send the request
-    let res = ureq::get(url.as_ref()).call()?;
+    let res = http::get(&HttpRequest::get(url.as_ref()).timeout(limit), operation())?;
 
-    let file_size = res
-        .header("Content-Length")
-        .unwrap_or_else(|| {
-            warn!("Response missing 'Content-Length' header");
-            "0"
-        })
-        .parse::<u64>()?;
+    let (report, file_size) = start_download(url.as_ref(), res.header("Content-Length"))?;
+    let downloaded = copy_body(
+        res,
+        &mut output,
+        0,
+        file_size,
+        &operation(),
+        started,
+        limit,
+        cb,
+    )?;
+    finish_download(report, downloaded, started)
+}
+
+/// The report of a download that's starting, and its size parsed from the `Content-Length` header
+fn start_download(url: &str, content_length: Option<&str>) -> Result<(DownloadReport, u64)> {
+    let mut report = DownloadReport {
+        url: url.into(),
+        ..Default::default()
+    };
+    let file_size = if let Some(len) = content_length {
+        len.parse::<u64>()?
+    } else {
+        report::warning(
+            &mut report.warnings,
+            "Response missing 'Content-Length' header",
+        );
+        0
+    };
     debug!("Downloading file of size: {}", file_size);
+    status::emit(StatusEvent::DownloadStarted {
+        url: url.into(),
+        size: file_size,
+    });
 
-    //start download in chunks
+    debug!("Starting download from {}", url);
+    Ok((report, file_size))
+}
+
+/// The config's download speed limit in bytes per second, if it has one
+fn throttle_rate() -> Option<u64> {
+    config::config()
+        .network
+        .throttle_bytes_per_sec
+        .filter(|rate| *rate > 0)
+}
+
+/// How long to wait for the average speed of a download to get back under `rate`
+fn throttle_delay(rate: u64, downloaded: u64, copy_started: Instant) -> Option<Duration> {
+    let nanos = u128::from(downloaded) * 1_000_000_000 / u128::from(rate);
+    let due = Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX));
+    due.checked_sub(copy_started.elapsed())
+}
+
+/// Writes a response body to `output` in chunks, calling `cb` like [`download_with_progress`] does
+///
+/// `offset` bytes of a `total` sized file were already written, progress includes them
+#[allow(clippy::too_many_arguments)]
+fn copy_body<F>(
+    res: HttpResponse,
+    output: &mut impl Write,
+    offset: u64,
+    total: u64,
+    operation: &str,
+    started: Instant,
+    limit: Option<Duration>,
+    cb: F,
+) -> Result<u64>
+where
+    F: Fn(u64, u64, u64),
+{
     let mut downloaded: u64 = 0;
     let mut buffer = [0; CHUNK_SIZE];
     let mut body = res.into_reader();
-    debug!("Starting download from {}", url.as_ref());
+    let throttle = throttle_rate();
+    let copy_started = Instant::now();
 
-    while let Ok(n) = body.read(&mut buffer) {
+    loop {
+        cancel::checkpoint(operation)?;
+        let n = match body.read(&mut buffer) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(ThermiteError::from_io(e, operation, started, limit)),
+        };
         output.write_all(&buffer[0..n])?;
         downloaded += n as u64;
 
-        cb(n as u64, downloaded, file_size);
+        cb(n as u64, offset + downloaded, total);
 
         if n == 0 {
             break;
         }
+        if let Some(ahead) =
+            throttle.and_then(|rate| throttle_delay(rate, downloaded, copy_started))
+        {
+            time::sleep(ahead);
+        }
     }
+    output.flush()?;
 
     Ok(downloaded)
 }
 
+fn finish_download(
+    mut report: DownloadReport,
+    downloaded: u64,
+    started: Instant,
+) -> Result<DownloadReport> {
+    status::emit(StatusEvent::DownloadFinished {
+        url: report.url.clone(),
+        bytes: downloaded,
+    });
+
+    report.bytes = downloaded;
+    report.duration = started.elapsed();
+    metrics::record_download(report.bytes, report.duration);
+    Ok(report)
+}
+
+/// Download a file to `path`, continuing where a previous attempt stopped
+///
+/// If `path` already has some of the file, only the rest is requested with a `Range` header. Servers that
+/// don't support ranges send the whole file, which replaces what was there. Either way the file's final size
+/// is checked against the size the server reported.
+///
+/// # Params
+/// * `path` - File to write the data to
+/// * `url` - URL to download from
+/// * `cb` - Callback to call with every chunk read. Params are |`delta_bytes`: u64, `current_bytes`: u64, `total_size`: u64|, counting the bytes that were already downloaded
+///
+/// # Returns
+/// * a [`DownloadReport`] with the bytes downloaded & written by this call
+///
+/// # Errors
+/// * IO Errors, the partial file is kept so the download can be resumed
+/// * `ThermiteError::Cancelled` or `ThermiteError::Timeout` if a token around the call was cancelled. The
+///   partial file is kept as well, remove it if the download won't be resumed or use
+///   [`download_file_cancellable`], which does
+/// * `ThermiteError::IncompleteDownload` if the file doesn't have the size the server reported
+/// * `ThermiteError::InsufficientSpace` if the rest of the file wouldn't fit on `path`'s filesystem
+pub fn download_resumable<F>(
+    path: impl AsRef<Path>,
+    url: impl AsRef<str>,
+    cb: F,
+) -> Result<DownloadReport>
+where
+    F: Fn(u64, u64, u64),
+{
+    let (path, url) = (path.as_ref(), url.as_ref());
+    let limit = config::config().download_timeout;
+    let started = Instant::now();
+    let operation = format!("downloading {url}");
+    let fs = vfs::current();
+
+    let offset = if fs.exists(path)? {
+        fs.file_len(path)?
+    } else {
+        0
+    };
+    let mut request = HttpRequest::get(url).timeout(limit);
+    if offset > 0 {
+        request = request.header("Range", format!("bytes={offset}-"));
+    }
+    let res = match http::get(&request, &operation) {
+        // the file is already complete, or bigger than it should be; start over to be sure
+        Err(ThermiteError::HttpStatus { status: 416, .. }) => {
+            http::get(&HttpRequest::get(url).timeout(limit), &operation)?
+        }
+        res => res?,
+    };
+
+    let mut report = DownloadReport {
+        url: url.into(),
+        ..Default::default()
+    };
+    let resumed = offset > 0 && res.status == 206;
+    // with a range the total is after the slash in `Content-Range: bytes start-end/total`
+    let total = if resumed {
+        res.header("Content-Range")
+            .and_then(|range| range.rsplit_once('/'))
+            .and_then(|(_, total)| total.parse::<u64>().ok())
+    } else {
+        res.header("Content-Length").map(str::parse).transpose()?
+    };
+    if total.is_none() {
+        report::warning(
+            &mut report.warnings,
+            "Response missing the file's size, it can't be checked",
+        );
+    }
+
+    let start = if resumed {
+        debug!("Resuming download of {url} at {offset} bytes");
+        report.resumed_from = offset;
+        offset
+    } else {
+        0
+    };
+    if let Some(total) = total {
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+        check_space(dir.unwrap_or(Path::new(".")), total.saturating_sub(offset))?;
+    }
+    status::emit(StatusEvent::DownloadStarted {
+        url: url.into(),
+        size: total.unwrap_or_default(),
+    });
+
+    let downloaded = {
+        let mut output = if resumed {
+            fs.append(path)?
+        } else {
+            fs.create(path)?
+        };
+        copy_body(
+            res,
+            &mut output,
+            start,
+            total.unwrap_or_default(),
+            &operation,
+            started,
+            limit,
+            cb,
+        )?
+    };
+
+    if let Some(expected) = total {
+        let actual = fs.file_len(path)?;
+        if actual != expected {
+            return Err(ThermiteError::IncompleteDownload {
+                url: url.into(),
+                expected,
+                actual,
+            });
+        }
+    }
+
+    finish_download(report, downloaded, started)
+}
+
 /// Wrapper for calling `download_with_progress` without a progress bar
 /// # Params
 /// * `output` - Writer to write the data to
 /// * `url` - Url to download from
 ///
 /// # Returns
-/// * total bytes downloaded & written
+/// * a [`DownloadReport`] with the total bytes downloaded & written
 ///
 /// # Errors
 /// * IO Errors
-pub fn download(output: impl Write, url: impl AsRef<str>) -> Result<u64> {
+pub fn download(output: impl Write, url: impl AsRef<str>) -> Result<DownloadReport> {
     download_with_progress(output, url, |_, _, _| {})
 }
 
-#[deprecated(since = "0.7.1", note = "just use std::fs directly")]
+/// [`download`] that stops once `token` is cancelled, the same as running it in [`CancelToken::run`]
+///
+/// # Errors
+/// * IO Errors
+/// * `ThermiteError::Cancelled` if `token` was cancelled. Whatever was downloaded until then has already
+///   been written to `output` and isn't removed, the caller has to discard it. Use
+///   [`download_file_cancellable`] to download to a file that's removed instead
+/// * `ThermiteError::Timeout` if `token`'s deadline passed, leaving `output` the same way
+pub fn download_cancellable(
+    output: impl Write,
+    url: impl AsRef<str>,
+    token: &CancelToken,
+) -> Result<DownloadReport> {
+    token.run(|| download(output, url))
+}
+
+/// [`download_with_progress`] that stops once `token` is cancelled, see [`download_cancellable`]
+///
+/// # Errors
+/// * The same as [`download_cancellable`]
+pub fn download_with_progress_cancellable<F>(
+    output: impl Write,
+    url: impl AsRef<str>,
+    token: &CancelToken,
+    cb: F,
+) -> Result<DownloadReport>
+where
+    F: Fn(u64, u64, u64),
+{
+    token.run(|| download_with_progress(output, url, cb))
+}
+
+/// Download a file to `path`, stopping once `token` is cancelled
+///
+/// Unlike [`download_cancellable`], which can't take back what it wrote, the partial file is removed when the
+/// download is cancelled or times out. [`download_resumable`] keeps it instead, to continue later.
+///
+/// # Params
+/// * `path` - File to write the data to, replaced if it exists
+/// * `url` - URL to download from
+/// * `token` - Token to stop the download with
+///
+/// # Errors
+/// * IO Errors
+/// * `ThermiteError::Cancelled` if `token` was cancelled
+/// * `ThermiteError::Timeout` if `token`'s deadline passed
+pub fn download_file_cancellable(
+    path: impl AsRef<Path>,
+    url: impl AsRef<str>,
+    token: &CancelToken,
+) -> Result<DownloadReport> {
+    download_file_with_progress_cancellable(path, url, token, |_, _, _| {})
+}
+
+/// [`download_file_cancellable`] that calls `cb` like [`download_with_progress`] does
+///
+/// # Errors
+/// * The same as [`download_file_cancellable`]
+pub fn download_file_with_progress_cancellable<F>(
+    path: impl AsRef<Path>,
+    url: impl AsRef<str>,
+    token: &CancelToken,
+    cb: F,
+) -> Result<DownloadReport>
+where
+    F: Fn(u64, u64, u64),
+{
+    let path = path.as_ref();
+    let res = token.run(|| {
+        let fs = vfs::current();
+        let output = fs.create(path)?;
+        download_with_progress(output, url, cb)
+    });
+    if let Err(ThermiteError::Cancelled | ThermiteError::Timeout { .. }) =
+        res.as_ref().map_err(ThermiteError::root)
+    {
+        debug!("Removing the partial download {}", path.display());
+        remove_staging(path);
+    }
+    res
+}
+
+/// Download a package and check it against the SHA-256 from the index while it's written to `output`
+///
+/// Packages without a checksum are downloaded anyway, with a warning in the report.
+///
+/// # Errors
+/// * IO Errors
+/// * `ThermiteError::ChecksumMismatch` if the download doesn't match the checksum. `output` has already
+///   been written to by then
+#[cfg(not(target_arch = "wasm32"))]
+pub fn download_verified(output: impl Write, version: &ModVersion) -> Result<DownloadReport> {
+    let Some(expected) = &version.sha256 else {
+        let mut report = download(output, &version.url)?;
+        report::warning(
+            &mut report.warnings,
+            format!("No checksum for {}, it wasn't verified", version.full_name),
+        );
+        return Ok(report);
+    };
+
+    let mut output = HashingWriter::new(output);
+    let report = download(&mut output, &version.url)?;
+    let actual = verify::encode_hash(&output.finish());
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(report)
+    } else {
+        Err(ThermiteError::ChecksumMismatch {
+            url: version.url.clone(),
+            expected: expected.clone(),
+            actual,
+        })
+    }
+}
+
+/// Downloaded package archives kept on disk, so installing a version again doesn't download it again
+///
+/// Archives are stored by `author-name-X.Y.Z` in a [`PackageCache`]. A cached copy that doesn't match the
+/// version's checksum is downloaded again, and the cache is pruned to its maximum size after every download.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadCache {
+    cache: PackageCache,
+    max_size: Option<u64>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DownloadCache {
+    /// A cache storing archives in `dir`, without a maximum size
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cache: PackageCache::new(dir),
+            max_size: None,
+        }
+    }
+
+    /// The package cache inside `config.cache_dir` limited to `config.cache_limit`, if caching is enabled
+    #[must_use]
+    pub fn from_config(config: &ThermiteConfig) -> Option<Self> {
+        PackageCache::from_config(config).map(|cache| Self {
+            cache,
+            max_size: config.cache_limit,
+        })
+    }
+
+    /// Size in bytes the cache is pruned to, least recently used archives first. `None` means no limit
+    #[must_use]
+    pub const fn max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    #[must_use]
+    pub const fn cache(&self) -> &PackageCache {
+        &self.cache
+    }
+
+    /// The archive of `version`, from the cache if it has a copy matching the version's checksum and
+    /// downloaded with [`download_verified`] otherwise
+    ///
+    /// Failing to read or write the cache only logs a warning.
+    ///
+    /// # Errors
+    /// * IO and network errors
+    /// * `ThermiteError::ChecksumMismatch` if the download doesn't match the checksum
+    pub fn fetch(&self, version: &ModVersion) -> Result<Vec<u8>> {
+        self.fetch_with(version, |version| {
+            let mut archive = vec![];
+            download_verified(&mut archive, version)?;
+            Ok(archive)
+        })
+    }
+
+    pub(crate) fn fetch_with(
+        &self,
+        version: &ModVersion,
+        download: impl FnOnce(&ModVersion) -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let cached = self
+            .cache
+            .get(&version.full_name)
+            .inspect_err(|e| warn!("Unable to read {} from the cache: {e}", version.full_name))
+            .ok()
+            .flatten()
+            .filter(|archive| {
+                let matches = version.sha256.as_ref().is_none_or(|expected| {
+                    verify::encode_hash(&verify::sha256(archive)).eq_ignore_ascii_case(expected)
+                });
+                if !matches {
+                    debug!("Cached {} doesn't match its checksum", version.full_name);
+                }
+                matches
+            });
+        metrics::record_cache_lookup(cached.is_some());
+        if let Some(archive) = cached {
+            return Ok(archive);
+        }
+
+        let archive = download(version)?;
+        if let Err(e) = self
+            .cache
+            .put(&version.full_name, &archive)
+            .and_then(|()| self.prune().map(drop))
+        {
+            warn!("Unable to cache {}: {e}", version.full_name);
+        }
+
+        Ok(archive)
+    }
+
+    /// Removes the least recently used archives until the cache is at most its maximum size
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn prune(&self) -> Result<PruneReport> {
+        self.max_size.map_or_else(
+            || Ok(PruneReport::default()),
+            |max| self.cache.prune_to_size(max),
+        )
+    }
+}
+
+type FileProgress = dyn Fn(&str, u64, u64, u64) + Send + Sync;
+type TotalProgress = dyn Fn(u64, u64) + Send + Sync;
+
+/// Downloads several files at once
+///
+/// ```no_run
+/// # use thermite::core::manage::Downloader;
+/// let results = Downloader::new()
+///     .url("https://example.com/Foo-Bar-1.0.0.zip")
+///     .url("https://example.com/Foo-Baz-1.0.0.zip")
+///     .on_total_progress(|done, total| println!("{done}/{total}"))
+///     .run();
+/// for res in results {
+///     match res {
+///         Ok(file) => println!("Downloaded {} bytes from {}", file.data.len(), file.url),
+///         Err(e) => eprintln!("{e}"),
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Downloader {
+    urls: Vec<String>,
+    workers: NonZeroUsize,
+    on_progress: Option<Arc<FileProgress>>,
+    on_total_progress: Option<Arc<TotalProgress>>,
+}
+
+/// A file downloaded by a [`Downloader`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Downloaded {
+    pub url: String,
+    pub data: Vec<u8>,
+    pub report: DownloadReport,
+}
+
+impl Debug for Downloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Downloader")
+            .field("urls", &self.urls)
+            .field("workers", &self.workers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Downloader {
+    /// A downloader using the global config's `parallelism` as its worker count
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            urls: vec![],
+            workers: config::config().parallelism,
+            on_progress: None,
+            on_total_progress: None,
+        }
+    }
+
+    #[must_use]
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.urls.push(url.into());
+        self
+    }
+
+    #[must_use]
+    pub fn urls(mut self, urls: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.urls.extend(urls.into_iter().map(Into::into));
+        self
+    }
+
+    /// Maximum number of files downloaded at the same time
+    #[must_use]
+    pub const fn workers(mut self, workers: NonZeroUsize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Called with every chunk read. Params are |`url`: &str, `delta_bytes`: u64, `current_bytes`: u64,
+    /// `total_size`: u64|, like [`download_with_progress`]
+    #[must_use]
+    pub fn on_progress(mut self, cb: impl Fn(&str, u64, u64, u64) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(cb));
+        self
+    }
+
+    /// Called with every chunk read from any file. Params are |`current_bytes`: u64, `total_size`: u64|, the
+    /// total only counting files that have started and reported their size
+    #[must_use]
+    pub fn on_total_progress(mut self, cb: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        self.on_total_progress = Some(Arc::new(cb));
+        self
+    }
+
+    /// Downloads every file, returning the results in the order the URLs were added
+    ///
+    /// A failed download doesn't stop the others.
+    #[must_use]
+    pub fn run(&self) -> Vec<Result<Downloaded>> {
+        let sizes = self
+            .urls
+            .iter()
+            .map(|_| AtomicU64::new(0))
+            .collect::<Vec<_>>();
+        let done = AtomicU64::new(0);
+        let total = AtomicU64::new(0);
+
+        let items = self.urls.iter().zip(&sizes).collect();
+        pool::map_with(&ThreadPool::new(self.workers), items, |(url, size)| {
+            let mut data = vec![];
+            let report = download_with_progress(&mut data, url, |delta, current, file_size| {
+                if let Some(cb) = &self.on_progress {
+                    cb(url, delta, current, file_size);
+                }
+                // every chunk reports the file's size, only count it once
+                let grown = file_size - size.swap(file_size, Ordering::Relaxed);
+                let total = total.fetch_add(grown, Ordering::Relaxed) + grown;
+                let done = done.fetch_add(delta, Ordering::Relaxed) + delta;
+                if let Some(cb) = &self.on_total_progress {
+                    cb(done, total);
+                }
+            })?;
+
+            Ok(Downloaded {
+                url: url.clone(),
+                data,
+                report,
+            })
+        })
+    }
+}
+
+/// Async version of [`download`], with the current [`AsyncHttpBackend`](crate::http::AsyncHttpBackend)
+///
+/// The response is written to `output` as it arrives, so `output` shouldn't block for long. Dropping the future
+/// cancels the download, leaving whatever was written to `output` until then.
+///
+/// # Returns
+/// * `output` once everything was written to it, and a [`DownloadReport`]
+///
+/// # Errors
+/// * IO Errors
+#[cfg(feature = "async")]
+pub async fn download_async<W>(mut output: W, url: impl Into<String>) -> Result<(W, DownloadReport)>
+where
+    W: Write + Send,
+{
+    let url = url.into();
+    let limit = config::config().download_timeout;
+    let started = Instant::now();
+    let operation = format!("downloading {url}");
+
+    let res = http::get_async(&HttpRequest::get(&url).timeout(limit), &operation).await?;
+    let (report, _) = start_download(&url, res.header("Content-Length"))?;
+    let mut body = res.body;
+    let mut downloaded = 0;
+    let throttle = throttle_rate();
+    let copy_started = Instant::now();
+    loop {
+        cancel::checkpoint(&operation)?;
+        let chunk = body
+            .chunk()
+            .await
+            .map_err(|e| ThermiteError::from_io(e, &operation, started, limit))?;
+        let Some(chunk) = chunk else {
+            break;
+        };
+        output.write_all(&chunk)?;
+        downloaded += chunk.len() as u64;
+        if let Some(ahead) =
+            throttle.and_then(|rate| throttle_delay(rate, downloaded, copy_started))
+        {
+            http::async_backend().sleep(ahead).await;
+        }
+    }
+    output.flush()?;
+
+    finish_download(report, downloaded, started).map(|report| (output, report))
+}
+
+#[deprecated(since = "0.7.1", note = "use `remove_mod` instead")]
 pub fn uninstall(mods: &[impl AsRef<Path>]) -> Result<()> {
+    check_game_running(&config::config())?;
+    let fs = vfs::current();
     for p in mods {
-        if fs::remove_dir_all(p).is_err() {
+        let p = p.as_ref();
+        let _lock = p.parent().map(lock_dir).transpose()?;
+        let entry = AuditEntry::new(AuditOperation::Uninstall, p.display().to_string(), p);
+        let files = package_files(p).unwrap_or_default();
+        let res = if fs.remove_dir_all(p).is_err() {
             //try removing a file too, just in case
             debug!("Removing dir failed, attempting to remove file...");
-            fs::remove_file(p)?;
+            fs.remove_file(p)
+        } else {
+            Ok(())
+        };
+        audit::record(entry, &res);
+        res?;
+        journal::record_removal(p, p.display().to_string(), None, files, BTreeMap::new());
+        events::emit(Event::ModRemoved {
+            name: p.display().to_string(),
+            path: p.into(),
+        });
+        #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+        if let (Some(dir), Some(Ok((author, name, _)))) = (
+            p.parent(),
+            p.file_name().map(|n| parse_modstring(n.to_string_lossy())),
+        ) {
+            crate::db::update(dir, false, |db| {
+                db.record_removal(&format!("{author}-{name}"))
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Removes an installed mod and cleans its entry out of the profile's `enabledmods.json`
+///
+/// Mods installed from a package take the whole package with them, including any other mods it provides.
+/// Mods installed by hand, outside of a package, only remove their own directory.
+///
+/// # Errors
+/// * `ThermiteError::CoreMod` if this would remove a core Northstar mod and `force` isn't set
+/// * The game is running and the global config's `running_game` policy refuses, or gave up waiting
+/// * `ThermiteError::DirectoryLocked` if another process is changing the directory it's in
+/// * IO errors
+/// * The profile's `enabledmods.json` is malformed
+pub fn remove_mod(installed: &InstalledMod, force: bool) -> Result<()> {
+    check_game_running(&config::config())?;
+    let fs = vfs::current();
+    let (dir, mods) = match package_dir(installed) {
+        Some(dir) => (
+            dir,
+            find_package_mods(&DirEntry {
+                path: dir.into(),
+                is_dir: true,
+            })?,
+        ),
+        None => (installed.path.as_path(), vec![installed.clone()]),
+    };
+    let _lock = dir.parent().map(lock_dir).transpose()?;
+    if !force {
+        let game = &config::config().game;
+        if let Some(core) = mods.iter().find(|m| game.is_core_mod(&m.mod_json.name)) {
+            return Err(ThermiteError::CoreMod(core.mod_json.name.clone()));
+        }
+    }
+
+    // either `packages` or `mods` is in the profile directory
+    let mut enabled = match dir.parent().and_then(Path::parent).map(get_enabled_mods) {
+        Some(Ok(enabled)) => Some(enabled),
+        Some(Err(ThermiteError::MissingFile(_))) | None => None,
+        Some(Err(e)) => return Err(e),
+    };
+
+    let name = dir
+        .file_name()
+        .map_or_else(|| dir.display().to_string(), |n| n.to_string_lossy().into());
+    let mut entry = AuditEntry::new(AuditOperation::Uninstall, &name, dir);
+    entry.version = Some(installed.manifest.version_number.clone());
+    let files = package_files(dir).unwrap_or_default();
+    let previous = enabled
+        .as_ref()
+        .map(|enabled| enabled_states(enabled, &mods))
+        .unwrap_or_default();
+    let res = fs.remove_dir_all(dir);
+    audit::record(entry, &res);
+    res?;
+    journal::record_removal(
+        dir,
+        &name,
+        Some(installed.manifest.version_number.clone()),
+        files,
+        previous,
+    );
+    events::emit(Event::ModRemoved {
+        name,
+        path: dir.into(),
+    });
+    #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+    if let (Some(_), Some(packages)) = (package_dir(installed), dir.parent()) {
+        crate::db::update(packages, false, |db| {
+            db.record_removal(&format!("{}-{}", installed.author, installed.manifest.name))
+        });
+    }
+
+    if let Some(enabled) = &mut enabled {
+        for m in &mods {
+            enabled.mods.remove(&m.mod_json.name);
         }
+        enabled.save()?;
     }
+
     Ok(())
 }
 
@@ -98,10 +947,27 @@ pub fn uninstall(mods: &[impl AsRef<Path>]) -> Result<()> {
 ///     - takes `File` of the zip file
 ///     - returns `bool`
 ///
-/// `target_dir` will be treated as the root of the `mods` directory in the mod file
+/// `target_dir` will be treated as the root of the `mods` directory in the mod file.
+/// An existing install is handled according to the global config's `overwrite` policy
+///
+/// Packages containing native plugins (`.dll` files) are only installed if the global config's
+/// `install_plugins` is set, and they're on its `verified_mods` list or `allow_unverified_plugins` is set.
+/// Plugins stay in the package in the `Packages` layout and go to the profile's `plugins` directory in the
+/// `Legacy` layout
+///
+/// Files only clients load are skipped if the global config's `strip_client_assets` is set
+///
+/// On Windows, installs whose paths would be longer than `MAX_PATH` use `\\?\` paths, as do their reports
 ////// # Errors
 /// * IO Errors
 /// * Misformatted mods (typically missing the `mods` directory)
+/// * The mod is already installed and the overwrite policy is `Fail`
+/// * The mod contains plugins and installing plugins isn't allowed, or it isn't verified
+/// * The game is running and the config's `running_game` policy refuses, or gave up waiting
+/// * `ThermiteError::DirectoryLocked` if another process is changing `target_dir`
+/// * `ThermiteError::InsufficientSpace` if the extracted package wouldn't fit on `target_dir`'s filesystem
+/// * `ThermiteError::PermissionDenied` if `target_dir` isn't writable, e.g. under Program Files without
+///   elevation
 ///
 /// # Panics
 /// This function will panic if it is unable to get the current system time
@@ -110,154 +976,2104 @@ pub fn install_with_sanity<T, F>(
     zip_file: T,
     target_dir: impl AsRef<Path>,
     sanity_check: F,
-) -> Result<PathBuf>
+) -> Result<InstallReport>
 where
     T: Read + Seek,
     F: FnOnce(&T) -> Result<(), Box<dyn Error + Send + Sync + 'static>>,
 {
-    if let Err(e) = sanity_check(&zip_file) {
-        return Err(ThermiteError::SanityError(e));
-    }
-
-    if !validate_modstring(mod_string.as_ref()) {
-        return Err(ThermiteError::NameError(mod_string.as_ref().into()));
-    }
-
-    let path = target_dir.as_ref().join(mod_string.as_ref());
-    ZipArchive::new(zip_file)?.extract(&path)?;
-
-    Ok(path)
+    install_with_config(
+        mod_string,
+        zip_file,
+        target_dir,
+        sanity_check,
+        &config::config(),
+    )
 }
 
-pub fn install_mod<T>(
+/// `install_with_sanity` with an explicit config instead of the global one
+pub(crate) fn install_with_config<T, F>(
     mod_string: impl AsRef<str>,
     zip_file: T,
     target_dir: impl AsRef<Path>,
-) -> Result<PathBuf>
+    sanity_check: F,
+    config: &ThermiteConfig,
+) -> Result<InstallReport>
 where
     T: Read + Seek,
+    F: FnOnce(&T) -> Result<(), Box<dyn Error + Send + Sync + 'static>>,
 {
-    install_with_sanity(mod_string, zip_file, target_dir, |_| Ok(()))
+    install_reporting(
+        mod_string,
+        zip_file,
+        target_dir,
+        sanity_check,
+        config,
+        &InstallOptions::default(),
+        &|_| {},
+    )
 }
 
-/// Install N* to the provided path
-///
-/// # Params
-/// * `zip_file` - compressed mod file
-/// * `game_path` - the path of the Titanfall 2 install
+/// [`install_mod`] laid out on disk the way `options` says, see [`InstallOptions`]
 ///
 /// # Errors
-/// * IO Errors
-pub fn install_northstar(zip_file: impl Read + Seek, game_path: impl AsRef<Path>) -> Result<()> {
-    let target = game_path.as_ref();
-    let mut archive = ZipArchive::new(zip_file)?;
-
-    let manifest = archive
-        .by_name("manifest.json")
-        .ok()
-        .map(|mut v| {
-            let mut buf = Vec::with_capacity(usize::try_from(v.size())?);
-            if let Err(e) = v.read_to_end(&mut buf) {
-                Err(ThermiteError::from(e))
-            } else {
-                Ok(buf)
-            }
-        })
-        .transpose()?;
-
-    for i in 0..archive.len() {
-        let mut f = archive.by_index(i)?;
+/// * The same as [`install_with_sanity`]
+/// * `ThermiteError::NameError` if `options.dir_name` isn't a plain directory name
+pub fn install_with_options<T>(
+    mod_string: impl AsRef<str>,
+    zip_file: T,
+    target_dir: impl AsRef<Path>,
+    options: &InstallOptions,
+) -> Result<InstallReport>
+where
+    T: Read + Seek,
+{
+    install_reporting(
+        mod_string,
+        zip_file,
+        target_dir,
+        |_| Ok(()),
+        &config::config(),
+        options,
+        &|_| {},
+    )
+}
 
-        //This should work fine for N* because the dir structure *should* always be the same
-        if f.enclosed_name()
-            .ok_or_else(|| ThermiteError::UnknownError("File missing enclosed name".into()))?
-            .starts_with("Northstar")
-        {
-            let out = target.join(
-                f.enclosed_name()
-                    .unwrap()
-                    .strip_prefix("Northstar")
-                    .unwrap(),
-            );
+/// [`install_mod`] reporting the file being extracted with [`ProgressEvent::ExtractingFile`], followed by
+/// [`ProgressEvent::Finished`] once the package is in place
+///
+/// # Errors
+/// * The same as [`install_with_sanity`]
+pub fn install_with_progress<T>(
+    mod_string: impl AsRef<str>,
+    zip_file: T,
+    target_dir: impl AsRef<Path>,
+    cb: impl Fn(ProgressEvent),
+) -> Result<InstallReport>
+where
+    T: Read + Seek,
+{
+    let report = install_reporting(
+        mod_string,
+        zip_file,
+        target_dir,
+        |_| Ok(()),
+        &config::config(),
+        &InstallOptions::default(),
+        &cb,
+    )?;
+    cb(ProgressEvent::Finished);
+    Ok(report)
+}
 
-            if (*f.name()).ends_with('/') {
-                trace!("Create directory {}", f.name());
-                fs::create_dir_all(target.join(f.name()))?;
-                continue;
-            } else if let Some(p) = out.parent() {
-                fs::create_dir_all(p)?;
+fn install_reporting<T, F>(
+    mod_string: impl AsRef<str>,
+    zip_file: T,
+    target_dir: impl AsRef<Path>,
+    sanity_check: F,
+    config: &ThermiteConfig,
+    options: &InstallOptions,
+    progress: &dyn Fn(ProgressEvent),
+) -> Result<InstallReport>
+where
+    T: Read + Seek,
+    F: FnOnce(&T) -> Result<(), Box<dyn Error + Send + Sync + 'static>>,
+{
+    check_game_running(config)?;
+    let started = Instant::now();
+    let mut entry = AuditEntry::new(
+        AuditOperation::Install,
+        mod_string.as_ref(),
+        target_dir.as_ref(),
+    );
+    let existing = target_dir.as_ref().join(
+        options
+            .package_dir_name(mod_string.as_ref())
+            .unwrap_or_else(|_| mod_string.as_ref().into()),
+    );
+    let operation = if vfs::current().exists(&existing).unwrap_or(false) {
+        HookOperation::Update
+    } else {
+        HookOperation::Install
+    };
+    let mut hook = HookContext::new(
+        operation,
+        HookStage::Before,
+        mod_string.as_ref(),
+        target_dir.as_ref(),
+    );
+    let res = hooks::run_hooks(&hook).and_then(|()| {
+        install_package(
+            mod_string.as_ref(),
+            zip_file,
+            target_dir.as_ref(),
+            sanity_check,
+            config,
+            options,
+            progress,
+        )
+    });
+    let res = res.map(|mut report| {
+        report.duration = started.elapsed();
+        report
+    });
+    metrics::record_install(res.is_ok(), started.elapsed());
+    // a skipped install left everything as it was
+    let skipped = matches!(&res, Ok(report) if report.skipped);
+    if let (Ok(report), false) = (&res, skipped) {
+        entry.version = report.version.clone();
+        if report.replaced {
+            entry.operation = AuditOperation::Update;
+        }
+        events::emit(Event::ModInstalled {
+            name: report.name.clone(),
+            version: report.version.clone(),
+            path: report.path.clone(),
+        });
+    }
+    if !skipped {
+        audit::record(entry, &res);
+    }
+
+    hook.stage = HookStage::After;
+    match &res {
+        Ok(report) => hook.version.clone_from(&report.version),
+        Err(e) => hook.error = Some(e.to_string()),
+    }
+    // failing after hooks are only logged
+    let _ = hooks::run_hooks(&hook);
+    res
+}
+
+fn install_package<T, F>(
+    mod_string: &str,
+    zip_file: T,
+    target_dir: &Path,
+    sanity_check: F,
+    config: &ThermiteConfig,
+    options: &InstallOptions,
+    progress: &dyn Fn(ProgressEvent),
+) -> Result<InstallReport>
+where
+    T: Read + Seek,
+    F: FnOnce(&T) -> Result<(), Box<dyn Error + Send + Sync + 'static>>,
+{
+    let mut transaction = InstallTransaction::new();
+    let report = transaction.stage_with_config(
+        mod_string,
+        zip_file,
+        target_dir,
+        sanity_check,
+        config,
+        options,
+        progress,
+    )?;
+    if report.skipped {
+        return Ok(report);
+    }
+    Ok(transaction.commit()?.pop().unwrap_or(report))
+}
+
+/// How [`install_with_options`] lays a package out on disk
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallOptions {
+    /// Name of the package's directory instead of its full name. Only used in the `Packages` layout
+    pub dir_name: Option<String>,
+    /// Overrides the config's `overwrite` policy
+    pub overwrite: Option<OverwritePolicy>,
+    /// Write the package's author to `thunderstore_author.txt`, in the package's directory or in the `Legacy`
+    /// layout in each of its mods. It's always written when the directory isn't named after the package, so
+    /// [`find_mods`](super::utils::find_mods) can still tell who the author is
+    pub write_author: bool,
+    /// Name the package's directory `author-name`, without the version, unless `dir_name` is set
+    pub strip_version: bool,
+}
+
+impl InstallOptions {
+    /// The name of the directory `mod_string` is installed to in the `Packages` layout
+    fn package_dir_name(&self, mod_string: &str) -> Result<String> {
+        match &self.dir_name {
+            Some(dir_name) => {
+                let mut components = Path::new(dir_name).components();
+                let plain = matches!(
+                    (components.next(), components.next()),
+                    (Some(Component::Normal(_)), None)
+                );
+                if !plain || dir_name.starts_with(STAGING_PREFIX) || dir_name == JOURNAL_DIR {
+                    return Err(ThermiteError::NameError(dir_name.clone()));
+                }
+                Ok(dir_name.clone())
+            }
+            None if self.strip_version => {
+                let (author, name, _) = parse_modstring(mod_string)?;
+                Ok(format!("{author}-{name}"))
+            }
+            None => Ok(mod_string.into()),
+        }
+    }
+}
+
+/// Prefix of the directories installs are staged in, which [`find_mods`](super::utils::find_mods) ignores
+pub(crate) const STAGING_PREFIX: &str = ".thermite-";
+
+/// A package extracted to its staging directory, waiting to be moved into place
+#[derive(Debug)]
+struct Staged {
+    staging: PathBuf,
+    /// The whole staging directory in the `Packages` layout, each mod directory and plugin in it in the
+    /// `Legacy` layout
+    moves: Vec<Move>,
+    report: InstallReport,
+    /// Held until the package is committed or discarded
+    _lock: DirLock,
+}
+
+#[derive(Debug)]
+struct Move {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+/// Installs one or more packages so that either all of them end up installed or none of them do
+///
+/// [`stage`](Self::stage) extracts each package into a hidden directory next to where it'll be installed, and
+/// [`commit`](Self::commit) moves them all into place. Existing installs being replaced are only removed once
+/// every package was moved, and put back if any move fails. Dropping the transaction without committing it
+/// removes everything it staged, so a failed extraction never leaves a partial package behind. Each directory
+/// staged into stays [locked](super::lock) until then.
+///
+/// Hooks and the audit log only see [`install_mod`] and friends, which use a transaction internally.
+#[derive(Debug, Default)]
+pub struct InstallTransaction {
+    staged: Vec<Staged>,
+}
+
+impl InstallTransaction {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extracts a package to a staging directory in `target_dir`, using the global config
+    ///
+    /// The config's `layout` decides whether the whole package or only its mods are installed, see
+    /// [`PackageLayout`]. The hashes of the extracted files are recorded for
+    /// [`verify_mod`](super::utils::verify_mod).
+    ///
+    /// # Errors
+    /// * IO Errors
+    /// * Misformatted mods
+    /// * The mod is already installed, or already staged, and the overwrite policy is `Fail`
+    /// * The mod contains plugins and installing plugins isn't allowed, or it isn't verified
+    /// * `ThermiteError::DirectoryLocked` if another process is changing `target_dir`
+    pub fn stage<T>(
+        &mut self,
+        mod_string: impl AsRef<str>,
+        zip_file: T,
+        target_dir: impl AsRef<Path>,
+    ) -> Result<InstallReport>
+    where
+        T: Read + Seek,
+    {
+        self.stage_with_config(
+            mod_string.as_ref(),
+            zip_file,
+            target_dir.as_ref(),
+            |_| Ok(()),
+            &config::config(),
+            &InstallOptions::default(),
+            &|_| {},
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn stage_with_config<T, F>(
+        &mut self,
+        mod_string: &str,
+        zip_file: T,
+        target_dir: &Path,
+        sanity_check: F,
+        config: &ThermiteConfig,
+        options: &InstallOptions,
+        progress: &dyn Fn(ProgressEvent),
+    ) -> Result<InstallReport>
+    where
+        T: Read + Seek,
+        F: FnOnce(&T) -> Result<(), Box<dyn Error + Send + Sync + 'static>>,
+    {
+        if let Err(e) = sanity_check(&zip_file) {
+            return Err(ThermiteError::SanityError(e));
+        }
+
+        if !validate_modstring(mod_string) {
+            return Err(ThermiteError::NameError(mod_string.into()));
+        }
+
+        let mut archive = share_archive(zip_file)?;
+        check_plugins(mod_string, &archive, config)?;
+        check_space(target_dir, extracted_size(&mut archive))?;
+        let (author, name, version) = parse_modstring(mod_string)?;
+        let dir_name = options.package_dir_name(mod_string)?;
+        let overwrite = options.overwrite.unwrap_or(config.overwrite);
+        let kind = PackageKind::detect(archive.file_names());
+        let staging_name = format!("{STAGING_PREFIX}staging-{mod_string}");
+        let longest = archive.file_names().map(str::len).max().unwrap_or_default();
+        let target_dir = &long_path(
+            target_dir,
+            staging_name.len().max(dir_name.len()) + longest + 2,
+        );
+        check_writable(target_dir)?;
+        let layout = config.layout.resolve(target_dir)?;
+        let lock = lock_dir(target_dir)?;
+        let staging = target_dir.join(staging_name);
+        let moves = if layout == PackageLayout::Legacy {
+            legacy_moves(&archive, kind, &name, &staging, target_dir)?
+        } else {
+            vec![Move {
+                from: staging.clone(),
+                to: target_dir.join(&dir_name),
+            }]
+        };
+        let targets = moves.iter().map(|m| m.to.clone()).collect::<Vec<_>>();
+        if let Some(path) = self
+            .staged
+            .iter()
+            .flat_map(|s| &s.moves)
+            .find(|m| targets.contains(&m.to))
+        {
+            return Err(ThermiteError::AlreadyInstalled(path.to.clone()));
+        }
+        let mut report = InstallReport {
+            name: mod_string.into(),
+            version: manifest_version(&mut archive),
+            kind,
+            path: if layout == PackageLayout::Legacy {
+                target_dir.into()
+            } else {
+                target_dir.join(&dir_name)
+            },
+            ..Default::default()
+        };
+
+        status::emit(StatusEvent::InstallStarted {
+            name: mod_string.into(),
+            target: target_dir.into(),
+        });
+
+        let fs = vfs::current();
+        for path in &targets {
+            if !fs.exists(path)? {
+                continue;
+            }
+            let replace = match overwrite {
+                OverwritePolicy::Fail => false,
+                OverwritePolicy::Replace | OverwritePolicy::MergePreservingConfigs => true,
+                OverwritePolicy::Ask => {
+                    let conflict = InstallConflict::AlreadyInstalled {
+                        name: mod_string.into(),
+                        path: path.clone(),
+                    };
+                    match resolve::resolve(&conflict) {
+                        Some(Resolution::UseNew) => true,
+                        Some(Resolution::KeepExisting | Resolution::Skip) => {
+                            debug!("Keeping existing install at {}", path.display());
+                            report.skipped = true;
+                            return Ok(report);
+                        }
+                        None => false,
+                    }
+                }
+            };
+            if !replace {
+                return Err(ThermiteError::AlreadyInstalled(path.clone()));
+            }
+            report.replaced = true;
+        }
+        let skip = if config.strip_client_assets {
+            server::client_asset_entries(&mut archive)
+        } else {
+            BTreeSet::new()
+        };
+
+        if fs.exists(&staging)? {
+            debug!("Removing stale staging directory {}", staging.display());
+            fs.remove_dir_all(&staging)?;
+        }
+        let extracted = fs
+            .create_dir_all(&staging)
+            .map_err(ThermiteError::from)
+            .and_then(|()| {
+                let route = |path: &Path| kind.route(path, &name);
+                extract_archive(
+                    &mut archive,
+                    &staging,
+                    &skip,
+                    mod_string,
+                    &route,
+                    config,
+                    progress,
+                )
+            })
+            .and_then(|mut written| {
+                if kind != PackageKind::Mod {
+                    let version = report.version.as_deref().unwrap_or(&version);
+                    write_mod_json(&staging.join("mods").join(&name), &name, version)?;
+                }
+                // without it a package that isn't in its `author-name-X.Y.Z` directory has no author
+                if options.write_author || dir_name != mod_string {
+                    for m in moves.iter().filter(|m| fs.is_dir(&m.from)) {
+                        fs.write(&m.from.join(AUTHOR_FILE), author.as_bytes())?;
+                        written.0 += 1;
+                    }
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                for m in moves.iter().filter(|m| fs.is_dir(&m.from)) {
+                    write_checksums(&m.from)?;
+                }
+                Ok(written)
+            });
+        match extracted {
+            Ok(written) => (report.files_written, report.bytes_written) = written,
+            Err(e) => {
+                remove_staging(&staging);
+                return Err(e);
+            }
+        }
+        if report.replaced && overwrite == OverwritePolicy::MergePreservingConfigs {
+            for m in moves
+                .iter()
+                .filter(|m| fs.is_dir(&m.from) && fs.is_dir(&m.to))
+            {
+                match preserve_configs(&m.to, &m.from) {
+                    Ok(kept) => report
+                        .preserved
+                        .extend(kept.into_iter().map(|rel| m.to.join(rel))),
+                    Err(e) => {
+                        remove_staging(&staging);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        self.staged.push(Staged {
+            staging,
+            moves,
+            report: report.clone(),
+            _lock: lock,
+        });
+        Ok(report)
+    }
+
+    /// Moves every staged package into place, replacing existing installs
+    ///
+    /// # Errors
+    /// * IO Errors, after which every install is as it was before the transaction
+    pub fn commit(mut self) -> Result<Vec<InstallReport>> {
+        cancel::checkpoint("committing installs")?;
+        let fs = vfs::current();
+        let mut moved: Vec<(&Move, Option<PathBuf>)> = vec![];
+        for staged in &self.staged {
+            for m in &staged.moves {
+                match move_into_place(m, staged.report.replaced) {
+                    Ok(backup) => moved.push((m, backup)),
+                    Err(e) => {
+                        // put back what was already moved, in reverse so nothing is overwritten
+                        for (m, backup) in moved.into_iter().rev() {
+                            if let Err(e) = fs.rename(&m.to, &m.from) {
+                                warn!("Unable to roll back {}: {e}", m.to.display());
+                                continue;
+                            }
+                            if let Some(backup) = backup {
+                                if let Err(e) = fs.rename(&backup, &m.to) {
+                                    warn!("Unable to restore {}: {e}", m.to.display());
+                                }
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        for (_, backup) in moved {
+            if let Some(backup) = backup {
+                remove_staging(&backup);
+            }
+        }
+        for staged in &mut self.staged {
+            remove_staging(&staged.staging);
+            staged.report.installed_files = installed_files(&staged.moves);
+            record_install(staged);
+            #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+            record_in_database(staged);
+        }
+        let reports: Vec<_> = self.staged.drain(..).map(|s| s.report).collect();
+        for report in &reports {
+            status::emit(StatusEvent::InstallFinished {
+                name: report.name.clone(),
+                path: report.path.clone(),
+            });
+        }
+        Ok(reports)
+    }
+
+    /// Removes everything staged so far, leaving existing installs untouched
+    pub fn rollback(mut self) {
+        self.discard();
+    }
+
+    fn discard(&mut self) {
+        for staged in self.staged.drain(..) {
+            remove_staging(&staged.staging);
+        }
+    }
+}
+
+impl Drop for InstallTransaction {
+    fn drop(&mut self) {
+        self.discard();
+    }
+}
+
+/// Adds a committed install to the journal of the directory it was installed into
+fn record_install(staged: &Staged) {
+    let report = &staged.report;
+    let operation = if report.replaced {
+        AuditOperation::Update
+    } else {
+        AuditOperation::Install
+    };
+    let mut entry = JournalEntry::new(operation, &report.name, &report.path);
+    entry.version.clone_from(&report.version);
+    entry.files.clone_from(&report.installed_files);
+    let dir = staged.staging.parent().unwrap_or(&staged.staging);
+    journal::record(dir, entry);
+}
+
+/// Adds a committed install in the `Packages` layout to the database of the directory it was installed into
+#[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+fn record_in_database(staged: &Staged) {
+    let report = &staged.report;
+    let dir = staged.staging.parent().unwrap_or(&staged.staging);
+    // in the legacy layout the package's mods are spread over the directory
+    if report.path == dir {
+        return;
+    }
+    let Ok((author, name, version)) = parse_modstring(&report.name) else {
+        return;
+    };
+    let version = report.version.as_deref().unwrap_or(&version);
+    crate::db::update(dir, true, |db| {
+        db.record_install(&format!("{author}-{name}"), version, &report.path)
+    });
+}
+
+/// Every file the moves put in place
+fn installed_files(moves: &[Move]) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for m in moves {
+        match package_files(&m.to) {
+            Ok(mut found) => files.append(&mut found),
+            Err(e) => warn!("Unable to list the files in {}: {e}", m.to.display()),
+        }
+    }
+    files
+}
+
+/// Moves a staged directory into place, returning where the install it replaced was moved to
+fn move_into_place(m: &Move, replace: bool) -> Result<Option<PathBuf>> {
+    let fs = vfs::current();
+    let backup = if fs.exists(&m.to)? {
+        if !replace {
+            return Err(ThermiteError::AlreadyInstalled(m.to.clone()));
+        }
+        let name = m.to.file_name().unwrap_or_default().to_string_lossy();
+        let backup =
+            m.to.with_file_name(format!("{STAGING_PREFIX}backup-{name}"));
+        if fs.exists(&backup)? {
+            remove_path(&backup)?;
+        }
+        debug!("Moving existing install at {} aside", m.to.display());
+        fs.rename(&m.to, &backup)?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    if let Some(parent) = m.to.parent() {
+        fs.create_dir_all(parent)?;
+    }
+    if let Err(e) = fs.rename(&m.from, &m.to) {
+        if let Some(backup) = &backup {
+            if let Err(e) = fs.rename(backup, &m.to) {
+                warn!("Unable to restore {}: {e}", m.to.display());
+            }
+        }
+        return Err(e.into());
+    }
+    Ok(backup)
+}
+
+/// Where the mods and plugins of a package extracted to `staging` go in the `Legacy` layout
+///
+/// Each directory in the package's `mods` directory goes into `target_dir`, and everything in its `plugins`
+/// directory into the `plugins` directory next to `target_dir`. Skins and audio overrides are a single mod
+/// named `name`, see [`PackageKind`]
+fn legacy_moves(
+    archive: &ZipArchive<impl Read + Seek>,
+    kind: PackageKind,
+    name: &str,
+    staging: &Path,
+    target_dir: &Path,
+) -> Result<Vec<Move>> {
+    if kind != PackageKind::Mod {
+        return Ok(vec![Move {
+            from: staging.join("mods").join(name),
+            to: target_dir.join(name),
+        }]);
+    }
+    let plugins_dir = target_dir.parent().unwrap_or(target_dir).join("plugins");
+    let mut entries = BTreeSet::new();
+    for name in archive.file_names() {
+        let mut parts = name.split('/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("mods"), Some(dir), Some(_)) if !matches!(dir, "" | "." | "..") => {
+                entries.insert(("mods", dir));
+            }
+            (Some("plugins"), Some(file), _) if !matches!(file, "" | "." | "..") => {
+                entries.insert(("plugins", file));
             }
+            _ => {}
+        }
+    }
+    if !entries.iter().any(|(kind, _)| *kind == "mods") {
+        return Err(zip::result::ZipError::InvalidArchive("Package has no mods directory").into());
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|(kind, name)| Move {
+            from: staging.join(kind).join(name),
+            to: if kind == "mods" {
+                target_dir.join(name)
+            } else {
+                plugins_dir.join(name)
+            },
+        })
+        .collect())
+}
+
+/// Removes a file or a directory and everything in it
+fn remove_path(path: &Path) -> io::Result<()> {
+    let fs = vfs::current();
+    if fs.is_dir(path) {
+        fs.remove_dir_all(path)
+    } else {
+        fs.remove_file(path)
+    }
+}
+
+/// Removes a staging or backup directory, only logging failures since there's nothing else to do about them
+fn remove_staging(dir: &Path) {
+    match remove_path(dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            warn!("Unable to remove {}: {e}", dir.display());
+        }
+        _ => {}
+    }
+}
+
+/// Refuses archives containing plugins unless installing plugins was opted into and they're verified or
+/// unverified plugins are allowed
+fn check_plugins(
+    mod_string: &str,
+    archive: &ZipArchive<impl Read + Seek>,
+    config: &ThermiteConfig,
+) -> Result<()> {
+    let plugins = archive
+        .file_names()
+        .filter(|name| name.to_lowercase().ends_with(".dll"))
+        .map(Into::into)
+        .collect::<Vec<String>>();
+    if plugins.is_empty() {
+        return Ok(());
+    }
+    if !config.install_plugins {
+        return Err(ThermiteError::PluginsDetected {
+            name: mod_string.into(),
+            plugins,
+        });
+    }
+    if config.allow_unverified_plugins {
+        return Ok(());
+    }
+
+    let (author, name, version) = parse_modstring(mod_string)?;
+    let verified = config
+        .verified_mods
+        .as_ref()
+        .is_some_and(|v| v.is_verified(format!("{author}-{name}"), &version));
+    if verified {
+        Ok(())
+    } else {
+        Err(ThermiteError::UnverifiedPlugin(mod_string.into()))
+    }
+}
+
+/// The uncompressed size of every entry in `archive`
+fn extracted_size(archive: &mut ZipArchive<impl Read + Seek>) -> u64 {
+    (0..archive.len())
+        .filter_map(|i| archive.by_index_raw(i).ok().map(|f| f.size()))
+        .sum()
+}
+
+/// Writes the `mod.json` Northstar needs to load a skin or audio override that came without a mod, unless the
+/// package brought one
+fn write_mod_json(dir: &Path, name: &str, version: &str) -> Result<()> {
+    let fs = vfs::current();
+    let path = dir.join("mod.json");
+    if fs.exists(&path)? {
+        return Ok(());
+    }
+    let mod_json = serde_json::json!({
+        "Name": name,
+        "Description": "",
+        "Version": version,
+        "LoadPriority": 1,
+    });
+    fs.create_dir_all(dir)?;
+    fs.write_atomic(&path, serde_json::to_string_pretty(&mod_json)?.as_bytes())?;
+    Ok(())
+}
+
+/// Reads `version_number` from the archive's manifest, if it has one
+fn manifest_version(archive: &mut ZipArchive<impl Read + Seek>) -> Option<String> {
+    let manifest = archive.by_name("manifest.json").ok()?;
+    let value: serde_json::Value = serde_json::from_reader(manifest).ok()?;
+    value["version_number"].as_str().map(ToOwned::to_owned)
+}
+
+/// An archive held in memory, which the workers extracting it each read from with their own cheap clone
+pub(crate) type SharedArchive = ZipArchive<io::Cursor<Arc<[u8]>>>;
+
+/// How many entries are handed to the [`pool`] at once, progress is reported between batches
+const EXTRACT_BATCH: usize = 64;
+
+/// Reads all of `reader` into memory so its entries can be extracted in parallel
+pub(crate) fn share_archive(mut reader: impl Read + Seek) -> Result<SharedArchive> {
+    reader.rewind()?;
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    Ok(ZipArchive::new(io::Cursor::new(data.into()))?)
+}
+
+/// An entry of an archive that was checked and is about to be extracted
+struct Entry {
+    index: usize,
+    name: String,
+    out: PathBuf,
+    size: u64,
+    mode: Option<u32>,
+}
+
+impl Entry {
+    fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// Writes the entry to `out`, returning the number of bytes written for files
+    ///
+    /// With `capped` the copy stops past the size the archive claims, since sizes in the archive can lie
+    fn extract(&self, archive: &SharedArchive, capped: bool) -> Result<Option<u64>> {
+        let fs = vfs::current();
+        let mut archive = archive.clone();
+        let mut file = archive.by_index(self.index)?;
+        let written = if self.is_dir() {
+            fs.create_dir_all(&self.out)?;
+            None
+        } else {
+            if let Some(p) = self.out.parent() {
+                fs.create_dir_all(p)?;
+            }
+            trace!("Write file {}", self.out.display());
+            let mut outfile = fs.create(&self.out)?;
+            let limit = if capped {
+                self.size.saturating_add(1)
+            } else {
+                u64::MAX
+            };
+            Some(io::copy(&mut (&mut file).take(limit), &mut outfile)?)
+        };
+        if let Some(mode) = self.mode {
+            fs.set_mode(&self.out, mode)?;
+        }
+        Ok(written)
+    }
+}
+
+/// Extracts every entry of `archive` not in `skip` into `dir` on the [`pool`], checking for cancellation
+/// between files and reporting each one to `progress`
+///
+/// `route` maps the path of each entry to where it goes in `dir`, see [`PackageKind`]
+///
+/// Entries leaving `dir`, symlinks and archives over the config's `max_extracted_bytes` or
+/// `max_extracted_files` are refused before anything is written. Returns the number of files and bytes written
+fn extract_archive(
+    archive: &mut SharedArchive,
+    dir: &Path,
+    skip: &BTreeSet<String>,
+    package: &str,
+    route: &dyn Fn(&Path) -> PathBuf,
+    config: &ThermiteConfig,
+    progress: &dyn Fn(ProgressEvent),
+) -> Result<(usize, u64)> {
+    let too_large = || ThermiteError::ArchiveTooLarge {
+        package: package.into(),
+        limit: config.max_extracted_bytes.unwrap_or_default(),
+    };
+    let mut entries = vec![];
+    let mut files = 0;
+    let mut declared = 0u64;
+    let total = archive.len();
+    for i in 0..total {
+        cancel::checkpoint(format!("extracting to {}", dir.display()))?;
+        let file = archive.by_index_raw(i)?;
+        if skip.contains(file.name()) {
+            trace!("Skipping {}", file.name());
+            continue;
+        }
+        let refused = |path: &str| ThermiteError::UnsafePath {
+            package: package.into(),
+            path: path.into(),
+        };
+        let name = file.enclosed_name().ok_or_else(|| refused(file.name()))?;
+        if name.components().any(|c| c == Component::ParentDir) {
+            return Err(refused(file.name()));
+        }
+        // symlinks are stored with the file type in the upper bits of the mode
+        if file
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170_000 == 0o120_000)
+        {
+            return Err(ThermiteError::SymlinkEntry {
+                package: package.into(),
+                path: file.name().into(),
+            });
+        }
+        let entry = Entry {
+            index: i,
+            name: file.name().into(),
+            // rebuilt from its components to use the platform's separator, which `\\?\` paths require
+            out: dir.join(route(name).components().collect::<PathBuf>()),
+            size: file.size(),
+            mode: file.unix_mode(),
+        };
+        if !entry.is_dir() {
+            if let Some(limit) = config.max_extracted_files.filter(|limit| files >= *limit) {
+                return Err(ThermiteError::TooManyFiles {
+                    package: package.into(),
+                    limit,
+                });
+            }
+            declared = declared.saturating_add(entry.size);
+            if config
+                .max_extracted_bytes
+                .is_some_and(|limit| declared > limit)
+            {
+                return Err(too_large());
+            }
+            files += 1;
+        }
+        entries.push(entry);
+    }
 
-            let mut outfile = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&out)?;
+    let capped = config.max_extracted_bytes.is_some();
+    let mut bytes = 0;
+    for batch in entries.chunks(EXTRACT_BATCH) {
+        for entry in batch {
+            progress(ProgressEvent::ExtractingFile {
+                name: entry.name.clone(),
+                current: entry.index + 1,
+                total,
+            });
+        }
+        let written = pool::map(batch.iter().collect(), |entry| {
+            cancel::checkpoint(format!("extracting to {}", dir.display()))?;
+            let written = entry.extract(archive, capped)?;
+            // every file stays within its claimed size, so the total stays within the limit checked above
+            if capped && written.is_some_and(|written| written > entry.size) {
+                return Err(too_large());
+            }
+            Ok(written)
+        });
+        for written in written {
+            bytes += written?.unwrap_or_default();
+        }
+    }
+
+    Ok((files, bytes))
+}
+
+pub fn install_mod<T>(
+    mod_string: impl AsRef<str>,
+    zip_file: T,
+    target_dir: impl AsRef<Path>,
+) -> Result<InstallReport>
+where
+    T: Read + Seek,
+{
+    install_with_sanity(mod_string, zip_file, target_dir, |_| Ok(()))
+}
+
+/// Async version of [`install_mod`], the install starts on a background thread as soon as this is called
+///
+/// Installing is disk IO, so it runs on the shared pool of the [`blocking`](::blocking) crate instead of the
+/// caller's runtime. Dropping the future cancels the install, which rolls back like any other cancelled install.
+///
+/// # Errors
+/// * IO Errors
+/// * Misformatted mod files
+#[cfg(feature = "async")]
+pub fn install_mod_async<T>(
+    mod_string: impl Into<String>,
+    zip_file: T,
+    target_dir: impl Into<PathBuf>,
+) -> impl Future<Output = Result<InstallReport>>
+where
+    T: Read + Seek + Send + 'static,
+{
+    let (mod_string, target_dir) = (mod_string.into(), target_dir.into());
+    blocking::spawn(move || install_mod(mod_string, zip_file, target_dir))
+}
+
+/// Installs a package and its dependencies from any [`PackageSource`] to `target_dir`
+///
+/// `name` is either `author-name`, to install the latest version, or `author-name-X.Y.Z`. Dependencies are
+/// resolved against the source's index with [`resolve_install_order`] and skipped if their directory already
+/// exists in `target_dir`. Packages the index marks as deprecated are still installed, with a warning in their
+/// report.
+///
+/// Failing to fetch or install a package is reported as a [`ThermiteError::Context`] naming the package.
+///
+/// # Errors
+/// * `ThermiteError::DepError` if the package or one of its dependencies isn't in the source
+/// * Whatever the source fails with
+/// * IO Errors
+/// * Misformatted mod files
+pub fn install_from_source<S>(
+    source: &S,
+    name: impl AsRef<str>,
+    target_dir: impl AsRef<Path>,
+) -> Result<Vec<InstallReport>>
+where
+    S: PackageSource + ?Sized,
+{
+    let (name, target_dir) = (name.as_ref(), target_dir.as_ref());
+    let index = source.index()?;
+    let target = find_version(name, &index).ok_or_else(|| ThermiteError::DepError {
+        name: name.into(),
+        suggestions: suggest_packages(name, &index),
+    })?;
+
+    let fs = vfs::current();
+    let mut reports = vec![];
+    let install = |version: &ModVersion| {
+        let archive = source.fetch(version).map_err(|e| {
+            e.with_context(
+                ErrorContext::new("fetching")
+                    .package(&version.full_name)
+                    .url(&version.url),
+            )
+        })?;
+        install_mod(&version.full_name, io::Cursor::new(archive), target_dir).map_err(|e| {
+            e.with_context(
+                ErrorContext::new("installing")
+                    .package(&version.full_name)
+                    .path(target_dir),
+            )
+        })
+    };
+    for dep in resolve_install_order(&target.deps, &index)? {
+        if fs.exists(&target_dir.join(&dep.full_name))? {
+            debug!("Dependency {} is already installed", dep.full_name);
+            continue;
+        }
+        reports.push(install(&dep)?);
+    }
+    reports.push(install(target)?);
+    warn_deprecated(&mut reports, &index);
+
+    Ok(reports)
+}
+
+/// Adds a warning to the reports of packages that are deprecated in `index`
+pub(crate) fn warn_deprecated(reports: &mut [InstallReport], index: &[Mod]) {
+    for report in reports {
+        if let Some(msg) = deprecation_warning(&report.name, index) {
+            report::warning(&mut report.warnings, msg);
+        }
+    }
+}
+
+/// `author-name-X.Y.Z` exactly, or the newest version of `author-name`
+fn find_version<'a>(name: &str, index: &'a [Mod]) -> Option<&'a ModVersion> {
+    let (key, version) = match parse_modstring(name) {
+        Ok((author, package, version)) => (format!("{author}-{package}"), Some(version)),
+        Err(_) => (name.to_owned(), None),
+    };
+    let package = index
+        .iter()
+        .find(|m| format!("{}-{}", m.author, m.name).eq_ignore_ascii_case(&key))?;
+    match version {
+        Some(version) => package.get_version(version),
+        None => package.get_latest(),
+    }
+}
+
+/// Files carried over from the old version of a package by [`update`], unless the new version ships them and
+/// they weren't edited
+const CONFIG_EXTENSIONS: [&str; 4] = ["cfg", "ini", "json", "txt"];
+
+/// Updates an outdated package in place to the version in `zip_file`
+///
+/// The new version is installed next to the old one, which is only removed once everything else worked. It's
+/// always installed under its full name, even if the old one was installed under another directory name. In
+/// the `Legacy` layout its mods replace the old ones in their directories instead, as installing them would.
+/// Config files (`.cfg`, `.ini`, `.json` and `.txt`) in the old install that the new version doesn't ship,
+/// like settings written by the mod or the user, are copied over, as are the ones the user edited since the
+/// old version was installed, going by its checksums. These are listed in [`InstallReport::preserved`].
+/// Mods keep their state in the profile's `enabledmods.json`, and mods new to the package start disabled if
+/// every mod it had was disabled.
+///
+/// # Errors
+/// * IO Errors
+/// * Misformatted mod files
+/// * The profile's `enabledmods.json` is malformed
+/// * `ThermiteError::DirectoryLocked` if another process is changing the directory it's in
+pub fn update<T>(outdated: &OutdatedPackage, zip_file: T) -> Result<InstallReport>
+where
+    T: Read + Seek,
+{
+    let fs = vfs::current();
+    let target_dir = outdated
+        .path
+        .parent()
+        .ok_or_else(|| ThermiteError::MissingFile(Box::new(outdated.path.clone())))?;
+    let _lock = lock_dir(target_dir)?;
+    let old_mods = find_package_mods(&DirEntry {
+        path: outdated.path.clone(),
+        is_dir: true,
+    })?;
+    let mut enabled = match target_dir.parent().map(get_enabled_mods) {
+        Some(Ok(enabled)) => Some(enabled),
+        Some(Err(ThermiteError::MissingFile(_))) | None => None,
+        Some(Err(e)) => return Err(e),
+    };
+
+    let mut report = install_mod(outdated.latest_modstring(), zip_file, target_dir)?;
+    // in the `Legacy` layout the new version's mods replace the outdated ones where they are
+    let in_place = report
+        .installed_files
+        .iter()
+        .any(|f| f.starts_with(&outdated.path));
+    let kept = if in_place {
+        Ok(vec![])
+    } else {
+        carry_over_configs(&outdated.path, &report.path)
+    };
+    match kept {
+        Ok(kept) => report.preserved = kept.into_iter().map(|p| report.path.join(p)).collect(),
+        Err(e) => {
+            // the old version still works, a new one missing its configs might not
+            if !report.replaced {
+                fs.remove_dir_all(&report.path)?;
+            }
+            return Err(e);
+        }
+    }
 
-            trace!("Write file {}", out.display());
+    let mut previous = BTreeMap::new();
+    if let Some(enabled) = &mut enabled {
+        let all_disabled = !old_mods.is_empty()
+            && old_mods
+                .iter()
+                .all(|m| !enabled.is_enabled(&m.mod_json.name));
+        let new_mods = find_package_mods(&DirEntry {
+            path: report.path.clone(),
+            is_dir: true,
+        })?;
+        previous = enabled_states(enabled, old_mods.iter().chain(&new_mods));
+        for m in new_mods {
+            let name = m.mod_json.name;
+            let state = if old_mods.iter().any(|o| o.mod_json.name == name) {
+                enabled.is_enabled(&name)
+            } else {
+                !all_disabled
+            };
+            enabled.mods.insert(name, state);
+        }
+        enabled.save()?;
+    }
+
+    if !in_place {
+        debug!("Removing outdated {}", outdated.path.display());
+        fs.remove_dir_all(&outdated.path)?;
+    }
+    journal::record_update(
+        target_dir,
+        &report.path,
+        format!(
+            "{}-{}-{}",
+            outdated.author, outdated.name, outdated.installed
+        ),
+        previous,
+    );
+    Ok(report)
+}
+
+/// The state of each of `mods` in `enabled`, for the journal
+fn enabled_states<'a>(
+    enabled: &EnabledMods,
+    mods: impl IntoIterator<Item = &'a InstalledMod>,
+) -> BTreeMap<String, Option<bool>> {
+    mods.into_iter()
+        .map(|m| {
+            let name = m.mod_json.name.clone();
+            let state = enabled.mods.get(&name).copied();
+            (name, state)
+        })
+        .collect()
+}
+
+/// Reverses the latest install, update or uninstall in the journal of `dir` and drops it from the journal
+///
+/// Installed packages are removed, removed packages are installed again and updated packages go back to the
+/// version they replaced. Packages are reinstalled from their archive in `cache`, so undoing an update or an
+/// uninstall only works if the previous version was downloaded through it. Mods the operation enabled or
+/// disabled in the profile's `enabledmods.json` get their previous state back.
+///
+/// Calling this again undoes the operation before that. Returns the entry that was undone, `None` if the
+/// journal is empty.
+///
+/// # Errors
+/// * `ThermiteError::NotCached` if the archive of the package to reinstall isn't in `cache`
+/// * IO errors
+/// * The journal or the profile's `enabledmods.json` is malformed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn undo_last(dir: impl AsRef<Path>, cache: &PackageCache) -> Result<Option<JournalEntry>> {
+    let dir = dir.as_ref();
+    let mut entries = journal::read(dir)?;
+    let Some(entry) = entries.pop() else {
+        return Ok(None);
+    };
+    debug!("Undoing {:?} of {}", entry.operation, entry.name);
+
+    let reinstall = match entry.operation {
+        AuditOperation::Install => None,
+        AuditOperation::Update => Some(entry.previous.clone().unwrap_or_else(|| entry.full_name())),
+        AuditOperation::Uninstall => Some(entry.full_name()),
+        AuditOperation::InstallNorthstar | AuditOperation::RepairNorthstar => {
+            return Err(ThermiteError::CannotUndo(entry.operation));
+        }
+    };
+    let archive = reinstall
+        .map(|full_name| {
+            cache
+                .get(&full_name)?
+                .map(|archive| (full_name.clone(), archive))
+                .ok_or(ThermiteError::NotCached(full_name))
+        })
+        .transpose()?;
+
+    // the previous version is only put back in place once it's known to be available
+    if entry.operation != AuditOperation::Uninstall {
+        let (name, version) = (entry.full_name(), entry.version.clone());
+        let replaced = archive
+            .as_ref()
+            .is_some_and(|(full_name, _)| *full_name == name);
+        if !replaced && vfs::current().exists(&entry.path)? {
+            let mut audit_entry = AuditEntry::new(AuditOperation::Uninstall, &name, &entry.path);
+            audit_entry.version = version;
+            let res = remove_path(&entry.path).map_err(ThermiteError::from);
+            audit::record(audit_entry, &res);
+            res?;
+            events::emit(Event::ModRemoved {
+                name,
+                path: entry.path.clone(),
+            });
+        }
+    }
+    if let Some((full_name, archive)) = archive {
+        install_mod(full_name, io::Cursor::new(archive), dir)?;
+    }
+
+    if !entry.enabled.is_empty() {
+        if let Some(profile) = dir.parent() {
+            let mut enabled = match get_enabled_mods(profile) {
+                Ok(enabled) => enabled,
+                Err(ThermiteError::MissingFile(_)) => {
+                    EnabledMods::default_with_path(profile.join("enabledmods.json"))
+                }
+                Err(e) => return Err(e),
+            };
+            for (name, state) in &entry.enabled {
+                match state {
+                    Some(state) => enabled.mods.insert(name.clone(), *state),
+                    None => enabled.mods.remove(name),
+                };
+            }
+            enabled.save()?;
+        }
+    }
+
+    // also drops what reinstalling recorded
+    journal::truncate(dir, entries.len())?;
+    Ok(Some(entry))
+}
+
+/// Directories of a profile a [`backup`] holds
+const BACKUP_DIRS: [&str; 3] = ["packages", "mods", "plugins"];
+const BACKUP_MANIFEST: &str = "thermite-backup.json";
+
+#[derive(Serialize)]
+struct BackupManifest {
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+}
+
+/// Writes a compressed archive of the mods installed in `profile_dir` and its `enabledmods.json` to `out`
+///
+/// The archive holds the profile's `packages`, `mods` and `plugins` directories as they are, so [`restore`]
+/// puts back exactly what was installed without downloading anything, e.g. after an update went wrong.
+///
+/// # Errors
+/// * IO errors
+pub fn backup(profile_dir: impl AsRef<Path>, out: impl Write + Seek) -> Result<()> {
+    let fs = vfs::current();
+    let profile_dir = profile_dir.as_ref();
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(out);
+    for dir in BACKUP_DIRS {
+        let path = profile_dir.join(dir);
+        if fs.is_dir(&path) {
+            add_backup_files(&mut zip, &path, dir, options)?;
+        }
+    }
+    let enabled = profile_dir.join("enabledmods.json");
+    if fs.exists(&enabled)? {
+        zip.start_file("enabledmods.json", options)?;
+        zip.write_all(&fs.read(&enabled)?)?;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    zip.start_file(BACKUP_MANIFEST, options)?;
+    zip.write_all(serde_json::to_string_pretty(&BackupManifest { timestamp })?.as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// Adds the files below `dir` to a backup under `rel`, leaving out staging directories and the journal
+fn add_backup_files(
+    zip: &mut ZipWriter<impl Write + Seek>,
+    dir: &Path,
+    rel: &str,
+    options: FileOptions,
+) -> Result<()> {
+    let fs = vfs::current();
+    for entry in fs.read_dir(dir)? {
+        let name = entry.file_name();
+        if name.starts_with(STAGING_PREFIX) || name == JOURNAL_DIR {
+            continue;
+        }
+
+        let rel = format!("{rel}/{name}");
+        if entry.is_dir {
+            add_backup_files(zip, &entry.path, &rel, options)?;
+        } else {
+            zip.start_file(rel, options)?;
+            zip.write_all(&fs.read(&entry.path)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the mods installed in `profile_dir` and its `enabledmods.json` with the ones in a [`backup`]
+///
+/// The backup is extracted next to the profile's directories before anything is replaced, so a broken archive
+/// leaves the profile as it was, and replacing a directory that fails puts back the ones already replaced.
+/// Mods and files the backup doesn't have are removed, the journal is kept.
+///
+/// # Errors
+/// * `ThermiteError::InvalidSnapshot` if the archive isn't a backup or has files outside of the profile
+/// * `ThermiteError::DirectoryLocked` if another process is changing one of the profile's directories
+/// * IO errors
+pub fn restore(archive: impl Read + Seek, profile_dir: impl AsRef<Path>) -> Result<()> {
+    let profile_dir = profile_dir.as_ref();
+    let mut archive = ZipArchive::new(archive)?;
+    if archive.by_name(BACKUP_MANIFEST).is_err() {
+        return Err(ThermiteError::InvalidSnapshot(format!(
+            "missing {BACKUP_MANIFEST}"
+        )));
+    }
+
+    let staging = profile_dir.join(format!("{STAGING_PREFIX}restore"));
+    let res = extract_backup(&mut archive, &staging)
+        .and_then(|()| replace_with_backup(profile_dir, &staging));
+    remove_staging(&staging);
+    res
+}
+
+fn extract_backup(archive: &mut ZipArchive<impl Read + Seek>, staging: &Path) -> Result<()> {
+    let fs = vfs::current();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let Some(rel) = file.enclosed_name().map(Path::to_path_buf) else {
+            return Err(ThermiteError::InvalidSnapshot(format!(
+                "{} would be extracted outside of the profile",
+                file.name()
+            )));
+        };
+        let included = rel == Path::new("enabledmods.json")
+            || rel
+                .components()
+                .next()
+                .is_some_and(|c| BACKUP_DIRS.iter().any(|dir| c.as_os_str() == *dir));
+        if !included {
+            continue;
+        }
+
+        let target = staging.join(rel);
+        if let Some(parent) = target.parent() {
+            fs.create_dir_all(parent)?;
+        }
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        fs.write(&target, &contents)?;
+    }
+    Ok(())
+}
+
+/// Swaps the profile's directories and `enabledmods.json` for the ones of an extracted backup
+///
+/// Each is renamed into `staging` as a whole and the extracted one renamed in its place. If a rename fails
+/// everything swapped so far is swapped back, the old directories are only removed with `staging` once all of
+/// them were replaced.
+fn replace_with_backup(profile_dir: &Path, staging: &Path) -> Result<()> {
+    let fs = vfs::current();
+    let _locks = BACKUP_DIRS
+        .iter()
+        .map(|dir| lock_dir(profile_dir.join(dir)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let replaced = staging.join("replaced");
+    fs.create_dir_all(&replaced)?;
+    let mut swaps = vec![];
+    for dir in BACKUP_DIRS {
+        let target = profile_dir.join(dir);
+        let staged = staging.join(dir);
+        // the journal, and the lock held on the directory, stay with it
+        let journal = target.join(JOURNAL_DIR);
+        for file in journal::files_in(&journal)? {
+            if let Ok(rel) = file.strip_prefix(&target) {
+                copy_file(&file, &staged.join(rel))?;
+            }
+        }
+        swaps.push((target, staged, replaced.join(dir)));
+    }
+    swaps.push((
+        profile_dir.join("enabledmods.json"),
+        staging.join("enabledmods.json"),
+        replaced.join("enabledmods.json"),
+    ));
+
+    for (done, (target, staged, old)) in swaps.iter().enumerate() {
+        if let Err(e) = swap(target, staged, old) {
+            for (target, staged, old) in swaps[..done].iter().rev() {
+                if let Err(e) = unswap(target, staged, old) {
+                    warn!("Unable to put back {}: {e}", target.display());
+                }
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Moves `target` to `old` and `staged` to `target`, whichever of them exist
+fn swap(target: &Path, staged: &Path, old: &Path) -> io::Result<()> {
+    let fs = vfs::current();
+    if fs.exists(target)? {
+        fs.rename(target, old)?;
+    }
+    if fs.exists(staged)? {
+        if let Err(e) = fs.rename(staged, target) {
+            if fs.exists(old)? {
+                fs.rename(old, target)?;
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Undoes a [`swap`]
+fn unswap(target: &Path, staged: &Path, old: &Path) -> io::Result<()> {
+    let fs = vfs::current();
+    if fs.exists(target)? {
+        fs.rename(target, staged)?;
+    }
+    if fs.exists(old)? {
+        fs.rename(old, target)?;
+    }
+    Ok(())
+}
+
+/// Copies config files from `old` that `new` doesn't have or that the user edited, keeping their paths in the
+/// package, and returns those paths
+fn carry_over_configs(old: &Path, new: &Path) -> Result<Vec<PathBuf>> {
+    let fs = vfs::current();
+    let unchanged = unchanged_files(old);
+    let mut kept = vec![];
+    for relative in config_files(old)? {
+        let target = new.join(&relative);
+        // without checksums an edited config can't be told apart from the old version's default
+        let edited = unchanged.as_ref().is_some_and(|u| !u.contains(&relative));
+        if edited || !fs.exists(&target)? {
+            trace!("Keeping {}", relative.display());
+            copy_file(&old.join(&relative), &target)?;
+            kept.push(relative);
+        }
+    }
+    Ok(kept)
+}
+
+/// Copies the config files the user edited in `old` to `new`, replacing the new version's, and returns their
+/// paths in the package
+fn preserve_configs(old: &Path, new: &Path) -> Result<Vec<PathBuf>> {
+    let unchanged = unchanged_files(old);
+    let mut preserved = vec![];
+    for relative in config_files(old)? {
+        if unchanged.as_ref().is_some_and(|u| u.contains(&relative)) {
+            continue;
+        }
+        trace!("Preserving {}", relative.display());
+        copy_file(&old.join(&relative), &new.join(&relative))?;
+        preserved.push(relative);
+    }
+    Ok(preserved)
+}
+
+/// The config files in `dir`, relative to it
+fn config_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let fs = vfs::current();
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs.read_dir(&current)? {
+            if entry.is_dir {
+                dirs.push(entry.path);
+                continue;
+            }
+            let is_config = entry
+                .path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| CONFIG_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+            // these describe the package and its mods, so they always come from the new version
+            let is_metadata = matches!(
+                entry.file_name(),
+                "manifest.json" | "mod.json" | AUTHOR_FILE
+            );
+            if !is_config || is_metadata {
+                continue;
+            }
+            if let Ok(relative) = entry.path.strip_prefix(dir) {
+                files.push(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    let fs = vfs::current();
+    if let Some(p) = to.parent() {
+        fs.create_dir_all(p)?;
+    }
+    fs.write(to, &fs.read(from)?)?;
+    Ok(())
+}
+
+/// Progress of [`install_modpack`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModpackProgress {
+    /// Bytes downloaded of every package, like [`Downloader::on_total_progress`]
+    Downloading { current: u64, total: u64 },
+    /// `current` of the `total` packages have been installed
+    Installing { current: usize, total: usize },
+}
+
+/// Installs every package pinned by a [`ModPack`], and their dependencies, to `target_dir`
+///
+/// Pinned packages are installed at exactly their pinned version. Dependencies the modpack doesn't pin get the
+/// newest version satisfying everything depending on them, see [`resolve_install_order`]. Every archive is
+/// downloaded, using the global config's `parallelism`, and checked against the index's checksum and the
+/// config's `verifier` before anything is installed. Pins of Northstar itself are skipped. Deprecated packages
+/// are installed with a warning in their report.
+///
+/// Errors downloading, checking or installing a package come as a [`ThermiteError::Context`] naming the package
+/// and the step, use [`ThermiteError::root`] to match on what went wrong.
+///
+/// # Params
+/// * `pack` - the packages to install
+/// * `index` - the package index to find them in
+/// * `target_dir` - the `packages` directory of the profile
+/// * `cb` - Callback to call with every chunk downloaded and every package installed
+///
+/// # Errors
+/// * `ThermiteError::DepError` if a pinned version or a dependency isn't in the index
+/// * `ThermiteError::DependencyConflict` if no version of a dependency satisfies every package depending on it
+/// * `ThermiteError::ChecksumMismatch` if an archive doesn't match its checksum
+/// * Network and IO errors
+/// * Misformatted mod files
+pub fn install_modpack<F>(
+    pack: &ModPack,
+    index: &[Mod],
+    target_dir: impl AsRef<Path>,
+    cb: F,
+) -> Result<Vec<InstallReport>>
+where
+    F: Fn(ModpackProgress) + Send + Sync + 'static,
+{
+    let config = config::config();
+    let versions = modpack_versions(pack, index, &config)?;
+    let mut reports = install_versions(&versions, target_dir.as_ref(), &config, cb)?;
+    warn_deprecated(&mut reports, index);
+    Ok(reports)
+}
+
+/// Installs the packages in a [`ModList`] that aren't installed in `target_dir` yet and applies the list's
+/// enabled state to the profile's `enabledmods.json`
+///
+/// Packages are installed like [`install_modpack`] does, with the listed versions pinned. Packages that aren't
+/// in the list are left installed and mods that aren't in it keep their state.
+///
+/// # Params
+/// * `list` - the list from [`export_mod_list`](super::utils::export_mod_list)
+/// * `index` - the package index to find the packages in
+/// * `target_dir` - the `packages` directory of the profile
+///
+/// # Errors
+/// * `ThermiteError::DepError` if a listed version or a dependency isn't in the index
+/// * Network and IO errors
+/// * Misformatted mod files
+/// * The profile's `enabledmods.json` isn't valid JSON
+pub fn import_mod_list(
+    list: &ModList,
+    index: &[Mod],
+    target_dir: impl AsRef<Path>,
+) -> Result<Vec<InstallReport>> {
+    let config = config::config();
+    let target_dir = target_dir.as_ref();
+    let profile = target_dir
+        .parent()
+        .ok_or_else(|| ThermiteError::MissingFile(Box::new(target_dir.to_path_buf())))?;
+
+    let fs = vfs::current();
+    let mut versions = modpack_versions(&list.to_modpack("import"), index, &config)?;
+    let mut missing = vec![];
+    for version in versions.drain(..) {
+        if fs.exists(&target_dir.join(&version.full_name))? {
+            debug!("{} is already installed", version.full_name);
+        } else {
+            missing.push(version);
+        }
+    }
+    let mut reports = install_versions(&missing, target_dir, &config, |_| {})?;
+    warn_deprecated(&mut reports, index);
+
+    for (name, state) in list.packages.values().flat_map(|p| &p.mods) {
+        set_mod_enabled(profile, name, *state)?;
+    }
+
+    Ok(reports)
+}
+
+/// Downloads and checks every archive, then installs them in order
+///
+/// Errors carry an [`ErrorContext`] naming the package and the step that failed.
+fn install_versions<F>(
+    versions: &[ModVersion],
+    target_dir: &Path,
+    config: &ThermiteConfig,
+    cb: F,
+) -> Result<Vec<InstallReport>>
+where
+    F: Fn(ModpackProgress) + Send + Sync + 'static,
+{
+    let cb = Arc::new(cb);
+    let progress = cb.clone();
+    let downloads = Downloader::new()
+        .urls(versions.iter().map(|v| v.url.clone()))
+        .on_total_progress(move |current, total| {
+            progress(ModpackProgress::Downloading { current, total });
+        })
+        .run()
+        .into_iter()
+        .zip(versions)
+        .map(|(res, version)| {
+            res.map_err(|e| {
+                e.with_context(
+                    ErrorContext::new("downloading")
+                        .package(&version.full_name)
+                        .url(&version.url),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    for (version, download) in versions.iter().zip(&downloads) {
+        check_archive(version, &download.data, config).map_err(|e| {
+            e.with_context(ErrorContext::new("verifying").package(&version.full_name))
+        })?;
+    }
+
+    let total = versions.len();
+    let mut reports = vec![];
+    for (i, (version, download)) in versions.iter().zip(downloads).enumerate() {
+        let mut report = install_with_config(
+            &version.full_name,
+            io::Cursor::new(download.data),
+            target_dir,
+            |_| Ok(()),
+            config,
+        )
+        .map_err(|e| {
+            e.with_context(
+                ErrorContext::new("installing")
+                    .package(&version.full_name)
+                    .path(target_dir),
+            )
+        })?;
+        report.warnings.splice(0..0, download.report.warnings);
+        reports.push(report);
+        cb(ModpackProgress::Installing {
+            current: i + 1,
+            total,
+        });
+    }
+
+    Ok(reports)
+}
+
+/// The versions [`install_modpack`] installs, unpinned dependencies first
+fn modpack_versions(
+    pack: &ModPack,
+    index: &[Mod],
+    config: &ThermiteConfig,
+) -> Result<Vec<ModVersion>> {
+    let mut pinned = vec![];
+    for (key, version) in &pack.packages {
+        if config.game.is_loader_package(key) {
+            debug!("Skip pinned {key}, it's installed with install_northstar");
+            continue;
+        }
+        let name = format!("{key}-{version}");
+        let found = index
+            .iter()
+            .find(|m| format!("{}-{}", m.author, m.name).eq_ignore_ascii_case(key))
+            .and_then(|m| m.get_version(version))
+            .ok_or_else(|| ThermiteError::DepError {
+                suggestions: suggest_packages(&name, index),
+                name,
+            })?;
+        pinned.push(found.clone());
+    }
+
+    let deps = pinned.iter().flat_map(|v| &v.deps).collect::<Vec<_>>();
+    let mut versions = resolve_install_order(&deps, index)?;
+    // the modpack's pins win over the versions its packages depend on
+    let key = |v: &ModVersion| {
+        v.full_name
+            .rsplit_once('-')
+            .map(|(key, _)| key.to_lowercase())
+    };
+    let pinned_keys = pinned.iter().filter_map(key).collect::<BTreeSet<_>>();
+    versions.retain(|v| key(v).is_none_or(|k| !pinned_keys.contains(&k)));
+    versions.extend(pinned);
+    Ok(versions)
+}
+
+/// Checks a downloaded archive against the index's checksum and the config's verifier
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn check_archive(
+    version: &ModVersion,
+    archive: &[u8],
+    config: &ThermiteConfig,
+) -> Result<()> {
+    if let Some(expected) = &version.sha256 {
+        let actual = verify::encode_hash(&verify::sha256(archive));
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(ThermiteError::ChecksumMismatch {
+                url: version.url.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+    if let Some(verifier) = &config.verifier {
+        verifier.verify(&version.full_name, archive)?;
+    }
+    Ok(())
+}
+
+/// Install N* to the provided path
+///
+/// If the `wsock32.dll` proxy was disabled with [`set_launch_method`](super::launch::set_launch_method) it stays
+/// disabled
+///
+/// # Params
+/// * `zip_file` - compressed mod file
+/// * `game_path` - the path of the Titanfall 2 install
+///
+/// # Errors
+/// * IO Errors
+/// * `ThermiteError::InsufficientSpace` if the extracted files wouldn't fit on `game_path`'s filesystem
+/// * The game is running and the global config's `running_game` policy refuses, or gave up waiting
+pub fn install_northstar(
+    zip_file: impl Read + Seek,
+    game_path: impl AsRef<Path>,
+) -> Result<NorthstarReport> {
+    install_northstar_reporting(
+        zip_file,
+        game_path.as_ref(),
+        &|_| true,
+        &|_, _| {},
+        &config::config(),
+    )
+}
+
+/// [`install_northstar`] that only extracts the files of the release `filter` accepts, given their path
+/// relative to `game_path`
+pub(crate) fn install_northstar_filtered(
+    zip_file: impl Read + Seek,
+    game_path: &Path,
+    filter: &dyn Fn(&Path) -> bool,
+) -> Result<NorthstarReport> {
+    install_northstar_reporting(zip_file, game_path, filter, &|_, _| {}, &config::config())
+}
+
+/// [`install_northstar`] that stops once `token` is cancelled, the same as running it in
+/// [`CancelToken::run`]
+///
+/// Files the install added are removed again when it's cancelled. Files it already replaced stay replaced,
+/// so the install should be repaired or run again.
+///
+/// # Errors
+/// * IO Errors
+/// * `ThermiteError::Cancelled` if `token` was cancelled
+/// * `ThermiteError::Timeout` if `token`'s deadline passed
+pub fn install_northstar_cancellable(
+    zip_file: impl Read + Seek,
+    game_path: impl AsRef<Path>,
+    token: &CancelToken,
+) -> Result<NorthstarReport> {
+    token.run(|| install_northstar(zip_file, game_path))
+}
+
+/// Progress of [`install_northstar_with_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NorthstarProgress {
+    /// Same as the params of [`download_with_progress`]'s callback
+    Downloading {
+        delta: u64,
+        current: u64,
+        total: u64,
+    },
+    /// `current` of the release's `total` files have been extracted
+    Extracting { current: usize, total: usize },
+}
+
+/// Download N* from `url` and install it to the provided path, see [`install_northstar`]
+///
+/// # Params
+/// * `url` - URL of the release to download
+/// * `game_path` - the path of the Titanfall 2 install
+/// * `cb` - Callback to call with every chunk downloaded and every file extracted
+///
+/// # Errors
+/// * IO Errors
+/// * The game is running and the global config's `running_game` policy refuses, or gave up waiting
+pub fn install_northstar_with_progress<F>(
+    url: impl AsRef<str>,
+    game_path: impl AsRef<Path>,
+    cb: F,
+) -> Result<NorthstarReport>
+where
+    F: Fn(NorthstarProgress),
+{
+    let mut archive = vec![];
+    let download = download_with_progress(&mut archive, url, |delta, current, total| {
+        cb(NorthstarProgress::Downloading {
+            delta,
+            current,
+            total,
+        });
+    })?;
+    let mut report = install_northstar_reporting(
+        io::Cursor::new(archive),
+        game_path.as_ref(),
+        &|_| true,
+        &|current, total| {
+            cb(NorthstarProgress::Extracting { current, total });
+        },
+        &config::config(),
+    )?;
+    report.warnings.splice(0..0, download.warnings);
+    Ok(report)
+}
+
+fn install_northstar_reporting(
+    zip_file: impl Read + Seek,
+    game_path: &Path,
+    filter: &dyn Fn(&Path) -> bool,
+    progress: &dyn Fn(usize, usize),
+    config: &ThermiteConfig,
+) -> Result<NorthstarReport> {
+    let started = Instant::now();
+    let mut entry = AuditEntry::new(AuditOperation::InstallNorthstar, "Northstar", game_path);
+    let res =
+        install_northstar_files(zip_file, game_path, filter, progress, config).map(|mut report| {
+            report.duration = started.elapsed();
+            report
+        });
+    if let Ok(report) = &res {
+        entry.version = report.version.clone();
+        events::emit(Event::NorthstarUpdated {
+            target: report.target.clone(),
+            version: report.version.clone(),
+        });
+    }
+    audit::record(entry, &res);
+    res
+}
+
+fn install_northstar_files(
+    zip_file: impl Read + Seek,
+    target: &Path,
+    filter: &dyn Fn(&Path) -> bool,
+    progress: &dyn Fn(usize, usize),
+    config: &ThermiteConfig,
+) -> Result<NorthstarReport> {
+    // Northstar's files are replaced where they are, which the running game would see half done
+    check_game_running(config)?;
+    let mut archive = share_archive(zip_file)?;
+    status::emit(StatusEvent::NorthstarInstallStarted {
+        target: target.into(),
+    });
+
+    let manifest = northstar_manifest(&mut archive)?;
+    let mut report = NorthstarReport {
+        target: target.into(),
+        version: northstar_version(manifest.as_deref()),
+        ..Default::default()
+    };
+    if manifest.is_none() {
+        report::warning(
+            &mut report.warnings,
+            "Northstar archive has no manifest.json",
+        );
+    }
+
+    // files being replaced free up their space as they're overwritten
+    let fs = vfs::current();
+    let mut required = 0;
+    for i in 0..archive.len() {
+        let f = archive.by_index_raw(i)?;
+        let Some(rel) = f
+            .enclosed_name()
+            .and_then(|p| p.strip_prefix("Northstar").ok())
+        else {
+            continue;
+        };
+        if !f.is_dir() && filter(rel) {
+            let existing = fs.file_len(&target.join(rel)).unwrap_or_default();
+            required += f.size().saturating_sub(existing);
+        }
+    }
+    check_space(target, required)?;
+
+    // files that are new to the install, so a cancelled install doesn't leave them behind
+    let added = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .filter_map(|name| Path::new(name).strip_prefix("Northstar").ok())
+        .filter(|rel| rel.components().all(|c| matches!(c, Component::Normal(_))))
+        .filter(|rel| filter(rel))
+        .map(|rel| target.join(rel))
+        .filter(|path| !fs.exists(path).unwrap_or(true))
+        .collect::<Vec<_>>();
+    let written = match extract_northstar(&mut archive, target, &mut report, filter, progress) {
+        Err(e @ (ThermiteError::Cancelled | ThermiteError::Timeout { .. })) => {
+            debug!("Removing the files added by the cancelled install");
+            for path in added {
+                remove_staging(&path);
+            }
+            return Err(e);
+        }
+        res => res?,
+    };
+    tag_core_mods(target, manifest.as_deref(), &mut report)?;
+    #[cfg(not(target_arch = "wasm32"))]
+    northstar::record_files(target, report.version.as_deref(), &written)?;
+    #[cfg(target_arch = "wasm32")]
+    let _ = written;
+
+    launch::keep_proxy_disabled(target)?;
+
+    status::emit(StatusEvent::NorthstarInstallFinished {
+        target: target.into(),
+    });
+
+    Ok(report)
+}
+
+/// The contents of a Northstar release's `manifest.json`, if it has one
+pub(crate) fn northstar_manifest(
+    archive: &mut ZipArchive<impl Read + Seek>,
+) -> Result<Option<Vec<u8>>> {
+    archive
+        .by_name("manifest.json")
+        .ok()
+        .map(|mut v| {
+            let mut buf = Vec::with_capacity(usize::try_from(v.size())?);
+            if let Err(e) = v.read_to_end(&mut buf) {
+                Err(ThermiteError::from(e))
+            } else {
+                Ok(buf)
+            }
+        })
+        .transpose()
+}
+
+pub(crate) fn northstar_version(manifest: Option<&[u8]>) -> Option<String> {
+    manifest
+        .and_then(|m| serde_json::from_slice::<serde_json::Value>(m).ok())
+        .and_then(|v| v["version_number"].as_str().map(ToOwned::to_owned))
+}
+
+/// Extracts the files in the release's `Northstar` directory that `filter` accepts into `target`
+///
+/// `filter` is given each file's path relative to `target`, `progress` the number of archive entries done and
+/// the total as it goes. Returns the relative paths of the files written
+pub(crate) fn extract_northstar(
+    archive: &mut SharedArchive,
+    target: &Path,
+    report: &mut NorthstarReport,
+    filter: impl Fn(&Path) -> bool,
+    progress: &dyn Fn(usize, usize),
+) -> Result<Vec<PathBuf>> {
+    let mut entries = vec![];
+    let mut written = vec![];
+    let total = archive.len();
+    for i in 0..total {
+        cancel::checkpoint(format!("installing Northstar to {}", target.display()))?;
+        let f = archive.by_index_raw(i)?;
+
+        //This should work fine for N* because the dir structure *should* always be the same
+        let name = f.enclosed_name().ok_or_else(|| ThermiteError::UnsafePath {
+            package: "Northstar".into(),
+            path: f.name().into(),
+        })?;
+        let Ok(rel) = name.strip_prefix("Northstar") else {
+            continue;
+        };
+        if !filter(rel) {
+            continue;
+        }
+        let entry = Entry {
+            index: i,
+            name: f.name().into(),
+            out: target.join(rel),
+            size: f.size(),
+            mode: None,
+        };
+        if !entry.is_dir() {
+            written.push(rel.to_path_buf());
+        }
+        entries.push(entry);
+    }
 
-            io::copy(&mut f, &mut outfile)?;
+    let mut reported = 0;
+    for batch in entries.chunks(EXTRACT_BATCH) {
+        let last = batch.last().map_or(reported, |e| e.index);
+        for i in reported..=last {
+            progress(i, total);
+        }
+        reported = last + 1;
+        let extracted = pool::map(batch.iter().collect(), |entry| {
+            cancel::checkpoint(format!("installing Northstar to {}", target.display()))?;
+            entry.extract(archive, false)
+        });
+        for bytes in extracted.into_iter().filter_map(Result::transpose) {
+            report.bytes_written += bytes?;
+            report.files_written += 1;
         }
     }
+    progress(total, total);
+    Ok(written)
+}
 
-    // add manifest and author file
-    for child in game_path
-        .as_ref()
-        .join("R2Northstar")
-        .join("mods")
-        .read_dir()?
-    {
-        let Ok(child) = child else {
-            continue;
-        };
-        if ![
-            OsString::from("Northstar.Client"),
-            OsString::from("Northstar.Custom"),
-            OsString::from("Northstar.CustomServers"),
-        ]
-        .contains(&child.file_name())
-        {
+/// Adds the manifest and author file Thunderstore packages have to the core mods in `target`
+pub(crate) fn tag_core_mods(
+    target: &Path,
+    manifest: Option<&[u8]>,
+    report: &mut NorthstarReport,
+) -> Result<()> {
+    let fs = vfs::current();
+    let game = &config::config().game;
+    for child in fs.read_dir(&target.join(&game.profile).join("mods"))? {
+        if !game.is_core_mod(child.file_name()) {
             continue;
         }
 
-        if child.file_type()?.is_dir() {
-            let dir = child.path();
-            let manifest_file = dir.join("manifest.json");
-            let author_file = dir.join("thunderstore_author.txt");
+        if child.is_dir {
+            let dir = child.path;
 
             // write the manifest to the mod's directory
-            {
-                let mut file = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(manifest_file)?;
-                if let Some(manifest) = &manifest {
-                    file.write_all(manifest)?;
-                }
-            }
+            fs.write(&dir.join("manifest.json"), manifest.unwrap_or_default())?;
 
             // write the author file to the mod's directory
-            {
-                let mut file = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(author_file)?;
-                file.write_all(b"northstar")?;
-            }
+            fs.write(&dir.join(AUTHOR_FILE), game.loader_author().as_bytes())?;
+            report.files_written += 2;
         }
     }
-
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::core::utils::TempDir;
+    use crate::{
+        api::verified::VerifiedMods,
+        config::RunningGamePolicy,
+        core::{
+            hooks::{add_hook, remove_hook, Hook},
+            utils::{check_updates, disable_mod, enable_mod, find_mods, history, TempDir},
+            vfs::{Fs, MemoryFs, RealFs},
+        },
+        game::GameSpec,
+        model::{EnabledMods, Mod},
+        test_util::{mod_archive, mod_archive_with, Failure, MockPackage, MockServer},
+    };
     use mockall::mock;
-    use std::io::Cursor;
+    use std::{fs, io::Cursor, sync::Arc};
     use tracing::info;
 
     use super::{install_mod, *};
@@ -300,11 +3116,221 @@ mod test {
 
         let res = download(mock_writer, TEST_URL);
         assert!(res.is_ok());
-        res.map(|size| {
-            assert_eq!(size, TEST_SIZE_BYTES);
-            size
+        assert_eq!(res.unwrap().bytes, TEST_SIZE_BYTES);
+    }
+
+    #[test]
+    fn download_from_mock() {
+        let server = MockServer::start().expect("start mock server");
+        let package = MockPackage::new("foo", "bar", "0.1.0");
+        server.add_package(package.clone());
+
+        let mut buf = vec![];
+        let report = download(&mut buf, server.download_url(&package)).expect("download");
+        assert_eq!(report.bytes, package.archive.len() as u64);
+        assert!(report.warnings.is_empty());
+        assert_eq!(buf, package.archive);
+    }
+
+    #[test]
+    fn progress_events() {
+        let server = MockServer::start().expect("start mock server");
+        let package = MockPackage::new("Foo", "Bar", "1.0.0");
+        server.add_package(package.clone());
+        let events = std::sync::Mutex::new(vec![]);
+
+        let mut buf = vec![];
+        download_with_events(&mut buf, server.download_url(&package), |e| {
+            events.lock().unwrap().push(e);
         })
-        .unwrap();
+        .expect("download");
+        let downloaded = std::mem::take(&mut *events.lock().unwrap());
+        let size = package.archive.len() as u64;
+        assert_eq!(downloaded[0], ProgressEvent::Started { total: size });
+        let bytes: u64 = downloaded
+            .iter()
+            .map(|e| match e {
+                ProgressEvent::Chunk { bytes } => *bytes,
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(bytes, size);
+        assert_eq!(downloaded.last(), Some(&ProgressEvent::Finished));
+
+        let dir = TempDir::create("./test_install_progress").expect("Unable to create temp dir");
+        install_with_progress("Foo-Bar-1.0.0", Cursor::new(buf), &dir, |e| {
+            events.lock().unwrap().push(e);
+        })
+        .expect("install");
+        let installed = events.into_inner().unwrap();
+        let Some((ProgressEvent::Finished, extracted)) = installed.split_last() else {
+            panic!("install didn't finish: {installed:?}");
+        };
+        assert!(extracted.iter().any(|e| matches!(
+            e,
+            ProgressEvent::ExtractingFile { name, .. } if name == "manifest.json"
+        )));
+        assert!(matches!(
+            extracted.last(),
+            Some(ProgressEvent::ExtractingFile { current, total, .. }) if current == total
+        ));
+    }
+
+    #[test]
+    fn verify_download() {
+        let server = MockServer::start().expect("start mock server");
+        let package = MockPackage::new("foo", "bar", "0.1.0");
+        server.add_package(package.clone());
+        let index =
+            crate::api::fetch_index(&server.index_url(), None, &Default::default()).expect("index");
+        let mut version = index[0].get_latest().cloned().expect("version");
+
+        let mut buf = vec![];
+        let report = download_verified(&mut buf, &version).expect("matching checksum");
+        assert!(report.warnings.is_empty());
+        assert_eq!(buf, package.archive);
+
+        version.sha256 = Some("00".repeat(32));
+        match download_verified(vec![], &version) {
+            Err(ThermiteError::ChecksumMismatch { expected, .. }) => {
+                assert_eq!(expected, "00".repeat(32));
+            }
+            res => panic!("Expected ChecksumMismatch, got {res:?}"),
+        }
+
+        version.sha256 = None;
+        let report = download_verified(vec![], &version).expect("no checksum");
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn resume_download() {
+        let server = MockServer::start().expect("start mock server");
+        let package = MockPackage::new("foo", "bar", "0.1.0");
+        server.add_package(package.clone());
+        let url = server.download_url(&package);
+        let path = url.trim_start_matches(&server.url()).to_owned();
+
+        let dir = TempDir::create("./test_resume_download").expect("Unable to create temp dir");
+        let file = dir.join("foo-bar-0.1.0.zip");
+        server.fail_next(&path, Failure::Truncate(100));
+        download_resumable(&file, &url, |_, _, _| {}).expect_err("connection dropped");
+        assert_eq!(fs::metadata(&file).unwrap().len(), 100);
+
+        let last = AtomicU64::new(0);
+        let report = download_resumable(&file, &url, |_, current, _| {
+            last.store(current, Ordering::Relaxed);
+        })
+        .expect("resume");
+        assert_eq!(report.resumed_from, 100);
+        assert_eq!(report.bytes, package.archive.len() as u64 - 100);
+        assert_eq!(last.load(Ordering::Relaxed), package.archive.len() as u64);
+        assert_eq!(fs::read(&file).unwrap(), package.archive);
+        let range = server
+            .requests()
+            .last()
+            .unwrap()
+            .headers
+            .get("range")
+            .cloned();
+        assert_eq!(range.as_deref(), Some("bytes=100-"));
+
+        // a complete file is downloaded again from the start
+        let report = download_resumable(&file, &url, |_, _, _| {}).expect("redownload");
+        assert_eq!(report.resumed_from, 0);
+        assert_eq!(fs::read(&file).unwrap(), package.archive);
+    }
+
+    #[test]
+    fn cancelled_download_output() {
+        let server = MockServer::start().expect("start mock server");
+        let body = vec![7; CHUNK_SIZE * 8];
+        server.serve("/big.bin", body.clone());
+        let url = format!("{}/big.bin", server.url());
+        // cancels after the first chunk
+        let cancel_after_first = |token: &CancelToken| {
+            let token = token.clone();
+            move |_, _, _| token.cancel()
+        };
+
+        let token = CancelToken::new();
+        let mut output = vec![];
+        let res = download_with_progress_cancellable(
+            &mut output,
+            &url,
+            &token,
+            cancel_after_first(&token),
+        );
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+        assert_eq!(
+            output.len(),
+            CHUNK_SIZE,
+            "the partial download is left for the caller"
+        );
+
+        let dir = TempDir::create("./test_cancelled_download").expect("Unable to create temp dir");
+        let file = dir.join("big.bin");
+        let token = CancelToken::new();
+        let res = token.run(|| download_resumable(&file, &url, cancel_after_first(&token)));
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+        assert_eq!(fs::metadata(&file).unwrap().len(), CHUNK_SIZE as u64);
+        let report = download_resumable(&file, &url, |_, _, _| {}).expect("resume");
+        assert_eq!(report.resumed_from, CHUNK_SIZE as u64);
+        assert_eq!(fs::read(&file).unwrap(), body);
+
+        let file = dir.join("cancelled.bin");
+        let token = CancelToken::new();
+        let res = download_file_with_progress_cancellable(
+            &file,
+            &url,
+            &token,
+            cancel_after_first(&token),
+        );
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+        assert!(!file.exists(), "the partial file should be removed");
+        download_file_cancellable(&file, &url, &CancelToken::new()).expect("download");
+        assert_eq!(fs::read(&file).unwrap(), body);
+    }
+
+    #[test]
+    fn parallel_downloads() {
+        let server = MockServer::start().expect("start mock server");
+        let packages = [
+            MockPackage::new("foo", "bar", "0.1.0"),
+            MockPackage::new("foo", "baz", "0.2.0"),
+        ];
+        for package in &packages {
+            server.add_package(package.clone());
+        }
+
+        let progress = Arc::new(AtomicU64::new(0));
+        let results = Downloader::new()
+            .url(server.download_url(&packages[0]))
+            .url(format!("{}/missing.zip", server.url()))
+            .url(server.download_url(&packages[1]))
+            .workers(NonZeroUsize::new(2).unwrap())
+            .on_total_progress({
+                let progress = progress.clone();
+                move |done, _| {
+                    progress.fetch_max(done, Ordering::Relaxed);
+                }
+            })
+            .run();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().expect("download").data,
+            packages[0].archive
+        );
+        assert!(results[1].is_err(), "missing file should fail on its own");
+        assert_eq!(
+            results[2].as_ref().expect("download").data,
+            packages[1].archive
+        );
+        assert_eq!(
+            progress.load(Ordering::Relaxed),
+            (packages[0].archive.len() + packages[1].archive.len()) as u64
+        );
     }
 
     #[test]
@@ -337,7 +3363,17 @@ mod test {
         let path = TempDir::create("./test_dir").expect("Unable to create temp dir");
         let res = install_mod("foo-bar-0.1.0", &mut cursor, &path);
 
-        if let Ok(path) = res {
+        if let Ok(report) = res {
+            assert!(report
+                .installed_files
+                .contains(&report.path.join("manifest.json")));
+            assert!(report.installed_files.iter().all(|f| f.is_file()));
+            assert!(!report
+                .installed_files
+                .contains(&report.path.join(crate::core::utils::CHECKSUMS_FILE)));
+            let path = report.path;
+            assert!(report.files_written > 0);
+            assert!(!report.replaced);
             assert!(
                 path.join("mods")
                     .join("Smart CAR")
@@ -355,6 +3391,901 @@ mod test {
         }
     }
 
+    #[test]
+    fn overwrite_policy() {
+        let path = TempDir::create("./test_overwrite_policy").expect("Unable to create temp dir");
+        let install = |overwrite| {
+            install_with_config(
+                "foo-bar-0.1.0",
+                Cursor::new(TEST_ARCHIVE),
+                &path,
+                |_| Ok(()),
+                &ThermiteConfig {
+                    overwrite,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let installed = install(OverwritePolicy::Fail)
+            .expect("First install should succeed")
+            .path;
+        let stale = installed.join("stale.txt");
+        fs::write(&stale, "old").expect("write stale file");
+
+        match install(OverwritePolicy::Fail) {
+            Err(ThermiteError::AlreadyInstalled(p)) => assert_eq!(p, installed),
+            res => panic!("Expected AlreadyInstalled, got {res:?}"),
+        }
+
+        let report = install(OverwritePolicy::Replace).expect("Replace should succeed");
+        assert!(report.replaced);
+        assert!(!stale.exists(), "Replace should remove the old install");
+        assert!(installed.join("manifest.json").exists());
+    }
+
+    #[test]
+    fn merge_preserving_configs() {
+        let path =
+            TempDir::create("./test_merge_preserving_configs").expect("Unable to create temp dir");
+        let install = |version, overwrite| {
+            install_with_config(
+                "Foo-Bar-1.0.0",
+                Cursor::new(mod_archive_with(
+                    "Bar",
+                    version,
+                    &[
+                        ("mods/Bar/settings.cfg", version),
+                        ("mods/Bar/keys.ini", version),
+                    ],
+                )),
+                &path,
+                |_| Ok(()),
+                &ThermiteConfig {
+                    overwrite,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let installed = install("1.0.0", OverwritePolicy::Replace)
+            .expect("First install should succeed")
+            .path;
+        let settings = installed.join("mods/Bar/settings.cfg");
+        fs::write(&settings, "edited").expect("edit config");
+
+        let report = install("1.1.0", OverwritePolicy::MergePreservingConfigs)
+            .expect("Merge should succeed");
+        assert!(report.replaced);
+        assert_eq!(report.preserved, vec![settings.clone()]);
+        assert_eq!(fs::read_to_string(&settings).unwrap(), "edited");
+        assert_eq!(
+            fs::read_to_string(installed.join("mods/Bar/keys.ini")).unwrap(),
+            "1.1.0",
+            "Unedited configs should come from the new version"
+        );
+    }
+
+    #[test]
+    fn install_options() {
+        let path = TempDir::create("./test_install_options").expect("Unable to create temp dir");
+        let install = |options: &InstallOptions| {
+            install_with_options(
+                "Foo-Bar-1.0.0",
+                Cursor::new(mod_archive("Bar", "1.0.0")),
+                &path,
+                options,
+            )
+        };
+
+        let stripped = InstallOptions {
+            strip_version: true,
+            ..Default::default()
+        };
+        let report = install(&stripped).expect("install without version");
+        assert_eq!(report.path, path.join("Foo-Bar"));
+        assert_eq!(
+            fs::read_to_string(report.path.join(AUTHOR_FILE)).unwrap(),
+            "Foo"
+        );
+        assert!(matches!(
+            install(&InstallOptions {
+                overwrite: Some(OverwritePolicy::Fail),
+                ..stripped
+            }),
+            Err(ThermiteError::AlreadyInstalled(_))
+        ));
+
+        let named = InstallOptions {
+            dir_name: Some("Custom".into()),
+            ..Default::default()
+        };
+        assert_eq!(install(&named).expect("install").path, path.join("Custom"));
+        let found = find_mods(&*path).expect("find mods");
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|m| m.author == "Foo"));
+
+        assert!(matches!(
+            install(&InstallOptions {
+                dir_name: Some("../Escape".into()),
+                ..Default::default()
+            }),
+            Err(ThermiteError::NameError(_))
+        ));
+        let report = install(&InstallOptions::default()).expect("default install");
+        assert!(!report.path.join(AUTHOR_FILE).exists());
+    }
+
+    #[test]
+    fn renamed_packages() {
+        let path = TempDir::create("./test_renamed_packages").expect("Unable to create temp dir");
+        let path = fs::canonicalize(&path).unwrap();
+        let index = [Mod {
+            name: "Bar".into(),
+            author: "Foo".into(),
+            latest: "2.0.0".into(),
+            ..Default::default()
+        }];
+        for (options, name) in [
+            (
+                InstallOptions {
+                    strip_version: true,
+                    ..Default::default()
+                },
+                "Foo-Bar",
+            ),
+            (
+                InstallOptions {
+                    dir_name: Some("Custom".into()),
+                    ..Default::default()
+                },
+                "Custom",
+            ),
+        ] {
+            let install = || {
+                install_with_options(
+                    "Foo-Bar-1.0.0",
+                    Cursor::new(mod_archive("Bar", "1.0.0")),
+                    &path,
+                    &options,
+                )
+                .expect("install")
+            };
+
+            install();
+            let outdated = check_updates(&find_mods(&path).unwrap(), &index);
+            assert_eq!(outdated.len(), 1, "{name} should be outdated");
+            assert_eq!(outdated[0].path, path.join(name));
+            let report =
+                update(&outdated[0], Cursor::new(mod_archive("Bar", "2.0.0"))).expect("update");
+            assert!(!path.join(name).exists());
+            assert_eq!(
+                find_mods(&path).unwrap()[0].manifest.version_number,
+                "2.0.0"
+            );
+            remove_mod(&find_mods(&path).unwrap()[0], false).expect("remove update");
+            assert!(!report.path.exists());
+
+            install();
+            remove_mod(&find_mods(&path).unwrap()[0], false).expect("remove");
+            assert!(
+                !path.join(name).exists(),
+                "the whole package should be removed"
+            );
+        }
+    }
+
+    #[test]
+    fn update_legacy_layout() {
+        let dir = TempDir::create("./test_update_legacy").expect("Unable to create temp dir");
+        let mods = fs::canonicalize(&dir).unwrap().join("mods");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &mods,
+        )
+        .expect("install");
+        // mods in the legacy layout have no author, so `check_updates` can't tell they're outdated
+        let outdated = OutdatedPackage {
+            author: "Foo".into(),
+            name: "Bar".into(),
+            installed: "1.0.0".into(),
+            latest: "2.0.0".into(),
+            path: mods.join("Bar"),
+            deprecated: false,
+        };
+
+        let report = update(&outdated, Cursor::new(mod_archive("Bar", "2.0.0"))).expect("update");
+        assert_eq!(report.path, mods);
+        let found = find_mods(&mods).unwrap();
+        assert_eq!(found.len(), 1, "the new version shouldn't be removed");
+        assert_eq!(found[0].path, mods.join("Bar"));
+        assert_eq!(found[0].manifest.version_number, "2.0.0");
+    }
+
+    #[test]
+    fn unverified_plugins() {
+        let path = TempDir::create("./test_unverified_plugins").expect("Unable to create temp dir");
+        let archive = mod_archive_with("Bar", "1.0.0", &[("plugins/bar.DLL", "MZ")]);
+        let install = |config: &ThermiteConfig| {
+            install_with_config(
+                "Foo-Bar-1.0.0",
+                Cursor::new(&archive),
+                &path,
+                |_| Ok(()),
+                config,
+            )
+        };
+
+        let verified: VerifiedMods = serde_json::from_str(
+            r#"{"Bar": {"DependencyPrefix": "Foo-Bar", "Versions": [{"Version": "1.0.0"}]}}"#,
+        )
+        .unwrap();
+        match install(&ThermiteConfig {
+            verified_mods: Some(Arc::new(verified.clone())),
+            ..Default::default()
+        }) {
+            Err(ThermiteError::PluginsDetected { name, plugins }) => {
+                assert_eq!(name, "Foo-Bar-1.0.0");
+                assert_eq!(plugins, ["plugins/bar.DLL"]);
+            }
+            res => panic!("Expected PluginsDetected, got {res:?}"),
+        }
+
+        match install(&ThermiteConfig {
+            install_plugins: true,
+            ..Default::default()
+        }) {
+            Err(ThermiteError::UnverifiedPlugin(name)) => assert_eq!(name, "Foo-Bar-1.0.0"),
+            res => panic!("Expected UnverifiedPlugin, got {res:?}"),
+        }
+        assert!(!path.join("Foo-Bar-1.0.0").exists());
+
+        install(&ThermiteConfig {
+            install_plugins: true,
+            verified_mods: Some(Arc::new(verified)),
+            ..Default::default()
+        })
+        .expect("Verified plugins should install");
+
+        let report = install(&ThermiteConfig {
+            install_plugins: true,
+            allow_unverified_plugins: true,
+            ..Default::default()
+        })
+        .expect("Override should allow unverified plugins");
+        assert!(report.path.join("plugins").join("bar.DLL").exists());
+
+        // the legacy layout has no package to keep them in
+        let profile = path.join("R2Northstar");
+        install_with_config(
+            "Foo-Bar-1.0.0",
+            Cursor::new(&archive),
+            profile.join("mods"),
+            |_| Ok(()),
+            &ThermiteConfig {
+                install_plugins: true,
+                allow_unverified_plugins: true,
+                ..Default::default()
+            },
+        )
+        .expect("legacy install");
+        assert!(profile.join("plugins").join("bar.DLL").exists());
+        assert!(profile.join("mods").join("Bar").join("mod.json").exists());
+    }
+
+    #[test]
+    fn failed_install_rolls_back() {
+        let path = TempDir::create("./test_install_rollback").expect("Unable to create temp dir");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &path,
+        )
+        .expect("install");
+        let manifest = path.join("Foo-Bar-1.0.0").join("manifest.json");
+        let original = fs::read_to_string(&manifest).unwrap();
+
+        // the invalid path comes after the manifest, so extraction fails halfway through
+        let broken = mod_archive_with("Bar", "2.0.0", &[("../escape.txt", "")]);
+        install_mod("Foo-Bar-1.0.0", Cursor::new(broken), &path).expect_err("invalid path");
+        assert_eq!(fs::read_to_string(&manifest).unwrap(), original);
+        let leftover = |path: &Path| {
+            fs::read_dir(path).unwrap().any(|e| {
+                e.unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(STAGING_PREFIX)
+            })
+        };
+        assert!(!leftover(&path));
+
+        let mut transaction = InstallTransaction::new();
+        transaction
+            .stage(
+                "Foo-Baz-1.0.0",
+                Cursor::new(mod_archive("Baz", "1.0.0")),
+                &path,
+            )
+            .expect("stage");
+        transaction.rollback();
+        assert!(!path.join("Foo-Baz-1.0.0").exists());
+        assert!(!leftover(&path));
+
+        let mut transaction = InstallTransaction::new();
+        for (name, version) in [("Bar", "1.0.0"), ("Baz", "1.0.0")] {
+            transaction
+                .stage(
+                    format!("Foo-{name}-1.0.0"),
+                    Cursor::new(mod_archive(name, version)),
+                    &path,
+                )
+                .expect("stage");
+        }
+        assert!(!path.join("Foo-Baz-1.0.0").exists());
+        let reports = transaction.commit().expect("commit");
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].replaced);
+        assert!(path.join("Foo-Baz-1.0.0").join("manifest.json").exists());
+        assert_eq!(find_mods(&*path).unwrap().len(), 2);
+        assert!(!leftover(&path));
+    }
+
+    #[test]
+    fn update_in_place() {
+        let dir = TempDir::create("./test_update_mod").expect("Unable to create temp dir");
+        let packages = dir.join("packages");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive_with(
+                "Bar",
+                "1.0.0",
+                &[
+                    ("mods/Bar/settings.cfg", "1.0.0"),
+                    ("mods/Bar/keys.ini", "1.0.0"),
+                ],
+            )),
+            &packages,
+        )
+        .expect("install");
+        let old = packages.join("Foo-Bar-1.0.0");
+        fs::write(old.join("mods/Bar/user.cfg"), "volume 0.5").unwrap();
+        fs::write(old.join("mods/Bar/settings.cfg"), "edited").unwrap();
+        fs::write(old.join("mods/Bar/old.nut"), "").unwrap();
+        let mut enabled = EnabledMods::default_with_path(dir.join("enabledmods.json"));
+        enabled.mods.insert("Mock.Bar".into(), false);
+        enabled.save().unwrap();
+        enabled.dont_save();
+
+        let index = [Mod {
+            name: "Bar".into(),
+            author: "Foo".into(),
+            latest: "2.0.0".into(),
+            installed: false,
+            upgradable: false,
+            global: false,
+            versions: Default::default(),
+            ..Default::default()
+        }];
+        let outdated = check_updates(&find_mods(&packages).unwrap(), &index);
+        assert_eq!(outdated.len(), 1);
+        assert_eq!(outdated[0].installed, "1.0.0");
+        assert_eq!(outdated[0].latest_modstring(), "Foo-Bar-2.0.0");
+
+        let archive = mod_archive_with(
+            "Bar",
+            "2.0.0",
+            &[
+                ("mods/Bar/settings.cfg", "2.0.0"),
+                ("mods/Bar/keys.ini", "2.0.0"),
+            ],
+        );
+        let report = update(&outdated[0], Cursor::new(archive)).expect("update");
+        assert!(!old.exists());
+        assert_eq!(
+            fs::read_to_string(report.path.join("mods/Bar/user.cfg")).unwrap(),
+            "volume 0.5"
+        );
+        assert_eq!(
+            fs::read_to_string(report.path.join("mods/Bar/settings.cfg")).unwrap(),
+            "edited"
+        );
+        assert_eq!(
+            fs::read_to_string(report.path.join("mods/Bar/keys.ini")).unwrap(),
+            "2.0.0"
+        );
+        let mut preserved = report.preserved.clone();
+        preserved.sort();
+        assert_eq!(
+            preserved,
+            [
+                report.path.join("mods/Bar/settings.cfg"),
+                report.path.join("mods/Bar/user.cfg")
+            ]
+        );
+        assert!(!report.path.join("mods/Bar/old.nut").exists());
+        assert!(!get_enabled_mods(&*dir).unwrap().is_enabled("Mock.Bar"));
+        assert!(check_updates(&find_mods(&packages).unwrap(), &index).is_empty());
+    }
+
+    #[test]
+    fn remove_installed_mod() {
+        let dir = TempDir::create("./test_remove_mod").expect("Unable to create temp dir");
+        let packages = dir.join("packages");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        let mut enabled = EnabledMods::default_with_path(dir.join("enabledmods.json"));
+        enabled.mods.insert("Mock.Bar".into(), false);
+        enabled.mods.insert("Other".into(), true);
+        enabled.save().unwrap();
+        enabled.dont_save();
+
+        let installed = find_mods(&packages).unwrap().remove(0);
+        let mut core = installed.clone();
+        core.mod_json.name = "Northstar.Client".into();
+        core.path = dir.join("mods").join("Northstar.Client");
+        fs::create_dir_all(&core.path).unwrap();
+        assert!(matches!(
+            remove_mod(&core, false),
+            Err(ThermiteError::CoreMod(name)) if name == "Northstar.Client"
+        ));
+        assert!(core.path.exists());
+        remove_mod(&core, true).expect("forced removal");
+        assert!(!core.path.exists());
+
+        remove_mod(&installed, false).expect("remove");
+        assert!(!packages.join("Foo-Bar-1.0.0").exists());
+        let enabled = get_enabled_mods(&*dir).unwrap();
+        assert!(!enabled.mods.contains_key("Mock.Bar"));
+        assert!(enabled.mods.contains_key("Other"));
+    }
+
+    #[test]
+    fn undo_operations() {
+        let dir = TempDir::create("./test_undo_last").expect("Unable to create temp dir");
+        let packages = dir.join("packages");
+        let cache = PackageCache::new(dir.join("cache"));
+        assert_eq!(undo_last(&packages, &cache).unwrap(), None);
+
+        let archive = mod_archive("Bar", "1.0.0");
+        cache.put("Foo-Bar-1.0.0", &archive).unwrap();
+        install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &packages).expect("install");
+        let mut enabled = EnabledMods::default_with_path(dir.join("enabledmods.json"));
+        enabled.mods.insert("Mock.Bar".into(), false);
+        enabled.save().unwrap();
+        enabled.dont_save();
+
+        let index = [Mod {
+            name: "Bar".into(),
+            author: "Foo".into(),
+            latest: "2.0.0".into(),
+            ..Default::default()
+        }];
+        let outdated = check_updates(&find_mods(&packages).unwrap(), &index);
+        update(&outdated[0], Cursor::new(mod_archive("Bar", "2.0.0"))).expect("update");
+        let undone = undo_last(&packages, &cache).unwrap().expect("undo update");
+        assert_eq!(undone.operation, AuditOperation::Update);
+        assert!(packages.join("Foo-Bar-1.0.0").exists());
+        assert!(!packages.join("Foo-Bar-2.0.0").exists());
+
+        let installed = find_mods(&packages).unwrap().remove(0);
+        remove_mod(&installed, false).expect("remove");
+        undo_last(&packages, &cache).unwrap().expect("undo removal");
+        assert!(packages.join("Foo-Bar-1.0.0").exists());
+        assert!(!get_enabled_mods(&*dir).unwrap().is_enabled("Mock.Bar"));
+
+        let undone = undo_last(&packages, &cache).unwrap().expect("undo install");
+        assert_eq!(undone.operation, AuditOperation::Install);
+        assert!(find_mods(&packages).unwrap().is_empty());
+        assert_eq!(undo_last(&packages, &cache).unwrap(), None);
+
+        install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &packages).expect("install");
+        remove_mod(&find_mods(&packages).unwrap()[0], false).expect("remove");
+        cache.clear().unwrap();
+        assert!(matches!(
+            undo_last(&packages, &cache),
+            Err(ThermiteError::NotCached(name)) if name == "Foo-Bar-1.0.0"
+        ));
+    }
+
+    #[test]
+    fn backup_and_restore() {
+        let dir = TempDir::create("./test_backup_restore").expect("Unable to create temp dir");
+        let packages = dir.join("packages");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        disable_mod(&*dir, "Mock.Bar").unwrap();
+        let mut archive = Cursor::new(vec![]);
+        backup(&*dir, &mut archive).expect("backup");
+
+        remove_mod(&find_mods(&packages).unwrap()[0], false).expect("remove");
+        install_mod(
+            "Foo-Baz-1.0.0",
+            Cursor::new(mod_archive("Baz", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        enable_mod(&*dir, "Mock.Baz").unwrap();
+
+        restore(Cursor::new(archive.into_inner()), &*dir).expect("restore");
+        assert!(packages.join("Foo-Bar-1.0.0/mods/Bar/mod.json").exists());
+        assert!(!packages.join("Foo-Baz-1.0.0").exists());
+        let enabled = get_enabled_mods(&*dir).unwrap();
+        assert!(!enabled.is_enabled("Mock.Bar"));
+        assert!(!enabled.mods.contains_key("Mock.Baz"));
+        assert_eq!(history(&packages).unwrap().len(), 3, "the journal is kept");
+        assert!(!dir.join(format!("{STAGING_PREFIX}restore")).exists());
+
+        assert!(matches!(
+            restore(Cursor::new(mod_archive("Bar", "1.0.0")), &*dir),
+            Err(ThermiteError::InvalidSnapshot(_))
+        ));
+    }
+
+    /// Passes everything through to the disk, except renaming `fail`
+    struct FailingRename {
+        fail: PathBuf,
+    }
+
+    impl Fs for FailingRename {
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            RealFs.create_dir_all(path)
+        }
+        fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+            RealFs.create(path)
+        }
+        fn append(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+            RealFs.append(path)
+        }
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            RealFs.read(path)
+        }
+        fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+            RealFs.read_dir(path)
+        }
+        fn exists(&self, path: &Path) -> io::Result<bool> {
+            RealFs.exists(path)
+        }
+        fn is_dir(&self, path: &Path) -> bool {
+            RealFs.is_dir(path)
+        }
+        fn remove_file(&self, path: &Path) -> io::Result<()> {
+            RealFs.remove_file(path)
+        }
+        fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+            RealFs.remove_dir_all(path)
+        }
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            if from == self.fail {
+                return Err(io::Error::other("rename failed"));
+            }
+            RealFs.rename(from, to)
+        }
+        fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+            RealFs.canonicalize(path)
+        }
+        fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+            RealFs.set_mode(path, mode)
+        }
+        fn write_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+            RealFs.write_new(path, contents)
+        }
+    }
+
+    #[test]
+    fn failed_restore_rolls_back() {
+        let dir = TempDir::create("./test_restore_rollback").expect("Unable to create temp dir");
+        let packages = dir.join("packages");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        let mut archive = Cursor::new(vec![]);
+        backup(&*dir, &mut archive).expect("backup");
+
+        remove_mod(&find_mods(&packages).unwrap()[0], false).expect("remove");
+        install_mod(
+            "Foo-Baz-1.0.0",
+            Cursor::new(mod_archive("Baz", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        fs::create_dir_all(dir.join("plugins")).unwrap();
+        fs::write(dir.join("plugins/plugin.dll"), "plugin").unwrap();
+
+        let failing = Arc::new(FailingRename {
+            fail: dir.join("plugins"),
+        });
+        let res = vfs::with(failing, || {
+            restore(Cursor::new(archive.into_inner()), &*dir)
+        });
+        assert!(res.is_err());
+        assert!(packages.join("Foo-Baz-1.0.0/mods/Baz/mod.json").exists());
+        assert!(!packages.join("Foo-Bar-1.0.0").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("plugins/plugin.dll")).unwrap(),
+            "plugin"
+        );
+        assert_eq!(history(&packages).unwrap().len(), 3, "the journal is kept");
+        assert!(!dir.join(format!("{STAGING_PREFIX}restore")).exists());
+        assert!(lock_dir(&packages).is_ok(), "the locks are released");
+    }
+
+    #[test]
+    fn refuse_malicious_archives() {
+        let dir = TempDir::create("./test_malicious_archives").expect("Unable to create temp dir");
+        let target = dir.join("packages");
+        let install = |archive: Vec<u8>, config: &ThermiteConfig| {
+            install_with_config(
+                "Foo-Bar-1.0.0",
+                Cursor::new(archive),
+                &target,
+                |_| Ok(()),
+                config,
+            )
+        };
+        let config = ThermiteConfig::default();
+
+        for path in ["mods/Mock.Bar/../../../evil.txt", "mods/../evil.txt"] {
+            let res = install(mod_archive_with("Bar", "1.0.0", &[(path, "evil")]), &config);
+            assert!(
+                matches!(&res, Err(ThermiteError::UnsafePath { path: p, .. }) if p == path),
+                "{path}: {res:?}"
+            );
+        }
+        assert!(!dir.join("evil.txt").exists());
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        zip.add_symlink("mods/link", "/etc/passwd", options)
+            .unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+        assert!(matches!(
+            install(archive, &config),
+            Err(ThermiteError::SymlinkEntry { .. })
+        ));
+
+        let archive = mod_archive_with(
+            "Bar",
+            "1.0.0",
+            &[("mods/Mock.Bar/big.txt", &"a".repeat(1000))],
+        );
+        let limited = ThermiteConfig {
+            max_extracted_bytes: Some(500),
+            ..Default::default()
+        };
+        assert!(matches!(
+            install(archive.clone(), &limited),
+            Err(ThermiteError::ArchiveTooLarge { limit: 500, .. })
+        ));
+        let limited = ThermiteConfig {
+            max_extracted_files: Some(2),
+            ..Default::default()
+        };
+        assert!(matches!(
+            install(archive.clone(), &limited),
+            Err(ThermiteError::TooManyFiles { limit: 2, .. })
+        ));
+        assert!(!target.join("Foo-Bar-1.0.0").exists());
+        install(archive, &config).expect("install within the limits");
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn download_and_install_async() {
+        let server = MockServer::start().expect("start mock server");
+        let archive = mod_archive("Bar", "1.0.0");
+        server.serve("/Foo-Bar-1.0.0.zip", archive.clone());
+        server.fail_next("/Foo-Bar-1.0.0.zip", Failure::Status(503));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime");
+
+        // spawning checks the futures can move between threads
+        let url = format!("{}/Foo-Bar-1.0.0.zip", server.url());
+        let (downloaded, report) = runtime
+            .block_on(async { tokio::spawn(download_async(vec![], url)).await })
+            .expect("join download")
+            .expect("download");
+        assert_eq!(downloaded, archive);
+        assert_eq!(report.bytes, archive.len() as u64);
+
+        let missing = format!("{}/Foo-Missing-1.0.0.zip", server.url());
+        assert!(matches!(
+            runtime.block_on(download_async(vec![], missing)),
+            Err(ThermiteError::HttpStatus { status: 404, .. })
+        ));
+
+        let dir = TempDir::create("./test_async_install").expect("Unable to create temp dir");
+        let report = runtime
+            .block_on(async {
+                tokio::spawn(install_mod_async(
+                    "Foo-Bar-1.0.0",
+                    Cursor::new(downloaded),
+                    dir.to_path_buf(),
+                ))
+                .await
+            })
+            .expect("join install")
+            .expect("install");
+        assert!(report.path.join("manifest.json").exists());
+    }
+
+    #[test]
+    fn extract_on_executor() {
+        use crate::pool::{self, Executor, Job};
+        use std::sync::atomic::AtomicUsize;
+
+        static JOBS: AtomicUsize = AtomicUsize::new(0);
+        struct Counting;
+        impl Executor for Counting {
+            fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+                JOBS.fetch_add(jobs.len(), Ordering::Relaxed);
+                jobs.into_iter().for_each(|job| job());
+            }
+        }
+
+        let dir = TempDir::create("./test_extract_on_executor").expect("Unable to create temp dir");
+        pool::set_executor(Counting);
+        let res = install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &dir,
+        );
+        pool::clear_executor();
+        let report = res.expect("install");
+        // every extracted file and every hashed one is a job
+        assert!(JOBS.load(Ordering::Relaxed) >= report.files_written * 2);
+        assert!(dir.join("Foo-Bar-1.0.0/manifest.json").exists());
+    }
+
+    #[test]
+    fn install_in_both_layouts() {
+        let dir = TempDir::create("./test_package_layouts").expect("Unable to create temp dir");
+        let mods = dir.join("mods");
+        let archive = mod_archive_with("Bar", "1.0.0", &[("mods/Baz/mod.json", "{}")]);
+        let report = install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &mods).expect("legacy");
+        assert_eq!(report.path, mods);
+        assert!(mods.join("Bar").join("mod.json").exists());
+        assert!(!mods.join("manifest.json").exists());
+        assert_eq!(PackageLayout::detect(&mods).unwrap(), PackageLayout::Legacy);
+        let found = find_mods(&mods).unwrap();
+        assert_eq!(found.len(), 1, "Baz has an invalid mod.json");
+        assert_eq!(found[0].mod_json.name, "Mock.Bar");
+        assert_eq!(found[0].manifest.version_number, "1.0.0");
+
+        let packages = dir.join("packages");
+        let config = ThermiteConfig {
+            layout: PackageLayout::Packages,
+            ..Default::default()
+        };
+        let report = install_with_config(
+            "Foo-Bar-1.0.0",
+            Cursor::new(&archive),
+            &mods,
+            |_| Ok(()),
+            &config,
+        )
+        .expect("forced packages layout");
+        assert_eq!(report.path, mods.join("Foo-Bar-1.0.0"));
+        install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &packages).expect("packages");
+        assert_eq!(
+            PackageLayout::detect(&packages).unwrap(),
+            PackageLayout::Packages
+        );
+        assert_eq!(find_mods(&packages).unwrap()[0].author, "Foo");
+    }
+
+    #[test]
+    fn install_skins_and_audio() {
+        use std::io::Write;
+
+        let dir = TempDir::create("./test_skin_packages").expect("Unable to create temp dir");
+        let package = |files: &[&str]| {
+            let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+            let options = zip::write::FileOptions::default();
+            zip.start_file("manifest.json", options).unwrap();
+            zip.write_all(br#"{"name": "Skin", "version_number": "1.2.0"}"#)
+                .unwrap();
+            for file in files {
+                zip.start_file(*file, options).unwrap();
+                zip.write_all(b"data").unwrap();
+            }
+            zip.finish().unwrap().into_inner()
+        };
+
+        let mods = dir.join("mods");
+        let skin = package(&["skins/materials/models/r101.vtf"]);
+        let report = install_mod("Foo-Skin-1.2.0", Cursor::new(skin), &mods).expect("skin");
+        assert_eq!(report.kind, PackageKind::Skin);
+        assert!(mods.join("Skin/mod/materials/models/r101.vtf").exists());
+        let found = find_mods(&mods).unwrap();
+        assert_eq!(found[0].mod_json.name, "Skin");
+        assert_eq!(found[0].mod_json.version, "1.2.0");
+
+        let packages = dir.join("packages");
+        let audio = package(&["audio/kill.json", "audio/kill/kill.wav"]);
+        let report = install_mod("Foo-Skin-1.2.0", Cursor::new(audio), &packages).expect("audio");
+        assert_eq!(report.kind, PackageKind::AudioOverride);
+        let mod_dir = packages.join("Foo-Skin-1.2.0/mods/Skin");
+        assert!(mod_dir.join("audio/kill/kill.wav").exists());
+        assert!(mod_dir.join("mod.json").exists());
+
+        let report = install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive_with(
+                "Bar",
+                "1.0.0",
+                &[("audio/kill.json", "{}")],
+            )),
+            &packages,
+        )
+        .expect("mod");
+        assert_eq!(report.kind, PackageKind::Mod);
+        assert!(packages.join("Foo-Bar-1.0.0/audio/kill.json").exists());
+    }
+
+    #[test]
+    fn before_hook_vetoes_install() {
+        let path = TempDir::create("./test_hook_veto").expect("Unable to create temp dir");
+        let id = add_hook(
+            Some(HookOperation::Install),
+            HookStage::Before,
+            Hook::closure(|ctx| match ctx.name.as_str() {
+                "hook-veto-0.1.0" => Err("maintenance".into()),
+                _ => Ok(()),
+            }),
+        );
+
+        let res = install_mod("hook-veto-0.1.0", Cursor::new(TEST_ARCHIVE), &path);
+        remove_hook(id);
+        assert!(
+            matches!(res, Err(ThermiteError::HookFailed { .. })),
+            "{res:?}"
+        );
+        assert!(!path.join("hook-veto-0.1.0").exists());
+    }
+
+    #[test]
+    fn cancel_install() {
+        let path = TempDir::create("./test_cancel_install").expect("Unable to create temp dir");
+        let token = CancelToken::new();
+        token.cancel();
+
+        let res = token.run(|| install_mod("foo-bar-0.1.0", Cursor::new(TEST_ARCHIVE), &path));
+        assert!(matches!(res, Err(ThermiteError::Cancelled)), "{res:?}");
+        assert!(!path.join("foo-bar-0.1.0").join("manifest.json").exists());
+    }
+
+    #[test]
+    fn install_in_memory() {
+        let fs = Arc::new(MemoryFs::new());
+        let target = PathBuf::from("/memory/packages");
+        fs.create_dir_all(&target).unwrap();
+
+        let res = vfs::with(fs.clone(), || {
+            install_mod("foo-bar-0.1.0", Cursor::new(TEST_ARCHIVE), &target)
+        })
+        .expect("install into memory");
+
+        assert!(fs.exists(&res.path.join("manifest.json")).unwrap());
+        assert!(
+            !Path::new("/memory").exists(),
+            "nothing should be written to disk"
+        );
+    }
+
     #[test]
     fn northstar() {
         let mut cursor = Cursor::new(TEST_NS_ARCHIVE);
@@ -383,4 +4314,218 @@ mod test {
             panic!("Install failed with {:?}", res);
         }
     }
+
+    #[test]
+    fn northstar_with_progress() {
+        let server = MockServer::start().expect("start mock server");
+        server.serve("/Northstar.zip", TEST_NS_ARCHIVE.to_vec());
+        let path = TempDir::create("./northstar_progress_test").expect("Create temp dir");
+        let updates = std::cell::RefCell::new(vec![]);
+
+        install_northstar_with_progress(format!("{}/Northstar.zip", server.url()), &path, |p| {
+            updates.borrow_mut().push(p);
+        })
+        .expect("install");
+        assert!(path.join("NorthstarLauncher.exe").exists());
+
+        let updates = updates.into_inner();
+        let size = TEST_NS_ARCHIVE.len() as u64;
+        assert!(updates.iter().any(|p| matches!(
+            p,
+            NorthstarProgress::Downloading { current, total, .. } if *current == size && *total == size
+        )));
+        let Some(NorthstarProgress::Extracting { current, total }) = updates.last() else {
+            panic!("Extraction should be reported last, got {updates:?}");
+        };
+        assert_eq!(current, total);
+    }
+
+    #[test]
+    fn refuse_northstar_while_running() {
+        let path = TempDir::create("./northstar_running_test").expect("Create temp dir");
+        let exe = std::env::current_exe().expect("current exe");
+        let config = ThermiteConfig {
+            running_game: RunningGamePolicy::Refuse,
+            game: GameSpec {
+                processes: vec![exe.file_name().unwrap().to_string_lossy().into_owned()],
+                ..GameSpec::default()
+            },
+            ..ThermiteConfig::default()
+        };
+        let res = install_northstar_reporting(
+            Cursor::new(TEST_NS_ARCHIVE),
+            &path,
+            &|_| true,
+            &|_, _| {},
+            &config,
+        );
+        assert!(matches!(res, Err(ThermiteError::GameRunning(_))), "{res:?}");
+        assert_eq!(fs::read_dir(&*path).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn cancel_northstar_install() {
+        let path = TempDir::create("./northstar_cancel_test").expect("Create temp dir");
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(matches!(
+            install_northstar_cancellable(Cursor::new(TEST_NS_ARCHIVE), &path, &token),
+            Err(ThermiteError::Cancelled)
+        ));
+
+        // cancel halfway through extracting
+        let token = CancelToken::new();
+        let res = token.run(|| {
+            install_northstar_reporting(
+                Cursor::new(TEST_NS_ARCHIVE),
+                &path,
+                &|_| true,
+                &|current, total| {
+                    if current == total / 2 {
+                        token.cancel();
+                    }
+                },
+                &ThermiteConfig::default(),
+            )
+        });
+        assert!(matches!(res, Err(ThermiteError::Cancelled)));
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let entry = entry.unwrap().path();
+                assert!(entry.is_dir(), "{} was left behind", entry.display());
+                dirs.push(entry);
+            }
+        }
+    }
+
+    #[test]
+    fn modpack() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(
+            MockPackage::new("Foo", "Bar", "1.0.0").with_dependencies(["Foo-Dep-1.0.0"]),
+        );
+        server.add_package(MockPackage::new("Foo", "Dep", "1.2.0"));
+        let index =
+            crate::api::fetch_index(&server.index_url(), None, &Default::default()).expect("index");
+        let dir = TempDir::create("./test_modpack_install").expect("Unable to create temp dir");
+
+        let pack = ModPack::from_json(
+            r#"{
+                // comments are allowed
+                "name": "Server",
+                "packages": { "Foo-Bar": "1.0.0" },
+            }"#,
+        )
+        .expect("parse modpack");
+        let updates = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = updates.clone();
+        let reports = install_modpack(&pack, &index, &dir, move |p| seen.lock().unwrap().push(p))
+            .expect("install modpack");
+        assert_eq!(
+            reports.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            ["Foo-Dep-1.2.0", "Foo-Bar-1.0.0"]
+        );
+        assert!(dir.join("Foo-Dep-1.2.0/manifest.json").exists());
+        assert_eq!(
+            updates.lock().unwrap().last(),
+            Some(&ModpackProgress::Installing {
+                current: 2,
+                total: 2
+            })
+        );
+
+        let mut pack = pack;
+        pack.packages.insert("Foo-Bar".into(), "2.0.0".into());
+        assert!(matches!(
+            install_modpack(&pack, &index, &dir, |_| {}),
+            Err(ThermiteError::DepError { .. })
+        ));
+    }
+
+    #[test]
+    fn download_cache() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        server.add_package(MockPackage::new("Foo", "Baz", "1.0.0"));
+        let index =
+            crate::api::fetch_index(&server.index_url(), None, &Default::default()).expect("index");
+        let bar = index.iter().find(|m| m.name == "Bar").unwrap();
+        let bar = bar.get_version("1.0.0").unwrap();
+        let baz = index.iter().find(|m| m.name == "Baz").unwrap();
+        let baz = baz.get_version("1.0.0").unwrap();
+        let downloads = |version: &ModVersion| {
+            server
+                .requests()
+                .iter()
+                .filter(|r| version.url.ends_with(&r.path))
+                .count()
+        };
+
+        let dir = TempDir::create("./test_download_cache").expect("Unable to create temp dir");
+        let cache = DownloadCache::new(dir.join("packages"));
+        let archive = cache.fetch(bar).expect("fetch");
+        assert_eq!(cache.fetch(bar).expect("fetch cached"), archive);
+        assert_eq!(downloads(bar), 1);
+
+        // a cached copy that doesn't match the checksum is replaced
+        cache.cache().put(&bar.full_name, b"corrupted").unwrap();
+        assert_eq!(cache.fetch(bar).expect("fetch again"), archive);
+        assert_eq!(downloads(bar), 2);
+
+        // Bar was used a while ago, so it's evicted to make room for Baz
+        let cache = cache.max_size(Some(baz.file_size));
+        fs::File::options()
+            .write(true)
+            .open(cache.cache().dir().join(format!("{}.zip", bar.full_name)))
+            .and_then(|f| f.set_modified(std::time::SystemTime::UNIX_EPOCH))
+            .unwrap();
+        cache.fetch(baz).expect("fetch");
+        let entries = cache.cache().entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].full_name, baz.full_name);
+    }
+
+    #[test]
+    fn export_and_import_mod_list() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        server.add_package(MockPackage::new("Foo", "Baz", "0.2.0"));
+        let index =
+            crate::api::fetch_index(&server.index_url(), None, &Default::default()).expect("index");
+
+        let dir = TempDir::create("./test_mod_list").expect("Unable to create temp dir");
+        let packages = dir.join("old/packages");
+        for (name, version) in [("Bar", "1.0.0"), ("Baz", "0.2.0")] {
+            install_mod(
+                format!("Foo-{name}-{version}"),
+                Cursor::new(mod_archive(name, version)),
+                &packages,
+            )
+            .expect("install");
+        }
+        crate::core::utils::disable_mod(dir.join("old"), "Mock.Baz").expect("disable");
+
+        let list = crate::core::utils::export_mod_list(&packages).expect("export");
+        assert_eq!(list.packages["Foo-Bar"].version, "1.0.0");
+        assert!(!list.packages["Foo-Baz"].mods["Mock.Baz"]);
+        list.save(dir.join("mods.json")).expect("save");
+
+        let list = ModList::load(dir.join("mods.json")).expect("load");
+        let packages = dir.join("new/packages");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        let reports = import_mod_list(&list, &index, &packages).expect("import");
+        assert_eq!(
+            reports.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            ["Foo-Baz-0.2.0"]
+        );
+        let enabled = get_enabled_mods(dir.join("new")).expect("enabledmods.json");
+        assert!(enabled.is_enabled("Mock.Bar"));
+        assert!(!enabled.is_enabled("Mock.Baz"));
+    }
 }
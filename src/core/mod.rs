@@ -1,9 +1,57 @@
+pub mod audit;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cache;
+pub mod events;
+pub mod github;
+pub mod hooks;
+pub mod journal;
+pub mod launch;
+pub mod lock;
+pub mod logs;
 pub mod manage;
+pub mod modpack;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod northstar;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plan;
+pub mod profiles;
+pub mod report;
+pub mod resolve;
+pub mod status;
 #[allow(dead_code)]
 pub mod utils;
+#[allow(dead_code)]
+pub(crate) mod vfs;
 
+pub use events::{subscribe, unsubscribe, Event, SubscriptionId};
+pub use journal::JournalEntry;
+pub use lock::{lock_dir, DirLock};
+pub use modpack::{create_modpack, ModpackMetadata};
+#[cfg(not(target_arch = "wasm32"))]
+pub use northstar::{
+    repair_northstar, verify_northstar, BrokenFile, FileProblem, NorthstarVerification,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use plan::{plan_install, plan_remove, plan_update, Plan};
+pub use profiles::Profile;
+pub use report::{DownloadReport, InstallReport, NorthstarReport};
+pub use resolve::{clear_resolver, set_resolver, InstallConflict, Resolution, Resolver};
+pub use status::{clear_status_sink, set_status_sink, StatusEvent, StatusSink};
 #[cfg(all(target_os = "linux", feature = "proton"))]
-pub use utils::proton::{download_ns_proton, install_ns_proton, latest_release};
+pub use utils::proton::{
+    compat_data_dir, compatibility_tools_dir, configure_prefix, download_ns_proton,
+    install_ns_proton, installed_versions, latest_release, local_configs, newest_version,
+    remove_old_versions, set_compat_tool, set_launch_options, steam_config, use_ns_proton,
+    PrefixStatus, ProtonInstall, LAUNCH_OPTIONS,
+};
 #[cfg(feature = "steam")]
-pub use utils::steam::{steam_dir, steam_libraries, titanfall};
-pub use utils::{find_mods, get_enabled_mods, resolve_deps};
+pub use utils::steam::{steam_dir, steam_dirs, steam_libraries, titanfall};
+#[cfg(not(target_arch = "wasm32"))]
+pub use utils::verify_mod;
+pub use utils::{
+    available_space, check_updates, detect_conflicts, disable_mod, enable_mod, export_mod_list,
+    find_mods, find_mods_nested, find_mods_with_layout, find_unmanaged_mods, get_enabled_mods,
+    history, is_game_running, northstar_version, resolve_deps, resolve_deps_recursive,
+    resolve_install_order, set_mod_enabled, validate_package, DiscoveredMod, IntegrityReport,
+    ModConflict, OutdatedPackage, PackageKind, PackageLayout, PackageProblem, ValidationReport,
+};
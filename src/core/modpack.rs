@@ -0,0 +1,187 @@
+//! Building Thunderstore modpacks from an existing install
+//!
+//! A modpack is a package without any mods of its own, just a `manifest.json` whose dependencies pin every
+//! package it bundles, an `icon.png` and a `README.md`.
+
+use std::{
+    collections::BTreeSet,
+    io::{Cursor, Write},
+};
+
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{
+    config,
+    error::{Result, ThermiteError},
+    model::{InstalledMod, Manifest},
+};
+
+use super::utils::validate_modstring;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Thunderstore only accepts icons of exactly this size
+pub const ICON_SIZE: u32 = 256;
+
+/// Everything about a modpack that can't be taken from the installed mods
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModpackMetadata {
+    pub author: String,
+    pub name: String,
+    pub version_number: String,
+    pub website_url: String,
+    pub description: String,
+    /// 256x256 PNG
+    pub icon: Vec<u8>,
+    pub readme: String,
+}
+
+/// Creates a publishable modpack depending on the exact versions of `installed`
+///
+/// Core mods are skipped and packages containing several mods are only depended on once.
+///
+/// # Errors
+/// * `ThermiteError::NameError` if the metadata doesn't make a valid `author-name-X.Y.Z` or an installed package
+///   doesn't have a valid name
+/// * `ThermiteError::InvalidModpack` if the icon isn't a 256x256 PNG or the description is too long
+pub fn create_modpack(installed: &[InstalledMod], metadata: &ModpackMetadata) -> Result<Vec<u8>> {
+    let full_name = format!(
+        "{}-{}-{}",
+        metadata.author, metadata.name, metadata.version_number
+    );
+    if !validate_modstring(&full_name) {
+        return Err(ThermiteError::NameError(full_name));
+    }
+    if metadata.description.chars().count() > 250 {
+        return Err(ThermiteError::InvalidModpack(
+            "description is longer than 250 characters".into(),
+        ));
+    }
+    check_icon(&metadata.icon)?;
+
+    let manifest = Manifest {
+        name: metadata.name.clone(),
+        version_number: metadata.version_number.clone(),
+        website_url: metadata.website_url.clone(),
+        description: metadata.description.clone(),
+        dependencies: pinned_dependencies(installed)?,
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = FileOptions::default();
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.start_file("icon.png", options)?;
+    zip.write_all(&metadata.icon)?;
+    zip.start_file("README.md", options)?;
+    zip.write_all(metadata.readme.as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// `author-name-X.Y.Z` of every package in `installed`, sorted and without duplicates
+fn pinned_dependencies(installed: &[InstalledMod]) -> Result<Vec<String>> {
+    let config = config::config();
+    let mut deps = BTreeSet::new();
+    for m in installed {
+        if config.game.is_core_mod(&m.mod_json.name) {
+            continue;
+        }
+
+        let dep = format!(
+            "{}-{}-{}",
+            m.author, m.manifest.name, m.manifest.version_number
+        );
+        if !validate_modstring(&dep) {
+            return Err(ThermiteError::NameError(dep));
+        }
+        deps.insert(dep);
+    }
+
+    Ok(deps.into_iter().collect())
+}
+
+fn check_icon(icon: &[u8]) -> Result<()> {
+    // the IHDR chunk always comes first, with the width and height right after its type
+    if icon.len() < 24 || !icon.starts_with(PNG_SIGNATURE) || &icon[12..16] != b"IHDR" {
+        return Err(ThermiteError::InvalidModpack("icon isn't a PNG".into()));
+    }
+
+    let width = u32::from_be_bytes([icon[16], icon[17], icon[18], icon[19]]);
+    let height = u32::from_be_bytes([icon[20], icon[21], icon[22], icon[23]]);
+    if (width, height) != (ICON_SIZE, ICON_SIZE) {
+        return Err(ThermiteError::InvalidModpack(format!(
+            "icon is {width}x{height}, it has to be {ICON_SIZE}x{ICON_SIZE}"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use zip::ZipArchive;
+
+    use crate::{
+        core::{find_mods, manage::install_mod, utils::TempDir},
+        error::ThermiteError,
+        model::Manifest,
+        test_util::mod_archive,
+    };
+
+    use super::{create_modpack, ModpackMetadata, PNG_SIGNATURE};
+
+    fn icon(size: u32) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(13u32.to_be_bytes());
+        png.extend(b"IHDR");
+        png.extend(size.to_be_bytes());
+        png.extend(size.to_be_bytes());
+        png
+    }
+
+    #[test]
+    fn modpack_from_install() {
+        let dir = TempDir::create("./test_modpack").expect("Unable to create temp dir");
+        for (name, version) in [("Bar", "1.0.0"), ("Baz", "0.2.0")] {
+            install_mod(
+                format!("Foo-{name}-{version}"),
+                Cursor::new(mod_archive(name, version)),
+                &dir,
+            )
+            .expect("install");
+        }
+        let installed = find_mods(&dir).expect("find mods");
+
+        let mut metadata = ModpackMetadata {
+            author: "Me".into(),
+            name: "Pack".into(),
+            version_number: "1.0.0".into(),
+            description: "My mods".into(),
+            icon: icon(128),
+            readme: "# Pack".into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            create_modpack(&installed, &metadata),
+            Err(ThermiteError::InvalidModpack(_))
+        ));
+
+        metadata.icon = icon(256);
+        let zip = create_modpack(&installed, &metadata).expect("modpack");
+        let mut archive = ZipArchive::new(Cursor::new(zip)).expect("valid zip");
+        assert!(archive.by_name("icon.png").is_ok());
+        assert!(archive.by_name("README.md").is_ok());
+
+        let mut raw = String::new();
+        archive
+            .by_name("manifest.json")
+            .expect("manifest")
+            .read_to_string(&mut raw)
+            .unwrap();
+        let manifest: Manifest = serde_json::from_str(&raw).unwrap();
+        assert_eq!(manifest.name, "Pack");
+        assert_eq!(manifest.dependencies, ["Foo-Bar-1.0.0", "Foo-Baz-0.2.0"]);
+    }
+}
@@ -0,0 +1,393 @@
+//! Checking a Northstar install for missing or modified files, and repairing it
+//!
+//! [`install_northstar`](super::manage::install_northstar) records the size and SHA-256 of every file it
+//! extracts in `.thermite-northstar.json` in the game directory. [`verify_northstar`] checks the install against
+//! that record. Installs thermite didn't make have no record, so for those it only checks that the launcher, the
+//! loader's DLL and the core mods are there.
+//!
+//! [`repair_northstar`] then extracts just the broken files from the release instead of reinstalling all of it.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    cancel, config,
+    error::{Result, ThermiteError},
+    pool,
+    time::Instant,
+    verify,
+};
+
+use super::{
+    audit::{self, AuditEntry, AuditOperation},
+    events::{self, Event},
+    launch,
+    manage::{
+        extract_northstar, northstar_manifest, northstar_version, share_archive, tag_core_mods,
+    },
+    report::{self, NorthstarReport},
+    status::{self, StatusEvent},
+    utils::check_game_running,
+    vfs,
+};
+
+/// Where the files of an install are recorded, relative to the game directory
+pub(crate) const RECORD_FILE: &str = ".thermite-northstar.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileProblem {
+    Missing,
+    /// The size differs from the one recorded at install time
+    WrongSize {
+        expected: u64,
+        actual: u64,
+    },
+    /// The size matches but the contents don't
+    Modified,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenFile {
+    /// Relative to the game directory. Without a record this is the whole directory of a missing core mod
+    pub path: PathBuf,
+    pub problem: FileProblem,
+}
+
+/// The result of [`verify_northstar`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NorthstarVerification {
+    /// The game directory that was checked
+    pub target: PathBuf,
+    /// The installed version, if thermite recorded it
+    pub version: Option<String>,
+    /// Whether the files were checked against the sizes and hashes recorded at install time. Otherwise only
+    /// the essential files were looked for
+    pub hashes_checked: bool,
+    pub files_checked: usize,
+    pub broken: Vec<BrokenFile>,
+}
+
+impl NorthstarVerification {
+    #[must_use]
+    pub fn is_intact(&self) -> bool {
+        self.broken.is_empty()
+    }
+}
+
+impl Display for NorthstarVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Northstar")?;
+        if let Some(version) = &self.version {
+            write!(f, " {version}")?;
+        }
+        write!(
+            f,
+            " in {}: {} of {} files broken",
+            self.target.display(),
+            self.broken.len(),
+            self.files_checked
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Record {
+    version: Option<String>,
+    /// Keyed by the path relative to the game directory, with `/` separators
+    files: BTreeMap<String, RecordedFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RecordedFile {
+    size: u64,
+    sha256: String,
+}
+
+fn record_key(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Records the size and hash of the files an install wrote, replacing the record of any earlier install
+pub(crate) fn record_files(
+    target: &Path,
+    version: Option<&str>,
+    written: &[PathBuf],
+) -> Result<()> {
+    let fs = vfs::current();
+    let hashed = pool::map(written.to_vec(), |rel| {
+        let contents = vfs::current().read(&target.join(&rel))?;
+        let file = RecordedFile {
+            size: contents.len() as u64,
+            sha256: verify::encode_hash(&verify::sha256(&contents)),
+        };
+        Ok((record_key(&rel), file))
+    });
+    let record = Record {
+        version: version.map(Into::into),
+        files: hashed.into_iter().collect::<Result<_>>()?,
+    };
+    fs.write_atomic(&target.join(RECORD_FILE), &serde_json::to_vec(&record)?)?;
+    Ok(())
+}
+
+/// Checks the Northstar install in `game_dir` for missing or modified files
+///
+/// The proxy DLL counts as present when it was disabled with
+/// [`set_launch_method`](super::launch::set_launch_method).
+///
+/// # Errors
+/// * IO errors
+/// * An invalid record of the install
+pub fn verify_northstar(game_dir: impl AsRef<Path>) -> Result<NorthstarVerification> {
+    let game_dir = game_dir.as_ref();
+    let fs = vfs::current();
+    let game = &config::config().game;
+    let mut report = NorthstarVerification {
+        target: game_dir.into(),
+        ..Default::default()
+    };
+
+    let record_path = game_dir.join(RECORD_FILE);
+    if !fs.exists(&record_path)? {
+        debug!(
+            "No record of the Northstar install in {}, only checking essential files",
+            game_dir.display()
+        );
+        // a missing core mod is restored whole, so its directory is what's broken
+        let mods = Path::new(&game.profile).join("mods");
+        let mut essential = [&game.launcher, &game.loader_dll]
+            .map(|f| (PathBuf::from(f), PathBuf::from(f)))
+            .to_vec();
+        essential.extend(
+            game.core_mods
+                .iter()
+                .map(|m| (mods.join(m), mods.join(m).join("mod.json"))),
+        );
+        for (path, check) in essential {
+            report.files_checked += 1;
+            if !fs.exists(&game_dir.join(check))? {
+                report.broken.push(BrokenFile {
+                    path,
+                    problem: FileProblem::Missing,
+                });
+            }
+        }
+        return Ok(report);
+    }
+
+    let record: Record = serde_json::from_slice(&fs.read(&record_path)?)?;
+    report.version = record.version;
+    report.hashes_checked = true;
+    let checked = pool::map(record.files.iter().collect(), |(key, expected)| {
+        cancel::checkpoint(format!("verifying Northstar in {}", game_dir.display()))?;
+        let rel = PathBuf::from(key);
+        let mut path = game_dir.join(&rel);
+        if rel == Path::new(&game.proxy_dll) && !vfs::current().exists(&path)? {
+            path = launch::disabled_proxy_path(game_dir);
+        }
+        Ok(check_file(&path, expected)?.map(|problem| BrokenFile { path: rel, problem }))
+    });
+    for broken in checked {
+        report.files_checked += 1;
+        report.broken.extend(broken?);
+    }
+    Ok(report)
+}
+
+fn check_file(path: &Path, expected: &RecordedFile) -> Result<Option<FileProblem>> {
+    let fs = vfs::current();
+    if !fs.exists(path)? {
+        return Ok(Some(FileProblem::Missing));
+    }
+    let actual = fs.file_len(path)?;
+    if actual != expected.size {
+        return Ok(Some(FileProblem::WrongSize {
+            expected: expected.size,
+            actual,
+        }));
+    }
+    let hash = verify::encode_hash(&verify::sha256(&fs.read(path)?));
+    Ok((!hash.eq_ignore_ascii_case(&expected.sha256)).then_some(FileProblem::Modified))
+}
+
+/// Restores the files [`verify_northstar`] finds broken in `game_path` from a Northstar release
+///
+/// Releases are a single archive, so the whole release still has to be downloaded, but only the broken files
+/// are extracted from it and everything else is left alone. The report counts only what was restored.
+///
+/// # Params
+/// * `zip_file` - the release that is installed
+/// * `game_path` - the path of the Titanfall 2 install
+///
+/// # Errors
+/// * `ThermiteError::NorthstarVersionMismatch` if the release isn't the installed version, reinstall Northstar with
+///   [`install_northstar`](super::manage::install_northstar) to change versions
+/// * IO Errors
+/// * The game is running and the global config's `running_game` policy refuses, or gave up waiting
+pub fn repair_northstar(
+    zip_file: impl Read + Seek,
+    game_path: impl AsRef<Path>,
+) -> Result<NorthstarReport> {
+    let started = Instant::now();
+    let mut entry = AuditEntry::new(
+        AuditOperation::RepairNorthstar,
+        "Northstar",
+        game_path.as_ref(),
+    );
+    let res = repair_files(zip_file, game_path.as_ref()).map(|mut report| {
+        report.duration = started.elapsed();
+        report
+    });
+    if let Ok(report) = &res {
+        entry.version = report.version.clone();
+        if report.files_written > 0 {
+            events::emit(Event::NorthstarUpdated {
+                target: report.target.clone(),
+                version: report.version.clone(),
+            });
+        }
+    }
+    audit::record(entry, &res);
+    res
+}
+
+fn repair_files(zip_file: impl Read + Seek, target: &Path) -> Result<NorthstarReport> {
+    let check = verify_northstar(target)?;
+    let mut report = NorthstarReport {
+        target: target.into(),
+        version: check.version.clone(),
+        ..Default::default()
+    };
+    if check.is_intact() {
+        debug!("Northstar in {} is intact", target.display());
+        return Ok(report);
+    }
+
+    let mut archive = share_archive(zip_file)?;
+    let manifest = northstar_manifest(&mut archive)?;
+    let release = northstar_version(manifest.as_deref());
+    if let (Some(installed), Some(release)) = (&check.version, &release) {
+        if installed != release {
+            return Err(ThermiteError::NorthstarVersionMismatch {
+                installed: installed.clone(),
+                release: release.clone(),
+            });
+        }
+    }
+    report.version = report.version.or(release);
+
+    check_game_running(&config::config())?;
+    status::emit(StatusEvent::NorthstarInstallStarted {
+        target: target.into(),
+    });
+    let broken = check.broken.iter().map(|b| b.path.as_path());
+    let written = extract_northstar(
+        &mut archive,
+        target,
+        &mut report,
+        |rel| broken.clone().any(|b| rel.starts_with(b)),
+        &|_, _| {},
+    )?;
+    for file in &check.broken {
+        if !written.iter().any(|w| w.starts_with(&file.path)) {
+            report::warning(
+                &mut report.warnings,
+                format!("{} isn't in the release", file.path.display()),
+            );
+        }
+    }
+
+    // a core mod that lost its files also lost the package files added for it
+    let mods = Path::new(&config::config().game.profile).join("mods");
+    if written.iter().any(|w| w.starts_with(&mods)) {
+        tag_core_mods(target, manifest.as_deref(), &mut report)?;
+    }
+    launch::keep_proxy_disabled(target)?;
+
+    status::emit(StatusEvent::NorthstarInstallFinished {
+        target: target.into(),
+    });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor};
+
+    use crate::core::{manage::install_northstar, utils::TempDir};
+
+    use super::{repair_northstar, verify_northstar, FileProblem, RECORD_FILE};
+
+    const TEST_NS_ARCHIVE: &[u8] = include_bytes!("test_media/northstar.zip");
+
+    #[test]
+    fn verify_and_repair() {
+        let dir = TempDir::create("./test_verify_northstar").expect("Unable to create temp dir");
+        install_northstar(Cursor::new(TEST_NS_ARCHIVE), &dir).expect("install");
+        let check = verify_northstar(&dir).expect("verify");
+        assert!(check.hashes_checked);
+        assert!(check.is_intact(), "{check}");
+
+        let client = dir.join("R2Northstar/mods/Northstar.Client/mod.json");
+        fs::remove_file(dir.join("NorthstarLauncher.exe")).unwrap();
+        let mut contents = fs::read(&client).unwrap();
+        contents[0] ^= 1;
+        fs::write(&client, &contents).unwrap();
+        fs::write(dir.join("r2ds.bat"), "echo").unwrap();
+
+        let check = verify_northstar(&dir).expect("verify");
+        let mut problems = check
+            .broken
+            .iter()
+            .map(|b| (b.path.to_str().unwrap(), b.problem))
+            .collect::<Vec<_>>();
+        problems.sort_by_key(|(path, _)| *path);
+        assert_eq!(
+            problems,
+            [
+                ("NorthstarLauncher.exe", FileProblem::Missing),
+                (
+                    "R2Northstar/mods/Northstar.Client/mod.json",
+                    FileProblem::Modified
+                ),
+                (
+                    "r2ds.bat",
+                    FileProblem::WrongSize {
+                        expected: 42,
+                        actual: 4
+                    }
+                ),
+            ]
+        );
+
+        let report = repair_northstar(Cursor::new(TEST_NS_ARCHIVE), &dir).expect("repair");
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert!(verify_northstar(&dir).unwrap().is_intact());
+        assert!(dir
+            .join("R2Northstar/mods/Northstar.Client/manifest.json")
+            .exists());
+
+        // without a record only the essential files are checked, and missing core mods are restored whole
+        fs::remove_file(dir.join(RECORD_FILE)).unwrap();
+        fs::remove_dir_all(dir.join("R2Northstar/mods/Northstar.CustomServers")).unwrap();
+        let check = verify_northstar(&dir).expect("verify");
+        assert!(!check.hashes_checked);
+        assert_eq!(check.broken.len(), 1);
+        repair_northstar(Cursor::new(TEST_NS_ARCHIVE), &dir).expect("repair");
+        assert!(dir
+            .join("R2Northstar/mods/Northstar.CustomServers/manifest.json")
+            .exists());
+        assert!(verify_northstar(&dir).unwrap().is_intact());
+    }
+}
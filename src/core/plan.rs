@@ -0,0 +1,213 @@
+//! Working out what an install, update or removal would do without doing it
+//!
+//! Each `plan_*` function runs the operation against an in-memory overlay of the disk, reads what it changed
+//! back out of the overlay and throws the overlay away. Hooks, events, status updates, the audit log and the
+//! running game check are skipped while planning, so a plan can be shown to the user before they confirm it,
+//! e.g. for a CLI's `--dry-run`. The [`Resolver`](super::resolve::Resolver) is still asked about conflicts.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use crate::{error::Result, model::InstalledMod};
+
+use super::{
+    journal::JOURNAL_DIR,
+    manage::{self, remove_mod},
+    report::InstallReport,
+    utils::{OutdatedPackage, CHECKSUMS_FILE},
+    vfs::{self, Fs, FsChange, MemoryFs, RealFs},
+};
+
+/// What an operation would change on disk
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Plan {
+    /// Files that would be created
+    pub created: Vec<PathBuf>,
+    /// Files that would be removed
+    pub removed: Vec<PathBuf>,
+    /// Existing files whose contents would change
+    pub overwritten: Vec<PathBuf>,
+    /// Packages that would be installed as dependencies, as `author-name-X.Y.Z`
+    pub dependencies: Vec<String>,
+    /// The reports the installs would have returned
+    pub installs: Vec<InstallReport>,
+}
+
+impl Plan {
+    /// Whether nothing on disk would change
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.removed.is_empty() && self.overwritten.is_empty()
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for dep in &self.dependencies {
+            writeln!(f, "Install dependency {dep}")?;
+        }
+        for (verb, files) in [
+            ("Create", &self.created),
+            ("Overwrite", &self.overwritten),
+            ("Remove", &self.removed),
+        ] {
+            for file in files {
+                writeln!(f, "{verb} {}", file.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plans [`install_mod`](manage::install_mod)
+///
+/// # Errors
+/// Anything the install itself would fail with
+pub fn plan_install<T>(
+    mod_string: impl AsRef<str>,
+    zip_file: T,
+    target_dir: impl AsRef<Path>,
+) -> Result<Plan>
+where
+    T: Read + Seek,
+{
+    let (report, mut plan) = plan(|| manage::install_mod(mod_string, zip_file, target_dir))?;
+    plan.installs.push(report);
+    Ok(plan)
+}
+
+/// Plans [`update`](manage::update)
+///
+/// # Errors
+/// Anything the update itself would fail with
+pub fn plan_update<T>(outdated: &OutdatedPackage, zip_file: T) -> Result<Plan>
+where
+    T: Read + Seek,
+{
+    let (report, mut plan) = plan(|| manage::update(outdated, zip_file))?;
+    plan.installs.push(report);
+    Ok(plan)
+}
+
+/// Plans [`remove_mod`]
+///
+/// # Errors
+/// Anything the removal itself would fail with
+pub fn plan_remove(installed: &InstalledMod, force: bool) -> Result<Plan> {
+    plan(|| remove_mod(installed, force)).map(|((), plan)| plan)
+}
+
+/// Runs `f` without touching the disk, returning its result and what it would have changed
+pub(crate) fn plan<T>(f: impl FnOnce() -> Result<T>) -> Result<(T, Plan)> {
+    let (res, overlay) = vfs::dry_run(f);
+    Ok((res?, diff(&overlay)?))
+}
+
+/// Compares every file in the paths the overlay changed with the real disk
+fn diff(overlay: &MemoryFs) -> Result<Plan> {
+    let mut files = BTreeSet::new();
+    for change in overlay.changes() {
+        let touched = match &change {
+            FsChange::CreateDir(path)
+            | FsChange::Remove(path)
+            | FsChange::WriteFile { path, .. } => vec![path],
+            FsChange::Rename { from, to } => vec![from, to],
+        };
+        for path in touched {
+            collect_files(&RealFs, path, &mut files)?;
+            collect_files(overlay, path, &mut files)?;
+        }
+    }
+
+    let mut plan = Plan::default();
+    // the journal, locks and checksums are bookkeeping, not something the user would want to confirm
+    for file in files.into_iter().filter(|f| {
+        !f.components().any(|c| c.as_os_str() == JOURNAL_DIR)
+            && f.file_name() != Some(CHECKSUMS_FILE.as_ref())
+    }) {
+        match (is_file(&RealFs, &file)?, is_file(overlay, &file)?) {
+            (false, true) => plan.created.push(file),
+            (true, false) => plan.removed.push(file),
+            (true, true) if RealFs.read(&file)? != overlay.read(&file)? => {
+                plan.overwritten.push(file);
+            }
+            _ => {}
+        }
+    }
+    Ok(plan)
+}
+
+fn is_file(fs: &dyn Fs, path: &Path) -> Result<bool> {
+    Ok(fs.exists(path)? && !fs.is_dir(path))
+}
+
+fn collect_files(fs: &dyn Fs, path: &Path, files: &mut BTreeSet<PathBuf>) -> Result<()> {
+    if !fs.exists(path)? {
+        return Ok(());
+    }
+    let mut dirs = vec![path.to_path_buf()];
+    if !fs.is_dir(path) {
+        files.insert(path.to_path_buf());
+        dirs.clear();
+    }
+    while let Some(dir) = dirs.pop() {
+        for entry in fs.read_dir(&dir)? {
+            if entry.is_dir {
+                dirs.push(entry.path);
+            } else {
+                files.insert(entry.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor};
+
+    use crate::{
+        core::{
+            manage::install_mod,
+            utils::{find_mods, TempDir, CHECKSUMS_FILE},
+        },
+        test_util::mod_archive,
+    };
+
+    use super::{plan_install, plan_remove};
+
+    #[test]
+    fn plan_without_changes() {
+        let dir = TempDir::create("./test_plan").expect("Unable to create temp dir");
+        let dir = fs::canonicalize(&dir).unwrap();
+        let archive = || Cursor::new(mod_archive("Bar", "1.0.0"));
+
+        let plan = plan_install("Foo-Bar-1.0.0", archive(), &dir).expect("plan install");
+        let installed = dir.join("Foo-Bar-1.0.0");
+        assert!(!installed.exists(), "planning shouldn't install anything");
+        assert!(plan.created.contains(&installed.join("manifest.json")));
+        assert!(plan.created.contains(&installed.join("mods/Bar/mod.json")));
+        assert!(plan.removed.is_empty() && plan.overwritten.is_empty());
+        assert!(!plan.created.contains(&installed.join(CHECKSUMS_FILE)));
+        assert_eq!(plan.installs[0].path, installed);
+
+        install_mod("Foo-Bar-1.0.0", archive(), &dir).expect("install");
+        let plan = plan_remove(&find_mods(&dir).unwrap()[0], false).expect("plan remove");
+        assert!(installed.join("manifest.json").exists());
+        assert!(plan.removed.contains(&installed.join("manifest.json")));
+        assert!(plan.created.is_empty());
+
+        fs::write(installed.join("manifest.json"), "edited").unwrap();
+        let plan = plan_install("Foo-Bar-1.0.0", archive(), &dir).expect("plan reinstall");
+        assert_eq!(plan.overwritten, [installed.join("manifest.json")]);
+        assert_eq!(
+            fs::read_to_string(installed.join("manifest.json")).unwrap(),
+            "edited"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Northstar profiles, the directories selected with `-profile=`
+//!
+//! Each profile is a directory in the game directory with its own `packages`, `mods` and `plugins` and its
+//! own `enabledmods.json`, so one install of the game can have a setup per server or per player.
+
+use std::{
+    io::{Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    config,
+    error::{Result, ThermiteError},
+    model::{EnabledMods, InstalledMod},
+};
+
+use super::{
+    manage::install_mod,
+    report::InstallReport,
+    utils::{find_mods, get_enabled_mods},
+    vfs,
+};
+
+/// Files and directories only a profile has, used to tell profiles apart from the game's own directories
+const MARKERS: [&str; 4] = ["packages", "mods", "plugins", "enabledmods.json"];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Profile {
+    game_dir: PathBuf,
+    name: String,
+}
+
+impl Profile {
+    /// A handle to the profile `name` in `game_dir`, which doesn't have to exist yet
+    ///
+    /// # Errors
+    /// * `ThermiteError::InvalidProfile` if `name` isn't a single directory name
+    pub fn new(game_dir: impl Into<PathBuf>, name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        let mut components = Path::new(&name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(ThermiteError::InvalidProfile(format!(
+                "{name} isn't a valid profile name"
+            )));
+        }
+
+        Ok(Self {
+            game_dir: game_dir.into(),
+            name,
+        })
+    }
+
+    /// The profile Northstar uses when it's started without `-profile=`, as set in the global config's game
+    #[must_use]
+    pub fn default_profile(game_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            game_dir: game_dir.into(),
+            name: config::config().game.profile.clone(),
+        }
+    }
+
+    /// Creates a new, empty profile
+    ///
+    /// # Errors
+    /// * `ThermiteError::InvalidProfile` if the name is invalid or the profile already exists
+    /// * IO errors
+    pub fn create(game_dir: impl Into<PathBuf>, name: impl Into<String>) -> Result<Self> {
+        let profile = Self::new(game_dir, name)?;
+        let fs = vfs::current();
+        if fs.exists(&profile.dir())? {
+            return Err(ThermiteError::InvalidProfile(format!(
+                "{} already exists",
+                profile.name
+            )));
+        }
+        fs.create_dir_all(&profile.packages_dir())?;
+        Ok(profile)
+    }
+
+    /// Every profile in `game_dir`, sorted by name
+    ///
+    /// A directory counts as a profile if it has any of `packages`, `mods`, `plugins` or `enabledmods.json`.
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn list(game_dir: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let fs = vfs::current();
+        let game_dir = game_dir.as_ref();
+        let mut profiles = vec![];
+        for entry in fs.read_dir(game_dir)? {
+            if !entry.is_dir {
+                continue;
+            }
+            let mut is_profile = false;
+            for marker in MARKERS {
+                if fs.exists(&entry.path.join(marker))? {
+                    is_profile = true;
+                    break;
+                }
+            }
+            if is_profile {
+                profiles.push(Self::new(game_dir, entry.file_name())?);
+            }
+        }
+        profiles.sort();
+        Ok(profiles)
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn game_dir(&self) -> &Path {
+        &self.game_dir
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> PathBuf {
+        self.game_dir.join(&self.name)
+    }
+
+    /// Where Thunderstore packages are installed
+    #[must_use]
+    pub fn packages_dir(&self) -> PathBuf {
+        self.dir().join("packages")
+    }
+
+    /// Where mods installed by hand are, outside of any package
+    #[must_use]
+    pub fn mods_dir(&self) -> PathBuf {
+        self.dir().join("mods")
+    }
+
+    #[must_use]
+    pub fn plugins_dir(&self) -> PathBuf {
+        self.dir().join("plugins")
+    }
+
+    /// The argument that makes Northstar use this profile
+    #[must_use]
+    pub fn launch_arg(&self) -> String {
+        format!("-profile={}", self.name)
+    }
+
+    /// # Errors
+    /// * IO errors
+    pub fn exists(&self) -> Result<bool> {
+        Ok(vfs::current().exists(&self.dir())?)
+    }
+
+    /// Copies the whole profile, including its packages and settings, to a new profile in the same game
+    /// directory
+    ///
+    /// # Errors
+    /// * `ThermiteError::InvalidProfile` if the name is invalid or the profile already exists
+    /// * IO errors
+    pub fn clone_to(&self, name: impl Into<String>) -> Result<Self> {
+        let target = Self::new(&self.game_dir, name)?;
+        let fs = vfs::current();
+        if fs.exists(&target.dir())? {
+            return Err(ThermiteError::InvalidProfile(format!(
+                "{} already exists",
+                target.name
+            )));
+        }
+        copy_dir(&self.dir(), &target.dir())?;
+        Ok(target)
+    }
+
+    /// Removes the profile and everything in it
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn delete(self) -> Result<()> {
+        Ok(vfs::current().remove_dir_all(&self.dir())?)
+    }
+
+    /// Installs a package into the profile's `packages` directory, see [`install_mod`]
+    ///
+    /// # Errors
+    /// * IO Errors
+    /// * Misformatted mod files
+    pub fn install_mod(
+        &self,
+        mod_string: impl AsRef<str>,
+        zip_file: impl Read + Seek,
+    ) -> Result<InstallReport> {
+        install_mod(mod_string, zip_file, self.packages_dir())
+    }
+
+    /// The mods installed in the profile's `packages` directory, see [`find_mods`]
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * Improperly formatted JSON files
+    pub fn find_mods(&self) -> Result<Vec<InstalledMod>> {
+        let dir = self.packages_dir();
+        if vfs::current().exists(&dir)? {
+            find_mods(dir)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// The profile's `enabledmods.json`, see [`get_enabled_mods`]
+    ///
+    /// # Errors
+    /// * `ThermiteError::MissingFile` if the profile has no `enabledmods.json`
+    /// * IO errors
+    pub fn enabled_mods(&self) -> Result<EnabledMods> {
+        get_enabled_mods(self.dir())
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    let fs = vfs::current();
+    fs.create_dir_all(to)?;
+    for entry in fs.read_dir(from)? {
+        let target = to.join(entry.file_name());
+        if entry.is_dir {
+            copy_dir(&entry.path, &target)?;
+        } else {
+            fs.write(&target, &fs.read(&entry.path)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::{core::utils::TempDir, error::ThermiteError, test_util::mod_archive};
+
+    use super::Profile;
+
+    #[test]
+    fn manage_profiles() {
+        let dir = TempDir::create("./test_profiles").expect("Unable to create temp dir");
+        std::fs::create_dir_all(dir.join("bin/x64_retail")).unwrap();
+
+        let server = Profile::create(&*dir, "Server").expect("create");
+        assert!(matches!(
+            Profile::create(&*dir, "Server"),
+            Err(ThermiteError::InvalidProfile(_))
+        ));
+        assert!(matches!(
+            Profile::new(&*dir, "../Server"),
+            Err(ThermiteError::InvalidProfile(_))
+        ));
+
+        server
+            .install_mod("Foo-Bar-1.0.0", Cursor::new(mod_archive("Bar", "1.0.0")))
+            .expect("install");
+        assert_eq!(server.find_mods().unwrap().len(), 1);
+        assert!(matches!(
+            server.enabled_mods(),
+            Err(ThermiteError::MissingFile(_))
+        ));
+        assert_eq!(server.launch_arg(), "-profile=Server");
+
+        let player = server.clone_to("Player").expect("clone");
+        assert_eq!(player.find_mods().unwrap().len(), 1);
+        let names = Profile::list(&*dir).unwrap();
+        assert_eq!(
+            names.iter().map(Profile::name).collect::<Vec<_>>(),
+            ["Player", "Server"]
+        );
+
+        server.delete().expect("delete");
+        assert_eq!(Profile::list(&*dir).unwrap(), [player]);
+    }
+}
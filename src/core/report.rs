@@ -0,0 +1,134 @@
+//! Summaries returned by the operations in `manage`
+
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    time::Duration,
+};
+
+use tracing::warn;
+
+use super::{
+    status::{self, StatusEvent},
+    utils::PackageKind,
+};
+
+/// The result of a successful download
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DownloadReport {
+    pub url: String,
+    /// Total bytes downloaded & written
+    pub bytes: u64,
+    /// Whether the data came from a local cache instead of the network
+    pub from_cache: bool,
+    /// Bytes that were already downloaded by an earlier attempt and weren't requested again
+    pub resumed_from: u64,
+    pub duration: Duration,
+    /// Non-fatal problems encountered along the way
+    pub warnings: Vec<String>,
+}
+
+/// The result of a successful mod install
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InstallReport {
+    pub name: String,
+    /// Directory the mod was installed to
+    pub path: PathBuf,
+    /// Version from the package's manifest, if it had one
+    pub version: Option<String>,
+    /// What the package contained, which decided where it was installed
+    pub kind: PackageKind,
+    /// Whether an existing install was replaced
+    pub replaced: bool,
+    /// Whether a [`Resolver`](super::resolve::Resolver) chose to leave the existing install in place
+    pub skipped: bool,
+    /// Config files kept from the install that was replaced, see
+    /// [`OverwritePolicy::MergePreservingConfigs`](crate::config::OverwritePolicy::MergePreservingConfigs)
+    pub preserved: Vec<PathBuf>,
+    /// Every file the install put in place, e.g. to remove exactly those later. Empty until the install is
+    /// committed
+    pub installed_files: Vec<PathBuf>,
+    pub files_written: usize,
+    pub bytes_written: u64,
+    pub duration: Duration,
+    /// Non-fatal problems encountered along the way
+    pub warnings: Vec<String>,
+}
+
+/// The result of a successful Northstar install
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NorthstarReport {
+    /// The game directory Northstar was installed into
+    pub target: PathBuf,
+    /// Version from the release's manifest, if it had one
+    pub version: Option<String>,
+    pub files_written: usize,
+    pub bytes_written: u64,
+    pub duration: Duration,
+    /// Non-fatal problems encountered along the way
+    pub warnings: Vec<String>,
+}
+
+/// Logs a warning, forwards it to the status sink and keeps it for the report
+pub(crate) fn warning(warnings: &mut Vec<String>, msg: impl Into<String>) {
+    let msg = msg.into();
+    warn!("{msg}");
+    status::emit(StatusEvent::Warning(msg.clone()));
+    warnings.push(msg);
+}
+
+impl Display for DownloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Downloaded {} bytes from {} in {:.1?}",
+            self.bytes, self.url, self.duration
+        )?;
+        if self.from_cache {
+            write!(f, " (cached)")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for InstallReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.replaced {
+            "Updated"
+        } else {
+            "Installed"
+        };
+        write!(f, "{verb} {}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, " {version}")?;
+        }
+        write!(
+            f,
+            " at {} ({} files, {} bytes in {:.1?})",
+            self.path.display(),
+            self.files_written,
+            self.bytes_written,
+            self.duration
+        )
+    }
+}
+
+impl Display for NorthstarReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Installed Northstar")?;
+        if let Some(version) = &self.version {
+            write!(f, " {version}")?;
+        }
+        write!(
+            f,
+            " to {} ({} files, {} bytes in {:.1?})",
+            self.target.display(),
+            self.files_written,
+            self.bytes_written,
+            self.duration
+        )
+    }
+}
@@ -0,0 +1,104 @@
+//! Letting frontends decide how install conflicts are resolved
+//!
+//! Register a [`Resolver`] with [`set_resolver`] and thermite will ask it what to do instead of failing or
+//! picking a default when it runs into an [`InstallConflict`]:
+//! * Installing a package whose directory already exists, if `ThermiteConfig::overwrite` is
+//!   `OverwritePolicy::Ask`
+//! * `ModManager` installing a package, or a dependency, that's already installed at another version
+//!
+//! Without a resolver, `OverwritePolicy::Ask` acts like `OverwritePolicy::Fail` and other versions are left
+//! installed alongside the new one.
+
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+/// A choice thermite needs the embedder to make
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InstallConflict {
+    /// `name` is being installed to `path`, which already exists
+    AlreadyInstalled { name: String, path: PathBuf },
+    /// `package` is being installed at `requested`, but `installed` is already installed
+    VersionMismatch {
+        package: String,
+        installed: String,
+        requested: String,
+    },
+}
+
+impl Display for InstallConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyInstalled { name, path } => {
+                write!(f, "{name} is already installed at {}", path.display())
+            }
+            Self::VersionMismatch {
+                package,
+                installed,
+                requested,
+            } => write!(
+                f,
+                "{package} {installed} is installed but {requested} was requested"
+            ),
+        }
+    }
+}
+
+/// How to settle an [`InstallConflict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resolution {
+    /// Leave the existing install as it is and treat the package as installed
+    KeepExisting,
+    /// Replace the existing install with the new one
+    UseNew,
+    /// Leave the package out of the operation. Nothing is installed or removed and the rest of a batch
+    /// carries on
+    Skip,
+}
+
+/// Decides how install conflicts are resolved, e.g. by asking the user
+///
+/// Resolvers are called on the thread doing the install and block it until they return. Closures taking an
+/// `&InstallConflict` implement this trait automatically.
+pub trait Resolver: Send + Sync {
+    fn resolve(&self, conflict: &InstallConflict) -> Resolution;
+}
+
+impl<F> Resolver for F
+where
+    F: Fn(&InstallConflict) -> Resolution + Send + Sync,
+{
+    fn resolve(&self, conflict: &InstallConflict) -> Resolution {
+        self(conflict)
+    }
+}
+
+lazy_static! {
+    static ref RESOLVER: RwLock<Option<Arc<dyn Resolver>>> = RwLock::new(None);
+}
+
+/// Sets the resolver asked about every conflict, replacing any previous one
+pub fn set_resolver(resolver: impl Resolver + 'static) {
+    if let Ok(mut lock) = RESOLVER.write() {
+        *lock = Some(Arc::new(resolver));
+    }
+}
+
+/// Removes the current resolver, if any
+pub fn clear_resolver() {
+    if let Ok(mut lock) = RESOLVER.write() {
+        *lock = None;
+    }
+}
+
+/// Asks the current resolver about a conflict, `None` if there isn't one
+pub(crate) fn resolve(conflict: &InstallConflict) -> Option<Resolution> {
+    // clone the Arc so the resolver can replace itself without deadlocking
+    let resolver = RESOLVER.read().ok().and_then(|lock| lock.clone());
+    resolver.map(|r| r.resolve(conflict))
+}
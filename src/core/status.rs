@@ -0,0 +1,153 @@
+//! Status reporting for frontends that don't consume `tracing`
+//!
+//! Register a [`StatusSink`] with [`set_status_sink`] to receive structured
+//! [`StatusEvent`]s (and human-readable lines) from the operations in `manage`.
+
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use super::vfs;
+
+/// Structured events emitted by long-running operations
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StatusEvent {
+    /// A download has started. `size` is 0 if the server didn't report one
+    DownloadStarted { url: String, size: u64 },
+    /// A download has completed
+    DownloadFinished { url: String, bytes: u64 },
+    /// A mod is about to be extracted into `target`
+    InstallStarted { name: String, target: PathBuf },
+    /// A mod was installed to `path`
+    InstallFinished { name: String, path: PathBuf },
+    /// Northstar is about to be installed into `target`
+    NorthstarInstallStarted { target: PathBuf },
+    /// Northstar was installed into `target`
+    NorthstarInstallFinished { target: PathBuf },
+    /// Something unexpected happened but the operation will continue
+    Warning(String),
+}
+
+impl Display for StatusEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DownloadStarted { url, size } => {
+                if *size == 0 {
+                    write!(f, "Downloading {url}")
+                } else {
+                    write!(f, "Downloading {url} ({size} bytes)")
+                }
+            }
+            Self::DownloadFinished { url, bytes } => {
+                write!(f, "Downloaded {bytes} bytes from {url}")
+            }
+            Self::InstallStarted { name, target } => {
+                write!(f, "Installing {name} to {}", target.display())
+            }
+            Self::InstallFinished { name, path } => {
+                write!(f, "Installed {name} at {}", path.display())
+            }
+            Self::NorthstarInstallStarted { target } => {
+                write!(f, "Installing Northstar to {}", target.display())
+            }
+            Self::NorthstarInstallFinished { target } => {
+                write!(f, "Installed Northstar to {}", target.display())
+            }
+            Self::Warning(msg) => write!(f, "Warning: {msg}"),
+        }
+    }
+}
+
+/// Receives status updates from thermite operations
+///
+/// Both methods have default implementations, so implementors only need to override the one they care about.
+/// Closures taking a `&StatusEvent` implement this trait automatically.
+pub trait StatusSink: Send + Sync {
+    /// Called with a human-readable status line
+    fn status(&self, line: &str) {
+        let _ = line;
+    }
+
+    /// Called with every structured event. Forwards the event's `Display` output to `status` by default
+    fn event(&self, event: &StatusEvent) {
+        self.status(&event.to_string());
+    }
+}
+
+impl<F> StatusSink for F
+where
+    F: Fn(&StatusEvent) + Send + Sync,
+{
+    fn event(&self, event: &StatusEvent) {
+        self(event);
+    }
+}
+
+lazy_static! {
+    static ref SINK: RwLock<Option<Arc<dyn StatusSink>>> = RwLock::new(None);
+}
+
+/// Sets the sink that will receive all status events, replacing any previous sink
+pub fn set_status_sink(sink: impl StatusSink + 'static) {
+    if let Ok(mut lock) = SINK.write() {
+        *lock = Some(Arc::new(sink));
+    }
+}
+
+/// Removes the current sink, if any
+pub fn clear_status_sink() {
+    if let Ok(mut lock) = SINK.write() {
+        *lock = None;
+    }
+}
+
+/// Sends an event to the current sink
+pub(crate) fn emit(event: StatusEvent) {
+    if vfs::is_dry_run() {
+        return;
+    }
+    // clone the Arc so the sink can't deadlock by replacing itself
+    let sink = SINK.read().ok().and_then(|lock| lock.clone());
+    if let Some(sink) = sink {
+        sink.event(&event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{emit, set_status_sink, StatusEvent, StatusSink};
+
+    struct Lines(Arc<Mutex<Vec<String>>>);
+
+    impl StatusSink for Lines {
+        fn status(&self, line: &str) {
+            if line.contains("status_sink_test") {
+                self.0.lock().unwrap().push(line.to_owned());
+            }
+        }
+    }
+
+    #[test]
+    fn sink_receives_lines() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        set_status_sink(Lines(lines.clone()));
+
+        emit(StatusEvent::InstallFinished {
+            name: "status_sink_test".into(),
+            path: PathBuf::from("packages"),
+        });
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.as_slice(), ["Installed status_sink_test at packages"]);
+    }
+}
@@ -1,19 +1,38 @@
+use crate::cancel;
+use crate::config::{self, RunningGamePolicy, ThermiteConfig};
 use crate::error::ThermiteError;
 use crate::model::EnabledMods;
 use crate::model::InstalledMod;
+use crate::model::ListedPackage;
 use crate::model::Manifest;
 use crate::model::Mod;
+use crate::model::ModJSON;
+use crate::model::ModList;
+use crate::model::ModVersion;
+use crate::model::VersionKey;
+use crate::pool;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::verify;
+
+use super::journal::{self, JournalEntry, JOURNAL_DIR};
+use super::manage::STAGING_PREFIX;
+use super::vfs::{self, DirEntry};
 
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::fmt::Debug;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Debug, Display};
 use std::fs;
+use std::io::{Read, Seek};
 use std::ops::Deref;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use tracing::trace;
 use tracing::{debug, error};
+use zip::ZipArchive;
 
 pub(crate) type ModString = (String, String, String);
 
@@ -62,17 +81,24 @@ impl Drop for TempDir {
 /// Returns a list of `Mod`s publled from an index based on the dep stings
 /// from Thunderstore
 ///
+/// Only the given deps are resolved, not their own dependencies, see [`resolve_deps_recursive`] for that.
+/// Versions in the dep strings are ignored, see [`resolve_install_order`] to resolve them too
+///
 /// # Errors
 /// - A dependency string isn't formatted like `author-name`
 /// - A dependency string isn't present in the index
 pub fn resolve_deps(deps: &[impl AsRef<str>], index: &[Mod]) -> Result<Vec<Mod>, ThermiteError> {
     let mut valid = vec![];
     for dep in deps {
+        cancel::checkpoint("resolving dependencies")?;
         let dep_name = dep
             .as_ref()
             .split('-')
             .nth(1)
-            .ok_or_else(|| ThermiteError::DepError(dep.as_ref().into()))?;
+            .ok_or_else(|| ThermiteError::DepError {
+                name: dep.as_ref().into(),
+                suggestions: vec![],
+            })?;
 
         if dep_name.to_lowercase() == "northstar" {
             debug!("Skip unfiltered Northstar dependency");
@@ -82,12 +108,269 @@ pub fn resolve_deps(deps: &[impl AsRef<str>], index: &[Mod]) -> Result<Vec<Mod>,
         if let Some(d) = index.iter().find(|f| f.name == dep_name) {
             valid.push(d.clone());
         } else {
-            return Err(ThermiteError::DepError(dep.as_ref().into()));
+            return Err(ThermiteError::DepError {
+                name: dep.as_ref().into(),
+                suggestions: suggest_packages(dep.as_ref(), index),
+            });
         }
     }
     Ok(valid)
 }
 
+/// Like [`resolve_deps`], also resolving the dependencies of every dependency, all the way down
+///
+/// Dependencies are followed through the latest version of each package. Every package is listed once,
+/// after the packages it depends on, so the whole list can be installed in order.
+///
+/// # Errors
+/// - A dependency string isn't formatted like `author-name`
+/// - A dependency string isn't present in the index
+pub fn resolve_deps_recursive(
+    deps: &[impl AsRef<str>],
+    index: &[Mod],
+) -> Result<Vec<Mod>, ThermiteError> {
+    let mut resolved = vec![];
+    resolve_deps_into(deps, index, &mut BTreeSet::new(), &mut resolved)?;
+    Ok(resolved)
+}
+
+fn resolve_deps_into(
+    deps: &[impl AsRef<str>],
+    index: &[Mod],
+    seen: &mut BTreeSet<String>,
+    resolved: &mut Vec<Mod>,
+) -> Result<(), ThermiteError> {
+    for dep in resolve_deps(deps, index)? {
+        // marked before recursing so packages depending on each other don't loop forever
+        if !seen.insert(package_key(&dep)) {
+            continue;
+        }
+        if let Some(latest) = dep.get_latest() {
+            resolve_deps_into(&latest.deps, index, seen, resolved)?;
+        }
+        resolved.push(dep);
+    }
+    Ok(())
+}
+
+/// A minimum version of a package, from a dependency string
+#[derive(Debug)]
+struct Requirement {
+    /// `author-name-X.Y.Z` of the package with the dependency, or `requested` for the packages asked for
+    by: String,
+    min: Option<String>,
+}
+
+/// Works out every package needed to install `deps` and the order to install them in, dependencies first
+///
+/// Dependency strings like `author-name-X.Y.Z` are minimum versions: any version from `X.Y.Z` up to, but not
+/// including, the next major version satisfies them. Strings without a version, `author-name`, accept any
+/// version. Each package is resolved to the newest version satisfying every package that depends on it, and
+/// the dependencies of that version are followed in turn. Dependencies on Northstar itself are skipped, like
+/// in [`resolve_deps`].
+///
+/// # Errors
+/// - `ThermiteError::DepError` if a dependency isn't formatted properly or isn't in the index
+/// - `ThermiteError::DependencyConflict` if no version of a package satisfies everything depending on it
+/// - `ThermiteError::DependencyCycle` if packages depend on each other
+pub fn resolve_install_order(
+    deps: &[impl AsRef<str>],
+    index: &[Mod],
+) -> Result<Vec<ModVersion>, ThermiteError> {
+    let requested = deps
+        .iter()
+        .map(|d| ("requested".to_owned(), Some(d.as_ref())))
+        .collect::<Vec<_>>();
+
+    // picking a version changes which dependencies are followed, so repeat until the picks stop changing
+    let attempts = index.iter().map(|m| m.versions.len()).sum::<usize>() + 1;
+    let mut chosen: BTreeMap<String, &ModVersion> = BTreeMap::new();
+    for _ in 0..attempts {
+        cancel::checkpoint("resolving dependencies")?;
+        let mut requirements: BTreeMap<String, (&Mod, Vec<Requirement>)> = BTreeMap::new();
+        let edges = requested
+            .iter()
+            .cloned()
+            .chain(chosen.values().flat_map(|v| {
+                v.deps
+                    .iter()
+                    .map(|d| (v.full_name.clone(), Some(d.as_str())))
+            }));
+        for (by, dep) in edges {
+            let Some((package, min)) = dep
+                .map(|d| find_dependency(d, index))
+                .transpose()?
+                .flatten()
+            else {
+                continue;
+            };
+            requirements
+                .entry(package_key(package))
+                .or_insert_with(|| (package, vec![]))
+                .1
+                .push(Requirement { by, min });
+        }
+
+        let mut next = BTreeMap::new();
+        for (key, (package, requirements)) in requirements {
+            let version = newest_satisfying(package, &requirements).ok_or_else(|| {
+                ThermiteError::DependencyConflict {
+                    package: key.clone(),
+                    requirements: requirements
+                        .iter()
+                        .map(|r| match &r.min {
+                            Some(min) => format!("{} needs {min}", r.by),
+                            None => format!("{} needs any version", r.by),
+                        })
+                        .collect(),
+                }
+            })?;
+            next.insert(key, version);
+        }
+
+        let settled = next
+            .iter()
+            .map(|(k, v)| (k, &v.version))
+            .eq(chosen.iter().map(|(k, v)| (k, &v.version)));
+        chosen = next;
+        if settled {
+            let mut order = vec![];
+            let mut done = BTreeSet::new();
+            for key in chosen.keys() {
+                visit(key, &chosen, index, &mut vec![], &mut done, &mut order)?;
+            }
+            return Ok(order);
+        }
+    }
+
+    Err(ThermiteError::UnsettledDependencies)
+}
+
+/// The package a dependency string refers to and its minimum version, `None` for Northstar itself
+fn find_dependency<'a>(
+    dep: &str,
+    index: &'a [Mod],
+) -> Result<Option<(&'a Mod, Option<String>)>, ThermiteError> {
+    let (author, name, min) = match parse_modstring(dep) {
+        Ok((author, name, version)) => (author, name, Some(version)),
+        Err(_) => match dep.split_once('-') {
+            Some((author, name)) if !name.contains('-') => (author.into(), name.into(), None),
+            _ => {
+                return Err(ThermiteError::DepError {
+                    name: dep.into(),
+                    suggestions: vec![],
+                })
+            }
+        },
+    };
+
+    if name.to_lowercase() == "northstar" {
+        debug!("Skip unfiltered Northstar dependency");
+        return Ok(None);
+    }
+
+    index
+        .iter()
+        .find(|m| m.author.eq_ignore_ascii_case(&author) && m.name.eq_ignore_ascii_case(&name))
+        .map(|m| Some((m, min)))
+        .ok_or_else(|| ThermiteError::DepError {
+            name: dep.into(),
+            suggestions: suggest_packages(dep, index),
+        })
+}
+
+fn package_key(package: &Mod) -> String {
+    format!("{}-{}", package.author, package.name)
+}
+
+fn newest_satisfying<'a>(package: &'a Mod, requirements: &[Requirement]) -> Option<&'a ModVersion> {
+    let major = |v: &str| v.split('.').next().map(str::to_owned);
+    // versions are ordered oldest first
+    package.versions.values().rev().find(|v| {
+        requirements.iter().all(|r| {
+            r.min
+                .as_ref()
+                .is_none_or(|min| major(min) == major(&v.version) && !is_newer(min, &v.version))
+        })
+    })
+}
+
+/// Adds `key` to `order` after its dependencies, `path` being the packages that led to it
+fn visit(
+    key: &str,
+    chosen: &BTreeMap<String, &ModVersion>,
+    index: &[Mod],
+    path: &mut Vec<String>,
+    done: &mut BTreeSet<String>,
+    order: &mut Vec<ModVersion>,
+) -> Result<(), ThermiteError> {
+    if done.contains(key) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|p| p == key) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(key.into());
+        return Err(ThermiteError::DependencyCycle(cycle));
+    }
+
+    let version = chosen[key];
+    path.push(key.into());
+    for dep in &version.deps {
+        if let Some((package, _)) = find_dependency(dep, index)? {
+            visit(&package_key(package), chosen, index, path, done, order)?;
+        }
+    }
+    path.pop();
+    done.insert(key.into());
+    order.push(version.clone());
+    Ok(())
+}
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns up to three `author-name` strings from the index that are close to the `author-name` part of `dep`
+pub(crate) fn suggest_packages(dep: &str, index: &[Mod]) -> Vec<String> {
+    let target = dep.splitn(3, '-').take(2).collect::<Vec<_>>().join("-");
+    let target = target.to_lowercase();
+    // allow roughly one typo for every three characters
+    let limit = (target.chars().count() / 3).max(1);
+
+    let mut candidates = index
+        .iter()
+        .map(|m| format!("{}-{}", m.author, m.name))
+        .filter_map(|name| {
+            let distance = edit_distance(&target, &name.to_lowercase());
+            (distance <= limit).then_some((distance, name))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.dedup_by(|a, b| a.1 == b.1);
+
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Levenshtein distance between two strings
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut current);
+    }
+
+    prev[b.len()]
+}
+
 /// Get `enabledmods.json` from the given directory, if it exists
 ///
 /// # Errors
@@ -95,10 +378,12 @@ pub fn resolve_deps(deps: &[impl AsRef<str>], index: &[Mod]) -> Result<Vec<Mod>,
 /// - The path is not a directory
 /// - There is no `enabledmods.json` file in the provided directory
 pub fn get_enabled_mods(dir: impl AsRef<Path>) -> Result<EnabledMods, ThermiteError> {
-    let path = dir.as_ref().canonicalize()?.join("enabledmods.json");
-    if path.exists() {
-        let raw = fs::read_to_string(&path)?;
-        let mut mods: EnabledMods = serde_json::from_str(&raw)?;
+    let fs = vfs::current();
+    let path = fs.canonicalize(dir.as_ref())?.join("enabledmods.json");
+    if fs.exists(&path)? {
+        let raw = fs.read_to_string(&path)?;
+        let mut mods: EnabledMods =
+            serde_json::from_str(&raw).map_err(|e| ThermiteError::invalid_json(&path, e))?;
         mods.set_path(path);
         Ok(mods)
     } else {
@@ -106,107 +391,465 @@ pub fn get_enabled_mods(dir: impl AsRef<Path>) -> Result<EnabledMods, ThermiteEr
     }
 }
 
+/// Enables a mod in the `enabledmods.json` in `dir`, see [`set_mod_enabled`]
+///
+/// # Errors
+/// - IO errors
+/// - The existing file isn't valid JSON
+pub fn enable_mod(dir: impl AsRef<Path>, name: impl AsRef<str>) -> Result<(), ThermiteError> {
+    set_mod_enabled(dir, name, true)
+}
+
+/// Disables a mod in the `enabledmods.json` in `dir`, see [`set_mod_enabled`]
+///
+/// # Errors
+/// - IO errors
+/// - The existing file isn't valid JSON
+pub fn disable_mod(dir: impl AsRef<Path>, name: impl AsRef<str>) -> Result<(), ThermiteError> {
+    set_mod_enabled(dir, name, false)
+}
+
+/// The core mods' keys in `enabledmods.json`
+const CORE_MOD_KEYS: [&str; 3] = [
+    "Northstar.Client",
+    "Northstar.Custom",
+    "Northstar.CustomServers",
+];
+
+/// Sets a mod's state in the `enabledmods.json` in `dir`, creating the file if it doesn't exist
+///
+/// Only the mod's own entry changes. Every other key in the file is kept as it was, even ones
+/// [`EnabledMods`] doesn't understand, and the file is replaced in one step so Northstar never reads it
+/// half written.
+///
+/// # Errors
+/// - IO errors
+/// - The existing file isn't valid JSON
+pub fn set_mod_enabled(
+    dir: impl AsRef<Path>,
+    name: impl AsRef<str>,
+    enabled: bool,
+) -> Result<(), ThermiteError> {
+    let fs = vfs::current();
+    let path = dir.as_ref().join("enabledmods.json");
+    let mut entries: serde_json::Map<String, serde_json::Value> = if fs.exists(&path)? {
+        json5::from_str(&fs.read_to_string(&path)?)
+            .map_err(|e| ThermiteError::invalid_json(&path, e))?
+    } else {
+        serde_json::Map::new()
+    };
+
+    // core mods are written with their proper capitalization, like `EnabledMods` does
+    let name = name.as_ref();
+    let key = CORE_MOD_KEYS
+        .into_iter()
+        .find(|core| core.eq_ignore_ascii_case(name))
+        .unwrap_or(name);
+    entries.insert(key.into(), enabled.into());
+
+    if let Some(parent) = path.parent() {
+        fs.create_dir_all(parent)?;
+    }
+    fs.write_atomic(&path, serde_json::to_string_pretty(&entries)?.as_bytes())?;
+    #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+    crate::db::update(&dir.as_ref().join("packages"), false, |db| {
+        db.set_enabled(key, enabled)
+    });
+    Ok(())
+}
+
+/// How mods are arranged in a directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PackageLayout {
+    /// Thunderstore packages, each in an `author-name-X.Y.Z` directory with its mods in a nested `mods`
+    /// directory, like Northstar's `packages` directory
+    Packages,
+    /// Mods directly in the directory, like Northstar's legacy `mods` directory. Installing a package only
+    /// extracts the mods in its `mods` directory, one directory per mod
+    Legacy,
+    /// Work out the layout from the directory with [`PackageLayout::detect`]
+    #[default]
+    Auto,
+}
+
+impl PackageLayout {
+    /// Works out the layout of `dir`
+    ///
+    /// A directory named `mods`, or one with mods directly in it, is [`PackageLayout::Legacy`]. Anything
+    /// else, including empty directories and ones that don't exist yet, is [`PackageLayout::Packages`].
+    ///
+    /// # Errors
+    /// - IO errors
+    pub fn detect(dir: impl AsRef<Path>) -> Result<Self, ThermiteError> {
+        let dir = dir.as_ref();
+        if dir.file_name().is_some_and(|n| n == "mods") {
+            return Ok(Self::Legacy);
+        }
+
+        let fs = vfs::current();
+        if !fs.is_dir(dir) {
+            return Ok(Self::Packages);
+        }
+        for child in fs.read_dir(dir)? {
+            if !child.is_dir {
+                continue;
+            }
+            if fs.exists(&child.path.join("manifest.json"))? {
+                return Ok(Self::Packages);
+            }
+            if fs.exists(&child.path.join("mod.json"))? {
+                return Ok(Self::Legacy);
+            }
+        }
+        Ok(Self::Packages)
+    }
+
+    /// The layout itself, or the one detected from `dir` for [`PackageLayout::Auto`]
+    pub(crate) fn resolve(self, dir: &Path) -> Result<Self, ThermiteError> {
+        match self {
+            Self::Auto => Self::detect(dir),
+            layout => Ok(layout),
+        }
+    }
+}
+
 /// Search a directory for mod.json files in its children
 ///
-/// Searches one level deep
+/// Searches one level deep, detecting whether the directory holds packages or mods with
+/// [`PackageLayout::detect`]
+///
+/// Use [`find_mods_nested`] to search something like a whole profile directory. Mods installed by hand,
+/// without a package around them, are listed by [`find_unmanaged_mods`].
 ///
 /// # Errors
 /// - The path cannot be canonicalized
 /// - IO Errors
 /// - Improperly formatted JSON files
 pub fn find_mods(dir: impl AsRef<Path>) -> Result<Vec<InstalledMod>, ThermiteError> {
-    let mut res = vec![];
-    let dir = dir.as_ref().canonicalize()?;
+    find_mods_with_layout(dir, PackageLayout::Auto)
+}
+
+/// Like [`find_mods`], for a directory in a known layout
+///
+/// Mods found in a [`PackageLayout::Legacy`] directory have no author and a manifest made up from their
+/// `mod.json`, since they aren't part of a package.
+///
+/// # Errors
+/// - The path cannot be canonicalized
+/// - IO Errors
+/// - Improperly formatted JSON files
+pub fn find_mods_with_layout(
+    dir: impl AsRef<Path>,
+    layout: PackageLayout,
+) -> Result<Vec<InstalledMod>, ThermiteError> {
+    let fs = vfs::current();
+    let dir = fs.canonicalize(dir.as_ref())?;
+    if layout.resolve(&dir)? == PackageLayout::Legacy {
+        return find_legacy_mods(&dir);
+    }
     debug!("Finding mods in '{}'", dir.display());
-    for child in dir.read_dir()? {
-        let child = child?;
-        if !child.file_type()?.is_dir() {
-            debug!("Skipping file {}", child.path().display());
-            continue;
+    let mut packages = vec![];
+    for child in fs.read_dir(&dir)? {
+        if is_ignored(&child) {
+            debug!("Skipping staging directory {}", child.path.display());
+        } else if child.is_dir {
+            packages.push(child);
+        } else {
+            debug!("Skipping file {}", child.path.display());
         }
+    }
 
-        let path = child.path().join("manifest.json");
-        let manifest = if path.try_exists()? {
-            let raw = fs::read_to_string(&path)?;
-            let Ok(parsed) = serde_json::from_str(&raw) else {
-                error!("Error parsing {}", path.display());
-                continue;
-            };
-            parsed
-        } else {
+    // packages are independent of each other so scan them in parallel
+    let found = pool::map(packages, |child| {
+        cancel::checkpoint(format!("finding mods in {}", dir.display()))?;
+        find_package_mods(&child)
+    });
+
+    let mut res = vec![];
+    for mods in found {
+        res.append(&mut mods?);
+    }
+
+    Ok(res)
+}
+
+/// Search a packages directory for mods that aren't part of a package
+///
+/// These are mods installed by hand, in directories of `dir` without a `manifest.json`, which [`find_mods`]
+/// skips. Like mods in a [`PackageLayout::Legacy`] directory they have no author and a manifest made up from
+/// their `mod.json`. [`find_mods`] already lists every mod of a legacy directory, so none are returned for one.
+///
+/// # Errors
+/// - The path cannot be canonicalized
+/// - IO Errors
+pub fn find_unmanaged_mods(dir: impl AsRef<Path>) -> Result<Vec<InstalledMod>, ThermiteError> {
+    let fs = vfs::current();
+    let dir = fs.canonicalize(dir.as_ref())?;
+    if PackageLayout::detect(&dir)? == PackageLayout::Legacy {
+        return Ok(vec![]);
+    }
+    debug!("Finding unmanaged mods in '{}'", dir.display());
+    let mut mods = vec![];
+    for child in fs.read_dir(&dir)? {
+        if !child.is_dir || is_ignored(&child) || fs.exists(&child.path.join("manifest.json"))? {
             continue;
-        };
+        }
+        find_unmanaged_in(child, &mut mods)?;
+    }
+    Ok(mods)
+}
 
-        if let Some(submods) = get_submods(&manifest, child.path()) {
-            debug!(
-                "Found {} submods in {}",
-                submods.len(),
-                child.path().display()
-            );
-            trace!("{:#?}", submods);
-            let modstring =
-                parse_modstring(child.file_name().to_str().ok_or(ThermiteError::UTF8Error)?)?;
-            res.append(
-                &mut submods
-                    .into_iter()
-                    .map(|mut m| {
-                        m.author = modstring.0.clone();
+/// Finds the mods in `child` or any directory below it
+fn find_unmanaged_in(child: DirEntry, mods: &mut Vec<InstalledMod>) -> Result<(), ThermiteError> {
+    let fs = vfs::current();
+    if fs.exists(&child.path.join("mod.json"))? {
+        mods.extend(find_legacy_mod(child)?);
+        return Ok(());
+    }
+    for next in fs.read_dir(&child.path)? {
+        if next.is_dir {
+            find_unmanaged_in(next, mods)?;
+        }
+    }
+    Ok(())
+}
+
+/// A mod found by [`find_mods_nested`]
+#[derive(Debug, Clone)]
+pub struct DiscoveredMod {
+    /// Full name of the package the mod is part of, e.g. `Author-Mod-1.0.0`, `None` for mods that aren't in
+    /// a package
+    pub package: Option<String>,
+    pub installed: InstalledMod,
+}
 
-                        m
-                    })
-                    .collect(),
+/// Search `dir` for packages and mods up to `max_depth` directories deep
+///
+/// Unlike [`find_mods`], `dir` can be something like a profile directory, with packages in `packages` and
+/// mods in `mods`. Directories holding a `manifest.json` and named like a package are read as packages, with
+/// the mods in them wherever they're nested, and directories holding a `mod.json` as mods outside of any
+/// package. Neither is searched any further. A `max_depth` of 1 only looks at the children of `dir`.
+///
+/// # Errors
+/// - The path cannot be canonicalized
+/// - IO Errors
+/// - Improperly formatted JSON files
+pub fn find_mods_nested(
+    dir: impl AsRef<Path>,
+    max_depth: usize,
+) -> Result<Vec<DiscoveredMod>, ThermiteError> {
+    let dir = vfs::current().canonicalize(dir.as_ref())?;
+    debug!("Finding mods in '{}' up to {max_depth} deep", dir.display());
+    let mut found = vec![];
+    walk_mods(&dir, max_depth, &mut found)?;
+    Ok(found)
+}
+
+fn walk_mods(
+    dir: &Path,
+    depth: usize,
+    found: &mut Vec<DiscoveredMod>,
+) -> Result<(), ThermiteError> {
+    if depth == 0 {
+        return Ok(());
+    }
+    cancel::checkpoint(format!("finding mods in {}", dir.display()))?;
+    let fs = vfs::current();
+    for child in fs.read_dir(dir)? {
+        if !child.is_dir || is_ignored(&child) {
+            continue;
+        }
+        if fs.exists(&child.path.join("manifest.json"))? && package_author(&child.path)?.is_some() {
+            let package = child.file_name().to_owned();
+            found.extend(
+                find_package_mods(&child)?
+                    .into_iter()
+                    .map(|installed| DiscoveredMod {
+                        package: Some(package.clone()),
+                        installed,
+                    }),
             );
+        } else if fs.exists(&child.path.join("mod.json"))? {
+            if let Some(installed) = find_legacy_mod(child)? {
+                found.push(DiscoveredMod {
+                    package: None,
+                    installed,
+                });
+            }
         } else {
-            debug!("No mods in {}", child.path().display());
+            walk_mods(&child.path, depth - 1, found)?;
         }
     }
+    Ok(())
+}
 
-    Ok(res)
+/// Every install, update and uninstall recorded in the journal of `dir`, oldest first
+///
+/// `dir` is the directory packages were installed into, see [`journal`]. Directories nothing was installed
+/// into yet have an empty history.
+///
+/// # Errors
+/// * IO errors
+/// * The journal isn't formatted properly
+pub fn history(dir: impl AsRef<Path>) -> Result<Vec<JournalEntry>, ThermiteError> {
+    journal::read(dir.as_ref())
+}
+
+/// Finds the mods directly in `dir`, each in its own directory
+fn find_legacy_mods(dir: &Path) -> Result<Vec<InstalledMod>, ThermiteError> {
+    let fs = vfs::current();
+    debug!("Finding legacy mods in '{}'", dir.display());
+    let mut mods = vec![];
+    for child in fs.read_dir(dir)? {
+        if !child.is_dir || is_ignored(&child) {
+            continue;
+        }
+        if let Some(installed) = find_legacy_mod(child)? {
+            mods.push(installed);
+        }
+    }
+    Ok(mods)
+}
+
+/// Reads the mod in a directory that isn't part of a package, `None` if it has no valid `mod.json`
+fn find_legacy_mod(child: DirEntry) -> Result<Option<InstalledMod>, ThermiteError> {
+    let fs = vfs::current();
+    let path = child.path.join("mod.json");
+    if !fs.exists(&path)? {
+        return Ok(None);
+    }
+    let mod_json: ModJSON = match json5::from_str(&fs.read_to_string(&path)?) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            error!("Error parsing JSON in {}: {e}", path.display());
+            return Ok(None);
+        }
+    };
+    Ok(Some(InstalledMod {
+        manifest: Manifest {
+            name: mod_json.name.clone(),
+            version_number: mod_json.version.clone(),
+            website_url: String::new(),
+            description: mod_json.description.clone(),
+            dependencies: vec![],
+        },
+        mod_json,
+        author: read_author(&child.path)?.unwrap_or_default(),
+        path: child.path,
+    }))
+}
+
+/// Staging and journal directories, which never hold mods
+fn is_ignored(child: &DirEntry) -> bool {
+    child.file_name().starts_with(STAGING_PREFIX) || child.file_name() == JOURNAL_DIR
+}
+
+/// File with the author of a package, or of a mod in the legacy layout, for directories not named after it
+pub(crate) const AUTHOR_FILE: &str = "thunderstore_author.txt";
+
+/// The author of the package in `dir`, from its `author-name-X.Y.Z` name or its [`AUTHOR_FILE`]
+fn package_author(dir: &Path) -> Result<Option<String>, ThermiteError> {
+    let name = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or(ThermiteError::UTF8Error)?;
+    if let Ok((author, _, _)) = parse_modstring(name) {
+        return Ok(Some(author));
+    }
+    read_author(dir)
+}
+
+/// The contents of the [`AUTHOR_FILE`] in `dir`, if it has one
+fn read_author(dir: &Path) -> Result<Option<String>, ThermiteError> {
+    let fs = vfs::current();
+    let path = dir.join(AUTHOR_FILE);
+    if !fs.exists(&path)? {
+        return Ok(None);
+    }
+    Ok(Some(fs.read_to_string(&path)?.trim().to_owned()))
+}
+
+/// Finds the mods provided by a single package directory
+pub(crate) fn find_package_mods(child: &DirEntry) -> Result<Vec<InstalledMod>, ThermiteError> {
+    let fs = vfs::current();
+    let path = child.path.join("manifest.json");
+    let manifest = if fs.exists(&path)? {
+        let raw = fs.read_to_string(&path)?;
+        let Ok(parsed) = serde_json::from_str(&raw) else {
+            error!("Error parsing {}", path.display());
+            return Ok(vec![]);
+        };
+        parsed
+    } else {
+        return Ok(vec![]);
+    };
+
+    if let Some(submods) = get_submods(&manifest, &child.path) {
+        debug!(
+            "Found {} submods in {}",
+            submods.len(),
+            child.path.display()
+        );
+        trace!("{:#?}", submods);
+        let author = package_author(&child.path)?
+            .ok_or_else(|| ThermiteError::NameError(child.file_name().into()))?;
+        Ok(submods
+            .into_iter()
+            .map(|mut m| {
+                m.author = author.clone();
+
+                m
+            })
+            .collect())
+    } else {
+        debug!("No mods in {}", child.path.display());
+        Ok(vec![])
+    }
 }
 
 fn get_submods(manifest: &Manifest, dir: impl AsRef<Path>) -> Option<Vec<InstalledMod>> {
+    let fs = vfs::current();
     let dir = dir.as_ref();
     debug!("Searching for submods in {}", dir.display());
-    if !dir.is_dir() {
+    if !fs.is_dir(dir) {
         debug!("Wasn't a directory, aborting");
         return None;
     }
 
     let mut mods = vec![];
-    for child in dir.read_dir().ok()? {
-        let Ok(child) = child else { continue };
-        match child.file_type() {
-            Ok(ty) => {
-                if ty.is_dir() {
-                    let Some(mut next) = get_submods(manifest, child.path()) else {
-                        continue;
-                    };
-                    mods.append(&mut next);
-                } else {
-                    trace!("Is file {:?} mod.json?", child.file_name());
-                    if child.file_name() == "mod.json" {
-                        trace!("Yes");
-                        let Ok(file) = fs::read_to_string(child.path()) else {
-                            continue;
-                        };
-                        match json5::from_str(&file) {
-                            Ok(mod_json) => mods.push(InstalledMod {
-                                author: String::new(),
-                                manifest: manifest.clone(),
-                                mod_json,
-                                path: dir.to_path_buf(),
-                            }),
-                            Err(e) => {
-                                error!("Error parsing JSON in {}: {e}", child.path().display());
-                            }
-                        }
-                    } else {
-                        trace!("No");
+    let children = match fs.read_dir(dir) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Error {e}");
+            return None;
+        }
+    };
+    for child in children {
+        if child.is_dir {
+            let Some(mut next) = get_submods(manifest, &child.path) else {
+                continue;
+            };
+            mods.append(&mut next);
+        } else {
+            trace!("Is file {:?} mod.json?", child.file_name());
+            if child.file_name() == "mod.json" {
+                trace!("Yes");
+                let Ok(file) = fs.read_to_string(&child.path) else {
+                    continue;
+                };
+                match json5::from_str(&file) {
+                    Ok(mod_json) => mods.push(InstalledMod {
+                        author: String::new(),
+                        manifest: manifest.clone(),
+                        mod_json,
+                        path: dir.to_path_buf(),
+                    }),
+                    Err(e) => {
+                        error!("Error parsing JSON in {}: {e}", child.path.display());
                     }
                 }
-            }
-            Err(e) => {
-                error!("Error {e}");
+            } else {
+                trace!("No");
             }
         }
     }
@@ -271,33 +914,1412 @@ pub fn validate_modstring(input: impl AsRef<str>) -> bool {
     RE.is_match(input.as_ref())
 }
 
-#[cfg(feature = "steam")]
-pub(crate) mod steam {
-    use std::path::PathBuf;
-    use steamlocate::SteamDir;
-
-    use crate::TITANFALL2_STEAM_ID;
+/// Files besides `mods` and `plugins` that belong at the top of a package
+const PACKAGE_FILES: [&str; 5] = [
+    "manifest.json",
+    "readme.md",
+    "changelog.md",
+    "icon.png",
+    "license",
+];
+/// What a package installs, which decides where [`install_mod`](super::manage::install_mod) puts its files
+///
+/// Northstar only loads files through mods, textures from a mod's `mod` directory and audio overrides from
+/// its `audio` directory. Skins and audio overrides packaged without a mod are installed as a mod named after
+/// the package, with a generated `mod.json`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PackageKind {
+    /// Mods in a `mods` directory, and maybe plugins
+    #[default]
+    Mod,
+    /// Textures in a `skins` or `mod` directory at the top of the package
+    Skin,
+    /// Audio overrides in an `audio` directory at the top of the package
+    AudioOverride,
+}
 
-    /// Returns the path to the Steam installation if it exists
+impl PackageKind {
+    /// The kind of the package with the entries `names`, anything with a `mods` directory is a mod
     #[must_use]
-    #[inline]
-    pub fn steam_dir() -> Option<PathBuf> {
-        SteamDir::locate().map(|v| v.path)
+    pub fn detect<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut kind = Self::Mod;
+        for name in names {
+            match name.split_once('/') {
+                Some(("mods", _)) => return Self::Mod,
+                Some(("skins" | "mod", _)) => kind = Self::Skin,
+                Some(("audio", _)) if kind == Self::Mod => kind = Self::AudioOverride,
+                _ => {}
+            }
+        }
+        kind
+    }
+
+    /// Where the archive entry at `path` goes in the directory of the package `package`
+    pub(crate) fn route(self, path: &Path, package: &str) -> PathBuf {
+        if self == Self::Mod {
+            return path.into();
+        }
+        let mut parts = path.components();
+        let dir = match parts.next().and_then(|top| top.as_os_str().to_str()) {
+            Some("skins" | "mod") => "mod",
+            Some("audio") => "audio",
+            _ => return path.into(),
+        };
+        Path::new("mods")
+            .join(package)
+            .join(dir)
+            .join(parts.as_path())
     }
+}
 
-    /// Returns paths to all known Steam libraries
+/// Extensions of files that have no business being in a mod
+const DISALLOWED_EXTENSIONS: [&str; 9] =
+    ["exe", "bat", "cmd", "com", "msi", "ps1", "scr", "vbs", "sh"];
+
+/// A problem [`validate_package`] found in a package
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PackageProblem {
+    /// There's no `manifest.json` at the top of the package
+    MissingManifest,
+    /// The whole package is inside the directory `prefix`, a common mistake when zipping a package by hand
+    NestedPackage { prefix: String },
+    /// `manifest.json` couldn't be parsed
+    InvalidManifest(String),
+    /// The package's `mods` directory has no mods
+    NoMods,
+    /// The directory `dir` in `mods` has no `mod.json`
+    MissingModJson { dir: String },
+    /// The `mod.json` of the mod in `dir` couldn't be parsed
+    InvalidModJson { dir: String, error: String },
+    /// An entry that would be extracted outside of the package's directory
+    UnsafePath(String),
+    /// A symlink, which installs refuse
+    Symlink(String),
+    /// A file that doesn't belong in a mod, like an executable
+    DisallowedFile(String),
+    /// A file at the top of the package that neither Thunderstore nor Northstar use
+    UnexpectedFile(String),
+}
+
+impl PackageProblem {
+    /// Whether the problem keeps the package from installing or loading, the others only deserve a warning
     #[must_use]
-    pub fn steam_libraries() -> Option<Vec<PathBuf>> {
-        let mut steamdir = SteamDir::locate()?;
-        let folders = steamdir.libraryfolders();
-        Some(folders.paths.clone())
+    pub const fn is_fatal(&self) -> bool {
+        !matches!(self, Self::UnexpectedFile(_))
+    }
+}
+
+impl Display for PackageProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingManifest => write!(f, "The package has no manifest.json"),
+            Self::NestedPackage { prefix } => {
+                write!(f, "The package is inside the directory {prefix} instead of at the top of the archive")
+            }
+            Self::InvalidManifest(e) => write!(f, "Invalid manifest.json: {e}"),
+            Self::NoMods => write!(f, "The package has no mods directory with mods in it"),
+            Self::MissingModJson { dir } => write!(f, "mods/{dir} has no mod.json"),
+            Self::InvalidModJson { dir, error } => {
+                write!(f, "Invalid mods/{dir}/mod.json: {error}")
+            }
+            Self::UnsafePath(path) => write!(f, "{path} would be extracted outside of the package"),
+            Self::Symlink(path) => write!(f, "{path} is a symlink"),
+            Self::DisallowedFile(path) => write!(f, "{path} isn't allowed in a package"),
+            Self::UnexpectedFile(path) => {
+                write!(f, "{path} isn't used by Thunderstore or Northstar")
+            }
+        }
     }
+}
+
+/// What [`validate_package`] found in a package
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The package's `manifest.json`, if it could be parsed
+    pub manifest: Option<Manifest>,
+    /// The `mod.json` of every mod in the package, keyed by its directory in `mods`
+    pub mods: BTreeMap<String, ModJSON>,
+    pub kind: PackageKind,
+    pub problems: Vec<PackageProblem>,
+}
 
-    /// Returns the path to the Titanfall installation if it exists
+impl ValidationReport {
+    /// Whether the package has no fatal problems, see [`PackageProblem::is_fatal`]
     #[must_use]
-    pub fn titanfall() -> Option<PathBuf> {
-        let mut steamdir = SteamDir::locate()?;
-        Some(steamdir.app(&TITANFALL2_STEAM_ID)?.path.clone())
+    pub fn is_valid(&self) -> bool {
+        !self.problems.iter().any(PackageProblem::is_fatal)
+    }
+}
+
+/// Checks a package archive for the mistakes broken Thunderstore uploads make, without extracting it
+///
+/// # Errors
+/// * The archive isn't a zip file
+/// * IO errors
+pub fn validate_package(archive: impl Read + Seek) -> Result<ValidationReport, ThermiteError> {
+    let mut archive = ZipArchive::new(archive)?;
+    let mut report = ValidationReport::default();
+    let mut has_manifest = false;
+    let mut nested = None;
+    let mut mod_dirs = BTreeSet::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = file.name().to_owned();
+        let safe = file
+            .enclosed_name()
+            .is_some_and(|p| p.components().all(|c| matches!(c, Component::Normal(_))));
+        if !safe {
+            report.problems.push(PackageProblem::UnsafePath(name));
+            continue;
+        }
+        if file
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170_000 == 0o120_000)
+        {
+            report.problems.push(PackageProblem::Symlink(name));
+            continue;
+        }
+
+        let parts = name.trim_end_matches('/').split('/').collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["manifest.json"] => has_manifest = true,
+            ["mods", dir, _, ..] => {
+                mod_dirs.insert((*dir).to_owned());
+            }
+            ["mods", dir] if file.is_dir() => {
+                mod_dirs.insert((*dir).to_owned());
+            }
+            [dir, "manifest.json"] => {
+                nested.get_or_insert_with(|| (*dir).to_owned());
+            }
+            [top] if !file.is_dir() => {
+                let lower = top.to_lowercase();
+                if !PACKAGE_FILES
+                    .iter()
+                    .any(|f| lower == *f || lower.starts_with(&format!("{f}.")))
+                {
+                    report
+                        .problems
+                        .push(PackageProblem::UnexpectedFile(name.clone()));
+                }
+            }
+            _ => {}
+        }
+        let disallowed = Path::new(&name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| DISALLOWED_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if !file.is_dir() && disallowed {
+            report.problems.push(PackageProblem::DisallowedFile(name));
+        }
+    }
+
+    if !has_manifest {
+        // the rest of the structure is off as well, so only report the cause
+        report.problems.push(match nested {
+            Some(prefix) => PackageProblem::NestedPackage { prefix },
+            None => PackageProblem::MissingManifest,
+        });
+        return Ok(report);
+    }
+    let mut manifest = String::new();
+    archive
+        .by_name("manifest.json")?
+        .read_to_string(&mut manifest)?;
+    match serde_json::from_str(manifest.trim_start_matches('\u{feff}')) {
+        Ok(manifest) => report.manifest = Some(manifest),
+        Err(e) => report
+            .problems
+            .push(PackageProblem::InvalidManifest(e.to_string())),
+    }
+
+    report.kind = PackageKind::detect(archive.file_names());
+    if mod_dirs.is_empty() && report.kind == PackageKind::Mod {
+        report.problems.push(PackageProblem::NoMods);
+    }
+    for dir in mod_dirs {
+        let mut mod_json = String::new();
+        match archive.by_name(&format!("mods/{dir}/mod.json")) {
+            Ok(mut file) => file.read_to_string(&mut mod_json)?,
+            Err(_) => {
+                report.problems.push(PackageProblem::MissingModJson { dir });
+                continue;
+            }
+        };
+        match json5::from_str(mod_json.trim_start_matches('\u{feff}')) {
+            Ok(parsed) => {
+                report.mods.insert(dir, parsed);
+            }
+            Err(e) => report.problems.push(PackageProblem::InvalidModJson {
+                dir,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+/// File in an installed package, or in each mod of the legacy layout, with the hash of every file installed
+pub(crate) const CHECKSUMS_FILE: &str = ".thermite_checksums";
+
+/// Files of an install that changed since it was installed, see [`verify_mod`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Directory the checksums were recorded for, the package's, or the mod's in the legacy layout
+    pub root: PathBuf,
+    /// Files whose contents changed
+    pub modified: Vec<PathBuf>,
+    /// Files that were installed but are gone
+    pub missing: Vec<PathBuf>,
+    /// Files that weren't part of the install
+    pub extra: Vec<PathBuf>,
+}
+
+impl IntegrityReport {
+    /// Whether every file is as it was installed
+    #[must_use]
+    pub fn is_intact(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Compares the files of an installed mod's package against the hashes recorded when it was installed
+///
+/// Every install writes a `.thermite_checksums` file to the package, or in the legacy layout to each of its
+/// mods, which this looks for in the mod's directory and its parents.
+///
+/// # Errors
+/// * The install has no checksums, e.g. it was installed by hand or by an older version
+/// * The checksums file isn't formatted properly
+/// * IO errors
+#[cfg(not(target_arch = "wasm32"))]
+pub fn verify_mod(installed: &InstalledMod) -> Result<IntegrityReport, ThermiteError> {
+    let fs = vfs::current();
+    let Some(root) = installed
+        .path
+        .ancestors()
+        .find(|dir| fs.exists(&dir.join(CHECKSUMS_FILE)).unwrap_or(false))
+    else {
+        return Err(ThermiteError::MissingFile(Box::new(
+            installed.path.join(CHECKSUMS_FILE),
+        )));
+    };
+    let recorded = read_checksums(root)?;
+    let mut current = hash_files(root)?;
+
+    let mut report = IntegrityReport {
+        root: root.into(),
+        ..Default::default()
+    };
+    for (file, hash) in recorded {
+        match current.remove(&file) {
+            Some(actual) if actual.eq_ignore_ascii_case(&hash) => {}
+            Some(_) => report.modified.push(root.join(file)),
+            None => report.missing.push(root.join(file)),
+        }
+    }
+    report.extra = current.into_keys().map(|file| root.join(file)).collect();
+    Ok(report)
+}
+
+/// The files in `root` that still match the hashes recorded when it was installed, relative to it, or `None`
+/// if no hashes were recorded
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unchanged_files(root: &Path) -> Option<BTreeSet<PathBuf>> {
+    let (Ok(recorded), Ok(current)) = (read_checksums(root), hash_files(root)) else {
+        return None;
+    };
+    let unchanged = recorded
+        .into_iter()
+        .filter(|(file, hash)| {
+            current
+                .get(file)
+                .is_some_and(|h| h.eq_ignore_ascii_case(hash))
+        })
+        .map(|(file, _)| file.into())
+        .collect();
+    Some(unchanged)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn unchanged_files(_root: &Path) -> Option<BTreeSet<PathBuf>> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_checksums(root: &Path) -> Result<BTreeMap<String, String>, ThermiteError> {
+    let path = root.join(CHECKSUMS_FILE);
+    serde_json::from_str(&vfs::current().read_to_string(&path)?)
+        .map_err(|e| ThermiteError::invalid_json(&path, e))
+}
+
+/// Records the hash of every file in `dir` for [`verify_mod`]
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_checksums(dir: &Path) -> Result<(), ThermiteError> {
+    let hashes = hash_files(dir)?;
+    vfs::current().write(
+        &dir.join(CHECKSUMS_FILE),
+        serde_json::to_string_pretty(&hashes)?.as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// The hash of every file in `root` but the checksums, keyed by their `/` separated path in it
+#[cfg(not(target_arch = "wasm32"))]
+fn hash_files(root: &Path) -> Result<BTreeMap<String, String>, ThermiteError> {
+    Ok(hash_package(root)?
+        .into_iter()
+        .map(|(relative, (_, hash))| {
+            let key = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (key, hash)
+        })
+        .collect())
+}
+
+/// The size and hex encoded SHA-256 of every file in a package directory but the checksums, keyed by their
+/// path relative to it
+pub(crate) fn hash_package(root: &Path) -> Result<BTreeMap<PathBuf, (u64, String)>, ThermiteError> {
+    let files = package_files(root)?
+        .into_iter()
+        .filter_map(|file| Some(file.strip_prefix(root).ok()?.to_path_buf()))
+        .collect();
+    // hashing is what verifying and installing large packages spends its time on, so spread it over the pool
+    let hashed = pool::map(files, |relative| {
+        let data = vfs::current().read(&root.join(&relative))?;
+        let hash = verify::encode_hash(&verify::sha256(&data));
+        Ok((relative, (data.len() as u64, hash)))
+    });
+    hashed.into_iter().collect()
+}
+
+/// Every file in a package directory but the checksums thermite writes to it
+pub(crate) fn package_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = journal::files_in(root)?;
+    files.retain(|file| file.strip_prefix(root).ok() != Some(Path::new(CHECKSUMS_FILE)));
+    Ok(files)
+}
+
+/// An installed package with a newer version in the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutdatedPackage {
+    pub author: String,
+    pub name: String,
+    pub installed: String,
+    pub latest: String,
+    /// The installed package's directory
+    pub path: PathBuf,
+    /// Whether the package or the installed version is deprecated on Thunderstore
+    pub deprecated: bool,
+}
+
+impl OutdatedPackage {
+    /// The `author-name-X.Y.Z` string of the latest version
+    #[must_use]
+    pub fn latest_modstring(&self) -> String {
+        format!("{}-{}-{}", self.author, self.name, self.latest)
+    }
+
+    /// Whether `latest` is newer than the installed version, deprecated packages are listed without one
+    #[must_use]
+    pub fn has_update(&self) -> bool {
+        is_newer(&self.latest, &self.installed)
+    }
+}
+
+/// Compares installed mods against the index, returning every package with a newer version available or that
+/// is deprecated
+///
+/// Packages providing several mods are only listed once. Packages the index doesn't have are ignored.
+#[must_use]
+pub fn check_updates(installed: &[InstalledMod], index: &[Mod]) -> Vec<OutdatedPackage> {
+    let mut outdated: Vec<OutdatedPackage> = vec![];
+    for m in installed {
+        let Some(path) = package_dir(m) else {
+            debug!("{} isn't inside a package directory", m.path.display());
+            continue;
+        };
+        if outdated.iter().any(|o| o.path == path) {
+            continue;
+        }
+        let Some(latest) = index
+            .iter()
+            .find(|i| i.author == m.author && i.name == m.manifest.name)
+        else {
+            continue;
+        };
+        let deprecated = latest.is_deprecated(&m.manifest.version_number);
+        if deprecated || is_newer(&latest.latest, &m.manifest.version_number) {
+            outdated.push(OutdatedPackage {
+                author: m.author.clone(),
+                name: m.manifest.name.clone(),
+                installed: m.manifest.version_number.clone(),
+                latest: latest.latest.clone(),
+                path: path.to_path_buf(),
+                deprecated,
+            });
+        }
+    }
+
+    outdated
+}
+
+/// A warning for installing `full_name` if it or its package is deprecated in `index`
+pub(crate) fn deprecation_warning(full_name: &str, index: &[Mod]) -> Option<String> {
+    let (author, name, version) = parse_modstring(full_name).ok()?;
+    let package = index
+        .iter()
+        .find(|m| m.author.eq_ignore_ascii_case(&author) && m.name.eq_ignore_ascii_case(&name))?;
+    if package.deprecated {
+        Some(format!("{author}-{name} is deprecated"))
+    } else if package.is_deprecated(&version) {
+        Some(format!("{full_name} is deprecated"))
+    } else {
+        None
+    }
+}
+
+/// Whether one of the game's processes, like `Titanfall2.exe` or `NorthstarLauncher.exe`, is running
+///
+/// The executables are the global config's `game.processes`, matched ignoring case. On Linux this includes
+/// the game running through Wine or Proton.
+#[must_use]
+pub fn is_game_running() -> bool {
+    is_running(&config::config().game.processes)
+}
+
+fn is_running(executables: &[String]) -> bool {
+    running_processes()
+        .iter()
+        .any(|p| executables.iter().any(|exe| exe.eq_ignore_ascii_case(p)))
+}
+
+/// Executable names of the running processes
+#[cfg(target_os = "linux")]
+fn running_processes() -> Vec<String> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .filter_map(|e| {
+            // `comm` is cut off at 15 characters, and Wine processes have a Windows path as their `argv[0]`
+            let cmdline = fs::read(e.path().join("cmdline")).ok()?;
+            let argv0 = String::from_utf8_lossy(cmdline.split(|b| *b == 0).next()?).into_owned();
+            match argv0.rsplit(['/', '\\']).next().map(str::trim) {
+                Some(name) if !name.is_empty() => Some(name.to_owned()),
+                _ => fs::read_to_string(e.path().join("comm"))
+                    .ok()
+                    .map(|comm| comm.trim().to_owned()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn running_processes() -> Vec<String> {
+    let Ok(output) = std::process::Command::new("tasklist")
+        .args(["/FO", "CSV", "/NH"])
+        .output()
+    else {
+        return vec![];
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|name| name.trim_matches('"').to_owned())
+        .collect()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn running_processes() -> Vec<String> {
+    let Ok(output) = std::process::Command::new("ps")
+        .args(["-A", "-o", "comm="])
+        .output()
+    else {
+        return vec![];
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().rsplit('/').next())
+        .map(Into::into)
+        .collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn running_processes() -> Vec<String> {
+    vec![]
+}
+
+/// Applies the config's `running_game` policy before mods are installed or removed
+pub(crate) fn check_game_running(config: &ThermiteConfig) -> Result<(), ThermiteError> {
+    // a plan can be shown while the game is still open
+    if vfs::is_dry_run() {
+        return Ok(());
+    }
+    let game = &config.game;
+    let limit = match config.running_game {
+        RunningGamePolicy::Ignore => return Ok(()),
+        _ if !is_running(&game.processes) => return Ok(()),
+        RunningGamePolicy::Refuse => return Err(ThermiteError::GameRunning(game.name.clone())),
+        RunningGamePolicy::Wait(limit) => limit,
+    };
+
+    let operation = format!("waiting for {} to close", game.name);
+    debug!("{} is running, waiting for it to close", game.name);
+    let started = Instant::now();
+    while is_running(&game.processes) {
+        let elapsed = started.elapsed();
+        if limit.is_some_and(|limit| elapsed >= limit) {
+            return Err(ThermiteError::Timeout {
+                operation,
+                elapsed,
+                limit,
+            });
+        }
+        let poll = limit.map_or(GAME_POLL_INTERVAL, |limit| {
+            GAME_POLL_INTERVAL.min(limit - elapsed)
+        });
+        cancel::sleep(poll, &operation)?;
+    }
+    Ok(())
+}
+
+const GAME_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Bytes free for this user on the filesystem `path` is on, or would be on if it doesn't exist yet
+///
+/// Returns `None` if that can't be found out.
+#[must_use]
+pub fn available_space(path: impl AsRef<Path>) -> Option<u64> {
+    let path = path.as_ref().ancestors().find(|p| p.exists())?;
+    free_space(path)
+}
+
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is nul terminated and `stat` is only read once `statvfs` filled it in
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    // the field types differ between platforms
+    #[allow(clippy::useless_conversion)]
+    Some(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::{os::windows::ffi::OsStrExt, ptr};
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = path
+        .as_os_str()
+        .encode_wide()
+        .chain([0])
+        .collect::<Vec<_>>();
+    let mut available = 0;
+    // SAFETY: `path` is nul terminated, the totals we don't need may be null
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(available)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Longest path most Windows APIs accept without the `\\?\` prefix
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// `path` made absolute and given the `\\?\` prefix, which lifts Windows' `MAX_PATH` limit, if it and
+/// `extra` more characters would exceed it
+///
+/// Anywhere but Windows, or if it's short enough, `path` is returned as is. Paths joined onto the result must use
+/// `\` as their separator.
+#[cfg(windows)]
+pub(crate) fn long_path(path: &Path, extra: usize) -> PathBuf {
+    use std::{ffi::OsString, path::Prefix};
+
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.into();
+    };
+    if absolute.as_os_str().len() + extra < MAX_PATH {
+        return path.into();
+    }
+    let mut long = OsString::new();
+    match absolute.components().next() {
+        Some(Component::Prefix(p)) if matches!(p.kind(), Prefix::Disk(_)) => {
+            long.push(r"\\?\");
+            long.push(&absolute);
+        }
+        Some(Component::Prefix(p)) if matches!(p.kind(), Prefix::UNC(..)) => {
+            long.push(r"\\?\UNC\");
+            long.push(absolute.to_string_lossy().trim_start_matches('\\'));
+        }
+        // already verbatim, or a device
+        _ => return absolute,
+    }
+    long.into()
+}
+
+#[cfg(not(windows))]
+pub(crate) fn long_path(path: &Path, _extra: usize) -> PathBuf {
+    path.into()
+}
+
+/// Fails with `ThermiteError::PermissionDenied` if this process can't create files in `dir`, creating it if it
+/// doesn't exist
+pub(crate) fn check_writable(dir: &Path) -> Result<(), ThermiteError> {
+    let fs = vfs::current();
+    let denied = |e: std::io::Error| {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            ThermiteError::PermissionDenied(dir.into())
+        } else {
+            e.into()
+        }
+    };
+    fs.create_dir_all(dir).map_err(denied)?;
+    let probe = dir.join(format!("{STAGING_PREFIX}write-test"));
+    // installs running at the same time share the probe, so another one may have removed it already
+    fs.write(&probe, &[]).map_err(denied)?;
+    match fs.remove_file(&probe) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(denied(e)),
+        _ => Ok(()),
+    }
+}
+
+/// Fails with `ThermiteError::InsufficientSpace` if writing `required` bytes to `dir` wouldn't fit
+///
+/// Passes if the free space can't be found out
+pub(crate) fn check_space(dir: &Path, required: u64) -> Result<(), ThermiteError> {
+    match available_space(dir) {
+        Some(available) if available < required => Err(ThermiteError::InsufficientSpace {
+            required,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The version of Northstar installed in `game_dir`, read from the first core mod's `mod.json`
+///
+/// Returns `None` if none of the core mods are installed.
+///
+/// # Errors
+/// - IO errors
+/// - A core mod's `mod.json` isn't valid JSON
+pub fn northstar_version(game_dir: impl AsRef<Path>) -> Result<Option<String>, ThermiteError> {
+    let fs = vfs::current();
+    let game = &config::config().game;
+    let mods = game_dir.as_ref().join(&game.profile).join("mods");
+    for name in &game.core_mods {
+        let path = mods.join(name).join("mod.json");
+        if fs.exists(&path)? {
+            let mod_json: ModJSON = json5::from_str(&fs.read_to_string(&path)?)
+                .map_err(|e| ThermiteError::invalid_mod_json(&path, e))?;
+            return Ok(Some(mod_json.version));
+        }
+    }
+
+    debug!("No core mods in {}", mods.display());
+    Ok(None)
+}
+
+/// Lists the packages installed in `dir`, a profile's `packages` directory, with whether each of their mods is
+/// enabled in the profile's `enabledmods.json`
+///
+/// Core mods and mods installed by hand aren't listed. Mods missing from `enabledmods.json`, or every mod if
+/// the profile doesn't have one, are listed as enabled since that's how Northstar treats them.
+///
+/// # Errors
+/// - IO Errors
+/// - Improperly formatted JSON files
+pub fn export_mod_list(dir: impl AsRef<Path>) -> Result<ModList, ThermiteError> {
+    let config = config::config();
+    let dir = dir.as_ref();
+    let enabled = match dir.parent().map(get_enabled_mods) {
+        Some(Ok(enabled)) => Some(enabled),
+        Some(Err(ThermiteError::MissingFile(_))) | None => None,
+        Some(Err(e)) => return Err(e),
+    };
+
+    let mut list = ModList::default();
+    for m in find_mods(dir)? {
+        if package_dir(&m).is_none() || config.game.is_core_mod(&m.mod_json.name) {
+            continue;
+        }
+        let package = list
+            .packages
+            .entry(format!("{}-{}", m.author, m.manifest.name))
+            .or_insert_with(|| ListedPackage {
+                version: m.manifest.version_number.clone(),
+                mods: BTreeMap::new(),
+            });
+        let state = enabled
+            .as_ref()
+            .is_none_or(|e| e.is_enabled(&m.mod_json.name));
+        package.mods.insert(m.mod_json.name, state);
+    }
+
+    Ok(list)
+}
+
+/// The package directory an installed mod is in, `None` for mods installed by hand
+///
+/// Packages installed under another directory name, see [`InstallOptions`](super::manage::InstallOptions), are
+/// found by the `manifest.json` and author of the directory they were installed to.
+pub(crate) fn package_dir(m: &InstalledMod) -> Option<&Path> {
+    let modstring = format!(
+        "{}-{}-{}",
+        m.author, m.manifest.name, m.manifest.version_number
+    );
+    if let Some(dir) = m
+        .path
+        .ancestors()
+        .find(|p| p.file_name().is_some_and(|n| *n == *modstring))
+    {
+        return Some(dir);
+    }
+    if m.author.is_empty() {
+        return None;
+    }
+
+    let fs = vfs::current();
+    m.path.ancestors().find(|dir| {
+        let path = dir.join("manifest.json");
+        let Ok(raw) = fs.read_to_string(&path) else {
+            return false;
+        };
+        serde_json::from_str::<Manifest>(&raw).is_ok_and(|manifest| {
+            manifest.name == m.manifest.name && manifest.version_number == m.manifest.version_number
+        }) && package_author(dir).ok().flatten().as_deref() == Some(m.author.as_str())
+    })
+}
+
+/// Something that keeps installed mods from all working as intended, from [`detect_conflicts`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ModConflict {
+    /// The mods ship a file at the same path in their `mod` directory, so only one of them is used
+    ///
+    /// `path` is relative to the mod's directory, paths differing only in case overlap as well since the
+    /// game doesn't tell them apart
+    OverlappingFile { path: String, mods: Vec<String> },
+    /// More than one mod uses `name` in its `mod.json`
+    DuplicateName { name: String, paths: Vec<PathBuf> },
+    /// The mods all define the ConVar `name`
+    ConVar { name: String, mods: Vec<String> },
+}
+
+impl Display for ModConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OverlappingFile { path, mods } => {
+                write!(f, "{} all ship {path}", mods.join(", "))
+            }
+            Self::DuplicateName { name, paths } => {
+                write!(f, "{} mods are named {name}", paths.len())
+            }
+            Self::ConVar { name, mods } => {
+                write!(f, "{} all define the ConVar {name}", mods.join(", "))
+            }
+        }
+    }
+}
+
+/// Finds the ways the mods in `mods` get in each other's way, so they can be pointed out before the game is
+/// launched
+///
+/// Pass only the enabled mods, e.g. from [`find_mods`] filtered with [`get_enabled_mods`]. Conflicts are
+/// listed by kind, then by path or name.
+///
+/// # Errors
+/// - IO errors while listing the mods' files
+pub fn detect_conflicts(mods: &[InstalledMod]) -> Result<Vec<ModConflict>, ThermiteError> {
+    let fs = vfs::current();
+    let mut files: BTreeMap<String, (String, BTreeSet<&str>)> = BTreeMap::new();
+    let mut names: BTreeMap<&str, Vec<PathBuf>> = BTreeMap::new();
+    let mut con_vars: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+
+    for m in mods {
+        let name = m.mod_json.name.as_str();
+        names.entry(name).or_default().push(m.path.clone());
+        for con_var in &m.mod_json.con_vars {
+            if let Some(var) = con_var.get("Name").and_then(|n| n.as_str()) {
+                con_vars.entry(var).or_default().insert(name);
+            }
+        }
+
+        let mut dirs = vec![m.path.join("mod")];
+        while let Some(dir) = dirs.pop() {
+            if !fs.is_dir(&dir) {
+                continue;
+            }
+            for entry in fs.read_dir(&dir)? {
+                if entry.is_dir {
+                    dirs.push(entry.path);
+                    continue;
+                }
+                let Ok(rel) = entry.path.strip_prefix(&m.path) else {
+                    continue;
+                };
+                let rel = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files
+                    .entry(rel.to_lowercase())
+                    .or_insert_with(|| (rel, BTreeSet::new()))
+                    .1
+                    .insert(name);
+            }
+        }
+    }
+
+    let owners = |mods: BTreeSet<&str>| mods.into_iter().map(ToOwned::to_owned).collect();
+    let mut conflicts = files
+        .into_values()
+        .filter(|(_, mods)| mods.len() > 1)
+        .map(|(path, mods)| ModConflict::OverlappingFile {
+            path,
+            mods: owners(mods),
+        })
+        .collect::<Vec<_>>();
+    conflicts.extend(names.into_iter().filter(|(_, paths)| paths.len() > 1).map(
+        |(name, paths)| ModConflict::DuplicateName {
+            name: name.to_owned(),
+            paths,
+        },
+    ));
+    conflicts.extend(con_vars.into_iter().filter(|(_, mods)| mods.len() > 1).map(
+        |(name, mods)| ModConflict::ConVar {
+            name: name.to_owned(),
+            mods: owners(mods),
+        },
+    ));
+    Ok(conflicts)
+}
+
+/// Compares version numbers the way [`VersionKey`] orders them
+pub(crate) fn is_newer(candidate: &str, current: &str) -> bool {
+    VersionKey::compare(candidate, current).is_gt()
+}
+
+#[cfg(feature = "steam")]
+pub(crate) mod steam {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+    use steamlocate::SteamDir;
+    use tracing::debug;
+
+    use crate::config;
+
+    /// Other places Steam is installed on Linux, relative to the home directory
+    #[cfg(target_os = "linux")]
+    const LINUX_STEAM_DIRS: [&str; 6] = [
+        ".steam/steam",
+        ".local/share/Steam",
+        // Flatpak
+        ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+        ".var/app/com.valvesoftware.Steam/.steam/steam",
+        // Snap
+        "snap/steam/common/.local/share/Steam",
+        "snap/steam/common/.steam/steam",
+    ];
+
+    /// Returns the path to the Steam installation if it exists
+    #[must_use]
+    #[inline]
+    pub fn steam_dir() -> Option<PathBuf> {
+        steam_dirs().into_iter().next()
+    }
+
+    /// Returns the paths to every Steam installation found, including Flatpak and Snap installs on Linux
+    #[must_use]
+    pub fn steam_dirs() -> Vec<PathBuf> {
+        #[allow(unused_mut)]
+        let mut candidates: Vec<PathBuf> = SteamDir::locate().map(|v| v.path).into_iter().collect();
+        #[cfg(target_os = "linux")]
+        if let Some(home) = std::env::var_os("HOME") {
+            candidates.extend(LINUX_STEAM_DIRS.iter().map(|d| Path::new(&home).join(d)));
+        }
+
+        let mut found = vec![];
+        let mut seen = vec![];
+        for dir in candidates {
+            // the default locations are usually symlinks to the same install
+            let Ok(real) = fs::canonicalize(&dir) else {
+                continue;
+            };
+            if real.join("steamapps").is_dir() && !seen.contains(&real) {
+                debug!("Found Steam in {}", dir.display());
+                seen.push(real);
+                found.push(dir);
+            }
+        }
+        found
+    }
+
+    /// Returns paths to all known Steam libraries, of every Steam installation
+    #[must_use]
+    pub fn steam_libraries() -> Option<Vec<PathBuf>> {
+        let mut libraries = vec![];
+        for dir in steam_dirs() {
+            for library in library_folders(&dir) {
+                if !libraries.contains(&library) {
+                    libraries.push(library);
+                }
+            }
+        }
+        (!libraries.is_empty()).then_some(libraries)
+    }
+
+    /// Returns the path to the Titanfall installation, or the global config's game, if it exists
+    ///
+    /// Checks the libraries of every Steam installation [`steam_dirs`] finds.
+    #[must_use]
+    pub fn titanfall() -> Option<PathBuf> {
+        let id = config::config().game.steam_id;
+        if let Some(path) = SteamDir::locate().and_then(|mut s| Some(s.app(&id)?.path.clone())) {
+            return Some(path);
+        }
+        steam_dirs()
+            .iter()
+            .flat_map(|dir| library_folders(dir))
+            .find_map(|library| find_app(&library, id))
+    }
+
+    /// The `steamapps` directories of Steam's libraries, from its `libraryfolders.vdf`
+    fn library_folders(steam_dir: &Path) -> Vec<PathBuf> {
+        let steamapps = steam_dir.join("steamapps");
+        let mut folders = vec![steamapps.clone()];
+        if let Ok(vdf) = fs::read_to_string(steamapps.join("libraryfolders.vdf")) {
+            for path in vdf_values(&vdf, "path") {
+                let library = PathBuf::from(path).join("steamapps");
+                if !folders.contains(&library) {
+                    folders.push(library);
+                }
+            }
+        }
+        folders
+    }
+
+    /// Where the app `id` is installed in a library's `steamapps` directory, from its app manifest
+    fn find_app(steamapps: &Path, id: u32) -> Option<PathBuf> {
+        let manifest = fs::read_to_string(steamapps.join(format!("appmanifest_{id}.acf"))).ok()?;
+        let dir = vdf_values(&manifest, "installdir").next()?;
+        Some(steamapps.join("common").join(dir)).filter(|p| p.is_dir())
+    }
+
+    /// The values of every `"key" "value"` pair called `key` in a VDF file
+    fn vdf_values<'a>(vdf: &'a str, key: &'a str) -> impl Iterator<Item = String> + 'a {
+        vdf.lines().filter_map(move |line| {
+            let mut parts = line.split('"').filter(|p| !p.trim().is_empty());
+            if !parts.next()?.eq_ignore_ascii_case(key) {
+                return None;
+            }
+            parts.next().map(|v| v.replace(r"\\", r"\"))
+        })
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::fs;
+
+        use crate::core::utils::TempDir;
+
+        use super::{find_app, library_folders};
+
+        #[test]
+        fn find_app_in_libraries() {
+            let dir = TempDir::create("./test_steam_libraries").expect("Unable to create temp dir");
+            let steam = dir.join("Steam");
+            let library = dir.join("Games");
+            fs::create_dir_all(steam.join("steamapps")).unwrap();
+            fs::create_dir_all(library.join("steamapps/common/Titanfall2")).unwrap();
+            fs::write(
+                steam.join("steamapps/libraryfolders.vdf"),
+                format!(
+                    "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                    library.display()
+                ),
+            )
+            .unwrap();
+            fs::write(
+                library.join("steamapps/appmanifest_1237970.acf"),
+                "\"AppState\"\n{\n\t\"appid\"\t\t\"1237970\"\n\t\"installdir\"\t\t\"Titanfall2\"\n}\n",
+            )
+            .unwrap();
+
+            let folders = library_folders(&steam);
+            assert_eq!(
+                folders,
+                [steam.join("steamapps"), library.join("steamapps")]
+            );
+            assert_eq!(
+                folders.iter().find_map(|f| find_app(f, 1_237_970)),
+                Some(library.join("steamapps/common/Titanfall2"))
+            );
+            assert_eq!(folders.iter().find_map(|f| find_app(f, 1)), None);
+        }
+    }
+}
+
+/// Finding copies of the game installed through the EA App or Origin
+#[cfg(all(target_os = "windows", feature = "ea"))]
+pub mod ea {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process::Command,
+    };
+    use tracing::debug;
+
+    use crate::config;
+
+    /// Where the EA App and Origin record the game's install directory
+    const REGISTRY_KEYS: [&str; 2] = [
+        r"HKLM\SOFTWARE\Respawn\Titanfall2",
+        r"HKLM\SOFTWARE\WOW6432Node\Respawn\Titanfall2",
+    ];
+    /// Default install locations, relative to the program files directories
+    const DEFAULT_DIRS: [&str; 2] = [r"EA Games\Titanfall2", r"Origin Games\Titanfall2"];
+
+    /// Returns the path to the Titanfall installation, if the EA App or Origin installed one
+    ///
+    /// Looks at the install directory in the registry, Origin's install manifests for the global config's
+    /// `origin_ids`, and finally the default install locations.
+    #[must_use]
+    pub fn titanfall() -> Option<PathBuf> {
+        REGISTRY_KEYS
+            .iter()
+            .find_map(|key| registry_install_dir(key))
+            .or_else(origin_manifest_dir)
+            .or_else(default_dir)
+    }
+
+    fn registry_install_dir(key: &str) -> Option<PathBuf> {
+        let output = Command::new("reg")
+            .args(["query", key, "/v", "Install Dir"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            debug!("{key} isn't in the registry");
+            return None;
+        }
+        parse_reg_value(&String::from_utf8_lossy(&output.stdout))
+            .map(PathBuf::from)
+            .filter(|p| p.is_dir())
+    }
+
+    /// The data of a `REG_SZ` value in the output of `reg query`
+    fn parse_reg_value(output: &str) -> Option<&str> {
+        output
+            .lines()
+            .find_map(|line| line.split_once("REG_SZ"))
+            .map(|(_, value)| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn origin_manifest_dir() -> Option<PathBuf> {
+        let program_data = env::var_os("PROGRAMDATA")
+            .map_or_else(|| PathBuf::from(r"C:\ProgramData"), PathBuf::from);
+        let ids = &config::config().game.origin_ids;
+        for game in fs::read_dir(program_data.join(r"Origin\LocalContent")).ok()? {
+            let Ok(files) = fs::read_dir(game.ok()?.path()) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().is_none_or(|e| e != "mfst") {
+                    continue;
+                }
+                let Ok(manifest) = fs::read_to_string(&path) else {
+                    continue;
+                };
+                if let Some(dir) = parse_manifest(&manifest, ids).filter(|p| p.is_dir()) {
+                    debug!("Found {} in {}", dir.display(), path.display());
+                    return Some(dir);
+                }
+            }
+        }
+        None
+    }
+
+    /// The install path in an Origin `.mfst` manifest, if it's for one of `ids`
+    ///
+    /// Manifests are a URL query string, e.g. `?id=Origin.OFR.50.0001452&dipinstallpath=C%3a%5cGames%5c`
+    fn parse_manifest(manifest: &str, ids: &[String]) -> Option<PathBuf> {
+        let mut id = None;
+        let mut install_path = None;
+        for (key, value) in manifest
+            .trim()
+            .trim_start_matches('?')
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+        {
+            match key.to_ascii_lowercase().as_str() {
+                "id" => id = Some(value),
+                "dipinstallpath" => install_path = Some(value),
+                _ => {}
+            }
+        }
+
+        // ids can have a suffix for the store it was bought from, e.g. `@steam`
+        let id = percent_decode(id?)?;
+        let id = id.split('@').next()?;
+        if !ids.iter().any(|i| i.eq_ignore_ascii_case(id)) {
+            return None;
+        }
+        percent_decode(install_path?).map(PathBuf::from)
+    }
+
+    fn percent_decode(value: &str) -> Option<String> {
+        let bytes = value.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'%' => {
+                    let hex = value.get(i + 1..i + 3)?;
+                    decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                    i += 3;
+                }
+                b'+' => {
+                    decoded.push(b' ');
+                    i += 1;
+                }
+                b => {
+                    decoded.push(b);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8(decoded).ok()
+    }
+
+    fn default_dir() -> Option<PathBuf> {
+        ["ProgramFiles", "ProgramFiles(x86)"]
+            .iter()
+            .filter_map(env::var_os)
+            .flat_map(|root| DEFAULT_DIRS.map(|dir| Path::new(&root).join(dir)))
+            .find(|p| p.is_dir())
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::path::PathBuf;
+
+        use crate::TITANFALL2_ORIGIN_IDS;
+
+        use super::{parse_manifest, parse_reg_value};
+
+        #[test]
+        fn parse_registry_output() {
+            let output = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Respawn\\Titanfall2\r\n    Install Dir    REG_SZ    C:\\Games\\Titanfall2\\\r\n\r\n";
+            assert_eq!(parse_reg_value(output), Some("C:\\Games\\Titanfall2\\"));
+            assert_eq!(parse_reg_value("ERROR: not found"), None);
+        }
+
+        #[test]
+        fn parse_origin_manifest() {
+            let ids = TITANFALL2_ORIGIN_IDS.map(Into::into);
+            let manifest = "?currentstate=kReadyToStart&id=Origin.OFR.50.0001452%40steam&dipinstallpath=C%3a%5cOrigin%20Games%5cTitanfall2%5c";
+            assert_eq!(
+                parse_manifest(manifest, &ids),
+                Some(PathBuf::from("C:\\Origin Games\\Titanfall2\\"))
+            );
+            assert_eq!(
+                parse_manifest("?id=Origin.OFR.50.1&dipinstallpath=C%3a", &ids),
+                None
+            );
+        }
+    }
+}
+
+/// Finding copies of the game installed into Wine prefixes by Lutris, Heroic or Bottles
+#[cfg(target_os = "linux")]
+pub mod wine {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+    };
+    use tracing::debug;
+
+    /// The launcher that manages a Wine prefix
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum PrefixOwner {
+        Lutris,
+        Heroic,
+        Bottles,
+    }
+
+    /// A copy of the game in a Wine prefix
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct WineInstall {
+        pub game_dir: PathBuf,
+        /// The prefix, the directory holding `drive_c`
+        pub prefix: PathBuf,
+        pub owner: PrefixOwner,
+    }
+
+    /// Directories every child of which may be a prefix, relative to the home directory
+    const PREFIX_ROOTS: [(&str, PrefixOwner); 5] = [
+        ("Games/Heroic/Prefixes", PrefixOwner::Heroic),
+        ("Games/Heroic/Prefixes/default", PrefixOwner::Heroic),
+        (".local/share/bottles/bottles", PrefixOwner::Bottles),
+        // Flatpak
+        (
+            ".var/app/com.usebottles.bottles/data/bottles/bottles",
+            PrefixOwner::Bottles,
+        ),
+        ("Games", PrefixOwner::Lutris),
+    ];
+    /// Lutris' game configs, which name prefixes outside of its default directory
+    const LUTRIS_GAMES: &str = ".config/lutris/games";
+    /// Default install locations of the EA App and Origin, relative to `drive_c`
+    const DEFAULT_DIRS: [&str; 4] = [
+        "Program Files/EA Games/Titanfall2",
+        "Program Files (x86)/EA Games/Titanfall2",
+        "Program Files/Origin Games/Titanfall2",
+        "Program Files (x86)/Origin Games/Titanfall2",
+    ];
+
+    /// Returns every copy of the game found in the Lutris, Heroic and Bottles prefixes of the current user
+    ///
+    /// Only the default EA App and Origin install locations in each prefix are checked.
+    #[must_use]
+    pub fn titanfall_installs() -> Vec<WineInstall> {
+        env::var_os("HOME").map_or_else(Vec::new, |home| installs_in(Path::new(&home)))
+    }
+
+    fn installs_in(home: &Path) -> Vec<WineInstall> {
+        let mut prefixes = lutris_prefixes(&home.join(LUTRIS_GAMES));
+        for (root, owner) in PREFIX_ROOTS {
+            let Ok(entries) = fs::read_dir(home.join(root)) else {
+                continue;
+            };
+            prefixes.extend(entries.flatten().map(|e| (e.path(), owner)));
+        }
+
+        let mut installs: Vec<WineInstall> = vec![];
+        for (prefix, owner) in prefixes {
+            if installs.iter().any(|i| i.prefix == prefix) {
+                continue;
+            }
+            let drive_c = prefix.join("drive_c");
+            let Some(game_dir) = DEFAULT_DIRS
+                .iter()
+                .map(|dir| drive_c.join(dir))
+                .find(|dir| dir.join("Titanfall2.exe").is_file())
+            else {
+                continue;
+            };
+            debug!("Found the game in {}", prefix.display());
+            installs.push(WineInstall {
+                game_dir,
+                prefix,
+                owner,
+            });
+        }
+        installs
+    }
+
+    /// The `prefix` of every Lutris game config in `dir`
+    fn lutris_prefixes(dir: &Path) -> Vec<(PathBuf, PrefixOwner)> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return vec![];
+        };
+        entries
+            .flatten()
+            .filter_map(|e| fs::read_to_string(e.path()).ok())
+            .filter_map(|config| {
+                config.lines().find_map(|line| {
+                    let value = line.trim().strip_prefix("prefix:")?;
+                    Some(PathBuf::from(value.trim().trim_matches(['"', '\''])))
+                })
+            })
+            .map(|prefix| (prefix, PrefixOwner::Lutris))
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::fs;
+
+        use crate::core::utils::TempDir;
+
+        use super::{installs_in, PrefixOwner};
+
+        #[test]
+        fn find_in_prefixes() {
+            let home = TempDir::create("./test_wine_prefixes").expect("Unable to create temp dir");
+            let lutris = home.join("Games/titanfall-2");
+            let bottles = home.join(".local/share/bottles/bottles/EA");
+            let custom = home.join("prefixes/tf2");
+            for (prefix, dir) in [
+                (&lutris, "Program Files/EA Games/Titanfall2"),
+                (&bottles, "Program Files (x86)/Origin Games/Titanfall2"),
+                (&custom, "Program Files/EA Games/Titanfall2"),
+            ] {
+                let game = prefix.join("drive_c").join(dir);
+                fs::create_dir_all(&game).unwrap();
+                fs::write(game.join("Titanfall2.exe"), "").unwrap();
+            }
+            fs::create_dir_all(home.join("Games/Heroic/Prefixes/empty/drive_c")).unwrap();
+            fs::create_dir_all(home.join(".config/lutris/games")).unwrap();
+            fs::write(
+                home.join(".config/lutris/games/titanfall-2.yml"),
+                format!(
+                    "game:\n  exe: Titanfall2.exe\n  prefix: {}\n",
+                    custom.display()
+                ),
+            )
+            .unwrap();
+
+            let mut installs = installs_in(&home);
+            installs.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+            assert_eq!(installs.len(), 3);
+            assert_eq!(installs[0].prefix, bottles);
+            assert_eq!(installs[0].owner, PrefixOwner::Bottles);
+            assert_eq!(
+                installs[0].game_dir,
+                bottles.join("drive_c/Program Files (x86)/Origin Games/Titanfall2")
+            );
+            assert_eq!(installs[1].prefix, lutris);
+            assert_eq!(installs[1].owner, PrefixOwner::Lutris);
+            assert_eq!(installs[2].prefix, custom);
+            assert_eq!(installs[2].owner, PrefixOwner::Lutris);
+        }
     }
 }
 
@@ -305,37 +2327,36 @@ pub(crate) mod steam {
 //#[deprecated(since = "0.8.0", note = "Northstar Proton is no longer required")]
 pub(crate) mod proton {
     use flate2::read::GzDecoder;
-    use std::{io::{Read, Write}, path::Path};
+    use std::{
+        fs,
+        io::{ErrorKind, Read, Write},
+        ops::Range,
+        path::{Path, PathBuf},
+    };
     use tar::Archive;
     use tracing::debug;
 
     use crate::{
-        core::manage::download,
+        config,
+        core::{github, manage::download, report::DownloadReport, vfs},
         error::{Result, ThermiteError},
     };
     const BASE_URL: &str = "https://github.com/R2NorthstarTools/NorthstarProton/releases/";
+    const OWNER: &str = "R2NorthstarTools";
+    const REPO: &str = "NorthstarProton";
 
     /// Returns the latest tag from the NorthstarProton repo
     ///
     /// # Errors
     /// * Network error
-    /// * Unexpected URL format
+    /// * Unexpected response format from GitHub
     pub fn latest_release() -> Result<String> {
-        let url = format!("{}latest", BASE_URL);
-        let res = ureq::get(&url).call()?;
-        let location = res.get_url();
-        debug!("{url} redirected to {location}");
-
-        Ok(location
-            .split('/')
-            .last()
-            .ok_or_else(|| ThermiteError::UnknownError("Malformed location URL".into()))?
-            .to_owned())
+        Ok(github::latest_release(OWNER, REPO)?.tag)
     }
 
     /// Convinience function for downloading a given tag from the NorthstarProton repo.
     /// If you have a URL already, just use `thermite::manage::download`
-    pub fn download_ns_proton(tag: impl AsRef<str>, output: impl Write) -> Result<u64> {
+    pub fn download_ns_proton(tag: impl AsRef<str>, output: impl Write) -> Result<DownloadReport> {
         let url = format!(
             "{}download/{}/NorthstarProton{}.tar.gz",
             BASE_URL,
@@ -347,7 +2368,7 @@ pub(crate) mod proton {
 
     /// Extract the NorthstarProton tarball into a given directory.
     /// Only supports extracting to a filesystem path.
-    /// 
+    ///
     /// # Errors
     /// * IO errors
     pub fn install_ns_proton(archive: impl Read, dest: impl AsRef<Path>) -> Result<()> {
@@ -357,25 +2378,388 @@ pub(crate) mod proton {
         Ok(())
     }
 
+    /// NorthstarProton release directories are named this followed by the version, e.g. `NorthstarProton8-28`
+    const DIR_PREFIX: &str = "NorthstarProton";
+
+    /// A NorthstarProton release extracted into Steam's `compatibilitytools.d`
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ProtonInstall {
+        /// The version from the directory name, e.g. `8-28`
+        pub version: String,
+        pub path: PathBuf,
+    }
+
+    impl ProtonInstall {
+        /// The numbers in the version, for comparing versions
+        fn version_key(&self) -> Vec<u64> {
+            self.version
+                .split(|c: char| !c.is_ascii_digit())
+                .filter_map(|n| n.parse().ok())
+                .collect()
+        }
+    }
+
+    /// Where Steam looks for extra compatibility tools like NorthstarProton
+    #[must_use]
+    pub fn compatibility_tools_dir(steam_dir: impl AsRef<Path>) -> PathBuf {
+        steam_dir.as_ref().join("compatibilitytools.d")
+    }
+
+    /// Every NorthstarProton version installed in `tools_dir`, oldest first
+    ///
+    /// # Errors
+    /// * IO errors, except for `tools_dir` not existing
+    pub fn installed_versions(tools_dir: impl AsRef<Path>) -> Result<Vec<ProtonInstall>> {
+        let entries = match fs::read_dir(tools_dir.as_ref()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut installs = vec![];
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            let Some(version) = name.to_str().and_then(|n| n.strip_prefix(DIR_PREFIX)) else {
+                continue;
+            };
+            installs.push(ProtonInstall {
+                version: version.into(),
+                path: entry.path(),
+            });
+        }
+        installs.sort_by_cached_key(ProtonInstall::version_key);
+        Ok(installs)
+    }
+
+    /// The newest NorthstarProton version installed in `tools_dir`
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn newest_version(tools_dir: impl AsRef<Path>) -> Result<Option<ProtonInstall>> {
+        Ok(installed_versions(tools_dir)?.pop())
+    }
+
+    /// Removes all but the `keep` newest NorthstarProton versions from `tools_dir`, returning the removed ones
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn remove_old_versions(
+        tools_dir: impl AsRef<Path>,
+        keep: usize,
+    ) -> Result<Vec<ProtonInstall>> {
+        let mut installs = installed_versions(tools_dir)?;
+        let old = installs.len().saturating_sub(keep);
+        installs.truncate(old);
+        for install in &installs {
+            debug!("Removing NorthstarProton {}", install.version);
+            fs::remove_dir_all(&install.path)?;
+        }
+        Ok(installs)
+    }
+
+    /// Launch options that make Proton load Northstar's `wsock32.dll` proxy instead of Wine's own
+    pub const LAUNCH_OPTIONS: &str = r#"WINEDLLOVERRIDES="wsock32=n,b" %command%"#;
+
+    /// The state of a Proton prefix, see [`configure_prefix`]
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct PrefixStatus {
+        /// The prefix didn't exist and its directories were created
+        pub created: bool,
+        /// Proton has set the prefix up, which it does the first time the game is started with it
+        pub initialized: bool,
+    }
+
+    /// The game's compatdata directory in a Steam library's `steamapps` directory, where Proton keeps its prefix
+    #[must_use]
+    pub fn compat_data_dir(steamapps: impl AsRef<Path>) -> PathBuf {
+        steamapps
+            .as_ref()
+            .join("compatdata")
+            .join(config::config().game.steam_id.to_string())
+    }
+
+    /// Creates the layout of the Proton prefix in `compat_data_dir` if it's missing and checks whether Proton has
+    /// initialized it
+    ///
+    /// An uninitialized prefix is filled in by Proton the next time the game is started through Steam.
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn configure_prefix(compat_data_dir: impl AsRef<Path>) -> Result<PrefixStatus> {
+        let dir = compat_data_dir.as_ref();
+        let prefix = dir.join("pfx");
+        let created = !prefix.is_dir();
+        if created {
+            debug!("Creating Proton prefix in {}", prefix.display());
+            fs::create_dir_all(&prefix)?;
+        }
+
+        Ok(PrefixStatus {
+            created,
+            initialized: dir.join("version").is_file()
+                && prefix.join("drive_c/windows/system32").is_dir(),
+        })
+    }
+
+    /// The `localconfig.vdf` of every Steam user that has logged in to the Steam installation in `steam_dir`
+    #[must_use]
+    pub fn local_configs(steam_dir: impl AsRef<Path>) -> Vec<PathBuf> {
+        let Ok(users) = fs::read_dir(steam_dir.as_ref().join("userdata")) else {
+            return vec![];
+        };
+        users
+            .flatten()
+            .map(|user| user.path().join("config").join("localconfig.vdf"))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Sets the game's launch options in a Steam user's `localconfig.vdf`, e.g. to [`LAUNCH_OPTIONS`]
+    ///
+    /// The file is copied to `localconfig.vdf.thermite-backup` first. Steam rewrites it when it exits, so it
+    /// has to be closed for the change to stick.
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * `ThermiteError::InvalidVdf` if the file has no `apps` section
+    pub fn set_launch_options(
+        localconfig: impl AsRef<Path>,
+        options: impl AsRef<str>,
+    ) -> Result<()> {
+        let path = localconfig.as_ref();
+        let fs = vfs::current();
+        let vdf = fs.read_to_string(path)?;
+        let updated = with_launch_options(&vdf, config::config().game.steam_id, options.as_ref())?;
+        backup_vdf(path, &vdf)?;
+        fs.write_atomic(path, updated.as_bytes())?;
+        Ok(())
+    }
+
+    /// Steam's `config.vdf` in `steam_dir`, with the settings shared by every user like compatibility tools
+    #[must_use]
+    pub fn steam_config(steam_dir: impl AsRef<Path>) -> PathBuf {
+        steam_dir.as_ref().join("config").join("config.vdf")
+    }
+
+    /// Makes Steam run the game with the compatibility tool `tool` by editing its `config.vdf`, see
+    /// [`steam_config`]
+    ///
+    /// `tool` is the tool's internal name, which for NorthstarProton is its directory name, see
+    /// [`use_ns_proton`]. Like [`set_launch_options`], the file is backed up first and Steam has to be closed.
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * `ThermiteError::InvalidVdf` if the file has no `CompatToolMapping` section
+    pub fn set_compat_tool(config_vdf: impl AsRef<Path>, tool: impl AsRef<str>) -> Result<()> {
+        let path = config_vdf.as_ref();
+        let fs = vfs::current();
+        let vdf = fs.read_to_string(path)?;
+        let updated = with_compat_tool(&vdf, config::config().game.steam_id, tool.as_ref())?;
+        backup_vdf(path, &vdf)?;
+        fs.write_atomic(path, updated.as_bytes())?;
+        Ok(())
+    }
+
+    /// Finishes setting up NorthstarProton in `steam_dir`: selects `install` as the game's compatibility tool and
+    /// sets [`LAUNCH_OPTIONS`] for every Steam user
+    ///
+    /// # Errors
+    /// * The same as [`set_compat_tool`] and [`set_launch_options`]
+    pub fn use_ns_proton(steam_dir: impl AsRef<Path>, install: &ProtonInstall) -> Result<()> {
+        let steam_dir = steam_dir.as_ref();
+        set_compat_tool(
+            steam_config(steam_dir),
+            format!("{DIR_PREFIX}{}", install.version),
+        )?;
+        for localconfig in local_configs(steam_dir) {
+            set_launch_options(localconfig, LAUNCH_OPTIONS)?;
+        }
+        Ok(())
+    }
+
+    /// Copies `vdf`, the contents of `path`, next to it before it's changed
+    fn backup_vdf(path: &Path, vdf: &str) -> Result<()> {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".thermite-backup");
+        debug!("Backing up {}", path.display());
+        vfs::current().write_atomic(Path::new(&backup), vdf.as_bytes())?;
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    enum Token {
+        Str(String),
+        Open,
+        Close,
+    }
+
+    /// The tokens of a VDF file with their byte ranges
+    fn tokenize(vdf: &str) -> Vec<(Token, Range<usize>)> {
+        let mut tokens = vec![];
+        let mut chars = vdf.char_indices();
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '{' => tokens.push((Token::Open, start..start + 1)),
+                '}' => tokens.push((Token::Close, start..start + 1)),
+                '"' => {
+                    let mut value = String::new();
+                    let mut end = vdf.len();
+                    while let Some((i, c)) = chars.next() {
+                        match c {
+                            '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                            '"' => {
+                                end = i + 1;
+                                break;
+                            }
+                            c => value.push(c),
+                        }
+                    }
+                    tokens.push((Token::Str(value), start..end));
+                }
+                _ => {}
+            }
+        }
+        tokens
+    }
+
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+    }
+
+    /// The indentation of the line `pos` is on, if there's only whitespace before it
+    fn indent_at(vdf: &str, pos: usize) -> Option<(usize, &str)> {
+        let line_start = vdf[..pos].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &vdf[line_start..pos];
+        indent.trim().is_empty().then_some((line_start, indent))
+    }
+
+    /// `vdf` with `app_id`'s `LaunchOptions` set to `options`
+    fn with_launch_options(vdf: &str, app_id: u32, options: &str) -> Result<String> {
+        with_app_value(
+            vdf,
+            &["software", "valve", "steam", "apps"],
+            app_id,
+            "LaunchOptions",
+            options,
+        )
+    }
+
+    /// `vdf` with `app_id` mapped to the compatibility tool `tool`
+    fn with_compat_tool(vdf: &str, app_id: u32, tool: &str) -> Result<String> {
+        const SECTION: [&str; 4] = ["software", "valve", "steam", "compattoolmapping"];
+        let vdf = with_app_value(vdf, &SECTION, app_id, "name", tool)?;
+        let vdf = with_app_value(&vdf, &SECTION, app_id, "config", "")?;
+        with_app_value(&vdf, &SECTION, app_id, "priority", "250")
+    }
+
+    /// `vdf` with `key` set to `value` in `app_id`'s block in `section`, the path of keys leading to it
+    fn with_app_value(
+        vdf: &str,
+        section: &[&str],
+        app_id: u32,
+        key: &str,
+        value: &str,
+    ) -> Result<String> {
+        let app_id = app_id.to_string();
+        let in_apps = |path: &[String]| {
+            path.len() >= section.len()
+                && path[path.len() - section.len()..]
+                    .iter()
+                    .zip(section)
+                    .all(|(a, b)| a.eq_ignore_ascii_case(b))
+        };
+
+        let tokens = tokenize(vdf);
+        let mut path: Vec<String> = vec![];
+        let mut apps_open = None;
+        let mut app_close = None;
+        let mut existing = None;
+        let mut i = 0;
+        while i < tokens.len() {
+            match (&tokens[i].0, tokens.get(i + 1).map(|t| &t.0)) {
+                (Token::Str(name), Some(Token::Open)) => {
+                    path.push(name.clone());
+                    if in_apps(&path) {
+                        apps_open = Some(tokens[i + 1].1.end);
+                    }
+                    i += 2;
+                    continue;
+                }
+                (Token::Str(k), Some(Token::Str(_))) => {
+                    let in_app = path.last() == Some(&app_id) && in_apps(&path[..path.len() - 1]);
+                    if in_app && k.eq_ignore_ascii_case(key) {
+                        existing = Some(tokens[i + 1].1.clone());
+                    }
+                    i += 2;
+                    continue;
+                }
+                (Token::Close, _) => {
+                    if path.last() == Some(&app_id) && in_apps(&path[..path.len() - 1]) {
+                        app_close = Some(tokens[i].1.start);
+                    }
+                    path.pop();
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        let mut updated = vdf.to_owned();
+        let entry = |indent: &str| format!("{indent}{}\t\t{}\n", quote(key), quote(value));
+        if let Some(range) = existing {
+            updated.replace_range(range, &quote(value));
+        } else if let Some(pos) = app_close {
+            match indent_at(vdf, pos) {
+                Some((line_start, indent)) => {
+                    updated.insert_str(line_start, &entry(&format!("{indent}\t")));
+                }
+                None => updated.insert_str(pos, &entry("")),
+            }
+        } else if let Some(pos) = apps_open {
+            let indent = indent_at(vdf, pos - 1).map_or("", |(_, indent)| indent);
+            updated.insert_str(
+                pos,
+                &format!(
+                    "\n{indent}\t{}\n{indent}\t{{\n{}{indent}\t}}",
+                    quote(&app_id),
+                    entry(&format!("{indent}\t\t"))
+                ),
+            );
+        } else {
+            return Err(ThermiteError::InvalidVdf(format!(
+                "no {} section",
+                section.last().unwrap_or(&"")
+            )));
+        }
+        Ok(updated)
+    }
+
     #[cfg(test)]
     mod test {
         use std::io::Cursor;
 
         use crate::core::utils::TempDir;
 
-        use super::latest_release;
-
+        use super::{
+            compat_data_dir, compatibility_tools_dir, configure_prefix, installed_versions,
+            latest_release, newest_version, remove_old_versions, set_compat_tool, with_compat_tool,
+            with_launch_options, PrefixStatus, LAUNCH_OPTIONS,
+        };
 
         #[test]
         fn get_latest_proton_version() {
             let res = latest_release();
             assert!(res.is_ok());
-            
         }
 
         #[test]
         fn extract_proton() {
-            let dir = TempDir::create(std::env::temp_dir().join("NSPROTON_TEST")).expect("temp dir");
+            let dir =
+                TempDir::create(std::env::temp_dir().join("NSPROTON_TEST")).expect("temp dir");
             let archive = include_bytes!("test_media/NorthstarProton8-28.tar.gz");
             let cursor = Cursor::new(archive);
             let res = super::install_ns_proton(cursor, &dir);
@@ -383,7 +2767,107 @@ pub(crate) mod proton {
 
             let extracted = dir.join("NorthstarProton8-28.txt");
             assert!(extracted.exists());
-            assert_eq!(std::fs::read_to_string(extracted).expect("read file"), "The real proton was too big to use as test media\n");
+            assert_eq!(
+                std::fs::read_to_string(extracted).expect("read file"),
+                "The real proton was too big to use as test media\n"
+            );
+        }
+
+        const LOCALCONFIG: &str = "\"UserLocalConfigStore\"\n{\n\t\"Software\"\n\t{\n\t\t\"Valve\"\n\t\t{\n\t\t\t\"Steam\"\n\t\t\t{\n\t\t\t\t\"apps\"\n\t\t\t\t{\n\t\t\t\t\t\"620\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"LastPlayed\"\t\t\"1700000000\"\n\t\t\t\t\t}\n\t\t\t\t}\n\t\t\t}\n\t\t}\n\t}\n}\n";
+
+        #[test]
+        fn edit_launch_options() {
+            let added =
+                with_launch_options(LOCALCONFIG, 1_237_970, LAUNCH_OPTIONS).expect("add app");
+            assert!(added.contains(
+                "\t\t\t\t\t\"1237970\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"LaunchOptions\"\t\t\"WINEDLLOVERRIDES=\\\"wsock32=n,b\\\" %command%\"\n\t\t\t\t\t}"
+            ));
+
+            let inserted = with_launch_options(LOCALCONFIG, 620, "-novid").expect("insert option");
+            assert!(inserted.contains("\"LastPlayed\"\t\t\"1700000000\"\n\t\t\t\t\t\t\"LaunchOptions\"\t\t\"-novid\"\n\t\t\t\t\t}"));
+
+            let replaced = with_launch_options(&inserted, 620, "-dev").expect("replace option");
+            assert_eq!(replaced, inserted.replace("-novid", "-dev"));
+
+            assert!(with_launch_options("\"UserLocalConfigStore\"\n{\n}\n", 620, "-dev").is_err());
+        }
+
+        const CONFIG: &str = "\"InstallConfigStore\"\n{\n\t\"Software\"\n\t{\n\t\t\"Valve\"\n\t\t{\n\t\t\t\"Steam\"\n\t\t\t{\n\t\t\t\t\"CompatToolMapping\"\n\t\t\t\t{\n\t\t\t\t\t\"1237970\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"proton_8\"\n\t\t\t\t\t\t\"config\"\t\t\"\"\n\t\t\t\t\t\t\"priority\"\t\t\"250\"\n\t\t\t\t\t}\n\t\t\t\t}\n\t\t\t}\n\t\t}\n\t}\n}\n";
+
+        #[test]
+        fn select_compat_tool() {
+            let replaced =
+                with_compat_tool(CONFIG, 1_237_970, "NorthstarProton8-28").expect("replace tool");
+            assert_eq!(replaced, CONFIG.replace("proton_8", "NorthstarProton8-28"));
+
+            let added = with_compat_tool(CONFIG, 620, "proton_9").expect("add app");
+            assert!(added.contains("\t\t\t\t\t\"620\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"proton_9\"\n\t\t\t\t\t\t\"config\"\t\t\"\"\n\t\t\t\t\t\t\"priority\"\t\t\"250\"\n\t\t\t\t\t}"));
+            assert!(added.contains("\"proton_8\""));
+
+            let dir = TempDir::create("./test_compat_tool").expect("temp dir");
+            let path = dir.join("config.vdf");
+            std::fs::write(&path, CONFIG).unwrap();
+            set_compat_tool(&path, "NorthstarProton8-28").expect("set tool");
+            assert!(std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("\"NorthstarProton8-28\""));
+            assert_eq!(
+                std::fs::read_to_string(dir.join("config.vdf.thermite-backup")).unwrap(),
+                CONFIG
+            );
+        }
+
+        #[test]
+        fn create_prefix() {
+            let dir = TempDir::create("./test_proton_prefix").expect("temp dir");
+            let compat = compat_data_dir(&*dir);
+            assert_eq!(compat, dir.join("compatdata/1237970"));
+
+            let status = configure_prefix(&compat).expect("configure");
+            assert!(status.created);
+            assert!(!status.initialized);
+
+            std::fs::create_dir_all(compat.join("pfx/drive_c/windows/system32")).unwrap();
+            std::fs::write(compat.join("version"), "8.0-5").unwrap();
+            assert_eq!(
+                configure_prefix(&compat).expect("configure"),
+                PrefixStatus {
+                    created: false,
+                    initialized: true
+                }
+            );
+        }
+
+        #[test]
+        fn prune_old_versions() {
+            let dir = TempDir::create("./test_proton_versions").expect("temp dir");
+            let tools = compatibility_tools_dir(&*dir);
+            for name in [
+                "NorthstarProton9-1",
+                "NorthstarProton10-2",
+                "NorthstarProton8-28",
+                "GE-Proton9-1",
+            ] {
+                std::fs::create_dir_all(tools.join(name)).unwrap();
+            }
+            std::fs::write(tools.join("NorthstarProton11-0.tar.gz"), "").unwrap();
+
+            let versions = installed_versions(&tools).expect("list");
+            assert_eq!(
+                versions
+                    .iter()
+                    .map(|v| v.version.as_str())
+                    .collect::<Vec<_>>(),
+                ["8-28", "9-1", "10-2"]
+            );
+            assert_eq!(newest_version(&tools).unwrap().unwrap().version, "10-2");
+
+            let removed = remove_old_versions(&tools, 1).expect("prune");
+            assert_eq!(removed.len(), 2);
+            assert!(!tools.join("NorthstarProton8-28").exists());
+            assert!(tools.join("NorthstarProton10-2").exists());
+            assert!(tools.join("GE-Proton9-1").exists());
+            assert!(installed_versions(dir.join("missing")).unwrap().is_empty());
         }
     }
 }
@@ -393,13 +2877,30 @@ mod test {
     use std::{
         collections::BTreeMap,
         fs,
+        io::Cursor,
         path::{Path, PathBuf},
+        sync::Arc,
     };
 
-    use crate::{error::ThermiteError, model::Mod};
+    use crate::{
+        cancel::CancelToken,
+        config::{RunningGamePolicy, ThermiteConfig},
+        core::{
+            manage::install_mod,
+            vfs::{self, Fs, MemoryFs},
+        },
+        error::ThermiteError,
+        game::GameSpec,
+        model::{InstalledMod, Manifest, Mod, ModVersion},
+        test_util::mod_archive_with,
+    };
 
+    use super::{check_game_running, is_running, GAME_POLL_INTERVAL};
     use super::{
-        find_mods, get_enabled_mods, parse_modstring, resolve_deps, validate_modstring, TempDir,
+        detect_conflicts, disable_mod, enable_mod, find_mods, find_mods_nested,
+        find_unmanaged_mods, get_enabled_mods, northstar_version, parse_modstring, resolve_deps,
+        resolve_deps_recursive, resolve_install_order, validate_modstring, validate_package,
+        verify_mod, ModConflict, PackageProblem, TempDir,
     };
 
     #[test]
@@ -443,7 +2944,8 @@ mod test {
         let test_folder = "parse_enabled_mods_test";
         let temp_dir = TempDir::create(test_folder).unwrap();
         fs::write(temp_dir.join("enabledmods.json"), b"invalid json").unwrap();
-        if let Err(ThermiteError::JsonError(_)) = get_enabled_mods(temp_dir) {
+        if let Err(ThermiteError::InvalidJson { path, .. }) = get_enabled_mods(&temp_dir) {
+            assert!(path.ends_with("enabledmods.json"));
         } else {
             panic!("enabledmods.json should not be valid json");
         }
@@ -474,6 +2976,7 @@ mod test {
             installed: false,
             versions: BTreeMap::new(),
             author: "Foo".into(),
+            ..Default::default()
         }];
 
         let test_deps = &["foo-test-0.1.0"];
@@ -494,6 +2997,7 @@ mod test {
             installed: false,
             versions: BTreeMap::new(),
             author: "Northstar".into(),
+            ..Default::default()
         }];
 
         let test_deps = &["Northstar-Northstar-0.1.0"];
@@ -514,6 +3018,7 @@ mod test {
             installed: false,
             versions: BTreeMap::new(),
             author: "Foo".into(),
+            ..Default::default()
         }];
 
         let test_deps = &["foo-test@0.1.0"];
@@ -529,6 +3034,163 @@ mod test {
         assert!(res.is_err());
     }
 
+    fn versioned_mod(name: &str, versions: &[(&str, &[&str])]) -> Mod {
+        Mod {
+            name: name.into(),
+            latest: versions.last().map(|v| v.0.into()).unwrap_or_default(),
+            upgradable: false,
+            global: false,
+            installed: false,
+            versions: versions
+                .iter()
+                .map(|(version, deps)| {
+                    let version = ModVersion {
+                        name: name.into(),
+                        full_name: format!("Foo-{name}-{version}"),
+                        version: (*version).into(),
+                        url: String::new(),
+                        desc: String::new(),
+                        deps: deps.iter().map(|d| (*d).into()).collect(),
+                        installed: false,
+                        global: false,
+                        file_size: 0,
+                        sha256: None,
+                        ..Default::default()
+                    };
+                    (version.version.as_str().into(), version)
+                })
+                .collect(),
+            author: "Foo".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn toggle_mods_in_enabled_mods() {
+        let dir = TempDir::create("./test_toggle_mods").expect("Unable to create temp dir");
+        disable_mod(&*dir, "Mock.Bar").expect("create the file");
+        enable_mod(&*dir, "northstar.custom").unwrap();
+        let mods = get_enabled_mods(&*dir).unwrap();
+        assert_eq!(mods.get("Mock.Bar"), Some(false));
+        assert_eq!(mods.get("Northstar.Custom"), Some(true));
+
+        let path = dir.join("enabledmods.json");
+        fs::write(&path, r#"{"Mock.Bar": false, "Future": {"key": 1}}"#).unwrap();
+        enable_mod(&*dir, "Mock.Bar").unwrap();
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["Mock.Bar"], true);
+        assert_eq!(raw["Future"]["key"], 1);
+        assert_eq!(fs::read_dir(&*dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn resolve_transitive_dependencies() {
+        let index = &[
+            versioned_mod("A", &[("1.0.0", &["Foo-B-1.0.0", "Foo-C-1.0.0"])]),
+            versioned_mod("B", &[("1.0.0", &["Foo-C-1.0.0", "Foo-A-1.0.0"])]),
+            versioned_mod("C", &[("1.0.0", &[])]),
+        ];
+
+        let resolved = resolve_deps_recursive(&["Foo-A-1.0.0"], index).unwrap();
+        assert_eq!(
+            resolved.iter().map(|m| m.name.as_str()).collect::<Vec<_>>(),
+            ["C", "B", "A"]
+        );
+        assert_eq!(resolve_deps(&["Foo-A-1.0.0"], index).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn resolve_versions_and_order() {
+        let index = &[
+            versioned_mod(
+                "A",
+                &[("1.0.0", &["Foo-Lib-1.0.0", "Northstar-Northstar-1.0.0"])],
+            ),
+            versioned_mod("B", &[("1.0.0", &["Foo-Lib-1.2.0"])]),
+            versioned_mod("C", &[("1.0.0", &["Foo-Lib-2.0.0"])]),
+            versioned_mod(
+                "Lib",
+                &[
+                    ("1.0.0", &[]),
+                    ("1.10.0", &["Foo-Util"]),
+                    ("1.9.0", &[]),
+                    ("2.0.0", &[]),
+                ],
+            ),
+            versioned_mod("Util", &[("0.1.0", &[])]),
+            versioned_mod("X", &[("1.0.0", &["Foo-Y-1.0.0"])]),
+            versioned_mod("Y", &[("1.0.0", &["Foo-X-1.0.0"])]),
+        ];
+
+        let order = resolve_install_order(&["Foo-B-1.0.0", "Foo-A-1.0.0"], index).unwrap();
+        assert_eq!(
+            order
+                .iter()
+                .map(|v| v.full_name.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "Foo-Util-0.1.0",
+                "Foo-Lib-1.10.0",
+                "Foo-A-1.0.0",
+                "Foo-B-1.0.0"
+            ]
+        );
+
+        match resolve_install_order(&["Foo-A-1.0.0", "Foo-C-1.0.0"], index) {
+            Err(ThermiteError::DependencyConflict {
+                package,
+                requirements,
+            }) => {
+                assert_eq!(package, "Foo-Lib");
+                assert_eq!(requirements.len(), 2);
+            }
+            res => panic!("Expected DependencyConflict, got {res:?}"),
+        }
+
+        match resolve_install_order(&["Foo-X-1.0.0"], index) {
+            Err(ThermiteError::DependencyCycle(cycle)) => {
+                assert_eq!(cycle, ["Foo-X", "Foo-Y", "Foo-X"]);
+            }
+            res => panic!("Expected DependencyCycle, got {res:?}"),
+        }
+        assert!(matches!(
+            resolve_install_order(&["Foo-Missing-1.0.0"], index),
+            Err(ThermiteError::DepError { .. })
+        ));
+    }
+
+    #[test]
+    fn suggest_similar_dependencies() {
+        let test_index: &[Mod] = &[Mod {
+            name: "Server_Utilities".into(),
+            latest: "0.1.0".into(),
+            upgradable: false,
+            global: false,
+            installed: false,
+            versions: BTreeMap::new(),
+            author: "Fifty".into(),
+            ..Default::default()
+        }];
+
+        let test_deps = &["Fifty-Server_Utilites-0.1.0"];
+
+        match resolve_deps(test_deps, test_index) {
+            Err(ThermiteError::DepError { name, suggestions }) => {
+                assert_eq!(name, "Fifty-Server_Utilites-0.1.0");
+                assert_eq!(suggestions, ["Fifty-Server_Utilities"]);
+            }
+            res => panic!("Expected a DepError, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(super::edit_distance("kitten", "sitting"), 3);
+        assert_eq!(super::edit_distance("", "abc"), 3);
+        assert_eq!(super::edit_distance("same", "same"), 0);
+    }
+
     #[test]
     fn sucessfully_validate_modstring() {
         let test_string = "author-mod-0.1.0";
@@ -594,6 +3256,193 @@ mod test {
         fs::write(_mod.join("mod.json"), MOD_JSON).expect("write mod.json");
     }
 
+    #[test]
+    fn discover_mods_in_memory() {
+        let fs = Arc::new(MemoryFs::new());
+        let root = PathBuf::from("/memory/packages/northstar-mod-1.2.3");
+        fs.create_dir_all(&root.join("RealMod")).unwrap();
+        fs.write(&root.join("manifest.json"), MANIFEST.as_bytes())
+            .unwrap();
+        fs.write(&root.join("RealMod").join("mod.json"), MOD_JSON.as_bytes())
+            .unwrap();
+
+        let mods = vfs::with(fs, || find_mods("/memory/packages")).expect("find mods");
+        assert_eq!(mods.len(), 1, "Should be one mod");
+        assert_eq!(mods[0].mod_json.name, "Yourname.Modname");
+        assert_eq!(mods[0].path, root.join("RealMod"));
+    }
+
+    #[test]
+    fn discover_nested_mods() {
+        let fs = Arc::new(MemoryFs::new());
+        let profile = PathBuf::from("/memory/R2Northstar");
+        let package = profile.join("packages/northstar-mod-1.2.3");
+        fs.create_dir_all(&package.join("mods/RealMod")).unwrap();
+        fs.write(&package.join("manifest.json"), MANIFEST.as_bytes())
+            .unwrap();
+        fs.write(&package.join("mods/RealMod/mod.json"), MOD_JSON.as_bytes())
+            .unwrap();
+        let legacy = profile.join("mods/Loose");
+        fs.create_dir_all(&legacy).unwrap();
+        fs.write(&legacy.join("mod.json"), MOD_JSON.as_bytes())
+            .unwrap();
+
+        let find = |depth| vfs::with(fs.clone(), || find_mods_nested(&profile, depth));
+        assert!(find(1).expect("find mods").is_empty());
+
+        let mut mods = find(2).expect("find mods");
+        mods.sort_by(|a, b| a.package.cmp(&b.package));
+        assert_eq!(mods.len(), 2);
+        assert_eq!(mods[0].package, None);
+        assert_eq!(mods[0].installed.path, legacy);
+        assert_eq!(mods[1].package.as_deref(), Some("northstar-mod-1.2.3"));
+        assert_eq!(mods[1].installed.author, "northstar");
+        assert_eq!(mods[1].installed.path, package.join("mods/RealMod"));
+    }
+
+    #[test]
+    fn discover_unmanaged_mods() {
+        let fs = Arc::new(MemoryFs::new());
+        let packages = PathBuf::from("/memory/packages");
+        let package = packages.join("northstar-mod-1.2.3");
+        fs.create_dir_all(&package.join("RealMod")).unwrap();
+        fs.write(&package.join("manifest.json"), MANIFEST.as_bytes())
+            .unwrap();
+        fs.write(&package.join("RealMod/mod.json"), MOD_JSON.as_bytes())
+            .unwrap();
+        let manual = packages.join("Manual/mods/Loose");
+        fs.create_dir_all(&manual).unwrap();
+        fs.write(&manual.join("mod.json"), MOD_JSON.as_bytes())
+            .unwrap();
+
+        let (managed, unmanaged) = vfs::with(fs, || {
+            (
+                find_mods(&packages).expect("find mods"),
+                find_unmanaged_mods(&packages).expect("find unmanaged mods"),
+            )
+        });
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].path, package.join("RealMod"));
+        assert_eq!(unmanaged.len(), 1);
+        assert_eq!(unmanaged[0].path, manual);
+        assert_eq!(unmanaged[0].author, "");
+        assert_eq!(unmanaged[0].manifest.name, "Yourname.Modname");
+    }
+
+    #[test]
+    fn detect_running_game() {
+        let exe = std::env::current_exe().expect("current exe");
+        let name = exe.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(is_running(&[name.to_uppercase()]));
+        assert!(!is_running(&["Titanfall2.exe".into()]));
+
+        let config = ThermiteConfig {
+            running_game: RunningGamePolicy::Refuse,
+            game: GameSpec {
+                processes: vec![name],
+                ..GameSpec::default()
+            },
+            ..ThermiteConfig::default()
+        };
+        assert!(matches!(
+            check_game_running(&config),
+            Err(ThermiteError::GameRunning(_))
+        ));
+        let config = ThermiteConfig {
+            running_game: RunningGamePolicy::Wait(Some(std::time::Duration::ZERO)),
+            ..config
+        };
+        assert!(matches!(
+            check_game_running(&config),
+            Err(ThermiteError::Timeout { .. })
+        ));
+
+        // the wait gives up when the token does, not at the next poll
+        let config = ThermiteConfig {
+            running_game: RunningGamePolicy::Wait(None),
+            ..config
+        };
+        let token = CancelToken::with_timeout(std::time::Duration::from_millis(100));
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            token.run(|| check_game_running(&config)),
+            Err(ThermiteError::Timeout { .. })
+        ));
+        assert!(started.elapsed() < GAME_POLL_INTERVAL);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn check_free_space() {
+        use super::{available_space, check_space};
+
+        let free = available_space(".").expect("free space of the working directory");
+        assert!(free > 0);
+        assert!(available_space("./does/not/exist").is_some());
+
+        assert!(check_space(Path::new("."), 0).is_ok());
+        assert!(matches!(
+            check_space(Path::new("."), u64::MAX),
+            Err(ThermiteError::InsufficientSpace {
+                required: u64::MAX,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn check_write_permission() {
+        use super::check_writable;
+
+        let dir = TempDir::create("./test_writable").expect("Unable to create temp dir");
+        let missing = dir.join("packages");
+        check_writable(&missing).expect("writable");
+        assert!(missing.is_dir());
+        assert_eq!(fs::read_dir(&missing).unwrap().count(), 0);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&missing, fs::Permissions::from_mode(0o555)).unwrap();
+            // root can write anywhere
+            if fs::write(missing.join("probe"), "").is_err() {
+                assert!(matches!(
+                    check_writable(&missing),
+                    Err(ThermiteError::PermissionDenied(p)) if p == missing
+                ));
+            }
+            fs::set_permissions(&missing, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn prefix_long_paths() {
+        use super::long_path;
+
+        let short = Path::new(r"C:\Games\Titanfall2");
+        assert_eq!(long_path(short, 10), short);
+        assert!(long_path(short, 300)
+            .to_string_lossy()
+            .starts_with(r"\\?\C:\Games"));
+    }
+
+    #[test]
+    fn detect_northstar_version() {
+        let fs = Arc::new(MemoryFs::new());
+        let game = PathBuf::from("/memory/game");
+        fs.create_dir_all(&game).unwrap();
+        let version = || vfs::with(fs.clone(), || northstar_version(&game));
+        assert_eq!(version().expect("no Northstar"), None);
+
+        let client = game.join("R2Northstar/mods/Northstar.Client");
+        fs.create_dir_all(&client).unwrap();
+        fs.write(&client.join("mod.json"), MOD_JSON.as_bytes())
+            .unwrap();
+        assert_eq!(version().expect("version").as_deref(), Some("1.2.3"));
+    }
+
     #[test]
     fn discover_mods() {
         let dir = TempDir::create("./mod_discovery").expect("Temp dir");
@@ -609,4 +3458,157 @@ mod test {
             panic!("Mod discovery failed: {res:?}");
         }
     }
+
+    #[test]
+    fn verify_installed_mod() {
+        let dir = TempDir::create("./verify_installed_mod").expect("temp dir");
+        let archive = mod_archive_with("Bar", "1.0.0", &[("README.md", "# Bar")]);
+        install_mod("Foo-Bar-1.0.0", Cursor::new(archive), &dir).expect("install");
+        let installed = find_mods(&dir).expect("find mods").remove(0);
+        let package = dir.canonicalize().unwrap().join("Foo-Bar-1.0.0");
+
+        let report = verify_mod(&installed).expect("verify");
+        assert!(report.is_intact(), "{report:?}");
+        assert_eq!(report.root, package);
+
+        fs::write(package.join("mods/Bar/mod.json"), "{}").unwrap();
+        fs::remove_file(package.join("README.md")).unwrap();
+        fs::write(package.join("extra.txt"), "extra").unwrap();
+        let report = verify_mod(&installed).expect("verify");
+        assert!(!report.is_intact());
+        assert_eq!(report.modified, [package.join("mods/Bar/mod.json")]);
+        assert_eq!(report.missing, [package.join("README.md")]);
+        assert_eq!(report.extra, [package.join("extra.txt")]);
+
+        fs::remove_file(package.join(super::CHECKSUMS_FILE)).unwrap();
+        assert!(matches!(
+            verify_mod(&installed),
+            Err(ThermiteError::MissingFile(_))
+        ));
+    }
+
+    #[test]
+    fn validate_packages() {
+        let archive = mod_archive_with("Bar", "1.0.0", &[("README.md", "# Bar")]);
+        let report = validate_package(Cursor::new(archive)).expect("validate");
+        assert!(report.is_valid(), "{:?}", report.problems);
+        assert!(report.problems.is_empty());
+        assert_eq!(report.manifest.unwrap().version_number, "1.0.0");
+        assert_eq!(report.mods["Bar"].name, "Mock.Bar");
+
+        let archive = mod_archive_with(
+            "Bar",
+            "1.0.0",
+            &[
+                ("notes.txt", ""),
+                ("mods/Bar/run.exe", ""),
+                ("mods/Broken/mod.json", "{"),
+                ("mods/Empty/readme.txt", ""),
+            ],
+        );
+        let report = validate_package(Cursor::new(archive)).expect("validate");
+        assert!(!report.is_valid());
+        assert_eq!(report.problems.len(), 4, "{:?}", report.problems);
+        assert_eq!(
+            report.problems[..2],
+            [
+                PackageProblem::UnexpectedFile("notes.txt".into()),
+                PackageProblem::DisallowedFile("mods/Bar/run.exe".into()),
+            ]
+        );
+        assert!(matches!(
+            &report.problems[2],
+            PackageProblem::InvalidModJson { dir, .. } if dir == "Broken"
+        ));
+        assert_eq!(
+            report.problems[3],
+            PackageProblem::MissingModJson {
+                dir: "Empty".into()
+            }
+        );
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        zip.start_file("Bar/manifest.json", options).unwrap();
+        zip.start_file("../evil.txt", options).unwrap();
+        let archive = zip.finish().unwrap();
+        let report = validate_package(archive).expect("validate");
+        assert_eq!(
+            report.problems,
+            [
+                PackageProblem::UnsafePath("../evil.txt".into()),
+                PackageProblem::NestedPackage {
+                    prefix: "Bar".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_mod_conflicts() {
+        let dir = TempDir::create("./test_detect_conflicts").expect("Unable to create temp dir");
+        let installed = |dir_name: &str, name: &str, con_vars: &[&str], files: &[&str]| {
+            let path = dir.join(dir_name);
+            fs::create_dir_all(&path).unwrap();
+            for file in files {
+                let file = path.join(file);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(file, "data").unwrap();
+            }
+            let con_vars = con_vars
+                .iter()
+                .map(|c| format!(r#"{{"Name": "{c}", "DefaultValue": "1"}}"#))
+                .collect::<Vec<_>>()
+                .join(",");
+            let mod_json = format!(
+                r#"{{"Name": "{name}", "Description": "", "Version": "1.0.0", "ConVars": [{con_vars}]}}"#
+            );
+            InstalledMod {
+                manifest: Manifest {
+                    name: dir_name.into(),
+                    version_number: "1.0.0".into(),
+                    website_url: String::new(),
+                    description: String::new(),
+                    dependencies: vec![],
+                },
+                mod_json: json5::from_str(&mod_json).unwrap(),
+                author: "Foo".into(),
+                path,
+            }
+        };
+        let mods = [
+            installed(
+                "A",
+                "Mock.A",
+                &["shared_var"],
+                &["mod/scripts/vscripts/a.nut", "mod/scripts/weapons/Gun.txt"],
+            ),
+            installed(
+                "B",
+                "Mock.B",
+                &["shared_var", "b_var"],
+                &["mod/scripts/weapons/gun.txt", "README.md"],
+            ),
+            installed("C", "Mock.A", &[], &[]),
+        ];
+
+        assert_eq!(
+            detect_conflicts(&mods).unwrap(),
+            [
+                ModConflict::OverlappingFile {
+                    path: "mod/scripts/weapons/Gun.txt".into(),
+                    mods: vec!["Mock.A".into(), "Mock.B".into()],
+                },
+                ModConflict::DuplicateName {
+                    name: "Mock.A".into(),
+                    paths: vec![dir.join("A"), dir.join("C")],
+                },
+                ModConflict::ConVar {
+                    name: "shared_var".into(),
+                    mods: vec!["Mock.A".into(), "Mock.B".into()],
+                },
+            ]
+        );
+        assert!(detect_conflicts(&mods[..1]).unwrap().is_empty());
+    }
 }
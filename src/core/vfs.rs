@@ -0,0 +1,688 @@
+//! Filesystem abstraction used by `manage` and `utils`
+//!
+//! Everything that touches the disk goes through [`current`], which is [`RealFs`] unless an
+//! operation is run inside [`with`]. [`MemoryFs`] keeps everything in memory, optionally reading
+//! through to the real disk, and records every change it was asked to make. That is what powers
+//! hermetic tests and previews of destructive operations.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+/// A directory entry returned by [`Fs::read_dir`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DirEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+impl DirEntry {
+    pub fn file_name(&self) -> &str {
+        self.path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+    }
+}
+
+/// A change made through an [`Fs`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FsChange {
+    CreateDir(PathBuf),
+    WriteFile { path: PathBuf, size: u64 },
+    Remove(PathBuf),
+    Rename { from: PathBuf, to: PathBuf },
+}
+
+pub(crate) trait Fs: Send + Sync {
+    /// Whether this is the disk itself, which what can't go through an `Fs`, like the SQLite database of the
+    /// `db` feature, is only written to
+    fn is_real(&self) -> bool {
+        false
+    }
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Creates or truncates a file, the returned writer must be dropped for the write to finish
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>>;
+    /// Opens a file to write after its current contents, creating it if it doesn't exist
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + '_>>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+    fn exists(&self, path: &Path) -> io::Result<bool>;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Sets unix permission bits. Does nothing on filesystems without them
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()>;
+    /// Writes a file that doesn't exist yet, failing with `AlreadyExists` if it does. Of several writers racing
+    /// to create the same file only one succeeds
+    fn write_new(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.create(path)?.write_all(contents)
+    }
+
+    /// Writes a file through a temporary file next to it, so nothing ever reads it half written
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Path doesn't name a file")
+        })?;
+        let mut temp = OsString::from(".");
+        temp.push(name);
+        temp.push(".tmp");
+        let temp = path.with_file_name(temp);
+
+        self.write(&temp, contents)?;
+        self.rename(&temp, path).inspect_err(|_| {
+            let _ = self.remove_file(&temp);
+        })
+    }
+
+    /// Size of a file in bytes
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        self.read(path).map(|contents| contents.len() as u64)
+    }
+
+    /// When a file was last written, `None` on filesystems that don't keep track
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        self.file_len(path).map(|_| None)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Passes everything through to `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RealFs;
+
+impl Fs for RealFs {
+    fn is_real(&self) -> bool {
+        true
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(fs::File::create(path)?))
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(
+            fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)?,
+        ))
+    }
+
+    fn write_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?
+            .write_all(contents)
+    }
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<Option<SystemTime>> {
+        fs::metadata(path)?.modified().map(Some)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        path.read_dir()?
+            .map(|child| {
+                let child = child?;
+                Ok(DirEntry {
+                    path: child.path(),
+                    is_dir: child.file_type()?.is_dir(),
+                })
+            })
+            .collect()
+    }
+
+    fn exists(&self, path: &Path) -> io::Result<bool> {
+        path.try_exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+    /// Paths removed from the underlying disk
+    removed: BTreeSet<PathBuf>,
+    changes: Vec<FsChange>,
+}
+
+/// An in-memory filesystem
+///
+/// When created with [`MemoryFs::overlay`], anything not written in memory is read from the real
+/// disk, but nothing is ever written to it
+#[derive(Debug, Default)]
+pub(crate) struct MemoryFs {
+    state: Mutex<MemoryState>,
+    overlay: bool,
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} does not exist", path.display()),
+    )
+}
+
+/// Makes a path absolute and resolves `.` and `..` without touching the disk
+fn normalize(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .unwrap_or_else(|_| PathBuf::from("/"))
+            .join(path)
+    };
+
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                out.pop();
+            }
+            Component::CurDir => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl MemoryFs {
+    /// Creates an empty filesystem
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a filesystem that reads through to the real disk
+    pub fn overlay() -> Self {
+        Self {
+            overlay: true,
+            ..Self::default()
+        }
+    }
+
+    /// Every change made so far, in order
+    pub fn changes(&self) -> Vec<FsChange> {
+        self.lock().changes.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        match self.state.lock() {
+            Ok(s) => s,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Whether a path under the real disk is still visible
+    fn on_disk(&self, state: &MemoryState, path: &Path) -> bool {
+        self.overlay && !state.removed.iter().any(|r| path.starts_with(r)) && path.exists()
+    }
+
+    fn is_dir_locked(&self, state: &MemoryState, path: &Path) -> bool {
+        state.dirs.contains(path) || (self.on_disk(state, path) && path.is_dir())
+    }
+
+    fn exists_locked(&self, state: &MemoryState, path: &Path) -> bool {
+        state.files.contains_key(path) || state.dirs.contains(path) || self.on_disk(state, path)
+    }
+
+    fn mkdirs(state: &mut MemoryState, path: &Path) {
+        for ancestor in path.ancestors() {
+            if ancestor.as_os_str().is_empty() || !state.dirs.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    fn insert_file(&self, path: PathBuf, contents: Vec<u8>) {
+        Self::insert_locked(&mut self.lock(), path, contents);
+    }
+
+    fn insert_locked(state: &mut MemoryState, path: PathBuf, contents: Vec<u8>) {
+        if let Some(parent) = path.parent() {
+            Self::mkdirs(state, parent);
+        }
+        state.removed.remove(&path);
+        state.changes.push(FsChange::WriteFile {
+            path: path.clone(),
+            size: contents.len() as u64,
+        });
+        state.files.insert(path, contents);
+    }
+
+    fn remove_locked(&self, state: &mut MemoryState, path: &Path) {
+        state.files.retain(|p, _| !p.starts_with(path));
+        state.dirs.retain(|p| !p.starts_with(path));
+        if self.overlay {
+            state.removed.insert(path.to_path_buf());
+        }
+        state.changes.push(FsChange::Remove(path.to_path_buf()));
+    }
+}
+
+/// Buffers writes and stores them in the [`MemoryFs`] when dropped
+struct MemoryFile<'a> {
+    fs: &'a MemoryFs,
+    path: PathBuf,
+    buf: Vec<u8>,
+}
+
+impl Write for MemoryFile<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for MemoryFile<'_> {
+    fn drop(&mut self) {
+        self.fs.insert_file(
+            std::mem::take(&mut self.path),
+            std::mem::take(&mut self.buf),
+        );
+    }
+}
+
+impl Fs for MemoryFs {
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.lock();
+        if !self.is_dir_locked(&state, &path) {
+            Self::mkdirs(&mut state, &path);
+            state.removed.remove(&path);
+            state.changes.push(FsChange::CreateDir(path));
+        }
+        Ok(())
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        let path = normalize(path);
+        {
+            let state = self.lock();
+            let parent = path.parent().unwrap_or(&path);
+            if !self.is_dir_locked(&state, parent) {
+                return Err(not_found(parent));
+            }
+        }
+
+        Ok(Box::new(MemoryFile {
+            fs: self,
+            path,
+            buf: vec![],
+        }))
+    }
+
+    fn write_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.lock();
+        if !self.is_dir_locked(&state, path.parent().unwrap_or(&path)) {
+            return Err(not_found(&path));
+        }
+        if self.exists_locked(&state, &path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        Self::insert_locked(&mut state, path, contents.to_vec());
+        Ok(())
+    }
+
+    fn append(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        let buf = if self.exists(path)? {
+            self.read(path)?
+        } else {
+            vec![]
+        };
+        let mut file = self.create(path)?;
+        file.write_all(&buf)?;
+        Ok(file)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let path = normalize(path);
+        let state = self.lock();
+        if let Some(contents) = state.files.get(&path) {
+            Ok(contents.clone())
+        } else if self.on_disk(&state, &path) {
+            fs::read(&path)
+        } else {
+            Err(not_found(&path))
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let path = normalize(path);
+        let state = self.lock();
+        if !self.is_dir_locked(&state, &path) {
+            return Err(not_found(&path));
+        }
+
+        let mut entries = BTreeMap::new();
+        if self.on_disk(&state, &path) {
+            for entry in RealFs.read_dir(&path)? {
+                if self.on_disk(&state, &entry.path) {
+                    entries.insert(entry.path, entry.is_dir);
+                }
+            }
+        }
+        for dir in state.dirs.iter().filter(|d| d.parent() == Some(&path)) {
+            entries.insert(dir.clone(), true);
+        }
+        for file in state.files.keys().filter(|f| f.parent() == Some(&path)) {
+            entries.insert(file.clone(), false);
+        }
+
+        Ok(entries
+            .into_iter()
+            .map(|(path, is_dir)| DirEntry { path, is_dir })
+            .collect())
+    }
+
+    fn exists(&self, path: &Path) -> io::Result<bool> {
+        let path = normalize(path);
+        Ok(self.exists_locked(&self.lock(), &path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        self.is_dir_locked(&self.lock(), &path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.lock();
+        if !self.exists_locked(&state, &path) || self.is_dir_locked(&state, &path) {
+            return Err(not_found(&path));
+        }
+        self.remove_locked(&mut state, &path);
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let path = normalize(path);
+        let mut state = self.lock();
+        if !self.is_dir_locked(&state, &path) {
+            return Err(not_found(&path));
+        }
+        self.remove_locked(&mut state, &path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from = normalize(from);
+        let to = normalize(to);
+
+        // collect everything under `from` before touching the state
+        let mut files = vec![];
+        let mut dirs = vec![];
+        let is_dir = self.is_dir(&from);
+        if is_dir {
+            let mut stack = vec![from.clone()];
+            while let Some(dir) = stack.pop() {
+                dirs.push(dir.clone());
+                for entry in self.read_dir(&dir)? {
+                    if entry.is_dir {
+                        stack.push(entry.path);
+                    } else {
+                        files.push((entry.path.clone(), self.read(&entry.path)?));
+                    }
+                }
+            }
+        } else {
+            files.push((from.clone(), self.read(&from)?));
+        }
+
+        let mut state = self.lock();
+        self.remove_locked(&mut state, &from);
+        state.changes.pop();
+        for dir in dirs {
+            let dest = to.join(dir.strip_prefix(&from).unwrap_or(&dir));
+            Self::mkdirs(&mut state, &dest);
+        }
+        for (path, contents) in files {
+            let dest = if is_dir {
+                to.join(path.strip_prefix(&from).unwrap_or(&path))
+            } else {
+                to.clone()
+            };
+            if let Some(parent) = dest.parent() {
+                Self::mkdirs(&mut state, parent);
+            }
+            state.files.insert(dest, contents);
+        }
+        state.removed.remove(&to);
+        state.changes.push(FsChange::Rename { from, to });
+
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let normalized = normalize(path);
+        let state = self.lock();
+        if state.files.contains_key(&normalized) || state.dirs.contains(&normalized) {
+            Ok(normalized)
+        } else if self.on_disk(&state, &normalized) {
+            path.canonicalize()
+        } else {
+            Err(not_found(&normalized))
+        }
+    }
+
+    fn set_mode(&self, _path: &Path, _mode: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Vec<Arc<dyn Fs>>> = const { RefCell::new(vec![]) };
+    static DRY_RUNS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns the filesystem operations on this thread should use
+pub(crate) fn current() -> Arc<dyn Fs> {
+    CURRENT
+        .with(|c| c.borrow().last().cloned())
+        .unwrap_or_else(default_fs)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_fs() -> Arc<dyn Fs> {
+    Arc::new(RealFs)
+}
+
+// there is no filesystem on the web, so default to one shared in-memory filesystem
+#[cfg(target_arch = "wasm32")]
+fn default_fs() -> Arc<dyn Fs> {
+    lazy_static::lazy_static! {
+        static ref MEMORY: Arc<dyn Fs> = Arc::new(MemoryFs::new());
+    }
+    MEMORY.clone()
+}
+
+/// Runs `f` with `fs` as the current thread's filesystem
+pub(crate) fn with<T>(fs: Arc<dyn Fs>, f: impl FnOnce() -> T) -> T {
+    CURRENT.with(|c| c.borrow_mut().push(fs));
+
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            CURRENT.with(|c| c.borrow_mut().pop());
+        }
+    }
+    let _guard = Guard;
+
+    f()
+}
+
+/// Runs `f` against an overlay of the real disk and returns the overlay, with everything `f` would have changed
+///
+/// [`is_dry_run`] is set while `f` runs, for things that don't go through the filesystem
+pub(crate) fn dry_run<T>(f: impl FnOnce() -> T) -> (T, Arc<MemoryFs>) {
+    DRY_RUNS.with(|d| d.set(d.get() + 1));
+
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            DRY_RUNS.with(|d| d.set(d.get() - 1));
+        }
+    }
+    let _guard = Guard;
+
+    let fs = Arc::new(MemoryFs::overlay());
+    (with(fs.clone(), f), fs)
+}
+
+/// Whether this thread is inside [`dry_run`], in which case hooks, events and logs should be skipped
+pub(crate) fn is_dry_run() -> bool {
+    DRY_RUNS.with(Cell::get) > 0
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Write,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use super::{current, with, Fs, FsChange, MemoryFs};
+
+    #[test]
+    fn memory_round_trip() {
+        let fs = MemoryFs::new();
+        let dir = Path::new("/memory/test");
+        fs.create_dir_all(dir).unwrap();
+        fs.write(&dir.join("file.txt"), b"hello").unwrap();
+
+        assert!(fs.is_dir(Path::new("/memory")));
+        assert_eq!(fs.read_to_string(&dir.join("file.txt")).unwrap(), "hello");
+        assert_eq!(fs.read_dir(dir).unwrap()[0].file_name(), "file.txt");
+
+        fs.rename(dir, Path::new("/memory/moved")).unwrap();
+        assert!(!fs.exists(dir).unwrap());
+        assert_eq!(
+            fs.read(Path::new("/memory/moved/file.txt")).unwrap(),
+            b"hello"
+        );
+
+        fs.remove_dir_all(Path::new("/memory")).unwrap();
+        assert!(!fs.exists(Path::new("/memory/moved/file.txt")).unwrap());
+    }
+
+    #[test]
+    fn memory_append() {
+        let fs = MemoryFs::new();
+        let file = Path::new("/memory/file.txt");
+        fs.create_dir_all(file.parent().unwrap()).unwrap();
+        fs.append(file).unwrap().write_all(b"hello").unwrap();
+        fs.append(file).unwrap().write_all(b" world").unwrap();
+        assert_eq!(fs.read_to_string(file).unwrap(), "hello world");
+        assert_eq!(fs.file_len(file).unwrap(), 11);
+    }
+
+    #[test]
+    fn memory_write_new() {
+        let fs = MemoryFs::new();
+        let file = Path::new("/memory/file.txt");
+        fs.create_dir_all(file.parent().unwrap()).unwrap();
+        fs.write_new(file, b"first").unwrap();
+        let err = fs.write_new(file, b"second").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs.read_to_string(file).unwrap(), "first");
+    }
+
+    #[test]
+    fn memory_requires_parent() {
+        let fs = MemoryFs::new();
+        assert!(fs.write(Path::new("/missing/file.txt"), b"").is_err());
+    }
+
+    #[test]
+    fn overlay_never_touches_disk() {
+        let fs = MemoryFs::overlay();
+        let real = std::env::current_dir().unwrap().join("src");
+        assert!(fs
+            .read_dir(&real)
+            .unwrap()
+            .iter()
+            .any(|e| e.file_name() == "lib.rs"));
+
+        fs.remove_file(&real.join("lib.rs")).unwrap();
+        assert!(!fs.exists(&real.join("lib.rs")).unwrap());
+        assert!(real.join("lib.rs").exists());
+        assert_eq!(fs.changes(), [FsChange::Remove(real.join("lib.rs"))]);
+    }
+
+    #[test]
+    fn scoped_filesystem() {
+        let fs = Arc::new(MemoryFs::new());
+        with(fs.clone(), || {
+            current().create_dir_all(Path::new("/scoped")).unwrap();
+        });
+
+        assert!(fs.is_dir(Path::new("/scoped")));
+        assert!(!current().is_dir(&PathBuf::from("/scoped")));
+    }
+}
@@ -0,0 +1,417 @@
+//! Database of installed packages, their files and enablement. Requires the `db` feature
+//!
+//! Installs, removals and enables through [`manage`](crate::core::manage),
+//! [`enable_mod`](crate::core::utils::enable_mod) and [`ModManager`](crate::manager::ModManager) keep a SQLite
+//! database in the `.thermite` directory of the packages directory in sync, so frontends can query what's
+//! installed without rescanning it. Enables look for it in the `packages` directory of the profile directory
+//! they're given. [`ModManager::database`](crate::manager::ModManager::database) opens the one of its profile.
+//!
+//! Only installs in the `Packages` layout are recorded. Updates are transactions, so processes sharing a
+//! profile don't corrupt the database, and an update that fails is logged instead of failing the operation,
+//! which already changed the disk by then. Nothing is recorded while a [`dry_run`](crate::core::vfs::dry_run)
+//! or another in-memory filesystem is in use.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use tracing::warn;
+
+use crate::{
+    core::{
+        journal::JOURNAL_DIR,
+        utils::{find_package_mods, hash_package},
+        vfs::{self, DirEntry},
+    },
+    error::Result,
+    verify::{encode_hash, sha256},
+};
+
+pub(crate) const DATABASE_NAME: &str = "thermite.db";
+
+/// How long to wait for another process to finish its update
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS packages (
+        key TEXT PRIMARY KEY,
+        version TEXT NOT NULL,
+        path TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        package TEXT NOT NULL REFERENCES packages (key) ON DELETE CASCADE,
+        path TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        sha256 TEXT NOT NULL,
+        PRIMARY KEY (package, path)
+    );
+    CREATE TABLE IF NOT EXISTS mods (
+        package TEXT NOT NULL REFERENCES packages (key) ON DELETE CASCADE,
+        name TEXT NOT NULL COLLATE NOCASE,
+        enabled INTEGER NOT NULL,
+        PRIMARY KEY (package, name)
+    );
+";
+
+/// An installed package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageRecord {
+    pub version: String,
+    pub path: PathBuf,
+    /// Files keyed by their path relative to `path`
+    pub files: BTreeMap<PathBuf, FileRecord>,
+    /// Whether each of the package's mods is enabled, keyed by the name in its `mod.json`
+    pub mods: BTreeMap<String, bool>,
+}
+
+impl PackageRecord {
+    /// Whether every mod of the package is enabled
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.mods.values().all(|enabled| *enabled)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    pub size: u64,
+    /// Hex encoded SHA-256 of the file when it was installed
+    pub sha256: String,
+}
+
+#[derive(Debug)]
+pub struct Database {
+    conn: Connection,
+}
+
+/// Where the database of the packages directory `dir` is
+pub(crate) fn database_path(dir: &Path) -> PathBuf {
+    dir.join(JOURNAL_DIR).join(DATABASE_NAME)
+}
+
+/// Applies `update` to the database of the packages directory `dir`, only logging failures
+///
+/// Without `create` nothing happens if the directory has no database yet
+pub(crate) fn update(dir: &Path, create: bool, update: impl FnOnce(&Database) -> Result<()>) {
+    let path = database_path(dir);
+    if !vfs::current().is_real() || !(create || path.exists()) {
+        return;
+    }
+    if let Err(e) = Database::open(&path).and_then(|db| update(&db)) {
+        warn!("Unable to update the database {}: {e}", path.display());
+    }
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if it doesn't exist yet
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * The file isn't a database thermite created
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// The package recorded as `author-name`
+    ///
+    /// # Errors
+    /// * Database errors
+    pub fn get(&self, key: impl AsRef<str>) -> Result<Option<PackageRecord>> {
+        let key = key.as_ref();
+        let Some((version, path)) = self
+            .conn
+            .query_row(
+                "SELECT version, path FROM packages WHERE key = ?1",
+                [key],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let mut files = self
+            .conn
+            .prepare_cached("SELECT path, size, sha256 FROM files WHERE package = ?1")?;
+        let files = files
+            .query_map([key], |row| {
+                Ok((
+                    PathBuf::from(row.get::<_, String>(0)?),
+                    FileRecord {
+                        size: row.get(1)?,
+                        sha256: row.get(2)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        let mut mods = self
+            .conn
+            .prepare_cached("SELECT name, enabled FROM mods WHERE package = ?1")?;
+        let mods = mods
+            .query_map([key], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(Some(PackageRecord {
+            version,
+            path: path.into(),
+            files,
+            mods,
+        }))
+    }
+
+    /// The `author-name` key of every package
+    ///
+    /// # Errors
+    /// * Database errors
+    pub fn packages(&self) -> Result<Vec<String>> {
+        let mut keys = self
+            .conn
+            .prepare_cached("SELECT key FROM packages ORDER BY key")?;
+        let keys = keys
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(keys)
+    }
+
+    /// The package that installed the file at `path`
+    ///
+    /// # Errors
+    /// * Database errors
+    pub fn owner_of(&self, path: impl AsRef<Path>) -> Result<Option<String>> {
+        let path = path.as_ref();
+        let mut packages = self.conn.prepare_cached("SELECT key, path FROM packages")?;
+        let packages = packages
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut owns = self
+            .conn
+            .prepare_cached("SELECT 1 FROM files WHERE package = ?1 AND path = ?2")?;
+        for (key, root) in packages {
+            let Ok(rel) = path.strip_prefix(root) else {
+                continue;
+            };
+            if owns.exists(params![key, file_key(rel)])? {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Files of a package that were changed or removed since it was installed
+    ///
+    /// # Errors
+    /// * IO errors other than missing files
+    /// * Database errors
+    pub fn modified_files(&self, key: impl AsRef<str>) -> Result<Vec<PathBuf>> {
+        let Some(package) = self.get(key)? else {
+            return Ok(vec![]);
+        };
+
+        let fs = vfs::current();
+        let mut modified = vec![];
+        for (rel, file) in &package.files {
+            let path = package.path.join(rel);
+            if !fs.exists(&path)? || encode_hash(&sha256(&fs.read(&path)?)) != file.sha256 {
+                modified.push(rel.clone());
+            }
+        }
+        Ok(modified)
+    }
+
+    /// Records a package installed at `path`, hashing every file in it
+    ///
+    /// Its mods keep the enabled state they had if it was installed before, new ones are enabled
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * Database errors
+    pub(crate) fn record_install(&self, key: &str, version: &str, path: &Path) -> Result<()> {
+        let files = hash_package(path)?;
+        let mods = find_package_mods(&DirEntry {
+            path: path.into(),
+            is_dir: true,
+        })?;
+
+        // taking the write lock up front makes concurrent updates wait out the busy timeout
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        let previous = {
+            let mut enabled = tx.prepare("SELECT name, enabled FROM mods WHERE package = ?1")?;
+            let previous = enabled
+                .query_map([key], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<BTreeMap<_, bool>>>()?;
+            previous
+        };
+        tx.execute("DELETE FROM packages WHERE key = ?1", [key])?;
+        tx.execute(
+            "INSERT INTO packages (key, version, path) VALUES (?1, ?2, ?3)",
+            params![key, version, path.to_string_lossy()],
+        )?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO files (package, path, size, sha256) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (rel, (size, sha256)) in &files {
+                insert.execute(params![key, file_key(rel), size, sha256])?;
+            }
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO mods (package, name, enabled) VALUES (?1, ?2, ?3)",
+            )?;
+            for m in &mods {
+                let name = &m.mod_json.name;
+                let enabled = previous
+                    .iter()
+                    .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                    .is_none_or(|(_, enabled)| *enabled);
+                insert.execute(params![key, name, enabled])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// * Database errors
+    pub(crate) fn record_removal(&self, key: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM packages WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    /// Records the state of every mod named `name`, which is how `enabledmods.json` refers to them
+    ///
+    /// # Errors
+    /// * Database errors
+    pub(crate) fn set_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE mods SET enabled = ?2 WHERE name = ?1",
+            params![name, enabled],
+        )?;
+        Ok(())
+    }
+}
+
+/// A relative path with `/` separators, like files are keyed by
+fn file_key(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor, path::Path, thread};
+
+    use crate::{
+        core::{
+            manage::{install_mod, remove_mod},
+            utils::{disable_mod, find_mods, TempDir},
+        },
+        test_util::mod_archive,
+    };
+
+    use super::{database_path, Database};
+
+    #[test]
+    fn track_files() {
+        let dir = TempDir::create("./test_database").expect("Unable to create temp dir");
+        let report = install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &dir,
+        )
+        .expect("install");
+
+        let db = Database::open(dir.join("thermite.db")).expect("open");
+        db.record_install("Foo-Bar", "1.0.0", &report.path).unwrap();
+        db.set_enabled("Mock.Bar", false).unwrap();
+
+        let db = Database::open(dir.join("thermite.db")).expect("reopen");
+        let package = db.get("Foo-Bar").unwrap().expect("recorded");
+        assert!(!package.enabled());
+        assert_eq!(package.files.len(), 2);
+        let mod_json = report.path.join("mods").join("Bar").join("mod.json");
+        assert_eq!(db.owner_of(&mod_json).unwrap().as_deref(), Some("Foo-Bar"));
+        assert!(db.modified_files("Foo-Bar").unwrap().is_empty());
+
+        // reinstalling keeps the mod disabled
+        db.record_install("Foo-Bar", "1.0.0", &report.path).unwrap();
+        assert!(!db.get("Foo-Bar").unwrap().unwrap().enabled());
+
+        fs::write(&mod_json, "{}").unwrap();
+        assert_eq!(
+            db.modified_files("Foo-Bar").unwrap(),
+            [Path::new("mods/Bar/mod.json")]
+        );
+    }
+
+    #[test]
+    fn kept_in_sync() {
+        let dir = TempDir::create("./test_database_sync").expect("Unable to create temp dir");
+        let packages = dir.join("packages");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &packages,
+        )
+        .expect("install");
+        let db = Database::open(database_path(&packages)).expect("open");
+        assert!(db.get("Foo-Bar").unwrap().expect("recorded").enabled());
+
+        disable_mod(&*dir, "Mock.Bar").expect("disable");
+        assert!(!db.get("Foo-Bar").unwrap().unwrap().enabled());
+
+        remove_mod(&find_mods(&packages).unwrap()[0], false).expect("remove");
+        assert!(db.packages().unwrap().is_empty());
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        let dir = TempDir::create("./test_database_concurrent").expect("Unable to create temp dir");
+        let path = dir.join("thermite.db");
+        let archive = || Cursor::new(mod_archive("Bar", "1.0.0"));
+        let installed = ["Foo-Bar", "Foo-Baz", "Foo-Qux"].map(|key| {
+            let report = install_mod(format!("{key}-1.0.0"), archive(), &dir).expect("install");
+            (key, report.path)
+        });
+
+        // as if each was a process of its own
+        thread::scope(|s| {
+            for (key, package) in &installed {
+                let path = &path;
+                s.spawn(move || {
+                    Database::open(path)
+                        .unwrap()
+                        .record_install(key, "1.0.0", package)
+                        .unwrap();
+                });
+            }
+        });
+        let db = Database::open(&path).unwrap();
+        assert_eq!(db.packages().unwrap(), ["Foo-Bar", "Foo-Baz", "Foo-Qux"]);
+
+        db.record_removal("Foo-Bar").unwrap();
+        let db = Database::open(&path).unwrap();
+        assert!(db.get("Foo-Bar").unwrap().is_none());
+        assert!(db.get("Foo-Baz").unwrap().is_some());
+        assert!(db
+            .owner_of(installed[0].1.join("manifest.json"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,12 +1,17 @@
 use std::{
     error::Error,
+    ffi::NulError,
+    fmt::{self, Display},
     io,
     num::{ParseIntError, TryFromIntError},
     path::{PathBuf, StripPrefixError},
+    time::Duration,
 };
 
 use thiserror::Error;
 
+use crate::{core::audit::AuditOperation, time::Instant};
+
 pub type Result<T, E = ThermiteError> = std::result::Result<T, E>;
 
 #[derive(Error, Debug)]
@@ -17,14 +22,44 @@ pub enum ThermiteError {
     IoError(#[from] io::Error),
     #[error("{0}")]
     UnknownError(String),
+    #[cfg(not(target_arch = "wasm32"))]
     #[error("Error making network request: {0}")]
     NetworkError(Box<ureq::Error>),
     #[error(transparent)]
     ZipError(#[from] zip::result::ZipError),
     #[error("Error parsing JSON: {0}")]
     JsonError(Box<dyn Error + Send + Sync + 'static>),
-    #[error("Error resolving dependency {0}")]
-    DepError(String),
+    #[error("Error parsing mod.json at {}: {source}", .path.display())]
+    InvalidModJson {
+        path: PathBuf,
+        source: Box<dyn Error + Send + Sync + 'static>,
+    },
+    #[error("Error parsing JSON in {}: {source}", .path.display())]
+    InvalidJson {
+        path: PathBuf,
+        source: Box<dyn Error + Send + Sync + 'static>,
+    },
+    #[error("Malformed URL {0}")]
+    MalformedUrl(String),
+    #[error("No HTTP backend set, call thermite::http::set_backend before requesting {0}")]
+    NoHttpBackend(String),
+    #[error("Error resolving dependency {name}{}", fmt_suggestions(.suggestions))]
+    DepError {
+        name: String,
+        /// Packages from the index with similar names, closest first
+        suggestions: Vec<String>,
+    },
+    #[error("No version of {package} satisfies every dependency on it: {}", .requirements.join(", "))]
+    DependencyConflict {
+        /// `author-name` of the package
+        package: String,
+        /// Each dependency on the package, like `Foo-Bar-1.0.0 needs 2.1.0`
+        requirements: Vec<String>,
+    },
+    #[error("Dependency cycle: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+    #[error("Dependency versions didn't settle")]
+    UnsettledDependencies,
     #[error("Error stripping directory prefix {0}\nIs the mod formatted correctly?")]
     PrefixError(#[from] StripPrefixError),
     #[error("Sanity check failed: {0}")]
@@ -39,9 +74,301 @@ pub enum ThermiteError {
     NameError(String),
     #[error("Expected string to be UTF8")]
     UTF8Error,
+    #[error(transparent)]
+    NulError(#[from] NulError),
+    #[error("{0} was NULL")]
+    NullPointer(String),
+    #[error("The operation was cancelled")]
+    Cancelled,
+    #[error("A package is already installed at {0}")]
+    AlreadyInstalled(PathBuf),
+    #[error("{0} is not installed")]
+    NotInstalled(String),
+    #[error("{0} isn't in the package cache")]
+    NotCached(String),
+    #[error("{0} is a core Northstar mod")]
+    CoreMod(String),
+    #[error("{0} is running, close it before changing mods")]
+    GameRunning(String),
+    #[error("Unable to find the install of {0}")]
+    GameNotFound(String),
+    #[error("{0} is being changed by another process")]
+    DirectoryLocked(PathBuf),
+    #[error("Not enough disk space, {required} bytes are needed but only {available} are free")]
+    InsufficientSpace { required: u64, available: u64 },
+    #[error("No permission to write to {0}, run as administrator or move the game out of Program Files")]
+    PermissionDenied(PathBuf),
+    #[error("{0:?} can't be undone")]
+    CannotUndo(AuditOperation),
+    #[error("Northstar {installed} is installed but the release is {release}")]
+    NorthstarVersionMismatch { installed: String, release: String },
+    #[error("Release {release} has no asset matching {pattern}")]
+    MissingAsset { release: String, pattern: String },
+    #[error("Invalid SHA-256 hash for {0}")]
+    InvalidHash(String),
+    #[error("Archive for {package} doesn't match its trusted hash (expected {expected}, got {actual})")]
+    HashMismatch {
+        package: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("No trusted hash is known for {0}")]
+    UntrustedPackage(String),
+    #[error("Signature doesn't match the manifest and public key")]
+    InvalidSignature,
+    #[error("{name} contains native plugins ({}), installing them has to be allowed explicitly", .plugins.join(", "))]
+    PluginsDetected {
+        name: String,
+        /// Paths of the plugins in the archive
+        plugins: Vec<String>,
+    },
+    #[error("{0} contains native plugins but isn't on the verified mods list")]
+    UnverifiedPlugin(String),
+    #[error("{package} contains {path}, which would be extracted outside of its directory")]
+    UnsafePath { package: String, path: String },
+    #[error("{package} contains the symlink {path}")]
+    SymlinkEntry { package: String, path: String },
+    #[error("{package} extracts to more than {limit} bytes")]
+    ArchiveTooLarge { package: String, limit: u64 },
+    #[error("{package} contains more than {limit} files")]
+    TooManyFiles { package: String, limit: usize },
+    #[error("Invalid modpack: {0}")]
+    InvalidModpack(String),
+    #[error("Invalid profile: {0}")]
+    InvalidProfile(String),
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Invalid VDF: {0}")]
+    InvalidVdf(String),
+    #[error("Hook {hook} failed: {reason}")]
+    HookFailed { hook: String, reason: String },
+    #[error("Checksum of {url} doesn't match (expected {expected}, got {actual})")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("Downloaded {actual} bytes from {url} but expected {expected}")]
+    IncompleteDownload {
+        url: String,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("Request to {url} failed with status code {status}")]
+    HttpStatus { url: String, status: u16 },
+    #[error("Timed out after {elapsed:.1?} while {operation}{}", fmt_limit(.limit))]
+    Timeout {
+        /// What was being done when the timeout happened, e.g. `"downloading <url>"`
+        operation: String,
+        elapsed: Duration,
+        /// The configured limit that was exceeded, if known
+        limit: Option<Duration>,
+    },
+    /// A panic caught before it could unwind out of an FFI call or a job on the [`crate::pool`], with the panic's
+    /// message
+    #[error("thermite panicked: {0}")]
+    Panicked(String),
+    /// An [`crate::pool::Executor`] returned without running every job it was given
+    #[error("The executor returned before running every job")]
+    JobNotRun,
+    /// An error from the database of the `db` feature
+    #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    /// An error from a step of a larger operation, e.g. one package of a modpack. See [`ThermiteError::root`]
+    #[error("Error {context}: {source}")]
+    Context {
+        context: ErrorContext,
+        source: Box<ThermiteError>,
+    },
+}
+
+/// Which step of an operation failed, and on what
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// What was being done, e.g. `"downloading"` or `"installing"`
+    pub step: String,
+    /// `author-name-X.Y.Z` of the package
+    pub package: Option<String>,
+    pub path: Option<PathBuf>,
+    pub url: Option<String>,
+}
+
+impl ErrorContext {
+    #[must_use]
+    pub fn new(step: impl Into<String>) -> Self {
+        Self {
+            step: step.into(),
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+
+    #[must_use]
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.step)?;
+        if let Some(package) = &self.package {
+            write!(f, " {package}")?;
+        }
+        if let Some(url) = &self.url {
+            write!(f, " from {url}")?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " at {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl ThermiteError {
+    /// Wraps the error in a [`ThermiteError::Context`]
+    #[must_use]
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        Self::Context {
+            context,
+            source: Box::new(self),
+        }
+    }
+
+    /// The outermost context the error was given, if any
+    #[must_use]
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Self::Context { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The error without any context, to match on what actually went wrong
+    #[must_use]
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+
+    /// Like [`ThermiteError::root`], but takes ownership
+    #[must_use]
+    pub fn into_root(self) -> Self {
+        match self {
+            Self::Context { source, .. } => source.into_root(),
+            e => e,
+        }
+    }
+
+    /// A JSON file at `path` that couldn't be parsed
+    pub(crate) fn invalid_json(
+        path: impl Into<PathBuf>,
+        source: impl Into<Box<dyn Error + Send + Sync + 'static>>,
+    ) -> Self {
+        Self::InvalidJson {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    /// A `mod.json` at `path` that couldn't be parsed
+    pub(crate) fn invalid_mod_json(
+        path: impl Into<PathBuf>,
+        source: impl Into<Box<dyn Error + Send + Sync + 'static>>,
+    ) -> Self {
+        Self::InvalidModJson {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+
+    /// Converts a `ureq` error into a `ThermiteError`, producing a `Timeout` if the transport timed out
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn from_ureq(
+        err: ureq::Error,
+        operation: impl Into<String>,
+        started: Instant,
+        limit: Option<Duration>,
+    ) -> Self {
+        if is_timeout(&err) {
+            Self::Timeout {
+                operation: operation.into(),
+                elapsed: started.elapsed(),
+                limit,
+            }
+        } else {
+            err.into()
+        }
+    }
+
+    /// Converts an IO error into a `ThermiteError`, producing a `Timeout` if the error is `TimedOut`
+    pub(crate) fn from_io(
+        err: io::Error,
+        operation: impl Into<String>,
+        started: Instant,
+        limit: Option<Duration>,
+    ) -> Self {
+        if is_timeout(&err) {
+            Self::Timeout {
+                operation: operation.into(),
+                elapsed: started.elapsed(),
+                limit,
+            }
+        } else {
+            err.into()
+        }
+    }
+}
+
+/// Walks the source chain of an error looking for a timed out IO error
+fn is_timeout(err: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(err);
+    while let Some(e) = current {
+        if let Some(io) = e.downcast_ref::<io::Error>() {
+            if matches!(
+                io.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            ) {
+                return true;
+            }
+        }
+        current = e.source();
+    }
+
+    false
+}
+
+fn fmt_limit(limit: &Option<Duration>) -> String {
+    limit
+        .map(|l| format!(" (limit was {l:.1?})"))
+        .unwrap_or_default()
+}
+
+fn fmt_suggestions(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(" (did you mean {}?)", suggestions.join(", "))
+    }
 }
 
 // ureq::Error is ~240 bytes so we store it in a box
+#[cfg(not(target_arch = "wasm32"))]
 impl From<ureq::Error> for ThermiteError {
     fn from(value: ureq::Error) -> Self {
         Self::NetworkError(Box::new(value))
@@ -62,9 +389,42 @@ impl From<serde_json::Error> for ThermiteError {
 
 #[cfg(test)]
 mod test {
+    use std::{
+        net::TcpListener,
+        time::{Duration, Instant},
+    };
+
     use ureq::ErrorKind;
 
-    use super::ThermiteError;
+    use super::{ErrorContext, ThermiteError};
+
+    #[test]
+    fn timeout_from_ureq() {
+        // accept the connection but never respond
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let addr = listener.local_addr().unwrap();
+        let limit = Duration::from_millis(100);
+        let agent = ureq::AgentBuilder::new().timeout_read(limit).build();
+
+        let started = Instant::now();
+        let err = agent
+            .get(&format!("http://{addr}"))
+            .call()
+            .expect_err("Request should time out");
+        drop(listener);
+
+        match ThermiteError::from_ureq(err, "testing", started, Some(limit)) {
+            ThermiteError::Timeout {
+                operation,
+                elapsed,
+                limit: Some(l),
+            } => {
+                assert_eq!(operation, "testing");
+                assert!(elapsed >= l);
+            }
+            e => panic!("Unexpected error type: {e:?}"),
+        }
+    }
 
     #[test]
     fn from_ureq() {
@@ -79,4 +439,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn context_wraps_root() {
+        let err = ThermiteError::NameError("Foo-Bar".into())
+            .with_context(ErrorContext::new("installing").package("Foo-Bar-1.0.0"))
+            .with_context(ErrorContext::new("installing modpack").package("Foo-Pack-1.0.0"));
+
+        assert_eq!(
+            err.context().and_then(|c| c.package.as_deref()),
+            Some("Foo-Pack-1.0.0")
+        );
+        assert!(matches!(err.root(), ThermiteError::NameError(_)));
+        assert_eq!(
+            err.to_string(),
+            "Error installing modpack Foo-Pack-1.0.0: Error installing Foo-Bar-1.0.0: Error parsing mod name: Foo-Bar"
+        );
+    }
 }
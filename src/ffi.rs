@@ -0,0 +1,351 @@
+//! C bindings for embedding thermite in non-Rust frontends
+//!
+//! Enabled with the `ffi` feature. Build a shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` and include `include/thermite.h`,
+//! which is generated from this module with `cbindgen --config cbindgen.toml --output include/thermite.h`.
+//!
+//! Functions returning `int` return [`THERMITE_OK`] on success. On failure the message can be
+//! retrieved with [`thermite_last_error`]. Strings returned by thermite must be freed with
+//! [`thermite_string_free`]. A panic never unwinds into the caller, it's reported as an error.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    fs::File,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+use crate::{
+    api, config,
+    core::{
+        manage,
+        status::{self, StatusEvent, StatusSink},
+    },
+    error::{Result, ThermiteError},
+    manager::{ModManager, DEFAULT_PROFILE},
+    pool,
+};
+
+pub const THERMITE_OK: c_int = 0;
+pub const THERMITE_ERROR: c_int = -1;
+pub const THERMITE_CANCELLED: c_int = -2;
+
+/// Called with the bytes downloaded so far and the total size, which is 0 if unknown
+pub type ThermiteProgressCallback =
+    Option<unsafe extern "C" fn(current: u64, total: u64, user_data: *mut c_void)>;
+/// Called with a human-readable status line, only valid for the duration of the call
+pub type ThermiteStatusCallback =
+    Option<unsafe extern "C" fn(line: *const c_char, user_data: *mut c_void)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: &ThermiteError) {
+    let msg = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+    // the thread is shutting down if its error is gone already
+    let _ = LAST_ERROR.try_with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Runs `f`, returning `failed` and setting the last error if it panics
+fn guard<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        set_last_error(&ThermiteError::Panicked(pool::panic_message(&*payload)));
+        failed
+    })
+}
+
+/// Runs `f` for a function returning a status code
+fn call(f: impl FnOnce() -> Result<()>) -> c_int {
+    guard(THERMITE_ERROR, || status_code(f()))
+}
+
+/// Runs `f` for a function returning an owned string, NULL on error
+fn call_string(f: impl FnOnce() -> Result<String>) -> *mut c_char {
+    guard(ptr::null_mut(), || into_c_string(f()))
+}
+
+fn status_code(res: Result<()>) -> c_int {
+    match res {
+        Ok(()) => THERMITE_OK,
+        Err(e) => {
+            set_last_error(&e);
+            if matches!(e.root(), ThermiteError::Cancelled) {
+                THERMITE_CANCELLED
+            } else {
+                THERMITE_ERROR
+            }
+        }
+    }
+}
+
+fn into_c_string(res: Result<String>) -> *mut c_char {
+    match res.and_then(|s| CString::new(s).map_err(ThermiteError::from)) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            set_last_error(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string
+unsafe fn to_str<'a>(s: *const c_char, name: &str) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(ThermiteError::NullPointer(name.into()));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| ThermiteError::UTF8Error)
+}
+
+/// Returns the message of the last error on this thread, or NULL if there wasn't one
+///
+/// The string is owned by thermite and valid until the next failing call on the same thread
+#[no_mangle]
+pub extern "C" fn thermite_last_error() -> *const c_char {
+    LAST_ERROR
+        .try_with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+        .unwrap_or(ptr::null())
+}
+
+/// Frees a string returned by thermite
+///
+/// # Safety
+/// `s` must be NULL or a string returned by thermite that hasn't been freed yet
+#[no_mangle]
+pub unsafe extern "C" fn thermite_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        guard((), || drop(CString::from_raw(s)));
+    }
+}
+
+struct CallbackSink {
+    cb: unsafe extern "C" fn(*const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// SAFETY: the caller of `thermite_set_status_callback` promises the callback and user data can be used from any thread
+unsafe impl Send for CallbackSink {}
+unsafe impl Sync for CallbackSink {}
+
+impl StatusSink for CallbackSink {
+    fn event(&self, event: &StatusEvent) {
+        if let Ok(line) = CString::new(event.to_string()) {
+            // SAFETY: see `thermite_set_status_callback`
+            unsafe { (self.cb)(line.as_ptr(), self.user_data) };
+        }
+    }
+}
+
+/// Sets the callback that receives status lines from every operation. Passing NULL removes it
+///
+/// # Safety
+/// `cb` must be safe to call from any thread with `user_data` until it is replaced or removed
+#[no_mangle]
+pub unsafe extern "C" fn thermite_set_status_callback(
+    cb: ThermiteStatusCallback,
+    user_data: *mut c_void,
+) {
+    guard((), || match cb {
+        Some(cb) => status::set_status_sink(CallbackSink { cb, user_data }),
+        None => status::clear_status_sink(),
+    });
+}
+
+/// Fetches the package index as a JSON array of mods, or NULL on error
+#[no_mangle]
+pub extern "C" fn thermite_get_package_index_json() -> *mut c_char {
+    call_string(|| {
+        api::get_package_index().and_then(|index| serde_json::to_string(&index).map_err(Into::into))
+    })
+}
+
+/// Downloads `url` to the file at `output_path`, calling `progress` after every chunk if it isn't NULL
+///
+/// # Safety
+/// `url` and `output_path` must be valid NUL-terminated strings.
+/// `progress` must be safe to call with `user_data` for the duration of the call
+#[no_mangle]
+pub unsafe extern "C" fn thermite_download(
+    url: *const c_char,
+    output_path: *const c_char,
+    progress: ThermiteProgressCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    call(|| {
+        let url = to_str(url, "url")?;
+        let file = File::create(to_str(output_path, "output_path")?)?;
+        manage::download_with_progress(file, url, |_, current, total| {
+            if let Some(cb) = progress {
+                cb(current, total, user_data);
+            }
+        })?;
+        Ok(())
+    })
+}
+
+/// Installs the mod archive at `zip_path` into `target_dir` as `mod_string` (`author-name-X.Y.Z`)
+///
+/// # Safety
+/// All arguments must be valid NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn thermite_install_mod(
+    mod_string: *const c_char,
+    zip_path: *const c_char,
+    target_dir: *const c_char,
+) -> c_int {
+    call(|| {
+        let archive = File::open(to_str(zip_path, "zip_path")?)?;
+        manage::install_mod(
+            to_str(mod_string, "mod_string")?,
+            archive,
+            to_str(target_dir, "target_dir")?,
+        )?;
+        Ok(())
+    })
+}
+
+/// Creates a [`ModManager`] for `profile` of the game at `game_dir`, or NULL on error
+///
+/// `profile` may be NULL to use the default profile. Free the manager with [`thermite_manager_free`]
+///
+/// # Safety
+/// `game_dir` must be a valid NUL-terminated string, `profile` must be NULL or one
+#[no_mangle]
+pub unsafe extern "C" fn thermite_manager_new(
+    game_dir: *const c_char,
+    profile: *const c_char,
+) -> *mut ModManager {
+    guard(ptr::null_mut(), || {
+        let manager = (|| {
+            let game_dir = PathBuf::from(to_str(game_dir, "game_dir")?);
+            let profile = if profile.is_null() {
+                DEFAULT_PROFILE
+            } else {
+                to_str(profile, "profile")?
+            };
+            ModManager::with_config(game_dir, profile, config::config().as_ref().clone())
+        })();
+
+        match manager {
+            Ok(m) => Box::into_raw(Box::new(m)),
+            Err(e) => {
+                set_last_error(&e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// # Safety
+/// `manager` must be NULL or a manager returned by [`thermite_manager_new`] that hasn't been freed yet
+#[no_mangle]
+pub unsafe extern "C" fn thermite_manager_free(manager: *mut ModManager) {
+    if !manager.is_null() {
+        guard((), || drop(Box::from_raw(manager)));
+    }
+}
+
+/// # Safety
+/// `manager` must be NULL or a live manager that isn't being used from another thread
+unsafe fn manager_mut<'a>(manager: *mut ModManager) -> Result<&'a mut ModManager> {
+    manager
+        .as_mut()
+        .ok_or_else(|| ThermiteError::NullPointer("manager".into()))
+}
+
+/// Installs a package (`author-name` or `author-name-X.Y.Z`) and its dependencies
+///
+/// # Safety
+/// `manager` must be a live manager that isn't being used from another thread, `name` a valid NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn thermite_manager_install(
+    manager: *mut ModManager,
+    name: *const c_char,
+) -> c_int {
+    call(|| {
+        manager_mut(manager)?.install(to_str(name, "name")?)?;
+        Ok(())
+    })
+}
+
+/// Removes every installed version of a package
+///
+/// # Safety
+/// `manager` must be a live manager that isn't being used from another thread, `name` a valid NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn thermite_manager_remove(
+    manager: *mut ModManager,
+    name: *const c_char,
+) -> c_int {
+    call(|| manager_mut(manager)?.remove(to_str(name, "name")?))
+}
+
+/// Returns the available updates as a JSON array of `{name, installed, latest}`, or NULL on error
+///
+/// # Safety
+/// `manager` must be a live manager that isn't being used from another thread
+#[no_mangle]
+pub unsafe extern "C" fn thermite_manager_check_updates_json(
+    manager: *mut ModManager,
+) -> *mut c_char {
+    call_string(|| {
+        let updates = manager_mut(manager)?.check_updates()?;
+        Ok(serde_json::to_string(&updates)?)
+    })
+}
+
+/// Updates every outdated package
+///
+/// # Safety
+/// `manager` must be a live manager that isn't being used from another thread
+#[no_mangle]
+pub unsafe extern "C" fn thermite_manager_update_all(manager: *mut ModManager) -> c_int {
+    call(|| {
+        manager_mut(manager)?.update_all()?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{ffi::CStr, ptr};
+
+    use super::{
+        call, thermite_install_mod, thermite_last_error, thermite_manager_free,
+        thermite_manager_new, thermite_manager_remove, THERMITE_ERROR,
+    };
+
+    #[test]
+    fn errors_are_reported() {
+        let res = unsafe { thermite_install_mod(ptr::null(), ptr::null(), ptr::null()) };
+        assert_eq!(res, THERMITE_ERROR);
+
+        let msg = unsafe { CStr::from_ptr(thermite_last_error()) };
+        assert_eq!(msg.to_str().unwrap(), "zip_path was NULL");
+    }
+
+    #[test]
+    fn panics_are_reported() {
+        assert_eq!(call(|| panic!("boom")), THERMITE_ERROR);
+        let msg = unsafe { CStr::from_ptr(thermite_last_error()) };
+        assert_eq!(msg.to_str().unwrap(), "thermite panicked: boom");
+    }
+
+    #[test]
+    fn manager_lifecycle() {
+        let manager = unsafe { thermite_manager_new(c"./test_ffi_manager".as_ptr(), ptr::null()) };
+        assert!(!manager.is_null());
+
+        let res = unsafe { thermite_manager_remove(manager, c"Foo-Bar".as_ptr()) };
+        assert_eq!(res, THERMITE_ERROR);
+        let msg = unsafe { CStr::from_ptr(thermite_last_error()) };
+        assert_eq!(msg.to_str().unwrap(), "Foo-Bar is not installed");
+
+        unsafe { thermite_manager_free(manager) };
+    }
+}
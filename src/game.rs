@@ -0,0 +1,108 @@
+//! Describing the game and mod loader thermite manages
+//!
+//! The Titanfall 2 and Northstar specifics thermite relies on come from the [`GameSpec`] in
+//! `ThermiteConfig::game`, so forks of Northstar, or other games with a similar mod layout, only need a
+//! different spec.
+
+use crate::{TITANFALL2_ORIGIN_IDS, TITANFALL2_STEAM_ID};
+
+/// A game and the mod loader installed into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSpec {
+    pub name: String,
+    pub steam_id: u32,
+    /// Origin/EA App ids
+    pub origin_ids: Vec<String>,
+    /// The loader's default profile directory, relative to the game directory
+    pub profile: String,
+    /// Names of the mods the loader ships, as found in their `mod.json` files
+    pub core_mods: Vec<String>,
+    /// The loader's own Thunderstore package, e.g. `northstar-Northstar`. Packages depending on it don't pull
+    /// it in as a regular mod
+    pub loader_package: String,
+    /// The loader's DLL, relative to the game directory
+    pub loader_dll: String,
+    /// The executable that injects the loader, relative to the game directory
+    pub launcher: String,
+    /// The proxy DLL that loads the loader when the game is started normally, relative to the game directory
+    pub proxy_dll: String,
+    /// Executable names of the game's processes, see
+    /// [`is_game_running`](crate::core::utils::is_game_running)
+    pub processes: Vec<String>,
+}
+
+impl Default for GameSpec {
+    fn default() -> Self {
+        Self::titanfall2()
+    }
+}
+
+impl GameSpec {
+    /// Titanfall 2 with Northstar
+    #[must_use]
+    pub fn titanfall2() -> Self {
+        Self {
+            name: "Titanfall 2".into(),
+            steam_id: TITANFALL2_STEAM_ID,
+            origin_ids: TITANFALL2_ORIGIN_IDS.map(Into::into).to_vec(),
+            profile: "R2Northstar".into(),
+            core_mods: vec![
+                "Northstar.Client".into(),
+                "Northstar.Custom".into(),
+                "Northstar.CustomServers".into(),
+            ],
+            loader_package: "northstar-Northstar".into(),
+            loader_dll: "Northstar.dll".into(),
+            launcher: "NorthstarLauncher.exe".into(),
+            proxy_dll: "bin/x64_retail/wsock32.dll".into(),
+            processes: vec!["Titanfall2.exe".into(), "NorthstarLauncher.exe".into()],
+        }
+    }
+
+    /// Whether `name` is one of the loader's core mods, ignoring case
+    #[must_use]
+    pub fn is_core_mod(&self, name: impl AsRef<str>) -> bool {
+        self.core_mods
+            .iter()
+            .any(|m| m.eq_ignore_ascii_case(name.as_ref()))
+    }
+
+    /// Whether a Thunderstore dependency string refers to the loader's package
+    #[must_use]
+    pub fn is_loader_package(&self, dependency: impl AsRef<str>) -> bool {
+        let dependency = dependency.as_ref().to_lowercase();
+        let loader = self.loader_package.to_lowercase();
+        dependency == loader
+            || dependency
+                .strip_prefix(&loader)
+                .is_some_and(|version| version.starts_with('-'))
+    }
+
+    /// The author the loader's core mods are published under
+    #[must_use]
+    pub fn loader_author(&self) -> &str {
+        self.loader_package
+            .split_once('-')
+            .map_or(self.loader_package.as_str(), |(author, _)| author)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GameSpec;
+    use crate::CORE_MODS;
+
+    #[test]
+    fn titanfall2_spec() {
+        let spec = GameSpec::default();
+        assert!(CORE_MODS.iter().all(|m| spec.is_core_mod(m)));
+        assert!(spec.is_core_mod("Northstar.Client"));
+        assert!(!spec.is_core_mod("Mock.Bar"));
+
+        assert!(spec.is_loader_package("northstar-Northstar-1.20.0"));
+        assert!(spec.is_loader_package("Northstar-Northstar"));
+        assert!(!spec.is_loader_package("northstar-NorthstarMods-1.0.0"));
+        assert!(!spec.is_loader_package("Foo-Bar-1.0.0"));
+        assert_eq!(spec.loader_author(), "northstar");
+    }
+}
@@ -0,0 +1,826 @@
+//! Pluggable HTTP layer used for every network request thermite makes
+//!
+//! By default requests go through [`UreqBackend`]. Embedders that already have an HTTP stack
+//! can implement [`HttpBackend`] for it and install it with [`set_backend`], with the `reqwest`
+//! feature there's `ReqwestBackend` for reqwest clients.
+//!
+//! Backends only do GET requests, since that's all thermite needs. [`HttpBackend`]s are blocking, the
+//! `*_async` functions of the `async` feature go through an [`AsyncHttpBackend`] instead, by default
+//! `AsyncReqwestBackend`. Installing one for another runtime with [`set_async_backend`] keeps those functions
+//! off tokio.
+//!
+//! On `wasm32` there is no default backend, so a backend wrapping e.g. the browser's `fetch`
+//! must be installed before making any requests.
+
+use std::{
+    fmt::{self, Debug},
+    io::{self, Read},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+
+use lazy_static::lazy_static;
+use tracing::debug;
+
+use crate::{
+    cancel, config,
+    error::{Result, ThermiteError},
+    metrics,
+};
+
+/// A GET request to be performed by an [`HttpBackend`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// Overall time limit for the request, if any
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: vec![],
+            timeout: None,
+        }
+    }
+
+    #[must_use]
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+}
+
+/// The response to an [`HttpRequest`]
+///
+/// Backends should return a response for every status code, thermite decides which ones are errors
+pub struct HttpResponse {
+    pub status: u16,
+    /// The final URL of the response, after any redirects
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl HttpResponse {
+    /// Returns the value of the first header matching `name`, ignoring case
+    pub fn header(&self, name: impl AsRef<str>) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[must_use]
+    pub const fn is_success(&self) -> bool {
+        self.status >= 200 && self.status < 300
+    }
+
+    /// Reads the whole body into a string
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * The body isn't valid UTF8
+    pub fn into_string(mut self) -> io::Result<String> {
+        let mut buf = String::new();
+        self.body.read_to_string(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn into_reader(self) -> Box<dyn Read + Send> {
+        self.body
+    }
+}
+
+impl Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Something that can perform HTTP requests for thermite
+pub trait HttpBackend: Send + Sync {
+    /// Perform a GET request
+    ///
+    /// # Errors
+    /// Transport level failures. Non-2xx responses should be returned as `Ok`
+    fn get(&self, request: &HttpRequest) -> Result<HttpResponse>;
+}
+
+/// A boxed future, as returned by [`AsyncHttpBackend`]s
+#[cfg(feature = "async")]
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The body of an [`AsyncHttpResponse`], read a chunk at a time
+#[cfg(feature = "async")]
+pub trait AsyncBody: Send {
+    /// The next chunk of the body, `None` once all of it was read
+    ///
+    /// # Errors
+    /// * IO errors, `TimedOut` ones if the request's timeout passed
+    fn chunk(&mut self) -> BoxFuture<'_, io::Result<Option<Vec<u8>>>>;
+}
+
+/// The response to an [`HttpRequest`] made by an [`AsyncHttpBackend`]
+///
+/// Like with [`HttpResponse`], backends should return a response for every status code
+#[cfg(feature = "async")]
+pub struct AsyncHttpResponse {
+    pub status: u16,
+    /// The final URL of the response, after any redirects
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Box<dyn AsyncBody>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncHttpResponse {
+    /// Returns the value of the first header matching `name`, ignoring case
+    pub fn header(&self, name: impl AsRef<str>) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[must_use]
+    pub const fn is_success(&self) -> bool {
+        self.status >= 200 && self.status < 300
+    }
+
+    /// Reads the whole body
+    ///
+    /// # Errors
+    /// * IO errors
+    pub async fn into_bytes(mut self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        while let Some(chunk) = self.body.chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
+    }
+}
+
+#[cfg(feature = "async")]
+impl Debug for AsyncHttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncHttpResponse")
+            .field("status", &self.status)
+            .field("url", &self.url)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Something that can perform HTTP requests for the `*_async` functions, on whichever runtime it was built for
+#[cfg(feature = "async")]
+pub trait AsyncHttpBackend: Send + Sync {
+    /// Perform a GET request
+    ///
+    /// # Errors
+    /// Transport level failures. Non-2xx responses should be returned as `Ok`
+    fn get<'a>(&'a self, request: &'a HttpRequest) -> BoxFuture<'a, Result<AsyncHttpResponse>>;
+
+    /// Resolves after `duration`, used to wait between retries and to throttle downloads
+    ///
+    /// thermite has no timer of its own, so this should be the one of the backend's runtime
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The proxy [`UreqBackend`] connects through
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// `<protocol>://<user>:<password>@<host>:<port>`, where only the host is required. `None` connects
+    /// directly
+    pub url: Option<String>,
+    /// Hosts connected to directly. Each one matches its subdomains too, `*` matches every host
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// The proxy from `HTTPS_PROXY`, `HTTP_PROXY` or `ALL_PROXY` and the hosts from `NO_PROXY`, in upper or
+    /// lower case
+    #[must_use]
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            [name.to_uppercase(), name.to_lowercase()]
+                .into_iter()
+                .find_map(|name| std::env::var(name).ok().filter(|v| !v.trim().is_empty()))
+        };
+        Self {
+            url: ["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY"]
+                .into_iter()
+                .find_map(var),
+            no_proxy: var("NO_PROXY")
+                .map(|hosts| {
+                    hosts
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|h| !h.is_empty())
+                        .map(ToOwned::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether requests to `url` skip the proxy
+    #[cfg(not(target_arch = "wasm32"))]
+    fn bypasses(&self, url: &str) -> bool {
+        let host = host(url).to_lowercase();
+        self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim_start_matches("*.").trim_start_matches('.');
+            entry == "*"
+                || host == entry.to_lowercase()
+                || host
+                    .strip_suffix(&entry.to_lowercase())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+}
+
+/// The host of `url`, without user info or port
+#[cfg(not(target_arch = "wasm32"))]
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    if let Some(ipv6) = host.strip_prefix('[') {
+        return ipv6.split(']').next().unwrap_or_default();
+    }
+    host.split(':').next().unwrap_or_default()
+}
+
+/// The default backend, backed by a `ureq::Agent`
+///
+/// The default one connects through the proxy from the environment, see [`ProxyConfig::from_env`]. To use
+/// another proxy, install a backend with it:
+///
+/// ```no_run
+/// # use thermite::http::{set_backend, ProxyConfig, UreqBackend};
+/// let proxy = ProxyConfig {
+///     url: Some("http://proxy.example.com:3128".into()),
+///     no_proxy: vec!["localhost".into()],
+/// };
+/// set_backend(UreqBackend::with_proxy(&proxy)?);
+/// # Ok::<(), thermite::error::ThermiteError>(())
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct UreqBackend {
+    agent: ureq::Agent,
+    /// Agent for the hosts in `proxy.no_proxy`, `agent` is used if it's `None`
+    direct: Option<ureq::Agent>,
+    proxy: ProxyConfig,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl UreqBackend {
+    #[must_use]
+    pub const fn new(agent: ureq::Agent) -> Self {
+        Self {
+            agent,
+            direct: None,
+            proxy: ProxyConfig {
+                url: None,
+                no_proxy: Vec::new(),
+            },
+        }
+    }
+
+    /// A backend connecting through `proxy`, or directly if it doesn't have a URL
+    ///
+    /// # Errors
+    /// * `ThermiteError::MalformedUrl` if the proxy URL is invalid
+    pub fn with_proxy(proxy: &ProxyConfig) -> Result<Self> {
+        let Some(url) = &proxy.url else {
+            return Ok(Self::new(ureq::agent()));
+        };
+        Ok(Self {
+            agent: ureq::AgentBuilder::new()
+                .proxy(ureq::Proxy::new(url).map_err(|_| ThermiteError::MalformedUrl(url.clone()))?)
+                .build(),
+            direct: Some(ureq::agent()),
+            proxy: proxy.clone(),
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for UreqBackend {
+    fn default() -> Self {
+        Self::with_proxy(&ProxyConfig::from_env()).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring the proxy from the environment: {e}");
+            Self::new(ureq::agent())
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HttpBackend for UreqBackend {
+    fn get(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let started = crate::time::Instant::now();
+        let agent = match &self.direct {
+            Some(direct) if self.proxy.bypasses(&request.url) => direct,
+            _ => &self.agent,
+        };
+        let mut req = agent.get(&request.url);
+        for (name, value) in &request.headers {
+            req = req.set(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            req = req.timeout(timeout);
+        }
+
+        let res = match req.call() {
+            Ok(res) | Err(ureq::Error::Status(_, res)) => res,
+            Err(ureq::Error::Transport(t)) if t.kind() == ureq::ErrorKind::InvalidUrl => {
+                return Err(ThermiteError::MalformedUrl(request.url.clone()))
+            }
+            Err(e) => {
+                return Err(ThermiteError::from_ureq(
+                    e,
+                    format!("requesting {}", request.url),
+                    started,
+                    request.timeout,
+                ))
+            }
+        };
+
+        let headers = res
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = res.header(&name)?.to_owned();
+                Some((name, value))
+            })
+            .collect();
+
+        Ok(HttpResponse {
+            status: res.status(),
+            url: res.get_url().to_owned(),
+            headers,
+            body: Box::new(res.into_reader()),
+        })
+    }
+}
+
+/// A backend backed by a blocking `reqwest::Client`, with the `reqwest` feature
+///
+/// For embedders already using reqwest, so thermite shares their client's connection pool, proxy and TLS
+/// settings. Like reqwest's blocking client it must not be used from inside a tokio runtime, async frontends
+/// use the `*_async` functions, which go through `AsyncReqwestBackend` instead.
+///
+/// ```no_run
+/// # use thermite::http::{set_backend, ReqwestBackend};
+/// set_backend(ReqwestBackend::new(reqwest::blocking::Client::new()));
+/// ```
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestBackend {
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+impl ReqwestBackend {
+    #[must_use]
+    pub const fn new(client: reqwest::blocking::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+impl HttpBackend for ReqwestBackend {
+    fn get(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        let started = crate::time::Instant::now();
+        let mut req = self.client.get(&request.url);
+        for (name, value) in &request.headers {
+            req = req.header(name, value);
+        }
+        if let Some(timeout) = request.timeout {
+            req = req.timeout(timeout);
+        }
+
+        let res = req.send().map_err(|e| reqwest_error(e, request, started))?;
+
+        Ok(HttpResponse {
+            status: res.status().as_u16(),
+            url: res.url().to_string(),
+            headers: reqwest_headers(res.headers()),
+            body: Box::new(res),
+        })
+    }
+}
+
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+fn reqwest_error(
+    e: reqwest::Error,
+    request: &HttpRequest,
+    started: crate::time::Instant,
+) -> ThermiteError {
+    if e.is_builder() {
+        ThermiteError::MalformedUrl(request.url.clone())
+    } else if e.is_timeout() {
+        ThermiteError::Timeout {
+            operation: format!("requesting {}", request.url),
+            elapsed: started.elapsed(),
+            limit: request.timeout,
+        }
+    } else {
+        io::Error::other(e).into()
+    }
+}
+
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+fn reqwest_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned()))
+        })
+        .collect()
+}
+
+/// The default [`AsyncHttpBackend`], backed by an async `reqwest::Client`
+///
+/// reqwest runs on tokio, so the `*_async` functions have to be awaited inside a tokio runtime while this is
+/// the backend. For other runtimes implement [`AsyncHttpBackend`] with their HTTP client and install it with
+/// [`set_async_backend`].
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone, Default)]
+pub struct AsyncReqwestBackend {
+    client: reqwest::Client,
+}
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+impl AsyncReqwestBackend {
+    #[must_use]
+    pub const fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+impl AsyncHttpBackend for AsyncReqwestBackend {
+    fn get<'a>(&'a self, request: &'a HttpRequest) -> BoxFuture<'a, Result<AsyncHttpResponse>> {
+        Box::pin(async move {
+            let started = crate::time::Instant::now();
+            let mut req = self.client.get(&request.url);
+            for (name, value) in &request.headers {
+                req = req.header(name, value);
+            }
+            if let Some(timeout) = request.timeout {
+                req = req.timeout(timeout);
+            }
+
+            let res = req
+                .send()
+                .await
+                .map_err(|e| reqwest_error(e, request, started))?;
+            Ok(AsyncHttpResponse {
+                status: res.status().as_u16(),
+                url: res.url().to_string(),
+                headers: reqwest_headers(res.headers()),
+                body: Box::new(ReqwestBody(res)),
+            })
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+struct ReqwestBody(reqwest::Response);
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+impl AsyncBody for ReqwestBody {
+    fn chunk(&mut self) -> BoxFuture<'_, io::Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            match self.0.chunk().await {
+                Ok(chunk) => Ok(chunk.map(|c| c.to_vec())),
+                Err(e) if e.is_timeout() => Err(io::Error::new(io::ErrorKind::TimedOut, e)),
+                Err(e) => Err(io::Error::other(e)),
+            }
+        })
+    }
+}
+
+/// Placeholder used until a backend is set on targets without a default one
+#[cfg(target_arch = "wasm32")]
+struct Unconfigured;
+
+#[cfg(target_arch = "wasm32")]
+impl HttpBackend for Unconfigured {
+    fn get(&self, request: &HttpRequest) -> Result<HttpResponse> {
+        Err(ThermiteError::NoHttpBackend(request.url.clone()))
+    }
+}
+
+#[cfg(all(feature = "async", target_arch = "wasm32"))]
+impl AsyncHttpBackend for Unconfigured {
+    fn get<'a>(&'a self, request: &'a HttpRequest) -> BoxFuture<'a, Result<AsyncHttpResponse>> {
+        Box::pin(async move { Err(ThermiteError::NoHttpBackend(request.url.clone())) })
+    }
+
+    fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_backend() -> Arc<dyn HttpBackend> {
+    Arc::new(UreqBackend::default())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_backend() -> Arc<dyn HttpBackend> {
+    Arc::new(Unconfigured)
+}
+
+lazy_static! {
+    static ref BACKEND: RwLock<Arc<dyn HttpBackend>> = RwLock::new(default_backend());
+}
+
+/// Replaces the backend used for all requests
+pub fn set_backend(backend: impl HttpBackend + 'static) {
+    if let Ok(mut lock) = BACKEND.write() {
+        *lock = Arc::new(backend);
+    }
+}
+
+/// Returns the backend currently in use
+pub fn backend() -> Arc<dyn HttpBackend> {
+    match BACKEND.read() {
+        Ok(lock) => lock.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+fn default_async_backend() -> Arc<dyn AsyncHttpBackend> {
+    Arc::new(AsyncReqwestBackend::default())
+}
+
+#[cfg(all(feature = "async", target_arch = "wasm32"))]
+fn default_async_backend() -> Arc<dyn AsyncHttpBackend> {
+    Arc::new(Unconfigured)
+}
+
+#[cfg(feature = "async")]
+lazy_static! {
+    static ref ASYNC_BACKEND: RwLock<Arc<dyn AsyncHttpBackend>> =
+        RwLock::new(default_async_backend());
+}
+
+/// Replaces the backend used for the requests of the `*_async` functions
+#[cfg(feature = "async")]
+pub fn set_async_backend(backend: impl AsyncHttpBackend + 'static) {
+    if let Ok(mut lock) = ASYNC_BACKEND.write() {
+        *lock = Arc::new(backend);
+    }
+}
+
+/// Returns the async backend currently in use
+#[cfg(feature = "async")]
+pub fn async_backend() -> Arc<dyn AsyncHttpBackend> {
+    match ASYNC_BACKEND.read() {
+        Ok(lock) => lock.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Performs a request with the current backend, turning non-2xx responses into errors
+///
+/// 5xx and 429 responses are retried with exponential backoff, as set in the global config's `network`.
+/// `operation` describes the request for `Timeout` errors
+pub(crate) fn get(request: &HttpRequest, operation: impl Into<String>) -> Result<HttpResponse> {
+    let network = config::config().network;
+    let operation = operation.into();
+    let request = limited(request, &network);
+
+    let mut attempt = 0;
+    loop {
+        let res = backend()
+            .get(&request)
+            .map_err(|e| transport_error(e, &operation))
+            .and_then(|res| checked(res.status, &res.url).map(|()| res));
+        match retry_wait(&res, &request, &network, attempt) {
+            Some(wait) => cancel::sleep(wait, &operation)?,
+            None => return res,
+        }
+        attempt += 1;
+    }
+}
+
+/// [`get`] with the current [`AsyncHttpBackend`]
+///
+/// Cancel tokens around the poll are checked before every attempt, but dropping the future is how async
+/// requests are usually cancelled
+#[cfg(feature = "async")]
+pub(crate) async fn get_async(
+    request: &HttpRequest,
+    operation: impl Into<String>,
+) -> Result<AsyncHttpResponse> {
+    let network = config::config().network;
+    let operation = operation.into();
+    let request = limited(request, &network);
+    let backend = async_backend();
+
+    let mut attempt = 0;
+    loop {
+        cancel::checkpoint(&operation)?;
+        let res = backend
+            .get(&request)
+            .await
+            .map_err(|e| transport_error(e, &operation))
+            .and_then(|res| checked(res.status, &res.url).map(|()| res));
+        match retry_wait(&res, &request, &network, attempt) {
+            Some(wait) => backend.sleep(wait).await,
+            None => return res,
+        }
+        attempt += 1;
+    }
+}
+
+/// `request` with its timeout capped by the config's `network.timeout`
+fn limited(request: &HttpRequest, network: &config::NetworkConfig) -> HttpRequest {
+    let mut request = request.clone();
+    if let Some(limit) = network.timeout {
+        request.timeout = Some(request.timeout.map_or(limit, |t| t.min(limit)));
+    }
+    request
+}
+
+/// How long to wait before retrying a 5xx or 429 response with exponential backoff, `None` if `res` is final
+fn retry_wait<T>(
+    res: &Result<T>,
+    request: &HttpRequest,
+    network: &config::NetworkConfig,
+    attempt: u32,
+) -> Option<Duration> {
+    match res {
+        Err(ThermiteError::HttpStatus { status, .. })
+            if (*status == 429 || *status >= 500) && attempt < network.retries =>
+        {
+            let wait = network.backoff.saturating_mul(2u32.saturating_pow(attempt));
+            debug!("Got {status} from {}, retrying in {wait:?}", request.url);
+            metrics::record_retry();
+            Some(wait)
+        }
+        _ => None,
+    }
+}
+
+/// Records a failed request, naming `operation` in timeouts
+fn transport_error(e: ThermiteError, operation: &str) -> ThermiteError {
+    metrics::record_request(false);
+    match e {
+        ThermiteError::Timeout { elapsed, limit, .. } => ThermiteError::Timeout {
+            operation: operation.into(),
+            elapsed,
+            limit,
+        },
+        e => e,
+    }
+}
+
+/// Records a request that got a response, which is an error unless it's a 2xx one
+fn checked(status: u16, url: &str) -> Result<()> {
+    let success = (200..300).contains(&status);
+    metrics::record_request(success);
+    if success {
+        Ok(())
+    } else {
+        Err(ThermiteError::HttpStatus {
+            url: url.into(),
+            status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{host, HttpBackend, HttpRequest, HttpResponse, ProxyConfig, UreqBackend};
+    use crate::{
+        error::{Result, ThermiteError},
+        test_util::MockServer,
+    };
+
+    struct Canned;
+
+    impl HttpBackend for Canned {
+        fn get(&self, request: &HttpRequest) -> Result<HttpResponse> {
+            Ok(HttpResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: vec![("Content-Length".into(), "5".into())],
+                body: Box::new(Cursor::new(b"hello")),
+            })
+        }
+    }
+
+    #[test]
+    fn custom_backend() {
+        let res = Canned
+            .get(&HttpRequest::get("http://localhost").header("accept", "text/plain"))
+            .expect("canned response");
+
+        assert!(res.is_success());
+        assert_eq!(res.header("content-length"), Some("5"));
+        assert_eq!(res.into_string().unwrap(), "hello");
+    }
+
+    #[test]
+    fn proxy_bypass() {
+        assert_eq!(
+            host("https://user:pw@Example.com:443/path?q"),
+            "Example.com"
+        );
+        assert_eq!(host("http://[::1]:8080/"), "::1");
+
+        let proxy = ProxyConfig {
+            url: Some("http://127.0.0.1:9".into()),
+            no_proxy: vec![".thunderstore.io".into(), "127.0.0.1".into()],
+        };
+        assert!(proxy.bypasses("https://northstar.thunderstore.io/c/northstar/"));
+        assert!(proxy.bypasses("https://thunderstore.io"));
+        assert!(!proxy.bypasses("https://notthunderstore.io"));
+
+        let server = MockServer::start().expect("start mock server");
+        server.serve("/hello", "hello");
+        let url = format!("{}/hello", server.url());
+        let backend = UreqBackend::with_proxy(&proxy).expect("backend");
+        let res = backend
+            .get(&HttpRequest::get(&url))
+            .expect("direct request");
+        assert_eq!(res.into_string().unwrap(), "hello");
+
+        // nothing listens on the proxy's port
+        let backend = UreqBackend::with_proxy(&ProxyConfig {
+            no_proxy: vec![],
+            ..proxy
+        })
+        .expect("backend");
+        assert!(backend.get(&HttpRequest::get(&url)).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "reqwest")]
+    fn reqwest_backend() {
+        let server = MockServer::start().expect("start mock server");
+        server.serve("/hello", "hello");
+        let backend = super::ReqwestBackend::default();
+        let res = backend
+            .get(
+                &HttpRequest::get(format!("{}/hello", server.url())).header("accept", "text/plain"),
+            )
+            .expect("request");
+        assert!(res.is_success());
+        assert_eq!(res.header("content-length"), Some("5"));
+        assert_eq!(res.into_string().unwrap(), "hello");
+
+        let res = backend
+            .get(&HttpRequest::get(format!("{}/missing", server.url())))
+            .expect("non-2xx responses aren't errors");
+        assert_eq!(res.status, 404);
+        assert!(matches!(
+            backend.get(&HttpRequest::get("not a url")),
+            Err(ThermiteError::MalformedUrl(_))
+        ));
+    }
+
+    #[test]
+    fn malformed_url() {
+        assert!(matches!(
+            UreqBackend::new(ureq::agent()).get(&HttpRequest::get("not a url")),
+            Err(ThermiteError::MalformedUrl(url)) if url == "not a url"
+        ));
+        assert!(matches!(
+            UreqBackend::with_proxy(&ProxyConfig {
+                url: Some("ftp://[::1".into()),
+                no_proxy: vec![],
+            }),
+            Err(ThermiteError::MalformedUrl(_))
+        ));
+    }
+}
@@ -0,0 +1,361 @@
+//! Importing profiles from other mod managers
+//!
+//! Importers only read the other manager's files. Applying an [`ImportedProfile`] to a
+//! [`ModManager`] installs the same package versions from Thunderstore, restores which ones are enabled and
+//! copies the mods' saved data, so nothing has to be set up again by hand.
+//!
+//! * r2modman keeps a `mods.yml` listing every package in each profile directory
+//! * Viper installs straight into the game's `R2Northstar` directory and stores the game's location in its
+//!   `viper.json`
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::{
+    config,
+    core::{find_mods, get_enabled_mods, report::InstallReport, vfs},
+    error::{Result, ThermiteError},
+    manager::ModManager,
+};
+
+/// Directories inside a profile holding data mods saved at runtime
+const SAVE_DATA_DIR: &str = "save_data";
+
+/// A package recorded by another mod manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedMod {
+    pub author: String,
+    pub name: String,
+    pub version: String,
+    pub enabled: bool,
+}
+
+impl ImportedMod {
+    /// `author-name-X.Y.Z`
+    #[must_use]
+    pub fn full_name(&self) -> String {
+        format!("{}-{}-{}", self.author, self.name, self.version)
+    }
+}
+
+/// A profile read from another mod manager, ready to be applied with [`ImportedProfile::apply`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedProfile {
+    pub mods: Vec<ImportedMod>,
+    /// Files to copy, as (source, destination relative to the profile directory)
+    pub configs: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ImportedProfile {
+    /// Installs every mod into `manager`'s profile, restores their enabled state and copies the configs
+    ///
+    /// # Errors
+    /// * A package version isn't in the index
+    /// * Network and IO errors
+    pub fn apply(&self, manager: &mut ModManager) -> Result<Vec<InstallReport>> {
+        let mut reports = vec![];
+        for m in &self.mods {
+            reports.append(&mut manager.install(m.full_name())?);
+            if !m.enabled {
+                manager.enable(format!("{}-{}", m.author, m.name), false)?;
+            }
+        }
+
+        let fs = vfs::current();
+        let profile = manager.profile_dir();
+        for (source, rel) in &self.configs {
+            let target = profile.join(rel);
+            if let Some(parent) = target.parent() {
+                fs.create_dir_all(parent)?;
+            }
+            debug!("Copying {} to {}", source.display(), target.display());
+            fs.write(&target, &fs.read(source)?)?;
+        }
+
+        Ok(reports)
+    }
+}
+
+/// The subset of a `mods.yml` entry thermite needs
+#[derive(Debug, Default)]
+struct R2Entry {
+    name: String,
+    major: Option<u32>,
+    minor: Option<u32>,
+    patch: Option<u32>,
+    enabled: bool,
+}
+
+/// Reads an r2modman profile, e.g. `r2modmanPlus-local/Northstar/profiles/Default`
+///
+/// The mod loader's own package, e.g. Northstar, is skipped since thermite installs it separately.
+///
+/// # Errors
+/// * The profile has no `mods.yml`
+/// * IO errors
+pub fn from_r2modman(profile_dir: impl AsRef<Path>) -> Result<ImportedProfile> {
+    let dir = profile_dir.as_ref();
+    let fs = vfs::current();
+    let path = dir.join("mods.yml");
+    if !fs.exists(&path)? {
+        return Err(ThermiteError::MissingFile(Box::new(path)));
+    }
+
+    let game = &config::config().game;
+    let mut profile = ImportedProfile::default();
+    for entry in parse_mods_yml(&fs.read_to_string(&path)?) {
+        let (Some(major), Some(minor), Some(patch)) = (entry.major, entry.minor, entry.patch)
+        else {
+            warn!("No version for {} in {}", entry.name, path.display());
+            continue;
+        };
+        let Some((author, name)) = entry.name.split_once('-') else {
+            warn!("Invalid package name {} in {}", entry.name, path.display());
+            continue;
+        };
+        if game.is_loader_package(&entry.name) {
+            continue;
+        }
+
+        profile.mods.push(ImportedMod {
+            author: author.into(),
+            name: name.into(),
+            version: format!("{major}.{minor}.{patch}"),
+            enabled: entry.enabled,
+        });
+    }
+
+    collect_files(
+        &dir.join(&game.profile).join(SAVE_DATA_DIR),
+        Path::new(SAVE_DATA_DIR),
+        &mut profile.configs,
+    )?;
+    Ok(profile)
+}
+
+/// Parses r2modman's `mods.yml`
+///
+/// Only handles what r2modman writes: a list of mappings with `versionNumber` as the one nested mapping
+fn parse_mods_yml(raw: &str) -> Vec<R2Entry> {
+    let mut entries = vec![];
+    let mut current: Option<R2Entry> = None;
+    for line in raw.lines() {
+        let trimmed = line.trim_start();
+        let item = trimmed.strip_prefix("- ");
+        if let Some(first) = item {
+            entries.extend(current.take());
+            current = Some(R2Entry {
+                enabled: true,
+                ..Default::default()
+            });
+            parse_yml_field(first, current.as_mut());
+        } else {
+            parse_yml_field(trimmed, current.as_mut());
+        }
+    }
+    entries.extend(current);
+    entries
+}
+
+fn parse_yml_field(field: &str, entry: Option<&mut R2Entry>) {
+    let (Some(entry), Some((key, value))) = (entry, field.split_once(':')) else {
+        return;
+    };
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    match key.trim() {
+        "name" => entry.name = value.into(),
+        "major" => entry.major = value.parse().ok(),
+        "minor" => entry.minor = value.parse().ok(),
+        "patch" => entry.patch = value.parse().ok(),
+        "enabled" => entry.enabled = value != "false",
+        _ => {}
+    }
+}
+
+#[derive(Deserialize)]
+struct ViperConfig {
+    gamepath: PathBuf,
+}
+
+/// Reads the mods Viper installed, given the path to its `viper.json`
+///
+/// # Errors
+/// * `viper.json` can't be read or doesn't have a `gamepath`
+/// * IO errors while scanning the game directory
+pub fn from_viper(config_path: impl AsRef<Path>) -> Result<ImportedProfile> {
+    let fs = vfs::current();
+    let viper: ViperConfig = serde_json::from_str(&fs.read_to_string(config_path.as_ref())?)?;
+    from_northstar_dir(viper.gamepath.join(&config::config().game.profile))
+}
+
+/// Reads the mods installed in an `R2Northstar` style directory, as Viper leaves them
+///
+/// # Errors
+/// * IO errors
+/// * Improperly formatted JSON files
+pub fn from_northstar_dir(dir: impl AsRef<Path>) -> Result<ImportedProfile> {
+    let dir = dir.as_ref();
+    let fs = vfs::current();
+    let enabled = match get_enabled_mods(dir) {
+        Ok(mut m) => {
+            // only reading, don't write the other manager's file back
+            m.dont_save();
+            Some(m)
+        }
+        Err(ThermiteError::MissingFile(_)) => None,
+        Err(e) => return Err(e),
+    };
+
+    let mut profile = ImportedProfile::default();
+    for packages in ["packages", "mods"].map(|d| dir.join(d)) {
+        if !fs.exists(&packages)? {
+            continue;
+        }
+        for m in find_mods(&packages)? {
+            let imported = ImportedMod {
+                author: m.author,
+                name: m.manifest.name,
+                version: m.manifest.version_number,
+                enabled: enabled
+                    .as_ref()
+                    .and_then(|e| e.get(&m.mod_json.name))
+                    .unwrap_or(true),
+            };
+            // packages with several mods show up once per mod
+            if !profile.mods.iter().any(|p| {
+                p.author == imported.author
+                    && p.name == imported.name
+                    && p.version == imported.version
+            }) {
+                profile.mods.push(imported);
+            }
+        }
+    }
+    collect_files(
+        &dir.join(SAVE_DATA_DIR),
+        Path::new(SAVE_DATA_DIR),
+        &mut profile.configs,
+    )?;
+    Ok(profile)
+}
+
+/// Adds every file below `source` to `files`, keeping its path relative to `rel`
+fn collect_files(source: &Path, rel: &Path, files: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+    let fs = vfs::current();
+    if !fs.exists(source)? {
+        return Ok(());
+    }
+
+    for entry in fs.read_dir(source)? {
+        let rel = rel.join(entry.file_name());
+        if entry.is_dir {
+            collect_files(&entry.path, &rel, files)?;
+        } else {
+            files.push((entry.path, rel));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor, path::PathBuf};
+
+    use crate::{
+        config::ThermiteConfig,
+        core::{get_enabled_mods, manage::install_mod, utils::TempDir},
+        manager::{ModManager, DEFAULT_PROFILE},
+        test_util::{mod_archive, MockPackage, MockServer},
+    };
+
+    use super::{from_r2modman, from_viper, ImportedMod};
+
+    const MODS_YML: &str = r#"- manifestVersion: 1
+  name: northstar-Northstar
+  authorName: northstar
+  versionNumber:
+    major: 1
+    minor: 20
+    patch: 0
+  enabled: true
+- manifestVersion: 1
+  name: Foo-Bar
+  authorName: Foo
+  websiteUrl: "https://example.com"
+  displayName: Bar
+  dependencies: []
+  versionNumber:
+    major: 1
+    minor: 0
+    patch: 0
+  enabled: false
+"#;
+
+    #[test]
+    fn import_r2modman() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+
+        let dir = TempDir::create("./test_import_r2modman").expect("Unable to create temp dir");
+        let r2 = dir.join("r2modman");
+        let save_data = r2.join("R2Northstar").join("save_data").join("Mock.Bar");
+        fs::create_dir_all(&save_data).unwrap();
+        fs::write(r2.join("mods.yml"), MODS_YML).unwrap();
+        fs::write(save_data.join("data.json"), "{}").unwrap();
+
+        let profile = from_r2modman(&r2).expect("import");
+        assert_eq!(
+            profile.mods,
+            [ImportedMod {
+                author: "Foo".into(),
+                name: "Bar".into(),
+                version: "1.0.0".into(),
+                enabled: false,
+            }]
+        );
+
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        let mut manager =
+            ModManager::with_config(dir.join("game"), DEFAULT_PROFILE, config).expect("manager");
+        profile.apply(&mut manager).expect("apply");
+
+        assert!(manager.lockfile().packages.contains_key("Foo-Bar"));
+        let enabled = get_enabled_mods(manager.profile_dir()).unwrap();
+        assert_eq!(enabled.get("Mock.Bar"), Some(false));
+        assert!(manager
+            .profile_dir()
+            .join("save_data")
+            .join("Mock.Bar")
+            .join("data.json")
+            .exists());
+    }
+
+    #[test]
+    fn import_viper() {
+        let dir = TempDir::create("./test_import_viper").expect("Unable to create temp dir");
+        let game = dir.join("Titanfall2");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            game.join("R2Northstar").join("packages"),
+        )
+        .expect("install");
+        let viper_json = dir.join("viper.json");
+        fs::write(
+            &viper_json,
+            serde_json::json!({ "gamepath": game, "lang": "en" }).to_string(),
+        )
+        .unwrap();
+
+        let profile = from_viper(&viper_json).expect("import");
+        assert_eq!(profile.mods.len(), 1);
+        assert_eq!(profile.mods[0].full_name(), "Foo-Bar-1.0.0");
+        assert!(profile.mods[0].enabled);
+        assert_eq!(profile.configs, Vec::<(PathBuf, PathBuf)>::new());
+    }
+}
@@ -0,0 +1,494 @@
+//! Queue of compound operations with progress, pausing and cancellation
+//!
+//! A [`Task`] is a named unit of work made of steps, such as downloading, verifying and extracting a package.
+//! Tasks are pushed onto a [`JobQueue`] and run with [`JobQueue::run`], at most `concurrency` at a time. The
+//! queue is cheap to clone, so one clone can run it on a worker thread while another is used to pause, resume,
+//! cancel and poll progress from a UI.
+//!
+//! Pausing takes effect at the next checkpoint: a step boundary or a progress update.
+
+use std::{
+    fmt::{self, Debug},
+    io::Cursor,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::core::manage::check_archive;
+use crate::{
+    cancel::{self, CancelToken},
+    config::{self, ThermiteConfig},
+    core::{
+        manage::{download_with_limit, install_with_config},
+        vfs,
+    },
+    error::{Result, ThermiteError},
+    model::ModVersion,
+    pool::{Executor, Job, ThreadPool},
+};
+
+/// Identifies a job within its queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Failed(String),
+    Cancelled,
+}
+
+impl JobState {
+    /// Returns `true` if the job won't run again
+    #[must_use]
+    pub const fn is_done(&self) -> bool {
+        matches!(self, Self::Finished | Self::Failed(_) | Self::Cancelled)
+    }
+}
+
+/// A snapshot of one job's progress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobStatus {
+    pub id: JobId,
+    pub name: String,
+    pub state: JobState,
+    /// The step the job is currently on
+    pub step: Option<String>,
+    /// Units of work done in the current step, usually bytes
+    pub done: u64,
+    /// Total units of work in the current step, if known
+    pub total: Option<u64>,
+}
+
+/// A snapshot of the whole queue's progress
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueProgress {
+    pub jobs: Vec<JobStatus>,
+    /// Number of jobs that won't run again
+    pub completed: usize,
+    pub paused: bool,
+}
+
+impl QueueProgress {
+    /// Overall completion between 0 and 1, counting every job equally
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> f64 {
+        if self.jobs.is_empty() {
+            return 1.0;
+        }
+
+        let sum: f64 = self
+            .jobs
+            .iter()
+            .map(|j| match (&j.state, j.total) {
+                (state, _) if state.is_done() => 1.0,
+                (_, Some(total)) if total > 0 => j.done.min(total) as f64 / total as f64,
+                _ => 0.0,
+            })
+            .sum();
+        sum / self.jobs.len() as f64
+    }
+}
+
+type TaskFn = Box<dyn FnOnce(&JobContext) -> Result<()> + Send>;
+
+/// A named unit of work for a [`JobQueue`]
+pub struct Task {
+    name: String,
+    run: TaskFn,
+}
+
+impl Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task").field("name", &self.name).finish()
+    }
+}
+
+impl Task {
+    /// Creates a task from a closure, which reports progress and checks for pauses through its [`JobContext`]
+    pub fn new(
+        name: impl Into<String>,
+        run: impl FnOnce(&JobContext) -> Result<()> + Send + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            run: Box::new(run),
+        }
+    }
+
+    /// Downloads, verifies and extracts a package into `target_dir`, using `config` for every step
+    #[must_use]
+    pub fn install(
+        version: ModVersion,
+        target_dir: impl Into<PathBuf>,
+        config: ThermiteConfig,
+    ) -> Self {
+        let target_dir = target_dir.into();
+        Self::new(version.full_name.clone(), move |ctx| {
+            ctx.step("download")?;
+            let mut archive = vec![];
+            download_with_limit(
+                &mut archive,
+                &version.url,
+                config.download_timeout,
+                |_, current, total| ctx.progress(current, Some(total)),
+            )?;
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if version.sha256.is_some() || config.verifier.is_some() {
+                ctx.step("verify")?;
+                check_archive(&version, &archive, &config)?;
+            }
+
+            ctx.step("extract")?;
+            install_with_config(
+                &version.full_name,
+                Cursor::new(archive),
+                &target_dir,
+                |_| Ok(()),
+                &config,
+            )?;
+            Ok(())
+        })
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Handed to a running [`Task`] to report progress
+#[derive(Debug)]
+pub struct JobContext {
+    id: JobId,
+    shared: Arc<Shared>,
+}
+
+impl JobContext {
+    /// Starts a new step, resetting the job's progress
+    ///
+    /// # Errors
+    /// * `ThermiteError::Cancelled` if the job or queue was cancelled
+    pub fn step(&self, name: impl Into<String>) -> Result<()> {
+        self.checkpoint()?;
+        let mut jobs = self.shared.jobs();
+        let status = &mut jobs[self.id.0].status;
+        status.step = Some(name.into());
+        status.done = 0;
+        status.total = None;
+        Ok(())
+    }
+
+    /// Updates the current step's progress, waiting here while the queue is paused
+    pub fn progress(&self, done: u64, total: Option<u64>) {
+        {
+            let mut jobs = self.shared.jobs();
+            let status = &mut jobs[self.id.0].status;
+            status.done = done;
+            status.total = total;
+        }
+        self.shared.wait_while_paused();
+    }
+
+    /// Waits while the queue is paused, then checks for cancellation
+    ///
+    /// # Errors
+    /// * `ThermiteError::Cancelled` if the job or queue was cancelled
+    pub fn checkpoint(&self) -> Result<()> {
+        self.shared.wait_while_paused();
+        cancel::checkpoint("running a job")
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    status: JobStatus,
+    token: CancelToken,
+    task: Option<Task>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    jobs: Mutex<Vec<Entry>>,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl Shared {
+    fn jobs(&self) -> MutexGuard<'_, Vec<Entry>> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn paused(&self) -> MutexGuard<'_, bool> {
+        self.paused.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait_while_paused(&self) {
+        let mut paused = self.paused();
+        while *paused {
+            paused = self
+                .resumed
+                .wait(paused)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobQueue {
+    concurrency: NonZeroUsize,
+    token: CancelToken,
+    shared: Arc<Shared>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(config::config().parallelism)
+    }
+}
+
+impl JobQueue {
+    /// Creates a queue that runs at most `concurrency` jobs at once
+    #[must_use]
+    pub fn new(concurrency: NonZeroUsize) -> Self {
+        Self {
+            concurrency,
+            token: CancelToken::new(),
+            shared: Arc::default(),
+        }
+    }
+
+    /// Adds a task to the queue, it will run on the next call to [`JobQueue::run`]
+    pub fn push(&self, task: Task) -> JobId {
+        let mut jobs = self.shared.jobs();
+        let id = JobId(jobs.len());
+        jobs.push(Entry {
+            status: JobStatus {
+                id,
+                name: task.name.clone(),
+                state: JobState::Queued,
+                step: None,
+                done: 0,
+                total: None,
+            },
+            token: CancelToken::new(),
+            task: Some(task),
+        });
+        id
+    }
+
+    /// Runs every queued job, returning once all of them are done
+    ///
+    /// Returns the outcome of each job that ran, in the order they were pushed
+    pub fn run(&self) -> Vec<(JobId, Result<()>)> {
+        let queued = self
+            .shared
+            .jobs()
+            .iter_mut()
+            .filter_map(|e| Some((e.status.id, e.task.take()?, e.token.clone())))
+            .collect::<Vec<_>>();
+
+        let tokens = cancel::current();
+        let fs = vfs::current();
+        let results = queued.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+        let jobs = queued
+            .into_iter()
+            .zip(&results)
+            .map(|((id, task, token), slot)| {
+                let tokens = &tokens;
+                let fs = fs.clone();
+                Box::new(move || {
+                    let res = vfs::with(fs, || {
+                        cancel::run_with(tokens, || {
+                            self.token.run(|| token.run(|| self.run_job(id, task)))
+                        })
+                    });
+                    if let Ok(mut slot) = slot.lock() {
+                        *slot = Some((id, res));
+                    }
+                }) as Job<'_>
+            })
+            .collect();
+        ThreadPool::new(self.concurrency).execute(jobs);
+
+        results
+            .into_iter()
+            .filter_map(|slot| slot.into_inner().ok().flatten())
+            .collect()
+    }
+
+    fn run_job(&self, id: JobId, task: Task) -> Result<()> {
+        let ctx = JobContext {
+            id,
+            shared: self.shared.clone(),
+        };
+        let res = ctx.checkpoint().and_then(|()| {
+            self.shared.jobs()[id.0].status.state = JobState::Running;
+            (task.run)(&ctx)
+        });
+
+        self.shared.jobs()[id.0].status.state = match &res {
+            Ok(()) => JobState::Finished,
+            Err(e) if matches!(e.root(), ThermiteError::Cancelled) => JobState::Cancelled,
+            Err(e) => JobState::Failed(e.to_string()),
+        };
+        res
+    }
+
+    /// Stops jobs at their next checkpoint until [`JobQueue::resume`] is called
+    pub fn pause(&self) {
+        *self.shared.paused() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.shared.paused() = false;
+        self.shared.resumed.notify_all();
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        *self.shared.paused()
+    }
+
+    /// Cancels a single job. Jobs that haven't started yet won't run
+    pub fn cancel(&self, id: JobId) {
+        if let Some(entry) = self.shared.jobs().get(id.0) {
+            entry.token.cancel();
+        }
+    }
+
+    /// Cancels every job, including ones pushed later. Paused jobs are resumed so they can stop
+    pub fn cancel_all(&self) {
+        self.token.cancel();
+        self.resume();
+    }
+
+    #[must_use]
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.shared.jobs().get(id.0).map(|e| e.status.clone())
+    }
+
+    #[must_use]
+    pub fn progress(&self) -> QueueProgress {
+        let jobs = self
+            .shared
+            .jobs()
+            .iter()
+            .map(|e| e.status.clone())
+            .collect::<Vec<_>>();
+        QueueProgress {
+            completed: jobs.iter().filter(|j| j.state.is_done()).count(),
+            jobs,
+            paused: self.is_paused(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        num::NonZeroUsize,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        api::fetch_index,
+        core::utils::TempDir,
+        error::ThermiteError,
+        game::GameSpec,
+        test_util::{mod_archive, MockPackage, MockServer},
+    };
+
+    use super::{JobQueue, JobState, Task};
+
+    #[test]
+    fn install_many() {
+        let server = MockServer::start().expect("start mock server");
+        for name in ["Bar", "Baz", "Qux"] {
+            server.add_package(MockPackage::new("Foo", name, "1.0.0"));
+        }
+        let index = fetch_index(&server.index_url(), None, &GameSpec::default()).expect("index");
+        let dir = TempDir::create("./test_job_queue").expect("Unable to create temp dir");
+
+        let queue = JobQueue::new(NonZeroUsize::new(2).unwrap());
+        for m in &index {
+            let version = m.get_latest().unwrap().clone();
+            queue.push(Task::install(version, &*dir, Default::default()));
+        }
+        let skipped = queue.push(Task::new("skipped", |_| Ok(())));
+        queue.cancel(skipped);
+
+        let results = queue.run();
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(|(_, r)| r.is_ok()), "{results:?}");
+        assert!(matches!(results[3].1, Err(ThermiteError::Cancelled)));
+
+        let progress = queue.progress();
+        assert_eq!(progress.completed, 4);
+        assert!((progress.fraction() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(progress.jobs[0].step.as_deref(), Some("extract"));
+        assert_eq!(progress.jobs[3].state, JobState::Cancelled);
+        assert!(dir.join("Foo-Baz-1.0.0").join("manifest.json").exists());
+    }
+
+    #[test]
+    fn reject_mismatched_archives() {
+        let server = MockServer::start().expect("start mock server");
+        let package = MockPackage::new("Foo", "Bar", "1.0.0");
+        server.add_package(package.clone());
+        server.serve(package.download_path(), mod_archive("Bar", "6.6.6"));
+        let index = fetch_index(&server.index_url(), None, &GameSpec::default()).expect("index");
+        let dir = TempDir::create("./test_job_checksum").expect("Unable to create temp dir");
+
+        let queue = JobQueue::new(NonZeroUsize::new(1).unwrap());
+        let version = index[0].get_latest().unwrap().clone();
+        queue.push(Task::install(version, &*dir, Default::default()));
+        let results = queue.run();
+        assert!(
+            matches!(results[0].1, Err(ThermiteError::ChecksumMismatch { .. })),
+            "{results:?}"
+        );
+        assert!(!dir.join("Foo-Bar-1.0.0").exists());
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let queue = JobQueue::new(NonZeroUsize::new(1).unwrap());
+        let steps = Arc::new(AtomicUsize::new(0));
+        let counter = steps.clone();
+        queue.push(Task::new("counting", move |ctx| {
+            for i in 0..100 {
+                ctx.progress(i, Some(100));
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            Ok(())
+        }));
+
+        queue.pause();
+        let runner = queue.clone();
+        let handle = thread::spawn(move || runner.run());
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(
+            steps.load(Ordering::SeqCst),
+            0,
+            "paused queue shouldn't start jobs"
+        );
+        assert!(queue.progress().paused);
+
+        queue.resume();
+        let results = handle.join().unwrap();
+        assert!(results[0].1.is_ok());
+        assert_eq!(steps.load(Ordering::SeqCst), 100);
+    }
+}
@@ -15,11 +15,34 @@
 //! ```
 
 pub mod api;
+#[cfg(feature = "async")]
+mod blocking;
+pub mod cancel;
+pub mod config;
 pub mod core;
+#[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+pub mod db;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod game;
+pub mod http;
+pub mod import;
+pub mod jobs;
+pub mod manager;
+pub mod metrics;
 pub mod model;
+pub mod pool;
+pub mod server;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod time;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod verify;
 
 /// The names of the Northstar core mods as found in their `mod.json` files, all lowercase
+///
+/// Thermite itself goes by [`GameSpec::core_mods`](game::GameSpec::core_mods)
 pub const CORE_MODS: [&str; 3] = [
     "northstar.custom",
     "northstar.customservers",
@@ -34,16 +57,18 @@ pub const TITANFALL2_ORIGIN_IDS: [&str; 2] = ["Origin.OFR.50.0001452", "Origin.O
 // Important functions and structs
 pub mod prelude {
     pub use crate::api::get_package_index;
+    pub use crate::config::{set_config, ThermiteConfig};
     pub use crate::core::manage::{
         download, download_with_progress, install_mod, install_northstar, install_with_sanity,
     };
+    pub use crate::manager::ModManager;
 
     pub use crate::core::utils::{find_mods, get_enabled_mods, resolve_deps};
     #[cfg(all(target_os = "linux", feature = "proton"))]
     pub use crate::core::{download_ns_proton, install_ns_proton, latest_release};
     #[cfg(feature = "steam")]
-    pub use crate::core::{steam_dir, steam_libraries, titanfall};
-    pub use crate::error::ThermiteError;
+    pub use crate::core::{steam_dir, steam_dirs, steam_libraries, titanfall};
+    pub use crate::error::{ErrorContext, ThermiteError};
     pub use crate::CORE_MODS;
     pub use crate::TITANFALL2_STEAM_ID;
 }
@@ -0,0 +1,1231 @@
+//! High-level facade over the free functions in `api` and `core`
+//!
+//! A [`ModManager`] owns everything needed to manage one Northstar profile: the game directory,
+//! the profile name, its own [`ThermiteConfig`], a cached copy of the package index and a lockfile
+//! recording what was installed and why.
+
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::core::{
+    manage::{check_archive, DownloadCache},
+    plan::{self, Plan},
+};
+#[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+use crate::db::{self, Database};
+use crate::{
+    api::{self, compat::Incompatibility},
+    config::{self, ThermiteConfig},
+    core::{
+        audit::{self, AuditEntry, AuditOperation},
+        events::{self, Event},
+        journal::{self, JOURNAL_DIR},
+        lock::lock_dir,
+        manage::{download_with_limit, install_with_config, warn_deprecated},
+        report::InstallReport,
+        resolve::{self, InstallConflict, Resolution},
+        utils::{
+            check_game_running, find_mods, find_unmanaged_mods, get_enabled_mods, is_newer,
+            package_files, parse_modstring, resolve_deps_recursive, suggest_packages,
+        },
+        vfs,
+    },
+    error::{ErrorContext, Result, ThermiteError},
+    model::{EnabledMods, InstalledMod, Mod, ModVersion},
+};
+
+/// The profile Northstar uses when none is given with `-profile`
+pub const DEFAULT_PROFILE: &str = "R2Northstar";
+const LOCKFILE_NAME: &str = "thermite.lock.json";
+const SNAPSHOT_MANIFEST: &str = "snapshot.json";
+/// Directories in a profile that snapshots leave out since they're reinstalled or regenerated
+const SNAPSHOT_SKIPPED: [&str; 5] = ["packages", "mods", "plugins", "logs", "runtime"];
+
+/// Record of the packages installed by a [`ModManager`], stored in the profile directory
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lockfile {
+    /// Packages keyed by `author-name`
+    pub packages: BTreeMap<String, LockedPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LockedPackage {
+    pub version: String,
+    /// `false` if the package was only installed as a dependency of another
+    pub explicit: bool,
+}
+
+/// Everything [`ModManager::snapshot`] records besides the profiles' files
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Lockfiles keyed by profile name
+    pub profiles: BTreeMap<String, Lockfile>,
+}
+
+/// A package with a newer version in the index than the one installed
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AvailableUpdate {
+    /// `author-name` of the package
+    pub name: String,
+    pub installed: String,
+    pub latest: String,
+    /// Whether the package or the installed version is deprecated on Thunderstore
+    pub deprecated: bool,
+}
+
+/// An installed package, as found in the `packages` directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InstalledPackage {
+    author: String,
+    name: String,
+    version: String,
+    path: PathBuf,
+}
+
+impl InstalledPackage {
+    fn key(&self) -> String {
+        format!("{}-{}", self.author, self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModManager {
+    game_dir: PathBuf,
+    profile: String,
+    config: ThermiteConfig,
+    index: Option<Vec<Mod>>,
+    lockfile: Lockfile,
+}
+
+impl ModManager {
+    /// Creates a manager for the default profile of the game installed at `game_dir`, using a copy of the global config
+    ///
+    /// # Errors
+    /// * The profile's lockfile exists but can't be read
+    pub fn new(game_dir: impl Into<PathBuf>) -> Result<Self> {
+        let config = config::config().as_ref().clone();
+        let profile = config.game.profile.clone();
+        Self::with_config(game_dir, profile, config)
+    }
+
+    /// Creates a manager for `profile` that uses `config` instead of the global config
+    ///
+    /// # Errors
+    /// * The profile's lockfile exists but can't be read
+    pub fn with_config(
+        game_dir: impl Into<PathBuf>,
+        profile: impl Into<String>,
+        config: ThermiteConfig,
+    ) -> Result<Self> {
+        let mut manager = Self {
+            game_dir: game_dir.into(),
+            profile: profile.into(),
+            config,
+            index: None,
+            lockfile: Lockfile::default(),
+        };
+        manager.load_profile()?;
+
+        Ok(manager)
+    }
+
+    /// Starts managing another profile of the same game, loading its lockfile
+    ///
+    /// # Errors
+    /// * The profile's lockfile exists but can't be read. The manager stays on the old profile
+    pub fn switch_profile(&mut self, profile: impl Into<String>) -> Result<()> {
+        let from = std::mem::replace(&mut self.profile, profile.into());
+        if let Err(e) = self.load_profile() {
+            self.profile = from;
+            self.load_profile()?;
+            return Err(e);
+        }
+
+        events::emit(Event::ProfileSwitched {
+            from,
+            to: self.profile.clone(),
+        });
+        Ok(())
+    }
+
+    fn load_profile(&mut self) -> Result<()> {
+        self.lockfile = read_lockfile(&self.lockfile_path())?;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn game_dir(&self) -> &Path {
+        &self.game_dir
+    }
+
+    #[must_use]
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    #[must_use]
+    pub const fn config(&self) -> &ThermiteConfig {
+        &self.config
+    }
+
+    #[must_use]
+    pub const fn lockfile(&self) -> &Lockfile {
+        &self.lockfile
+    }
+
+    /// Opens the database of installed packages in the profile's packages directory, kept in sync by every
+    /// operation. Requires the `db` feature
+    ///
+    /// # Errors
+    /// * IO and database errors
+    #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+    pub fn database(&self) -> Result<Database> {
+        Database::open(db::database_path(&self.packages_dir()))
+    }
+
+    #[must_use]
+    pub fn profile_dir(&self) -> PathBuf {
+        self.game_dir.join(&self.profile)
+    }
+
+    /// The directory packages are installed to
+    #[must_use]
+    pub fn packages_dir(&self) -> PathBuf {
+        self.profile_dir().join("packages")
+    }
+
+    fn lockfile_path(&self) -> PathBuf {
+        self.profile_dir().join(LOCKFILE_NAME)
+    }
+
+    /// Returns the package index, fetching it the first time it's needed
+    ///
+    /// # Errors
+    /// * Network errors
+    /// * Unexpected response format from thunderstore
+    pub fn index(&mut self) -> Result<&[Mod]> {
+        if self.index.is_none() {
+            self.refresh_index()?;
+        }
+        Ok(self.index.as_deref().unwrap_or_default())
+    }
+
+    /// Fetches a fresh copy of the package index
+    ///
+    /// # Errors
+    /// * Network errors
+    /// * Unexpected response format from thunderstore
+    pub fn refresh_index(&mut self) -> Result<&[Mod]> {
+        let index = api::fetch_index_cached(&self.config)?;
+        Ok(self.index.insert(index))
+    }
+
+    /// Installs a package and its dependencies, including the dependencies' own dependencies
+    ///
+    /// `name` is either `author-name`, to install the latest version, or `author-name-X.Y.Z`.
+    /// Dependencies that are already installed at the required version are skipped. If another version of a
+    /// package is installed, the [`Resolver`](crate::core::resolve::Resolver) decides whether to replace it,
+    /// keep it or skip the package. Deprecated packages are installed with a warning in their report.
+    ///
+    /// Failing to download, verify or install a package is reported as a [`ThermiteError::Context`] naming the
+    /// package and the step, see [`ThermiteError::root`].
+    ///
+    /// # Errors
+    /// * The package or one of its dependencies isn't in the index
+    /// * Network and IO errors
+    pub fn install(&mut self, name: impl AsRef<str>) -> Result<Vec<InstallReport>> {
+        let target = self.find_version(name.as_ref())?;
+
+        let mut reports = vec![];
+        let deps = resolve_deps_recursive(&target.deps, self.index()?)?;
+        for dep in deps {
+            let Some(latest) = dep.get_latest() else {
+                continue;
+            };
+            let key = format!("{}-{}", dep.author, dep.name);
+            if self.is_installed(&key, &latest.version)? {
+                debug!("Dependency {key} is already installed");
+                continue;
+            }
+            if self.resolve_version(&key, &latest.version, false)? {
+                reports.push(self.install_version(&key, latest, false)?);
+            }
+        }
+
+        let key = package_key(&target.full_name);
+        if self.resolve_version(&key, &target.version, true)? {
+            reports.push(self.install_version(&key, &target, true)?);
+        }
+        self.save_lockfile()?;
+        warn_deprecated(&mut reports, self.index()?);
+
+        Ok(reports)
+    }
+
+    /// Removes every installed version of a package
+    ///
+    /// # Errors
+    /// * The package isn't installed
+    /// * The game is running and the config's `running_game` policy refuses, or gave up waiting
+    /// * `ThermiteError::DirectoryLocked` if another process is changing its directory
+    /// * IO errors
+    pub fn remove(&mut self, name: impl AsRef<str>) -> Result<()> {
+        let name = name.as_ref();
+        let packages = self
+            .installed_packages()?
+            .into_iter()
+            .filter(|p| p.key().eq_ignore_ascii_case(name))
+            .collect::<Vec<_>>();
+        if packages.is_empty() {
+            return Err(ThermiteError::NotInstalled(name.into()));
+        }
+        check_game_running(&self.config)?;
+
+        let fs = vfs::current();
+        for package in packages {
+            let _lock = package.path.parent().map(lock_dir).transpose()?;
+            let mut entry =
+                AuditEntry::new(AuditOperation::Uninstall, package.key(), &package.path);
+            entry.version = Some(package.version.clone());
+            let files = package_files(&package.path).unwrap_or_default();
+            let res = fs.remove_dir_all(&package.path);
+            audit::record(entry, &res);
+            res?;
+            journal::record_removal(
+                &package.path,
+                package.key(),
+                Some(package.version.clone()),
+                files,
+                BTreeMap::new(),
+            );
+            events::emit(Event::ModRemoved {
+                name: package.key(),
+                path: package.path.clone(),
+            });
+            self.lockfile.packages.remove(&package.key());
+            #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+            db::update(&self.packages_dir(), false, |db| {
+                db.record_removal(&package.key())
+            });
+        }
+
+        self.save_lockfile()
+    }
+
+    /// Works out what [`install`](Self::install) would do, without changing the disk or this manager
+    ///
+    /// Packages still get downloaded, to see what they contain. The dependencies it would pull in are listed
+    /// in [`Plan::dependencies`].
+    ///
+    /// # Errors
+    /// Anything the install itself would fail with
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn plan_install(&self, name: impl AsRef<str>) -> Result<Plan> {
+        let mut manager = self.clone();
+        let (reports, mut plan) = plan::plan(|| manager.install(name.as_ref()))?;
+        let requested = package_key(name.as_ref());
+        plan.dependencies = reports
+            .iter()
+            .filter(|r| package_key(&r.name) != requested)
+            .map(|r| r.name.clone())
+            .collect();
+        plan.installs = reports;
+        Ok(plan)
+    }
+
+    /// Works out what [`remove`](Self::remove) would do, without changing the disk or this manager
+    ///
+    /// # Errors
+    /// Anything the removal itself would fail with
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn plan_remove(&self, name: impl AsRef<str>) -> Result<Plan> {
+        let mut manager = self.clone();
+        plan::plan(|| manager.remove(name)).map(|((), plan)| plan)
+    }
+
+    /// Works out what [`update_all`](Self::update_all) would do, without changing the disk or this manager
+    ///
+    /// # Errors
+    /// Anything the update itself would fail with
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn plan_update_all(&self) -> Result<Plan> {
+        let mut manager = self.clone();
+        let (reports, mut plan) = plan::plan(|| manager.update_all())?;
+        plan.installs = reports;
+        Ok(plan)
+    }
+
+    /// Enables or disables every mod provided by a package in the profile's `enabledmods.json`
+    ///
+    /// # Errors
+    /// * The package isn't installed
+    /// * IO errors
+    pub fn enable(&mut self, name: impl AsRef<str>, enabled: bool) -> Result<()> {
+        let name = name.as_ref();
+        let mods = self
+            .list()?
+            .into_iter()
+            .filter(|m| format!("{}-{}", m.author, m.manifest.name).eq_ignore_ascii_case(name))
+            .collect::<Vec<_>>();
+        if mods.is_empty() {
+            return Err(ThermiteError::NotInstalled(name.into()));
+        }
+
+        let mut enabled_mods = match get_enabled_mods(self.profile_dir()) {
+            Ok(m) => m,
+            Err(ThermiteError::MissingFile(path)) => EnabledMods::default_with_path(*path),
+            Err(e) => return Err(e),
+        };
+        for m in &mods {
+            enabled_mods.set(&m.mod_json.name, enabled);
+        }
+        enabled_mods.save()?;
+        enabled_mods.dont_save();
+
+        #[cfg(all(feature = "db", not(target_arch = "wasm32")))]
+        db::update(&self.packages_dir(), false, |db| {
+            mods.iter()
+                .try_for_each(|m| db.set_enabled(&m.mod_json.name, enabled))
+        });
+
+        Ok(())
+    }
+
+    /// Lists the mods installed in the profile
+    ///
+    /// # Errors
+    /// * IO errors
+    /// * Improperly formatted JSON files
+    pub fn list(&self) -> Result<Vec<InstalledMod>> {
+        let dir = self.packages_dir();
+        if !vfs::current().exists(&dir)? {
+            return Ok(vec![]);
+        }
+        find_mods(dir)
+    }
+
+    /// Lists the mods installed by hand in the profile, outside of any package, see [`find_unmanaged_mods`]
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn list_unmanaged(&self) -> Result<Vec<InstalledMod>> {
+        let dir = self.packages_dir();
+        if !vfs::current().exists(&dir)? {
+            return Ok(vec![]);
+        }
+        find_unmanaged_mods(dir)
+    }
+
+    /// Compares the installed packages against the index
+    ///
+    /// Deprecated packages are listed even if there's no newer version, with `latest` being the index's latest.
+    ///
+    /// # Errors
+    /// * Network errors while fetching the index
+    /// * IO errors
+    pub fn check_updates(&mut self) -> Result<Vec<AvailableUpdate>> {
+        let installed = self.installed_packages()?;
+        let index = self.index()?;
+
+        let mut updates: Vec<AvailableUpdate> = vec![];
+        for package in installed {
+            let Some(m) = index
+                .iter()
+                .find(|m| m.author == package.author && m.name == package.name)
+            else {
+                continue;
+            };
+            let deprecated = m.is_deprecated(&package.version);
+            if !deprecated && !is_newer(&m.latest, &package.version) {
+                continue;
+            }
+
+            // several versions may be installed side by side, only report the newest
+            match updates.iter_mut().find(|u| u.name == package.key()) {
+                Some(u) if is_newer(&package.version, &u.installed) => {
+                    u.installed = package.version;
+                    u.deprecated = deprecated;
+                }
+                Some(_) => {}
+                None => updates.push(AvailableUpdate {
+                    name: package.key(),
+                    installed: package.version,
+                    latest: m.latest.clone(),
+                    deprecated,
+                }),
+            }
+        }
+
+        Ok(updates)
+    }
+
+    /// Checks the profile's mods against the list at the config's `compatibility_url`
+    ///
+    /// Finds nothing if no URL is configured.
+    ///
+    /// # Errors
+    /// * Network errors while fetching the list
+    /// * IO errors
+    pub fn check_compatibility(&self) -> Result<Vec<Incompatibility>> {
+        let Some(url) = &self.config.compatibility_url else {
+            return Ok(vec![]);
+        };
+        let list = api::compat::fetch_compatibility_list(url, self.config.timeout)?;
+        Ok(list.check(&self.list()?))
+    }
+
+    /// Packs every profile managed in the game directory into one archive that [`ModManager::restore`] can
+    /// recreate elsewhere, e.g. on another machine
+    ///
+    /// The archive holds each profile's lockfile, which pins the exact package versions, and every file in the
+    /// profile except the packages and mods themselves, so `enabledmods.json` and the mods' saved data come
+    /// along. A profile is included if it's the current one or has a lockfile.
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn snapshot(&self) -> Result<Vec<u8>> {
+        let fs = vfs::current();
+        let mut profiles = vec![self.profile.clone()];
+        if fs.exists(&self.game_dir)? {
+            for entry in fs.read_dir(&self.game_dir)? {
+                let name = entry.file_name().to_owned();
+                if entry.is_dir
+                    && !profiles.contains(&name)
+                    && fs.exists(&entry.path.join(LOCKFILE_NAME))?
+                {
+                    profiles.push(name);
+                }
+            }
+        }
+
+        let mut snapshot = Snapshot::default();
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for name in profiles {
+            let dir = self.game_dir.join(&name);
+            let lockfile = if name == self.profile {
+                self.lockfile.clone()
+            } else {
+                read_lockfile(&dir.join(LOCKFILE_NAME))?
+            };
+            if fs.exists(&dir)? {
+                add_snapshot_files(&mut zip, &dir, &format!("profiles/{name}"), true)?;
+            }
+            snapshot.profiles.insert(name, lockfile);
+        }
+
+        zip.start_file(SNAPSHOT_MANIFEST, FileOptions::default())?;
+        zip.write_all(serde_json::to_string_pretty(&snapshot)?.as_bytes())?;
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// Makes every profile in a [`ModManager::snapshot`] archive match it
+    ///
+    /// Packages are installed at the versions the snapshot pins, packages it doesn't have are removed and the
+    /// profile files are written over the existing ones. Profiles that aren't in the snapshot aren't touched.
+    /// The manager is back on its current profile afterwards, even if restoring failed.
+    ///
+    /// # Errors
+    /// * `ThermiteError::InvalidSnapshot` if the archive isn't a snapshot
+    /// * A pinned package version isn't in the index
+    /// * Network and IO errors
+    pub fn restore(&mut self, snapshot: impl Read + Seek) -> Result<Vec<InstallReport>> {
+        let mut archive = ZipArchive::new(snapshot)?;
+        let manifest: Snapshot = match archive.by_name(SNAPSHOT_MANIFEST) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(_) => {
+                return Err(ThermiteError::InvalidSnapshot(format!(
+                    "missing {SNAPSHOT_MANIFEST}"
+                )))
+            }
+        };
+
+        let original = self.profile.clone();
+        let mut reports = vec![];
+        let res = manifest.profiles.iter().try_for_each(|(name, lockfile)| {
+            self.restore_profile(&mut archive, name, lockfile, &mut reports)
+        });
+        self.switch_profile(original)?;
+        res.map(|()| reports)
+    }
+
+    fn restore_profile(
+        &mut self,
+        archive: &mut ZipArchive<impl Read + Seek>,
+        name: &str,
+        lockfile: &Lockfile,
+        reports: &mut Vec<InstallReport>,
+    ) -> Result<()> {
+        let mut components = Path::new(name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(ThermiteError::InvalidSnapshot(format!(
+                "invalid profile name {name}"
+            )));
+        }
+        self.switch_profile(name)?;
+
+        let fs = vfs::current();
+        for package in self.installed_packages()? {
+            match lockfile.packages.get(&package.key()) {
+                None => self.remove(package.key())?,
+                Some(locked) if locked.version != package.version => {
+                    debug!(
+                        "Removing {} for the snapshot's version",
+                        package.path.display()
+                    );
+                    fs.remove_dir_all(&package.path)?;
+                }
+                Some(_) => {}
+            }
+        }
+        for (key, locked) in &lockfile.packages {
+            if self.is_installed(key, &locked.version)? {
+                continue;
+            }
+            let version = self.find_version(&format!("{key}-{}", locked.version))?;
+            reports.push(self.install_version(key, &version, locked.explicit)?);
+        }
+        self.lockfile = lockfile.clone();
+        self.save_lockfile()?;
+
+        // written after installing so the snapshot's enabledmods.json wins
+        let prefix = Path::new("profiles").join(name);
+        let profile_dir = self.profile_dir();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let Some(rel) = file
+                .enclosed_name()
+                .and_then(|p| p.strip_prefix(&prefix).ok())
+                .map(Path::to_path_buf)
+            else {
+                continue;
+            };
+            if file.is_dir() {
+                continue;
+            }
+
+            let target = profile_dir.join(rel);
+            if let Some(parent) = target.parent() {
+                fs.create_dir_all(parent)?;
+            }
+            let mut contents = vec![];
+            file.read_to_end(&mut contents)?;
+            fs.write(&target, &contents)?;
+        }
+
+        Ok(())
+    }
+
+    /// Installs the latest version of every outdated package, removing the old versions
+    ///
+    /// # Errors
+    /// * Network and IO errors
+    pub fn update_all(&mut self) -> Result<Vec<InstallReport>> {
+        let mut reports = vec![];
+        for update in self.check_updates()? {
+            if !is_newer(&update.latest, &update.installed) {
+                continue;
+            }
+            let old = self
+                .installed_packages()?
+                .into_iter()
+                .filter(|p| p.key() == update.name)
+                .collect::<Vec<_>>();
+
+            let latest = self.find_version(&update.name)?;
+            let explicit = self
+                .lockfile
+                .packages
+                .get(&update.name)
+                .is_none_or(|p| p.explicit);
+            reports.push(self.install_version(&update.name, &latest, explicit)?);
+
+            let fs = vfs::current();
+            for package in old {
+                debug!("Removing outdated {}", package.path.display());
+                fs.remove_dir_all(&package.path)?;
+            }
+        }
+        self.save_lockfile()?;
+
+        Ok(reports)
+    }
+
+    /// Finds `author-name` or `author-name-X.Y.Z` in the index
+    fn find_version(&mut self, name: &str) -> Result<ModVersion> {
+        let (author, package, version) = match parse_modstring(name) {
+            Ok((author, package, version)) => (author, package, Some(version)),
+            Err(_) => {
+                let mut parts = name.splitn(2, '-');
+                let (Some(author), Some(package)) = (parts.next(), parts.next()) else {
+                    return Err(ThermiteError::NameError(name.into()));
+                };
+                (author.to_owned(), package.to_owned(), None)
+            }
+        };
+
+        let index = self.index()?;
+        let found = index
+            .iter()
+            .find(|m| m.author == author && m.name == package)
+            .and_then(|m| match &version {
+                Some(v) => m.get_version(v),
+                None => m.get_latest(),
+            });
+
+        found.cloned().ok_or_else(|| ThermiteError::DepError {
+            name: name.into(),
+            suggestions: suggest_packages(name, index),
+        })
+    }
+
+    fn install_version(
+        &mut self,
+        key: &str,
+        version: &ModVersion,
+        explicit: bool,
+    ) -> Result<InstallReport> {
+        let archive = self.fetch_archive(version).map_err(|e| {
+            e.with_context(
+                ErrorContext::new("downloading")
+                    .package(&version.full_name)
+                    .url(&version.url),
+            )
+        })?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        check_archive(version, &archive, &self.config).map_err(|e| {
+            e.with_context(ErrorContext::new("verifying").package(&version.full_name))
+        })?;
+
+        let report = install_with_config(
+            &version.full_name,
+            Cursor::new(archive),
+            self.packages_dir(),
+            |_| Ok(()),
+            &self.config,
+        )
+        .map_err(|e| {
+            e.with_context(
+                ErrorContext::new("installing")
+                    .package(&version.full_name)
+                    .path(self.packages_dir()),
+            )
+        })?;
+
+        // a package that was explicitly installed once stays explicit
+        let explicit = explicit || self.lockfile.packages.get(key).is_some_and(|p| p.explicit);
+        self.lockfile.packages.insert(
+            key.to_owned(),
+            LockedPackage {
+                version: version.version.clone(),
+                explicit,
+            },
+        );
+
+        Ok(report)
+    }
+
+    /// Downloads a package archive, going through the package cache if one is configured
+    fn fetch_archive(&self, version: &ModVersion) -> Result<Vec<u8>> {
+        let download = |version: &ModVersion| {
+            let mut archive = vec![];
+            download_with_limit(
+                &mut archive,
+                &version.url,
+                self.config.download_timeout,
+                |_, _, _| {},
+            )?;
+            Ok(archive)
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(cache) = DownloadCache::from_config(&self.config) {
+            return cache.fetch_with(version, download);
+        }
+        download(version)
+    }
+
+    /// Asks the resolver what to do if another version of the package is installed, returning whether to
+    /// install `requested`
+    ///
+    /// Without a resolver the other versions are left installed alongside it.
+    fn resolve_version(&mut self, key: &str, requested: &str, explicit: bool) -> Result<bool> {
+        let others = self
+            .installed_packages()?
+            .into_iter()
+            .filter(|p| p.key() == key && p.version != requested)
+            .collect::<Vec<_>>();
+        let Some(installed) = others.first() else {
+            return Ok(true);
+        };
+
+        let conflict = InstallConflict::VersionMismatch {
+            package: key.into(),
+            installed: installed.version.clone(),
+            requested: requested.into(),
+        };
+        match resolve::resolve(&conflict) {
+            None => Ok(true),
+            Some(Resolution::UseNew) => {
+                let fs = vfs::current();
+                for package in &others {
+                    debug!("Removing {} for {requested}", package.path.display());
+                    fs.remove_dir_all(&package.path)?;
+                }
+                Ok(true)
+            }
+            Some(Resolution::KeepExisting) => {
+                let explicit =
+                    explicit || self.lockfile.packages.get(key).is_some_and(|p| p.explicit);
+                self.lockfile.packages.insert(
+                    key.to_owned(),
+                    LockedPackage {
+                        version: installed.version.clone(),
+                        explicit,
+                    },
+                );
+                Ok(false)
+            }
+            Some(Resolution::Skip) => Ok(false),
+        }
+    }
+
+    fn is_installed(&self, key: &str, version: &str) -> Result<bool> {
+        Ok(self
+            .installed_packages()?
+            .iter()
+            .any(|p| p.key() == key && p.version == version))
+    }
+
+    fn installed_packages(&self) -> Result<Vec<InstalledPackage>> {
+        let fs = vfs::current();
+        let dir = self.packages_dir();
+        if !fs.exists(&dir)? {
+            return Ok(vec![]);
+        }
+
+        let mut packages = vec![];
+        for child in fs.read_dir(&dir)? {
+            if !child.is_dir {
+                continue;
+            }
+            let Ok((author, name, version)) = parse_modstring(child.file_name()) else {
+                debug!("Skipping unrecognised directory {}", child.path.display());
+                continue;
+            };
+            packages.push(InstalledPackage {
+                author,
+                name,
+                version,
+                path: child.path,
+            });
+        }
+
+        Ok(packages)
+    }
+
+    fn save_lockfile(&mut self) -> Result<()> {
+        let fs = vfs::current();
+        let path = self.lockfile_path();
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent)?;
+        }
+        fs.write(
+            &path,
+            serde_json::to_string_pretty(&self.lockfile)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+}
+
+fn read_lockfile(path: &Path) -> Result<Lockfile> {
+    let fs = vfs::current();
+    if fs.exists(path)? {
+        serde_json::from_str(&fs.read_to_string(path)?)
+            .map_err(|e| ThermiteError::invalid_json(path, e))
+    } else {
+        Ok(Lockfile::default())
+    }
+}
+
+/// Adds the files below `dir` to a snapshot under `rel`, skipping what [`ModManager::restore`] recreates
+fn add_snapshot_files(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    dir: &Path,
+    rel: &str,
+    top: bool,
+) -> Result<()> {
+    let fs = vfs::current();
+    for entry in fs.read_dir(dir)? {
+        let name = entry.file_name();
+        if top && (SNAPSHOT_SKIPPED.contains(&name) || name == LOCKFILE_NAME || name == JOURNAL_DIR)
+        {
+            continue;
+        }
+
+        let rel = format!("{rel}/{name}");
+        if entry.is_dir {
+            add_snapshot_files(zip, &entry.path, &rel, false)?;
+        } else {
+            zip.start_file(rel, FileOptions::default())?;
+            zip.write_all(&fs.read(&entry.path)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Turns `author-name-X.Y.Z` into `author-name`
+fn package_key(full_name: &str) -> String {
+    parse_modstring(full_name).map_or_else(
+        |_| full_name.to_owned(),
+        |(author, name, _)| format!("{author}-{name}"),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, sync::Arc};
+
+    use crate::{
+        config::{OverwritePolicy, ThermiteConfig},
+        core::{
+            cache::PackageCache,
+            resolve::{clear_resolver, set_resolver, InstallConflict, Resolution},
+            utils::TempDir,
+        },
+        error::ThermiteError,
+        model::EnabledMods,
+        test_util::{mod_archive, MockPackage, MockServer},
+        verify::Verifier,
+    };
+
+    use super::{is_newer, ModManager, DEFAULT_PROFILE};
+
+    #[test]
+    fn manage_profile() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(
+            MockPackage::new("Foo", "Bar", "1.0.0").with_dependencies(["Foo-Baz-0.1.0"]),
+        );
+        server.add_package(MockPackage::new("Foo", "Baz", "0.1.0"));
+
+        let dir = TempDir::create("./test_mod_manager").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        let mut manager =
+            ModManager::with_config(&*dir, DEFAULT_PROFILE, config.clone()).expect("manager");
+
+        let reports = manager.install("Foo-Bar").expect("install");
+        assert_eq!(reports.len(), 2, "dependency should be installed first");
+        assert_eq!(manager.list().unwrap().len(), 2);
+        assert!(!manager.lockfile().packages["Foo-Baz"].explicit);
+        assert!(manager.check_updates().unwrap().is_empty());
+
+        manager.enable("Foo-Bar", false).expect("disable");
+        let enabled =
+            EnabledMods::load(manager.profile_dir().join("enabledmods.json")).expect("load");
+        assert_eq!(enabled.get("Mock.Bar"), Some(false));
+        #[cfg(feature = "db")]
+        assert!(!manager
+            .database()
+            .expect("open database")
+            .get("Foo-Bar")
+            .unwrap()
+            .expect("in database")
+            .enabled());
+
+        server.add_package(MockPackage::new("Foo", "Bar", "1.1.0"));
+        manager.refresh_index().unwrap();
+        let updates = manager.check_updates().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].latest, "1.1.0");
+
+        let reports = manager.update_all().expect("update");
+        assert_eq!(reports[0].version.as_deref(), Some("1.1.0"));
+        assert!(!manager.packages_dir().join("Foo-Bar-1.0.0").exists());
+
+        server.add_package(MockPackage::new("Foo", "Old", "1.0.0").deprecated());
+        manager.refresh_index().unwrap();
+        let reports = manager.install("Foo-Old").expect("install deprecated");
+        assert_eq!(reports[0].warnings, ["Foo-Old is deprecated"]);
+        let updates = manager.check_updates().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert!(updates[0].deprecated);
+        assert_eq!(updates[0].latest, "1.0.0");
+        assert!(manager.update_all().unwrap().is_empty());
+        manager.remove("Foo-Old").expect("remove");
+
+        // the lockfile is picked up by new managers
+        let mut manager = ModManager::with_config(&*dir, DEFAULT_PROFILE, config).unwrap();
+        assert_eq!(manager.lockfile().packages["Foo-Bar"].version, "1.1.0");
+
+        manager.remove("Foo-Bar").expect("remove");
+        assert!(!manager.packages_dir().join("Foo-Bar-1.1.0").exists());
+        assert!(matches!(
+            manager.remove("Foo-Bar"),
+            Err(ThermiteError::NotInstalled(_))
+        ));
+
+        manager.switch_profile("Other").expect("switch profile");
+        assert_eq!(manager.profile_dir(), dir.join("Other"));
+        assert!(manager.lockfile().packages.is_empty());
+        manager.switch_profile(DEFAULT_PROFILE).unwrap();
+        assert!(manager.lockfile().packages.contains_key("Foo-Baz"));
+    }
+
+    #[test]
+    fn plan_operations() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(
+            MockPackage::new("Foo", "Bar", "1.0.0").with_dependencies(["Foo-Baz-0.1.0"]),
+        );
+        server.add_package(MockPackage::new("Foo", "Baz", "0.1.0"));
+
+        let dir = TempDir::create("./test_plan_manager").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        let mut manager = ModManager::with_config(&*dir, DEFAULT_PROFILE, config).expect("manager");
+
+        let plan = manager.plan_install("Foo-Bar").expect("plan install");
+        assert_eq!(plan.dependencies, ["Foo-Baz-0.1.0"]);
+        assert_eq!(plan.installs.len(), 2);
+        assert!(plan
+            .created
+            .iter()
+            .any(|p| p.ends_with("Foo-Bar-1.0.0/manifest.json")));
+        assert!(manager.list().unwrap().is_empty());
+        assert!(manager.lockfile().packages.is_empty());
+
+        manager.install("Foo-Bar").expect("install");
+        let plan = manager.plan_remove("Foo-Baz").expect("plan remove");
+        assert!(plan
+            .removed
+            .iter()
+            .any(|p| p.ends_with("Foo-Baz-0.1.0/manifest.json")));
+        assert_eq!(manager.list().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+        server.add_package(MockPackage::new("Foo", "Baz", "0.1.0"));
+        server.add_package(MockPackage::new("Foo", "Stale", "1.0.0"));
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+
+        let desktop =
+            TempDir::create("./test_snapshot_desktop").expect("Unable to create temp dir");
+        let mut manager =
+            ModManager::with_config(&*desktop, DEFAULT_PROFILE, config.clone()).expect("manager");
+        manager.install("Foo-Bar").expect("install");
+        manager.enable("Foo-Bar", false).expect("disable");
+        std::fs::create_dir_all(manager.profile_dir().join("save_data/Mock.Bar")).unwrap();
+        std::fs::write(
+            manager
+                .profile_dir()
+                .join("save_data/Mock.Bar/settings.txt"),
+            "fov 110",
+        )
+        .unwrap();
+        manager.switch_profile("Other").unwrap();
+        manager.install("Foo-Baz").expect("install");
+        manager.switch_profile(DEFAULT_PROFILE).unwrap();
+        let snapshot = manager.snapshot().expect("snapshot");
+
+        let deck = TempDir::create("./test_snapshot_deck").expect("Unable to create temp dir");
+        let mut manager = ModManager::with_config(&*deck, "Other", config).expect("manager");
+        manager.switch_profile(DEFAULT_PROFILE).unwrap();
+        manager.install("Foo-Stale").expect("install");
+        manager.switch_profile("Other").unwrap();
+
+        let reports = manager.restore(Cursor::new(snapshot)).expect("restore");
+        assert_eq!(reports.len(), 2);
+        assert_eq!(manager.profile(), "Other");
+        assert_eq!(manager.lockfile().packages["Foo-Baz"].version, "0.1.0");
+        assert!(manager.is_installed("Foo-Baz", "0.1.0").unwrap());
+
+        manager.switch_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(
+            manager.lockfile().packages.keys().collect::<Vec<_>>(),
+            ["Foo-Bar"]
+        );
+        assert!(!manager.packages_dir().join("Foo-Stale-1.0.0").exists());
+        let enabled =
+            EnabledMods::load(manager.profile_dir().join("enabledmods.json")).expect("load");
+        assert_eq!(enabled.get("Mock.Bar"), Some(false));
+        assert_eq!(
+            std::fs::read_to_string(
+                manager
+                    .profile_dir()
+                    .join("save_data/Mock.Bar/settings.txt")
+            )
+            .unwrap(),
+            "fov 110"
+        );
+
+        assert!(matches!(
+            manager.restore(Cursor::new(mod_archive("Bar", "1.0.0"))),
+            Err(ThermiteError::InvalidSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn resolve_conflicts() {
+        let server = MockServer::start().expect("start mock server");
+        for name in ["Keep", "Skip", "New"] {
+            server.add_package(MockPackage::new("Resolve", name, "1.0.0"));
+        }
+        let dir = TempDir::create("./test_resolve_conflicts").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        let mut manager =
+            ModManager::with_config(&*dir, DEFAULT_PROFILE, config.clone()).expect("manager");
+        for name in ["Keep", "Skip", "New"] {
+            manager.install(format!("Resolve-{name}")).expect("install");
+            server.add_package(MockPackage::new("Resolve", name, "2.0.0"));
+        }
+        manager.refresh_index().unwrap();
+
+        // other tests install at the same time, so only answer for these packages
+        set_resolver(|conflict: &InstallConflict| match conflict {
+            InstallConflict::VersionMismatch { package, .. } if package == "Resolve-Skip" => {
+                Resolution::Skip
+            }
+            InstallConflict::VersionMismatch { package, .. }
+            | InstallConflict::AlreadyInstalled { name: package, .. }
+                if package.starts_with("Resolve-Keep") =>
+            {
+                Resolution::KeepExisting
+            }
+            _ => Resolution::UseNew,
+        });
+
+        assert!(manager.install("Resolve-Keep").expect("keep").is_empty());
+        assert_eq!(manager.lockfile().packages["Resolve-Keep"].version, "1.0.0");
+        assert!(!manager.packages_dir().join("Resolve-Keep-2.0.0").exists());
+
+        assert!(manager.install("Resolve-Skip").expect("skip").is_empty());
+        assert!(!manager.packages_dir().join("Resolve-Skip-2.0.0").exists());
+
+        let reports = manager.install("Resolve-New").expect("use new");
+        assert_eq!(reports[0].version.as_deref(), Some("2.0.0"));
+        assert!(!manager.packages_dir().join("Resolve-New-1.0.0").exists());
+        assert_eq!(manager.lockfile().packages["Resolve-New"].version, "2.0.0");
+
+        let mut manager = ModManager::with_config(
+            &*dir,
+            DEFAULT_PROFILE,
+            ThermiteConfig {
+                overwrite: OverwritePolicy::Ask,
+                ..config
+            },
+        )
+        .unwrap();
+        let reports = manager.install("Resolve-Keep-1.0.0").expect("keep");
+        assert!(reports[0].skipped);
+
+        clear_resolver();
+        assert!(matches!(
+            manager
+                .install("Resolve-Keep-1.0.0")
+                .map_err(ThermiteError::into_root),
+            Err(ThermiteError::AlreadyInstalled(_))
+        ));
+    }
+
+    #[test]
+    fn cached_archives() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+
+        let dir = TempDir::create("./test_manager_cache").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            cache_dir: Some(dir.join("cache")),
+            ..Default::default()
+        };
+        let mut manager =
+            ModManager::with_config(&*dir, DEFAULT_PROFILE, config.clone()).expect("manager");
+
+        manager.install("Foo-Bar").expect("install");
+        manager.remove("Foo-Bar").expect("remove");
+        manager.install("Foo-Bar").expect("install from cache");
+
+        let downloads = server
+            .requests()
+            .iter()
+            .filter(|r| r.path.starts_with("/package/download/"))
+            .count();
+        assert_eq!(downloads, 1, "second install should use the cache");
+        let cache = PackageCache::from_config(&config).unwrap();
+        assert_eq!(cache.stats().unwrap().entries, 1);
+    }
+
+    #[test]
+    fn reject_mismatched_archives() {
+        let server = MockServer::start().expect("start mock server");
+        let package = MockPackage::new("Foo", "Bar", "1.0.0");
+        server.add_package(package.clone());
+        server.serve(package.download_path(), mod_archive("Bar", "6.6.6"));
+
+        let dir = TempDir::create("./test_manager_checksum").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        let mut manager = ModManager::with_config(&*dir, DEFAULT_PROFILE, config).unwrap();
+
+        let err = manager.install("Foo-Bar").expect_err("tampered archive");
+        assert!(matches!(err.root(), ThermiteError::ChecksumMismatch { .. }));
+        assert!(manager.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reject_unverified() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Bar", "1.0.0"));
+
+        let dir = TempDir::create("./test_mod_manager_verify").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            verifier: Some(Arc::new(Verifier::new())),
+            ..Default::default()
+        };
+        let mut manager = ModManager::with_config(&*dir, DEFAULT_PROFILE, config).unwrap();
+
+        let err = manager.install("Foo-Bar").expect_err("untrusted package");
+        assert!(matches!(err.root(), ThermiteError::UntrustedPackage(_)));
+        assert_eq!(
+            err.to_string(),
+            "Error verifying Foo-Bar-1.0.0: No trusted hash is known for Foo-Bar-1.0.0"
+        );
+        assert!(manager.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn compare_versions() {
+        assert!(is_newer("1.10.0", "1.9.0"));
+        assert!(!is_newer("1.0.0", "1.0.0"));
+        assert!(!is_newer("0.9.1", "1.0.0"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Counters and timings for network requests, the package cache and installs
+//!
+//! Everything thermite does in the process is counted, whichever thread or
+//! [`ModManager`](crate::manager::ModManager) did it. Read the totals with [`snapshot`], or [`take`] them to
+//! measure from a clean slate.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Totals since the process started or the last [`reset`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// HTTP requests made, including failed ones
+    pub requests: u64,
+    /// Requests that failed or got a non-2xx response
+    pub failed_requests: u64,
+    /// Requests retried after a transient failure
+    pub retries: u64,
+    /// Finished downloads
+    pub downloads: u64,
+    pub bytes_downloaded: u64,
+    /// Time spent in finished downloads
+    pub download_time: Duration,
+    /// Archives taken from the package cache instead of downloaded
+    pub cache_hits: u64,
+    /// Archives downloaded because the package cache didn't have them
+    pub cache_misses: u64,
+    /// Package installs and updates attempted
+    pub installs: u64,
+    pub failed_installs: u64,
+    /// Time spent in package installs, successful or not
+    pub install_time: Duration,
+}
+
+impl Metrics {
+    /// Fraction of archive lookups served by the package cache, `None` before the first lookup
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// `None` before the first install
+    #[must_use]
+    pub fn average_install_time(&self) -> Option<Duration> {
+        let installs = u32::try_from(self.installs).unwrap_or(u32::MAX);
+        (installs > 0).then(|| self.install_time / installs)
+    }
+
+    /// Average download speed in bytes per second, `None` before any time was spent downloading
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn download_speed(&self) -> Option<f64> {
+        let secs = self.download_time.as_secs_f64();
+        (secs > 0.0).then(|| self.bytes_downloaded as f64 / secs)
+    }
+}
+
+struct Counters {
+    requests: AtomicU64,
+    failed_requests: AtomicU64,
+    retries: AtomicU64,
+    downloads: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    download_nanos: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    installs: AtomicU64,
+    failed_installs: AtomicU64,
+    install_nanos: AtomicU64,
+}
+
+static COUNTERS: Counters = Counters {
+    requests: AtomicU64::new(0),
+    failed_requests: AtomicU64::new(0),
+    retries: AtomicU64::new(0),
+    downloads: AtomicU64::new(0),
+    bytes_downloaded: AtomicU64::new(0),
+    download_nanos: AtomicU64::new(0),
+    cache_hits: AtomicU64::new(0),
+    cache_misses: AtomicU64::new(0),
+    installs: AtomicU64::new(0),
+    failed_installs: AtomicU64::new(0),
+    install_nanos: AtomicU64::new(0),
+};
+
+fn add(counter: &AtomicU64, n: u64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+pub(crate) fn record_request(ok: bool) {
+    add(&COUNTERS.requests, 1);
+    if !ok {
+        add(&COUNTERS.failed_requests, 1);
+    }
+}
+
+pub(crate) fn record_retry() {
+    add(&COUNTERS.retries, 1);
+}
+
+pub(crate) fn record_download(bytes: u64, duration: Duration) {
+    add(&COUNTERS.downloads, 1);
+    add(&COUNTERS.bytes_downloaded, bytes);
+    add(&COUNTERS.download_nanos, nanos(duration));
+}
+
+pub(crate) fn record_cache_lookup(hit: bool) {
+    add(
+        if hit {
+            &COUNTERS.cache_hits
+        } else {
+            &COUNTERS.cache_misses
+        },
+        1,
+    );
+}
+
+pub(crate) fn record_install(ok: bool, duration: Duration) {
+    add(&COUNTERS.installs, 1);
+    if !ok {
+        add(&COUNTERS.failed_installs, 1);
+    }
+    add(&COUNTERS.install_nanos, nanos(duration));
+}
+
+/// Reads every counter, resetting them too if `reset` is set
+fn read(reset: bool) -> Metrics {
+    let get = |counter: &AtomicU64| {
+        if reset {
+            counter.swap(0, Ordering::Relaxed)
+        } else {
+            counter.load(Ordering::Relaxed)
+        }
+    };
+    Metrics {
+        requests: get(&COUNTERS.requests),
+        failed_requests: get(&COUNTERS.failed_requests),
+        retries: get(&COUNTERS.retries),
+        downloads: get(&COUNTERS.downloads),
+        bytes_downloaded: get(&COUNTERS.bytes_downloaded),
+        download_time: Duration::from_nanos(get(&COUNTERS.download_nanos)),
+        cache_hits: get(&COUNTERS.cache_hits),
+        cache_misses: get(&COUNTERS.cache_misses),
+        installs: get(&COUNTERS.installs),
+        failed_installs: get(&COUNTERS.failed_installs),
+        install_time: Duration::from_nanos(get(&COUNTERS.install_nanos)),
+    }
+}
+
+/// The current totals
+#[must_use]
+pub fn snapshot() -> Metrics {
+    read(false)
+}
+
+/// The current totals, setting every counter back to zero
+///
+/// Each counter is reset as it's read, so nothing recorded in between is lost
+pub fn take() -> Metrics {
+    read(true)
+}
+
+/// Sets every counter back to zero
+pub fn reset() {
+    read(true);
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Cursor, time::Duration};
+
+    use crate::{
+        core::{manage::install_mod, utils::TempDir},
+        test_util::mod_archive,
+    };
+
+    use super::{snapshot, Metrics};
+
+    #[test]
+    fn derived_metrics() {
+        let metrics = Metrics {
+            cache_hits: 3,
+            cache_misses: 1,
+            installs: 4,
+            install_time: Duration::from_secs(2),
+            bytes_downloaded: 1000,
+            download_time: Duration::from_millis(500),
+            ..Default::default()
+        };
+        assert_eq!(metrics.cache_hit_rate(), Some(0.75));
+        assert_eq!(
+            metrics.average_install_time(),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(metrics.download_speed(), Some(2000.0));
+
+        let empty = Metrics::default();
+        assert_eq!(empty.cache_hit_rate(), None);
+        assert_eq!(empty.average_install_time(), None);
+        assert_eq!(empty.download_speed(), None);
+    }
+
+    #[test]
+    fn installs_are_counted() {
+        // other tests run at the same time, so only check that the counters went up
+        let before = snapshot();
+        let dir = TempDir::create("./test_metrics").expect("Unable to create temp dir");
+        install_mod(
+            "Foo-Bar-1.0.0",
+            Cursor::new(mod_archive("Bar", "1.0.0")),
+            &dir,
+        )
+        .expect("install");
+        install_mod("invalid", Cursor::new(mod_archive("Bar", "1.0.0")), &dir)
+            .expect_err("invalid name");
+
+        let after = snapshot();
+        assert!(after.installs >= before.installs + 2);
+        assert!(after.failed_installs > before.failed_installs);
+    }
+}
@@ -1,16 +1,23 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value};
+use std::path::{Path, PathBuf};
 use std::{
+    cmp::Ordering,
     collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fmt::Display,
     hash::{Hash, Hasher},
-};
-use std::{
-    fs,
-    path::{Path, PathBuf},
+    time::SystemTime,
 };
 use tracing::{debug, error};
 
-use crate::{error::ThermiteError, CORE_MODS};
+use crate::{
+    core::{
+        utils::{is_newer, package_dir},
+        vfs,
+    },
+    error::ThermiteError,
+    CORE_MODS,
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
@@ -30,7 +37,87 @@ pub struct ModJSON {
     pub _extra: HashMap<String, Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// A version number as the index writes it, e.g. `1.10.0`, which [`Mod::versions`] is keyed by
+///
+/// Versions are ordered part by part, numerically where both parts are numbers, so `0.10.0` comes after
+/// `0.9.0`. Parts that aren't numbers compare as text and sort after numeric ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct VersionKey(String);
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum VersionPart<'a> {
+    Number(u64),
+    Text(&'a str),
+}
+
+impl VersionKey {
+    #[must_use]
+    pub fn new(version: impl Into<String>) -> Self {
+        Self(version.into())
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The parsed version, if it's `X.Y.Z`
+    #[must_use]
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(&self.0)
+    }
+
+    /// Compares two version strings the way [`VersionKey`]s are ordered, except that versions only written
+    /// differently, like `1.01.0` and `1.1.0`, are equal
+    pub(crate) fn compare(a: &str, b: &str) -> Ordering {
+        let parts = |v| {
+            str::split(v, '.').map(|part| {
+                part.parse()
+                    .map_or(VersionPart::Text(part), VersionPart::Number)
+            })
+        };
+        parts(a).cmp(parts(b))
+    }
+}
+
+impl Ord for VersionKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Self::compare(&self.0, &other.0).then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for VersionKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for VersionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for VersionKey {
+    fn from(version: &str) -> Self {
+        Self::new(version)
+    }
+}
+
+impl From<String> for VersionKey {
+    fn from(version: String) -> Self {
+        Self(version)
+    }
+}
+
+impl PartialEq<str> for VersionKey {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Mod {
     pub name: String,
     ///The latest version of the mod
@@ -41,24 +128,65 @@ pub struct Mod {
     pub upgradable: bool,
     #[serde(default)]
     pub global: bool,
-    ///A map of each version of a mod
-    pub versions: BTreeMap<String, ModVersion>,
+    ///A map of each version of a mod, oldest first
+    pub versions: BTreeMap<VersionKey, ModVersion>,
     pub author: String,
+    /// Thunderstore categories, e.g. `Mods`, `Skins` or `Server-side`
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub nsfw: bool,
+    #[serde(default)]
+    pub deprecated: bool,
+    #[serde(default)]
+    pub rating: i64,
+    /// Downloads of every version combined
+    #[serde(default)]
+    pub downloads: u64,
+    #[serde(default)]
+    pub pinned: bool,
+    /// The package's page on Thunderstore
+    #[serde(default)]
+    pub package_url: String,
+    #[serde(default)]
+    pub donation_link: Option<String>,
+    /// When the package was first uploaded, as an RFC 3339 timestamp
+    #[serde(default)]
+    pub date_created: String,
+    /// When the package last changed, as an RFC 3339 timestamp
+    #[serde(default)]
+    pub date_updated: String,
 }
 
 impl Mod {
+    /// The description of the latest version, Thunderstore doesn't have one for the whole package
+    #[must_use]
+    pub fn description(&self) -> &str {
+        self.get_latest()
+            .map(|v| v.desc.as_str())
+            .unwrap_or_default()
+    }
+
+    /// The version `latest` names, or the newest version if that isn't one of `versions`
     #[must_use]
     pub fn get_latest(&self) -> Option<&ModVersion> {
-        self.versions.get(&self.latest)
+        self.get_version(&self.latest)
+            .or_else(|| self.versions.values().next_back())
     }
 
     #[must_use]
     pub fn get_version(&self, version: impl AsRef<str>) -> Option<&ModVersion> {
-        self.versions.get(version.as_ref())
+        self.versions.get(&VersionKey::new(version.as_ref()))
+    }
+
+    /// Whether the package or this version of it is deprecated
+    #[must_use]
+    pub fn is_deprecated(&self, version: impl AsRef<str>) -> bool {
+        self.deprecated || self.get_version(version).is_some_and(|v| v.deprecated)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct ModVersion {
     pub name: String,
     pub full_name: String,
@@ -69,6 +197,22 @@ pub struct ModVersion {
     pub installed: bool,
     pub global: bool,
     pub file_size: u64,
+    /// Hex encoded SHA-256 of the archive, if the index has one
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub downloads: u64,
+    /// When the version was uploaded, as an RFC 3339 timestamp
+    #[serde(default)]
+    pub date_created: String,
+    /// URL of the version's icon
+    #[serde(default)]
+    pub icon: String,
+    #[serde(default)]
+    pub website_url: String,
+    /// Whether Thunderstore no longer lists this version, even if the package itself isn't deprecated
+    #[serde(default)]
+    pub deprecated: bool,
 }
 
 impl ModVersion {
@@ -160,7 +304,7 @@ impl Default for EnabledMods {
 
 impl Drop for EnabledMods {
     fn drop(&mut self) {
-        if self.path.is_some() {
+        if let Some(path) = &self.path {
             let hash = {
                 let mut hasher = DefaultHasher::new();
                 self.hash(&mut hasher);
@@ -171,11 +315,11 @@ impl Drop for EnabledMods {
                 if let Err(e) = self.save() {
                     error!(
                         "Encountered error while saving enabled_mods.json to {}:\n {}",
-                        self.path.as_ref().unwrap().display(),
+                        path.display(),
                         e
                     );
                 } else {
-                    debug!("Wrote file at {}", self.path.as_ref().unwrap().display());
+                    debug!("Wrote file at {}", path.display());
                 }
             }
         }
@@ -189,9 +333,10 @@ impl EnabledMods {
     /// - The file doesn't exist
     /// - The file isn't formatted properly
     pub fn load(path: impl AsRef<Path>) -> Result<Self, ThermiteError> {
-        let raw = fs::read_to_string(path)?;
+        let path = path.as_ref();
+        let raw = vfs::current().read_to_string(path)?;
 
-        json5::from_str(&raw).map_err(|e| e.into())
+        json5::from_str(&raw).map_err(|e| ThermiteError::invalid_json(path, e))
     }
 
     /// Returns a default `EnabledMods` with the path property set
@@ -219,11 +364,12 @@ impl EnabledMods {
     pub fn save(&self) -> Result<(), ThermiteError> {
         let parsed = serde_json::to_string_pretty(self)?;
         if let Some(path) = &self.path {
+            let fs = vfs::current();
             if let Some(p) = path.parent() {
-                fs::create_dir_all(p)?;
+                fs.create_dir_all(p)?;
             }
 
-            fs::write(path, parsed)?;
+            fs.write_atomic(path, parsed.as_bytes())?;
             Ok(())
         } else {
             Err(ThermiteError::MissingPath)
@@ -259,11 +405,11 @@ impl EnabledMods {
 
     /// Get the current state of a mod if it exists
     pub fn get(&self, name: impl AsRef<str>) -> Option<bool> {
-        if CORE_MODS.contains(&name.as_ref()) {
-            Some(match name.as_ref() {
-                "Northstar.Client" => self.client,
-                "Northstar.Custom" => self.custom,
-                "Northstar.CustomServers" => self.servers,
+        if CORE_MODS.contains(&name.as_ref().to_lowercase().as_str()) {
+            Some(match name.as_ref().to_lowercase().as_str() {
+                "northstar.client" => self.client,
+                "northstar.custom" => self.custom,
+                "northstar.customservers" => self.servers,
                 _ => unimplemented!(),
             })
         } else {
@@ -297,13 +443,220 @@ pub struct InstalledMod {
     pub path: PathBuf,
 }
 
+impl InstalledMod {
+    /// The package's version, `None` if the manifest's isn't `X.Y.Z`
+    #[must_use]
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(&self.manifest.version_number)
+    }
+
+    /// Whether `package` is this mod's package and its latest version is newer than the installed one
+    #[must_use]
+    pub fn is_outdated(&self, package: &Mod) -> bool {
+        package.author.eq_ignore_ascii_case(&self.author)
+            && package.name == self.manifest.name
+            && is_newer(&package.latest, &self.manifest.version_number)
+    }
+
+    /// Total size in bytes of the package's files, or of the mod's directory for mods that weren't
+    /// installed from a package
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn size(&self) -> Result<u64, ThermiteError> {
+        fn dir_size(fs: &dyn vfs::Fs, dir: &Path) -> Result<u64, ThermiteError> {
+            let mut total = 0;
+            for entry in fs.read_dir(dir)? {
+                total += if entry.is_dir {
+                    dir_size(fs, &entry.path)?
+                } else {
+                    fs.file_len(&entry.path)?
+                };
+            }
+            Ok(total)
+        }
+
+        let fs = vfs::current();
+        dir_size(&*fs, package_dir(self).unwrap_or(&self.path))
+    }
+
+    /// When the package was installed, going by when its `manifest.json` was written, or the mod's
+    /// `mod.json` for mods that weren't installed from a package
+    ///
+    /// `None` if the filesystem doesn't keep track of modification times.
+    ///
+    /// # Errors
+    /// * IO errors
+    pub fn installed_at(&self) -> Result<Option<SystemTime>, ThermiteError> {
+        let path = package_dir(self).map_or_else(
+            || self.path.join("mod.json"),
+            |dir| dir.join("manifest.json"),
+        );
+        Ok(vfs::current().modified(&path)?)
+    }
+}
+
+/// A parsed `X.Y.Z` version number, the only kind Thunderstore accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// `None` if `version` isn't three dot separated numbers
+    #[must_use]
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.').map(str::parse::<u64>);
+        let (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+        Some(Self {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A named set of packages pinned to exact versions, so a server's mods can be shared and reproduced
+///
+/// Stored as JSON, comments and trailing commas are allowed when loading:
+/// ```json
+/// {
+///     "name": "My Server",
+///     "packages": {
+///         "Foo-Bar": "1.0.0",
+///     }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModPack {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Versions keyed by `author-name`
+    pub packages: BTreeMap<String, String>,
+}
+
+impl ModPack {
+    /// # Errors
+    /// - The modpack isn't formatted properly
+    pub fn from_json(raw: impl AsRef<str>) -> Result<Self, ThermiteError> {
+        json5::from_str(raw.as_ref()).map_err(|e| e.into())
+    }
+
+    /// Attempts to read a `ModPack` from the path
+    ///
+    /// # Errors
+    /// - The file doesn't exist
+    /// - The file isn't formatted properly
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ThermiteError> {
+        Self::from_json(vfs::current().read_to_string(path.as_ref())?)
+    }
+
+    /// Writes the modpack to the path as pretty printed JSON
+    ///
+    /// # Errors
+    /// - If there is an IO error
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ThermiteError> {
+        let parsed = serde_json::to_string_pretty(self)?;
+        vfs::current().write_atomic(path.as_ref(), parsed.as_bytes())?;
+        Ok(())
+    }
+
+    /// `author-name-X.Y.Z` of every package, sorted by name
+    #[must_use]
+    pub fn pins(&self) -> Vec<String> {
+        self.packages
+            .iter()
+            .map(|(name, version)| format!("{name}-{version}"))
+            .collect()
+    }
+}
+
+/// The packages installed in a profile and which of their mods are enabled, so the setup can be reproduced
+/// elsewhere
+///
+/// Made with [`export_mod_list`](crate::core::utils::export_mod_list) and applied with
+/// [`import_mod_list`](crate::core::manage::import_mod_list).
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModList {
+    /// Packages keyed by `author-name`
+    pub packages: BTreeMap<String, ListedPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListedPackage {
+    pub version: String,
+    /// Whether each of the package's mods is enabled, keyed by the mod's name
+    #[serde(default)]
+    pub mods: BTreeMap<String, bool>,
+}
+
+impl ModList {
+    /// # Errors
+    /// - The list isn't formatted properly
+    pub fn from_json(raw: impl AsRef<str>) -> Result<Self, ThermiteError> {
+        json5::from_str(raw.as_ref()).map_err(|e| e.into())
+    }
+
+    /// Attempts to read a `ModList` from the path
+    ///
+    /// # Errors
+    /// - The file doesn't exist
+    /// - The file isn't formatted properly
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ThermiteError> {
+        Self::from_json(vfs::current().read_to_string(path.as_ref())?)
+    }
+
+    /// Writes the list to the path as pretty printed JSON
+    ///
+    /// # Errors
+    /// - If there is an IO error
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ThermiteError> {
+        let parsed = serde_json::to_string_pretty(self)?;
+        vfs::current().write_atomic(path.as_ref(), parsed.as_bytes())?;
+        Ok(())
+    }
+
+    /// A [`ModPack`] pinning every package at its listed version
+    #[must_use]
+    pub fn to_modpack(&self, name: impl Into<String>) -> ModPack {
+        ModPack {
+            name: name.into(),
+            description: String::new(),
+            packages: self
+                .packages
+                .iter()
+                .map(|(key, package)| (key.clone(), package.version.clone()))
+                .collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
+    use std::{
+        collections::{BTreeMap, HashMap},
+        io::Cursor,
+    };
 
-    use crate::core::utils::TempDir;
+    use crate::{
+        core::{manage::install_mod, utils::find_mods, utils::TempDir},
+        test_util::mod_archive,
+    };
 
-    use super::{EnabledMods, Manifest, ModJSON};
+    use super::{EnabledMods, Manifest, Mod, ModJSON, ModVersion, Version, VersionKey};
 
     const TEST_MOD_JSON: &str = r#"{
         "Name": "Test",
@@ -440,4 +793,100 @@ mod test {
         assert!(test_mod.is_some());
         assert!(!test_mod.unwrap());
     }
+
+    #[test]
+    fn order_versions() {
+        let mut versions = ["1.10.0", "0.9.0", "1.9.2", "0.10.0", "1.10.0-beta", "1.10"]
+            .map(VersionKey::from)
+            .to_vec();
+        versions.sort();
+        assert_eq!(
+            versions.iter().map(VersionKey::as_str).collect::<Vec<_>>(),
+            ["0.9.0", "0.10.0", "1.9.2", "1.10", "1.10.0", "1.10.0-beta"]
+        );
+        assert_eq!(
+            VersionKey::from("1.10.0").version(),
+            Some(Version {
+                major: 1,
+                minor: 10,
+                patch: 0
+            })
+        );
+        assert!(Version::parse("1.10.0").unwrap() > Version::parse("1.9.2").unwrap());
+        assert_eq!(Version::parse("1.0"), None);
+        assert_eq!(Version::parse("1.0.0.0"), None);
+        assert_eq!(Version::parse("1.0.0").unwrap().to_string(), "1.0.0");
+
+        let version = |v: &str| ModVersion {
+            name: "Bar".into(),
+            full_name: format!("Foo-Bar-{v}"),
+            version: v.into(),
+            url: String::new(),
+            desc: String::new(),
+            deps: vec![],
+            installed: false,
+            global: false,
+            file_size: 0,
+            sha256: None,
+            ..Default::default()
+        };
+        let mut package = Mod {
+            name: "Bar".into(),
+            latest: "0.9.0".into(),
+            installed: false,
+            upgradable: false,
+            global: false,
+            versions: ["0.10.0", "0.9.0", "0.2.0"]
+                .into_iter()
+                .map(|v| (v.into(), version(v)))
+                .collect(),
+            author: "Foo".into(),
+            ..Default::default()
+        };
+        assert_eq!(package.versions.keys().next_back().unwrap(), "0.10.0");
+        assert_eq!(package.get_latest().unwrap().version, "0.9.0");
+        package.latest = "1.0.0".into();
+        assert_eq!(package.get_latest().unwrap().version, "0.10.0");
+
+        let json = serde_json::to_string(&package).unwrap();
+        assert_eq!(serde_json::from_str::<Mod>(&json).unwrap(), package);
+    }
+
+    #[test]
+    fn installed_mod_details() {
+        let dir =
+            TempDir::create("./test_installed_mod_details").expect("Unable to create temp dir");
+        let archive = mod_archive("Bar", "1.0.0");
+        install_mod("Foo-Bar-1.0.0", Cursor::new(&archive), &dir).expect("install");
+        let installed = find_mods(&dir).expect("find mods").remove(0);
+
+        assert_eq!(installed.version(), Version::parse("1.0.0"));
+        let files = ["manifest.json", "mods/Bar/mod.json"];
+        let expected: u64 = files
+            .iter()
+            .map(|f| {
+                std::fs::metadata(dir.join("Foo-Bar-1.0.0").join(f))
+                    .unwrap()
+                    .len()
+            })
+            .sum();
+        assert!(installed.size().expect("size") >= expected);
+        assert!(installed.installed_at().expect("installed at").is_some());
+
+        let mut package = Mod {
+            name: "Bar".into(),
+            latest: "1.0.0".into(),
+            installed: true,
+            upgradable: false,
+            global: false,
+            versions: BTreeMap::new(),
+            author: "Foo".into(),
+            ..Default::default()
+        };
+        assert!(!installed.is_outdated(&package));
+        package.latest = "1.10.0".into();
+        assert!(installed.is_outdated(&package));
+        package.author = "Someone".into();
+        assert!(!installed.is_outdated(&package));
+    }
 }
@@ -0,0 +1,234 @@
+//! Shared worker pool for CPU-bound work such as scanning, hashing and verification
+//!
+//! By default work runs on up to `ThermiteConfig::parallelism` scoped threads.
+//! Embedders that already have a pool (e.g. rayon) can implement [`Executor`] for it and install it with [`set_executor`].
+
+use std::{
+    any::Any,
+    collections::VecDeque,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, RwLock},
+    thread,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{cancel, config, core::vfs, error::ThermiteError};
+
+/// A unit of work handed to an [`Executor`]
+pub type Job<'a> = Box<dyn FnOnce() + Send + 'a>;
+
+/// Something that can run a batch of jobs in parallel
+pub trait Executor: Send + Sync {
+    /// Runs every job, only returning once all of them have finished
+    fn execute<'a>(&self, jobs: Vec<Job<'a>>);
+}
+
+/// The default executor, runs jobs on a bounded number of scoped threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadPool {
+    threads: NonZeroUsize,
+}
+
+impl ThreadPool {
+    #[must_use]
+    pub const fn new(threads: NonZeroUsize) -> Self {
+        Self { threads }
+    }
+
+    #[must_use]
+    pub const fn threads(&self) -> NonZeroUsize {
+        self.threads
+    }
+}
+
+impl Executor for ThreadPool {
+    fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+        let workers = self.threads.get().min(jobs.len());
+        if workers <= 1 {
+            jobs.into_iter().for_each(|job| job());
+            return;
+        }
+
+        let queue = Mutex::new(VecDeque::from(jobs));
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| loop {
+                    let next = match queue.lock() {
+                        Ok(mut q) => q.pop_front(),
+                        Err(poisoned) => poisoned.into_inner().pop_front(),
+                    };
+                    match next {
+                        Some(job) => job(),
+                        None => break,
+                    }
+                });
+            }
+        });
+    }
+}
+
+lazy_static! {
+    static ref EXECUTOR: RwLock<Option<Arc<dyn Executor>>> = RwLock::new(None);
+}
+
+/// Replaces the executor used for all parallel work
+pub fn set_executor(executor: impl Executor + 'static) {
+    if let Ok(mut lock) = EXECUTOR.write() {
+        *lock = Some(Arc::new(executor));
+    }
+}
+
+/// Goes back to using a [`ThreadPool`] sized by the global config
+pub fn clear_executor() {
+    if let Ok(mut lock) = EXECUTOR.write() {
+        *lock = None;
+    }
+}
+
+fn executor() -> Arc<dyn Executor> {
+    EXECUTOR
+        .read()
+        .ok()
+        .and_then(|lock| lock.clone())
+        .unwrap_or_else(|| Arc::new(ThreadPool::new(config::config().parallelism)))
+}
+
+/// Applies `f` to every item using the current executor, preserving order
+///
+/// Cancellation tokens that are active on the calling thread also apply inside `f`. A job that panics, or that
+/// the executor never ran, results in an error instead of taking the caller down with it
+pub(crate) fn map<T, R, F>(items: Vec<T>, f: F) -> Vec<Result<R, ThermiteError>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Result<R, ThermiteError> + Sync,
+{
+    map_with(&*executor(), items, f)
+}
+
+/// [`map`] on a specific executor instead of the current one
+pub(crate) fn map_with<T, R, F>(
+    executor: &dyn Executor,
+    items: Vec<T>,
+    f: F,
+) -> Vec<Result<R, ThermiteError>>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Result<R, ThermiteError> + Sync,
+{
+    let tokens = cancel::current();
+    let fs = vfs::current();
+    let results = items.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+
+    let jobs = items
+        .into_iter()
+        .zip(&results)
+        .map(|(item, slot)| {
+            let f = &f;
+            let tokens = &tokens;
+            let fs = fs.clone();
+            Box::new(move || {
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    vfs::with(fs, || cancel::run_with(tokens, || f(item)))
+                }))
+                .unwrap_or_else(|payload| Err(ThermiteError::Panicked(panic_message(&*payload))));
+                if let Ok(mut slot) = slot.lock() {
+                    *slot = Some(res);
+                }
+            }) as Job<'_>
+        })
+        .collect();
+    executor.execute(jobs);
+
+    results
+        .into_iter()
+        .map(|slot| {
+            slot.into_inner()
+                .ok()
+                .flatten()
+                .unwrap_or(Err(ThermiteError::JobNotRun))
+        })
+        .collect()
+}
+
+/// The message a panic was started with, empty if it wasn't a string
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        num::NonZeroUsize,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::{map, map_with, Executor, Job, ThreadPool};
+    use crate::{cancel::CancelToken, error::ThermiteError};
+
+    #[test]
+    fn map_preserves_order() {
+        let res = map((0..64).collect(), |i: i32| Ok(i * 2));
+        assert_eq!(
+            res.into_iter().collect::<Result<Vec<_>, _>>().unwrap(),
+            (0..64).map(|i| i * 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn pool_runs_every_job() {
+        let count = AtomicUsize::new(0);
+        let jobs = (0..10)
+            .map(|_| {
+                Box::new(|| {
+                    count.fetch_add(1, Ordering::Relaxed);
+                }) as Job<'_>
+            })
+            .collect();
+
+        ThreadPool::new(NonZeroUsize::new(3).unwrap()).execute(jobs);
+        assert_eq!(count.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn cancellation_reaches_workers() {
+        let token = CancelToken::new();
+        token.cancel();
+
+        let res = token.run(|| map(vec![1, 2, 3], |_| crate::cancel::checkpoint("testing")));
+        assert!(res
+            .iter()
+            .all(|r| matches!(r, Err(ThermiteError::Cancelled))));
+    }
+
+    #[test]
+    fn failed_jobs_are_errors() {
+        let res = map(vec![1, 2, 3], |i| {
+            assert_ne!(i, 2, "two");
+            Ok(i)
+        });
+        assert!(matches!(res[0], Ok(1)));
+        assert!(matches!(&res[1], Err(ThermiteError::Panicked(msg)) if msg.contains("two")));
+        assert!(matches!(res[2], Ok(3)));
+
+        // an executor that gives up after the first job
+        struct FirstOnly;
+        impl Executor for FirstOnly {
+            fn execute<'a>(&self, jobs: Vec<Job<'a>>) {
+                if let Some(job) = jobs.into_iter().next() {
+                    job();
+                }
+            }
+        }
+        let res = map_with(&FirstOnly, vec![1, 2], Ok);
+        assert!(matches!(res[0], Ok(1)));
+        assert!(matches!(res[1], Err(ThermiteError::JobNotRun)));
+    }
+}
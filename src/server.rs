@@ -0,0 +1,696 @@
+//! Setting up dedicated Northstar servers
+//!
+//! [`provision`] turns a copy of the game into a dedicated server: it installs Northstar, installs the
+//! server's mods, writes `autoexec_ns_server.cfg` from a [`ServerConfig`] and removes packages that only do
+//! something on clients. Start the server with the `r2ds.bat` Northstar ships.
+//!
+//! Setting `ThermiteConfig::strip_client_assets` on the manager's config also skips textures, audio and client
+//! scripts inside the mods that are kept, see [`is_client_asset`].
+//!
+//! [`install_northstar_dedicated`] installs just the parts of Northstar a server needs, [`validate_dedicated`]
+//! checks an existing server for missing stub DLLs and leftover client-only files and mods.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Cursor;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Write as _},
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+use tracing::{debug, warn};
+use zip::ZipArchive;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{api::fetch_index_cached, core::manage::download_verified, error::ThermiteError};
+use crate::{
+    config,
+    core::{
+        manage::install_northstar_filtered,
+        report::{InstallReport, NorthstarReport},
+        utils::{find_mods, get_enabled_mods},
+        vfs,
+    },
+    error::Result,
+    manager::ModManager,
+    model::ModJSON,
+};
+
+/// Directories inside a mod that only clients load
+const CLIENT_DIRS: [&str; 3] = ["audio", "mod/materials", "mod/resource"];
+
+/// Parts of a Northstar release only the game client uses, relative to the game directory
+///
+/// The proxy DLL, `GameSpec::proxy_dll`, is one too since servers are started with
+/// `NorthstarLauncher.exe -dedicated` or `r2ds.bat`.
+pub const CLIENT_ONLY_FILES: [&str; 2] = [
+    "R2Northstar/mods/Northstar.Client",
+    "R2Northstar/plugins/DiscordRPC.dll",
+];
+
+/// Stand-ins for the renderer's DLLs that Northstar ships, dedicated servers crash on startup without them
+pub const DEDICATED_STUBS: [&str; 2] = [
+    "bin/x64_dedi/d3d11.dll",
+    "bin/x64_dedi/GFSDK_SSAO.win64.dll",
+];
+
+/// Where Northstar reads the server config from, relative to the game directory
+pub const SERVER_CFG: &str =
+    "R2Northstar/mods/Northstar.CustomServers/mod/cfg/autoexec_ns_server.cfg";
+
+/// The settings written to `autoexec_ns_server.cfg`. The defaults match the file Northstar ships
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    pub name: String,
+    pub description: String,
+    /// Leave empty for a public server
+    pub password: String,
+    /// List the server on the masterserver's server browser
+    pub report_to_masterserver: bool,
+    /// Let players join without masterserver authentication. Their progression won't be saved
+    pub allow_insecure_auth: bool,
+    pub masterserver: String,
+    pub everything_unlocked: bool,
+    /// Go back to the private match lobby after a game instead of the next map in the playlist
+    pub return_to_lobby: bool,
+    /// Server ticks per second
+    pub tick_rate: u32,
+    /// Snapshots sent to clients per second
+    pub update_rate: u32,
+    /// Any other ConVars, written after the typed settings so they can override them
+    pub con_vars: BTreeMap<String, String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            name: "Unnamed Northstar Server".into(),
+            description: "Default server description".into(),
+            password: String::new(),
+            report_to_masterserver: true,
+            allow_insecure_auth: false,
+            masterserver: "https://northstar.tf".into(),
+            everything_unlocked: true,
+            return_to_lobby: true,
+            tick_rate: 60,
+            update_rate: 20,
+            con_vars: BTreeMap::new(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Renders the config in `autoexec_ns_server.cfg` format
+    #[must_use]
+    pub fn to_cfg(&self) -> String {
+        let flag = |b: bool| if b { "1" } else { "0" };
+        let tick_interval = 1.0 / f64::from(self.tick_rate.max(1));
+        let mut settings = vec![
+            ("ns_server_name", quote(&self.name)),
+            ("ns_server_desc", quote(&self.description)),
+            ("ns_server_password", quote(&self.password)),
+            (
+                "ns_report_server_to_masterserver",
+                flag(self.report_to_masterserver).into(),
+            ),
+            ("ns_report_sp_server_to_masterserver", "0".into()),
+            (
+                "ns_auth_allow_insecure",
+                flag(self.allow_insecure_auth).into(),
+            ),
+            ("ns_erase_auth_info", "1".into()),
+            ("ns_masterserver_hostname", quote(&self.masterserver)),
+            ("everything_unlocked", flag(self.everything_unlocked).into()),
+            (
+                "ns_should_return_to_lobby",
+                flag(self.return_to_lobby).into(),
+            ),
+            ("net_chan_limit_mode", "2".into()),
+            ("net_chan_limit_msec_per_sec", "100".into()),
+            ("sv_querylimit_per_sec", "15".into()),
+            ("base_tickinterval_mp", format!("{tick_interval:.9}")),
+            ("sv_updaterate_mp", self.update_rate.to_string()),
+            ("sv_minupdaterate", self.update_rate.to_string()),
+            // clients disconnect in the kill replay if this is lower
+            (
+                "sv_max_snapshots_multiplayer",
+                (self.update_rate * 15).to_string(),
+            ),
+            ("net_data_block_enabled", "0".into()),
+            ("host_skip_client_dll_crc", "1".into()),
+        ];
+        settings.extend(
+            self.con_vars
+                .iter()
+                .map(|(name, value)| (name.as_str(), quote(value))),
+        );
+
+        let mut cfg = String::new();
+        for (name, value) in settings {
+            // writing to a String can't fail
+            let _ = writeln!(cfg, "{name} {value}");
+        }
+        cfg
+    }
+}
+
+/// ConVar values can't escape quotes, so they're swapped for single quotes
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "'"))
+}
+
+/// What [`provision`] did
+#[derive(Debug, Clone)]
+pub struct ServerReport {
+    pub northstar: NorthstarReport,
+    pub installed: Vec<InstallReport>,
+    /// Keys of the client-only packages that were removed
+    pub pruned: Vec<String>,
+    /// The `autoexec_ns_server.cfg` that was written
+    pub config_path: PathBuf,
+}
+
+/// Sets up the game in `manager`'s game directory as a dedicated server
+///
+/// The game directory should be a copy of the game kept for the server, since anything client-only in it
+/// gets removed. Running this again updates an existing server.
+///
+/// # Errors
+/// * Any error from installing Northstar or the mods
+/// * IO errors
+pub fn provision(
+    manager: &mut ModManager,
+    northstar: impl Read + Seek,
+    mods: &[impl AsRef<str>],
+    config: &ServerConfig,
+) -> Result<ServerReport> {
+    let northstar = install_northstar_dedicated_from(northstar, manager.game_dir())?;
+
+    let mut installed = vec![];
+    for name in mods {
+        installed.append(&mut manager.install(name)?);
+    }
+
+    // Northstar's update replaced the config with its default, so this has to come after
+    let config_path = write_server_config(manager.game_dir(), config)?;
+    let pruned = prune_client_only(manager)?;
+
+    Ok(ServerReport {
+        northstar,
+        installed,
+        pruned,
+        config_path,
+    })
+}
+
+/// Installs the latest release of Northstar from the global config's index to `game_dir`, leaving out the
+/// parts only clients use, see [`CLIENT_ONLY_FILES`]
+///
+/// Client-only files already in `game_dir` are left alone, [`validate_dedicated`] reports them.
+///
+/// # Errors
+/// * `ThermiteError::DepError` if the index doesn't have Northstar
+/// * IO and network errors
+#[cfg(not(target_arch = "wasm32"))]
+pub fn install_northstar_dedicated(game_dir: impl AsRef<Path>) -> Result<NorthstarReport> {
+    let config = config::config();
+    let index = fetch_index_cached(&config)?;
+    let latest = index
+        .iter()
+        .filter(|m| {
+            config
+                .game
+                .is_loader_package(format!("{}-{}", m.author, m.name))
+        })
+        .find_map(|m| m.get_latest())
+        .ok_or_else(|| ThermiteError::DepError {
+            name: config.game.loader_package.clone(),
+            suggestions: vec![],
+        })?;
+
+    let mut archive = vec![];
+    download_verified(&mut archive, latest)?;
+    install_northstar_dedicated_from(Cursor::new(archive), game_dir)
+}
+
+/// Like [`install_northstar_dedicated`], from a release that's already been downloaded
+///
+/// # Errors
+/// * IO errors
+pub fn install_northstar_dedicated_from(
+    zip_file: impl Read + Seek,
+    game_dir: impl AsRef<Path>,
+) -> Result<NorthstarReport> {
+    install_northstar_filtered(zip_file, game_dir.as_ref(), &|rel| !is_client_file(rel))
+}
+
+/// Whether `rel`, relative to the game directory, is Northstar's proxy DLL or one of [`CLIENT_ONLY_FILES`]
+fn is_client_file(rel: &Path) -> bool {
+    rel == Path::new(&config::config().game.proxy_dll)
+        || CLIENT_ONLY_FILES.iter().any(|f| rel.starts_with(f))
+}
+
+/// Something [`validate_dedicated`] found wrong with a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ServerProblem {
+    /// The launcher or the loader's DLL is missing, so Northstar isn't installed
+    MissingNorthstar(PathBuf),
+    /// One of [`DEDICATED_STUBS`] is missing
+    MissingStub(PathBuf),
+    /// A client-only part of Northstar, see [`CLIENT_ONLY_FILES`]
+    ClientOnlyFile(PathBuf),
+    /// An enabled mod that [`is_client_only`]
+    ClientOnlyMod { name: String, path: PathBuf },
+}
+
+impl Display for ServerProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingNorthstar(path) => write!(f, "{} is missing", path.display()),
+            Self::MissingStub(path) => {
+                write!(f, "{} is missing, the server won't start", path.display())
+            }
+            Self::ClientOnlyFile(path) => {
+                write!(f, "{} is only used by clients", path.display())
+            }
+            Self::ClientOnlyMod { name, .. } => {
+                write!(f, "{name} only runs on clients but is enabled")
+            }
+        }
+    }
+}
+
+/// Checks that `game_dir` is set up as a dedicated server, an empty list meaning it is
+///
+/// Northstar's launcher, its DLL and the [`DEDICATED_STUBS`] have to be there, while client-only parts of
+/// Northstar and enabled mods that [`is_client_only`] are reported so they can be removed. Core mods are
+/// covered by [`CLIENT_ONLY_FILES`] instead.
+///
+/// # Errors
+/// * IO errors
+/// * Improperly formatted JSON files
+pub fn validate_dedicated(game_dir: impl AsRef<Path>) -> Result<Vec<ServerProblem>> {
+    let game_dir = game_dir.as_ref();
+    let game = &config::config().game;
+    let fs = vfs::current();
+    let mut problems = vec![];
+
+    for rel in [&game.launcher, &game.loader_dll] {
+        let path = game_dir.join(rel);
+        if !fs.exists(&path)? {
+            problems.push(ServerProblem::MissingNorthstar(path));
+        }
+    }
+    for rel in DEDICATED_STUBS {
+        let path = game_dir.join(rel);
+        if !fs.exists(&path)? {
+            problems.push(ServerProblem::MissingStub(path));
+        }
+    }
+    for rel in CLIENT_ONLY_FILES.iter().chain([&game.proxy_dll.as_str()]) {
+        let path = game_dir.join(rel);
+        if fs.exists(&path)? {
+            problems.push(ServerProblem::ClientOnlyFile(path));
+        }
+    }
+
+    let profile = game_dir.join(&game.profile);
+    let enabled = get_enabled_mods(&profile).ok();
+    for dir in ["packages", "mods"].map(|d| profile.join(d)) {
+        if !fs.exists(&dir)? {
+            continue;
+        }
+        for m in find_mods(&dir)? {
+            let name = &m.mod_json.name;
+            let disabled = enabled.as_ref().and_then(|e| e.get(name)) == Some(false);
+            if !game.is_core_mod(name) && !disabled && is_client_only(&m.mod_json) {
+                problems.push(ServerProblem::ClientOnlyMod {
+                    name: name.clone(),
+                    path: m.path,
+                });
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Writes `config` to the game's `autoexec_ns_server.cfg`, returning its path
+///
+/// # Errors
+/// * IO errors
+pub fn write_server_config(game_dir: impl AsRef<Path>, config: &ServerConfig) -> Result<PathBuf> {
+    let path = game_dir.as_ref().join(SERVER_CFG);
+    let fs = vfs::current();
+    if let Some(parent) = path.parent() {
+        fs.create_dir_all(parent)?;
+    }
+    debug!("Writing server config to {}", path.display());
+    fs.write(&path, config.to_cfg().as_bytes())?;
+    Ok(path)
+}
+
+/// Removes every package in `manager`'s profile whose mods are all client-only, returning their keys
+///
+/// # Errors
+/// * IO errors
+/// * Improperly formatted JSON files
+pub fn prune_client_only(manager: &mut ModManager) -> Result<Vec<String>> {
+    let mut packages: BTreeMap<String, bool> = BTreeMap::new();
+    for m in manager.list()? {
+        let client_only = packages
+            .entry(format!("{}-{}", m.author, m.manifest.name))
+            .or_insert(true);
+        *client_only &= is_client_only(&m.mod_json);
+    }
+
+    let mut pruned = vec![];
+    for (key, client_only) in packages {
+        if client_only {
+            debug!("Removing client-only package {key}");
+            manager.remove(&key)?;
+            pruned.push(key);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Whether a mod has scripts and none of them run on the server
+///
+/// Mods without scripts aren't client-only, they can carry maps or playlists the server needs.
+#[must_use]
+pub fn is_client_only(mod_json: &ModJSON) -> bool {
+    !mod_json.scripts.is_empty() && !mod_json.scripts.iter().any(runs_on_server)
+}
+
+/// Whether a `Scripts` entry's `RunOn` condition includes the server VM
+fn runs_on_server(script: &Value) -> bool {
+    script
+        .get("RunOn")
+        .and_then(Value::as_str)
+        .is_some_and(|run_on| {
+            run_on
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|context| context.eq_ignore_ascii_case("SERVER"))
+        })
+}
+
+/// Whether `rel`, a path inside a mod's directory, is only loaded by clients
+///
+/// That's textures, custom audio, UI resources and scripts that never run on the server. `mod.json` is left
+/// alone, dedicated servers don't start the client VMs so the missing scripts are never looked for.
+#[must_use]
+pub fn is_client_asset(mod_json: &ModJSON, rel: &Path) -> bool {
+    if CLIENT_DIRS.iter().any(|dir| rel.starts_with(dir)) {
+        return true;
+    }
+    let Ok(script) = rel.strip_prefix("mod/scripts/vscripts") else {
+        return false;
+    };
+
+    let mut entries = mod_json
+        .scripts
+        .iter()
+        .filter(|s| {
+            s.get("Path")
+                .and_then(Value::as_str)
+                .is_some_and(|p| Path::new(p) == script)
+        })
+        .peekable();
+    entries.peek().is_some() && entries.all(|s| !runs_on_server(s))
+}
+
+/// Names of the entries in a package archive [`is_client_asset`] matches, for installs to skip
+pub(crate) fn client_asset_entries(archive: &mut ZipArchive<impl Read + Seek>) -> BTreeSet<String> {
+    let names = archive
+        .file_names()
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    let mut mods = vec![];
+    for name in names.iter().filter(|n| n.ends_with("/mod.json")) {
+        let Some(dir) = Path::new(name).parent() else {
+            continue;
+        };
+        let mut raw = String::new();
+        if archive
+            .by_name(name)
+            .map(|mut f| f.read_to_string(&mut raw))
+            .is_err()
+        {
+            continue;
+        }
+        match json5::from_str::<ModJSON>(&raw) {
+            Ok(mod_json) => mods.push((dir.to_path_buf(), mod_json)),
+            Err(e) => warn!("Unable to parse {name}, keeping its client assets: {e}"),
+        }
+    }
+
+    names
+        .into_iter()
+        .filter(|name| {
+            mods.iter().any(|(dir, mod_json)| {
+                Path::new(name)
+                    .strip_prefix(dir)
+                    .is_ok_and(|rel| is_client_asset(mod_json, rel))
+            })
+        })
+        .collect()
+}
+
+/// Deletes the client-only files of every mod in an installed package, returning how many were removed
+///
+/// Installs already skip these files when `ThermiteConfig::strip_client_assets` is set, this is for packages
+/// installed without it.
+///
+/// # Errors
+/// * IO errors
+/// * Improperly formatted `mod.json` files
+pub fn strip_client_assets(package_dir: impl AsRef<Path>) -> Result<usize> {
+    let fs = vfs::current();
+    let mods_dir = package_dir.as_ref().join("mods");
+    if !fs.exists(&mods_dir)? {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for dir in fs.read_dir(&mods_dir)?.into_iter().filter(|e| e.is_dir) {
+        let path = dir.path.join("mod.json");
+        if !fs.exists(&path)? {
+            continue;
+        }
+        let mod_json: ModJSON = json5::from_str(&fs.read_to_string(&path)?)
+            .map_err(|e| ThermiteError::invalid_mod_json(&path, e))?;
+        removed += strip_dir(&mod_json, &dir.path, &dir.path)?;
+    }
+    Ok(removed)
+}
+
+fn strip_dir(mod_json: &ModJSON, mod_dir: &Path, dir: &Path) -> Result<usize> {
+    let fs = vfs::current();
+    let mut removed = 0;
+    for entry in fs.read_dir(dir)? {
+        let rel = entry.path.strip_prefix(mod_dir).unwrap_or(&entry.path);
+        if is_client_asset(mod_json, rel) {
+            debug!("Removing client asset {}", entry.path.display());
+            if entry.is_dir {
+                removed += count_files(&entry.path)?;
+                fs.remove_dir_all(&entry.path)?;
+            } else {
+                removed += 1;
+                fs.remove_file(&entry.path)?;
+            }
+        } else if entry.is_dir {
+            removed += strip_dir(mod_json, mod_dir, &entry.path)?;
+        }
+    }
+    Ok(removed)
+}
+
+fn count_files(dir: &Path) -> Result<usize> {
+    let mut count = 0;
+    for entry in vfs::current().read_dir(dir)? {
+        count += if entry.is_dir {
+            count_files(&entry.path)?
+        } else {
+            1
+        };
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io::Cursor};
+
+    use serde_json::json;
+
+    use crate::{
+        config::ThermiteConfig,
+        core::{
+            manage::{install_mod, install_with_config},
+            utils::TempDir,
+        },
+        manager::{ModManager, DEFAULT_PROFILE},
+        test_util::{mod_archive_with_scripts, MockPackage, MockServer},
+    };
+
+    use super::{
+        install_northstar_dedicated_from, provision, strip_client_assets, validate_dedicated,
+        ServerConfig, ServerProblem, CLIENT_ONLY_FILES, DEDICATED_STUBS, SERVER_CFG,
+    };
+
+    const NORTHSTAR: &[u8] = include_bytes!("core/test_media/northstar.zip");
+
+    #[test]
+    fn provision_server() {
+        let server = MockServer::start().expect("start mock server");
+        server.add_package(MockPackage::new("Foo", "Gamemode", "1.0.0"));
+        server.add_package(MockPackage::new("Foo", "Hud", "1.0.0").with_archive(
+            mod_archive_with_scripts(
+                "Hud",
+                "1.0.0",
+                json!([{ "Path": "hud.nut", "RunOn": "CLIENT && MP" }]),
+                &[],
+            ),
+        ));
+
+        let dir = TempDir::create("./test_provision_server").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            index_url: server.index_url(),
+            ..Default::default()
+        };
+        let mut manager =
+            ModManager::with_config(dir.join("server"), DEFAULT_PROFILE, config).expect("manager");
+
+        let server_config = ServerConfig {
+            name: "Test \"Server\"".into(),
+            tick_rate: 30,
+            con_vars: [("sv_cheats".to_owned(), "1".to_owned())].into(),
+            ..Default::default()
+        };
+        let report = provision(
+            &mut manager,
+            Cursor::new(NORTHSTAR),
+            &["Foo-Gamemode", "Foo-Hud"],
+            &server_config,
+        )
+        .expect("provision");
+
+        assert_eq!(report.pruned, ["Foo-Hud"]);
+        assert!(manager.lockfile().packages.contains_key("Foo-Gamemode"));
+        assert!(!manager.lockfile().packages.contains_key("Foo-Hud"));
+
+        let cfg = fs::read_to_string(dir.join("server").join(SERVER_CFG)).unwrap();
+        assert_eq!(report.config_path, dir.join("server").join(SERVER_CFG));
+        assert!(cfg.contains("ns_server_name \"Test 'Server'\"\n"));
+        assert!(cfg.contains("base_tickinterval_mp 0.033333333\n"));
+        assert!(cfg.contains("sv_max_snapshots_multiplayer 300\n"));
+        assert!(cfg.ends_with("sv_cheats \"1\"\n"));
+    }
+
+    #[test]
+    fn client_assets() {
+        let archive = mod_archive_with_scripts(
+            "Skins",
+            "1.0.0",
+            json!([
+                { "Path": "gamemode.nut", "RunOn": "SERVER && MP" },
+                { "Path": "shared.nut", "RunOn": "CLIENT || SERVER" },
+                { "Path": "hud.nut", "RunOn": "CLIENT && MP" },
+            ]),
+            &[
+                "mod/scripts/vscripts/gamemode.nut",
+                "mod/scripts/vscripts/shared.nut",
+                "mod/scripts/vscripts/hud.nut",
+                "mod/materials/skin.vtf",
+                "mod/maps/mp_box.ent",
+                "audio/kill.json",
+            ],
+        );
+        let kept = [
+            "mod.json",
+            "mod/scripts/vscripts/gamemode.nut",
+            "mod/scripts/vscripts/shared.nut",
+            "mod/maps/mp_box.ent",
+        ];
+        let stripped = [
+            "mod/scripts/vscripts/hud.nut",
+            "mod/materials/skin.vtf",
+            "audio/kill.json",
+        ];
+
+        let dir = TempDir::create("./test_client_assets").expect("Unable to create temp dir");
+        let config = ThermiteConfig {
+            strip_client_assets: true,
+            ..Default::default()
+        };
+        let report = install_with_config(
+            "Foo-Skins-1.0.0",
+            Cursor::new(archive.clone()),
+            dir.join("server"),
+            |_| Ok(()),
+            &config,
+        )
+        .expect("install");
+        assert_eq!(report.files_written, kept.len() + 1);
+        let mod_dir = report.path.join("mods").join("Skins");
+        assert!(kept.iter().all(|p| mod_dir.join(p).exists()));
+        assert!(!stripped.iter().any(|p| mod_dir.join(p).exists()));
+
+        // packages installed normally can be stripped afterwards
+        let report =
+            install_mod("Foo-Skins-1.0.0", Cursor::new(archive), dir.join("client")).unwrap();
+        assert_eq!(strip_client_assets(&report.path).unwrap(), stripped.len());
+        let mod_dir = report.path.join("mods").join("Skins");
+        assert!(kept.iter().all(|p| mod_dir.join(p).exists()));
+        assert!(!stripped.iter().any(|p| mod_dir.join(p).exists()));
+        assert!(!mod_dir.join("audio").exists());
+    }
+
+    #[test]
+    fn dedicated_install() {
+        let dir = TempDir::create("./test_dedicated_install").expect("Unable to create temp dir");
+        install_northstar_dedicated_from(Cursor::new(NORTHSTAR), &*dir).expect("install");
+        assert!(dir.join("NorthstarLauncher.exe").exists());
+        assert!(dir
+            .join("R2Northstar/mods/Northstar.CustomServers")
+            .exists());
+        assert!(DEDICATED_STUBS.iter().all(|f| dir.join(f).exists()));
+        assert!(!CLIENT_ONLY_FILES.iter().any(|f| dir.join(f).exists()));
+        assert!(!dir.join("bin/x64_retail/wsock32.dll").exists());
+        assert_eq!(validate_dedicated(&*dir).unwrap(), []);
+
+        let hud = mod_archive_with_scripts(
+            "Hud",
+            "1.0.0",
+            json!([{ "Path": "hud.nut", "RunOn": "CLIENT && MP" }]),
+            &[],
+        );
+        install_mod(
+            "Foo-Hud-1.0.0",
+            Cursor::new(hud),
+            dir.join("R2Northstar/packages"),
+        )
+        .unwrap();
+        fs::write(dir.join("bin/x64_retail/wsock32.dll"), "proxy").unwrap();
+        fs::remove_file(dir.join(DEDICATED_STUBS[0])).unwrap();
+
+        let problems = validate_dedicated(&*dir).unwrap();
+        assert_eq!(problems.len(), 3);
+        assert_eq!(
+            problems[0],
+            ServerProblem::MissingStub(dir.join(DEDICATED_STUBS[0]))
+        );
+        assert_eq!(
+            problems[1],
+            ServerProblem::ClientOnlyFile(dir.join("bin/x64_retail/wsock32.dll"))
+        );
+        assert!(matches!(
+            &problems[2],
+            ServerProblem::ClientOnlyMod { name, .. } if name == "Mock.Hud"
+        ));
+    }
+}
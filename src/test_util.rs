@@ -0,0 +1,583 @@
+//! Test helpers for code built on thermite. Requires the `test-util` feature
+//!
+//! [`MockServer`] is a tiny HTTP server that serves a Thunderstore-style package index and package
+//! archives from memory, with optional failure injection:
+//!
+//! ```no_run
+//! use thermite::test_util::{MockPackage, MockServer};
+//!
+//! let server = MockServer::start().unwrap();
+//! server.add_package(MockPackage::new("Author", "Mod", "1.0.0"));
+//! // point `ThermiteConfig::index_url` at `server.index_url()` and use thermite as normal
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, BufReader, Cursor, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::verify::{encode_hash, sha256};
+
+/// Path the package index is served from, matching Thunderstore
+pub const INDEX_PATH: &str = "/c/northstar/api/v1/package/";
+
+/// A package served by a [`MockServer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockPackage {
+    pub author: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    /// Thunderstore dependency strings, e.g. `Author-Mod-1.0.0`
+    pub dependencies: Vec<String>,
+    /// The zip file served for this package. Defaults to [`mod_archive`]
+    pub archive: Vec<u8>,
+    /// Served as Thunderstore's `is_deprecated` for the whole package
+    pub deprecated: bool,
+}
+
+impl MockPackage {
+    #[must_use]
+    pub fn new(
+        author: impl Into<String>,
+        name: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        let author = author.into();
+        let name = name.into();
+        let version = version.into();
+        Self {
+            archive: mod_archive(&name, &version),
+            description: format!("{name} by {author}"),
+            dependencies: vec![],
+            author,
+            name,
+            version,
+            deprecated: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn deprecated(mut self) -> Self {
+        self.deprecated = true;
+        self
+    }
+
+    #[must_use]
+    pub fn with_dependencies(mut self, deps: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.dependencies = deps.into_iter().map(Into::into).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn with_archive(mut self, archive: impl Into<Vec<u8>>) -> Self {
+        self.archive = archive.into();
+        self
+    }
+
+    /// `author-name-version`
+    #[must_use]
+    pub fn full_name(&self) -> String {
+        format!("{}-{}-{}", self.author, self.name, self.version)
+    }
+
+    /// The path this package's archive is served from
+    #[must_use]
+    pub fn download_path(&self) -> String {
+        format!(
+            "/package/download/{}/{}/{}/",
+            self.author, self.name, self.version
+        )
+    }
+}
+
+/// Builds a minimal valid package archive containing a `manifest.json` and a single mod
+#[must_use]
+pub fn mod_archive(name: &str, version: &str) -> Vec<u8> {
+    mod_archive_with(name, version, &[])
+}
+
+/// Like [`mod_archive`], with extra `(path, contents)` files, e.g. a plugin in `plugins/`
+#[must_use]
+pub fn mod_archive_with(name: &str, version: &str, extra: &[(&str, &str)]) -> Vec<u8> {
+    let extra = extra
+        .iter()
+        .map(|(p, c)| ((*p).to_owned(), (*c).to_owned()));
+    build_archive(name, version, None, extra)
+}
+
+/// Like [`mod_archive`], with the mod's `Scripts` set, e.g. to tell client and server mods apart, and `extra`
+/// files in the mod's directory
+#[must_use]
+pub fn mod_archive_with_scripts(
+    name: &str,
+    version: &str,
+    scripts: Value,
+    extra: &[&str],
+) -> Vec<u8> {
+    let extra = extra
+        .iter()
+        .map(|p| (format!("mods/{name}/{p}"), "data".to_owned()));
+    build_archive(name, version, Some(scripts), extra)
+}
+
+fn build_archive(
+    name: &str,
+    version: &str,
+    scripts: Option<Value>,
+    extra: impl IntoIterator<Item = (String, String)>,
+) -> Vec<u8> {
+    let manifest = json!({
+        "name": name,
+        "version_number": version,
+        "website_url": "",
+        "description": "Mock package",
+        "dependencies": [],
+    });
+    let mut mod_json = json!({
+        "Name": format!("Mock.{name}"),
+        "Description": "Mock mod",
+        "Version": version,
+        "LoadPriority": 1,
+    });
+    if let Some(scripts) = scripts {
+        mod_json["Scripts"] = scripts;
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    let options = FileOptions::default();
+    let files = [
+        ("manifest.json".to_owned(), manifest.to_string()),
+        (format!("mods/{name}/mod.json"), mod_json.to_string()),
+    ]
+    .into_iter()
+    .chain(extra);
+    for (path, contents) in files {
+        zip.start_file(path, options)
+            .and_then(|()| zip.write_all(contents.as_bytes()).map_err(Into::into))
+            .expect("Writing to memory shouldn't fail");
+    }
+
+    zip.finish()
+        .expect("Writing to memory shouldn't fail")
+        .into_inner()
+}
+
+/// A failure to inject into the next request for a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// Respond with this status code and an empty body
+    Status(u16),
+    /// Close the connection without responding
+    Disconnect,
+    /// Send the headers and only this many bytes of the body before closing the connection
+    Truncate(usize),
+    /// Wait before responding normally
+    Delay(Duration),
+}
+
+/// A request received by a [`MockServer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    body: Vec<u8>,
+    content_type: &'static str,
+    etag: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    packages: Vec<MockPackage>,
+    routes: HashMap<String, Route>,
+    failures: HashMap<String, VecDeque<Failure>>,
+    requests: Vec<RecordedRequest>,
+    index_etag: Option<String>,
+}
+
+/// An HTTP server on localhost that behaves enough like Thunderstore for tests
+///
+/// Shuts down when dropped
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Starts a server on a random port
+    ///
+    /// # Errors
+    /// * Unable to bind to localhost
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let handle = {
+            let state = state.clone();
+            let running = running.clone();
+            thread::spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let state = state.clone();
+                            let addr = addr;
+                            thread::spawn(move || {
+                                // errors here just mean the client went away
+                                let _ = handle_connection(stream, &state, addr);
+                            });
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(5));
+                        }
+                        Err(_) => break,
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            addr,
+            state,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    /// Base URL of the server, without a trailing slash
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Full URL of the package index
+    #[must_use]
+    pub fn index_url(&self) -> String {
+        format!("{}{INDEX_PATH}", self.url())
+    }
+
+    /// Full URL a package's archive is served from
+    #[must_use]
+    pub fn download_url(&self, package: &MockPackage) -> String {
+        format!("{}{}", self.url(), package.download_path())
+    }
+
+    /// Adds a package to the index and serves its archive
+    pub fn add_package(&self, package: MockPackage) {
+        let mut state = self.lock();
+        state.routes.insert(
+            package.download_path(),
+            Route {
+                body: package.archive.clone(),
+                content_type: "application/zip",
+                etag: None,
+            },
+        );
+        state.packages.push(package);
+        state.index_etag = None;
+    }
+
+    /// Serves arbitrary bytes from `path`
+    pub fn serve(&self, path: impl Into<String>, body: impl Into<Vec<u8>>) {
+        self.lock().routes.insert(
+            path.into(),
+            Route {
+                body: body.into(),
+                content_type: "application/octet-stream",
+                etag: None,
+            },
+        );
+    }
+
+    /// Queues a failure for the next request to `path`. Multiple failures are used in order
+    pub fn fail_next(&self, path: impl Into<String>, failure: Failure) {
+        self.lock()
+            .failures
+            .entry(path.into())
+            .or_default()
+            .push_back(failure);
+    }
+
+    /// Every request received so far
+    #[must_use]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    match state.lock() {
+        Ok(s) => s,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn index_json(state: &State, base: &str) -> Vec<u8> {
+    // group versions by package, newest added first like Thunderstore
+    let mut listings: Vec<(String, String, Vec<&MockPackage>)> = vec![];
+    for p in state.packages.iter().rev() {
+        if let Some(l) = listings
+            .iter_mut()
+            .find(|(a, n, _)| *a == p.author && *n == p.name)
+        {
+            l.2.push(p);
+        } else {
+            listings.push((p.author.clone(), p.name.clone(), vec![p]));
+        }
+    }
+
+    let listings = listings
+        .into_iter()
+        .map(|(author, name, versions)| {
+            json!({
+                "name": name,
+                "full_name": format!("{author}-{name}"),
+                "owner": author,
+                "package_url": format!("{base}/package/{author}/{name}/"),
+                "is_deprecated": versions.iter().any(|v| v.deprecated),
+                "is_pinned": false,
+                "categories": [],
+                "versions": versions.into_iter().map(|v| json!({
+                    "name": v.name,
+                    "full_name": v.full_name(),
+                    "description": v.description,
+                    "version_number": v.version,
+                    "dependencies": v.dependencies,
+                    "download_url": format!("{base}{}", v.download_path()),
+                    "file_size": v.archive.len(),
+                    "sha256": encode_hash(&sha256(&v.archive)),
+                    "downloads": 0,
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_vec(&listings).expect("Serializing the mock index shouldn't fail")
+}
+
+fn handle_connection(stream: TcpStream, state: &Mutex<State>, addr: SocketAddr) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+        }
+    }
+
+    let (failure, route) = {
+        let mut state = lock(state);
+        state.requests.push(RecordedRequest {
+            method,
+            path: path.clone(),
+            headers: headers.clone(),
+        });
+        let failure = state.failures.get_mut(&path).and_then(VecDeque::pop_front);
+
+        let route = if path == INDEX_PATH {
+            let body = index_json(&state, &format!("http://{addr}"));
+            let etag = state
+                .index_etag
+                .get_or_insert_with(|| format!("\"{}-{}\"", state_version(&body), body.len()))
+                .clone();
+            Some(Route {
+                body,
+                content_type: "application/json",
+                etag: Some(etag),
+            })
+        } else {
+            state.routes.get(&path).cloned()
+        };
+        (failure, route)
+    };
+
+    match failure {
+        Some(Failure::Status(code)) => return respond(&mut stream, code, &[], &[]),
+        Some(Failure::Disconnect) => return Ok(()),
+        Some(Failure::Delay(d)) => thread::sleep(d),
+        Some(Failure::Truncate(_)) | None => {}
+    }
+
+    let Some(route) = route else {
+        return respond(&mut stream, 404, &[], b"Not found");
+    };
+
+    if let (Some(etag), Some(sent)) = (&route.etag, headers.get("if-none-match")) {
+        if etag == sent {
+            return respond(&mut stream, 304, &[("ETag", etag.clone())], &[]);
+        }
+    }
+
+    let mut extra = vec![("Content-Type", route.content_type.to_owned())];
+    if let Some(etag) = &route.etag {
+        extra.push(("ETag", etag.clone()));
+    }
+    extra.push(("Accept-Ranges", "bytes".into()));
+
+    let (status, body) = match headers
+        .get("range")
+        .and_then(|r| parse_range(r, route.body.len()))
+    {
+        Some((start, end)) => {
+            extra.push((
+                "Content-Range",
+                format!("bytes {start}-{}/{}", end - 1, route.body.len()),
+            ));
+            (206, &route.body[start..end])
+        }
+        None => (200, route.body.as_slice()),
+    };
+
+    if let Some(Failure::Truncate(n)) = failure {
+        // advertise the full length but stop early
+        write_head(&mut stream, status, &extra, body.len())?;
+        stream.write_all(&body[..n.min(body.len())])?;
+        return stream.flush();
+    }
+
+    respond(&mut stream, status, &extra, body)
+}
+
+/// A cheap content hash so ETags change when the index does
+fn state_version(body: &[u8]) -> u64 {
+    body.iter().fold(0xcbf2_9ce4_8422_2325, |h: u64, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Parses a `bytes=start-` or `bytes=start-end` header into a half-open range
+fn parse_range(header: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = header.strip_prefix("bytes=")?.split_once('-')?;
+    let start = start.parse::<usize>().ok()?;
+    let end = if end.is_empty() {
+        len
+    } else {
+        end.parse::<usize>().ok()?.saturating_add(1).min(len)
+    };
+    (start < end).then_some((start, end))
+}
+
+fn write_head(
+    stream: &mut impl Write,
+    status: u16,
+    headers: &[(&str, String)],
+    len: usize,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status} Mock\r\nContent-Length: {len}\r\nConnection: close\r\n"
+    )?;
+    for (name, value) in headers {
+        write!(stream, "{name}: {value}\r\n")?;
+    }
+    write!(stream, "\r\n")
+}
+
+fn respond(
+    stream: &mut impl Write,
+    status: u16,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> io::Result<()> {
+    write_head(stream, status, headers, body.len())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// Reads everything from `reader`, for comparing downloads in tests
+///
+/// # Errors
+/// * IO errors
+pub fn read_all(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Failure, MockPackage, MockServer};
+
+    #[test]
+    fn serves_index_and_packages() {
+        let server = MockServer::start().expect("start server");
+        let package = MockPackage::new("Author", "Mod", "1.0.0");
+        server.add_package(package.clone());
+
+        let index = ureq::get(&server.index_url())
+            .call()
+            .expect("index")
+            .into_string()
+            .unwrap();
+        assert!(index.contains("Author-Mod-1.0.0"));
+
+        let res = ureq::get(&server.download_url(&package))
+            .call()
+            .expect("download");
+        let body = super::read_all(res.into_reader()).unwrap();
+        assert_eq!(body, package.archive);
+    }
+
+    #[test]
+    fn injects_failures() {
+        let server = MockServer::start().expect("start server");
+        server.serve("/file", "hello");
+        server.fail_next("/file", Failure::Status(503));
+
+        let first = ureq::get(&format!("{}/file", server.url())).call();
+        assert!(matches!(first, Err(ureq::Error::Status(503, _))));
+
+        let second = ureq::get(&format!("{}/file", server.url()))
+            .call()
+            .expect("second request should succeed");
+        assert_eq!(second.into_string().unwrap(), "hello");
+        assert_eq!(server.requests().len(), 2);
+    }
+}
@@ -0,0 +1,50 @@
+//! Clock used for timeouts and durations
+//!
+//! `std::time::Instant::now` panics on `wasm32-unknown-unknown`, so there this is a stand-in without a clock:
+//! elapsed times are always zero and deadlines never pass. Frontends on the web should rely on their
+//! HTTP backend's own timeouts instead.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use clockless::Instant;
+
+/// Blocks the current thread for `duration`, returning right away where there's no clock
+pub(crate) fn sleep(duration: std::time::Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::sleep(duration);
+    #[cfg(target_arch = "wasm32")]
+    let _ = duration;
+}
+
+#[cfg(target_arch = "wasm32")]
+mod clockless {
+    use std::{ops::Sub, time::Duration};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub(crate) struct Instant;
+
+    impl Instant {
+        pub(crate) const fn now() -> Self {
+            Self
+        }
+
+        pub(crate) const fn elapsed(&self) -> Duration {
+            Duration::ZERO
+        }
+
+        /// Always `None`, so deadlines based on this are never set
+        pub(crate) const fn checked_add(&self, _: Duration) -> Option<Self> {
+            None
+        }
+    }
+
+    impl Sub for Instant {
+        type Output = Duration;
+
+        fn sub(self, _: Self) -> Duration {
+            Duration::ZERO
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Verification of downloaded packages against trusted hashes
+//!
+//! A [`Verifier`] collects SHA-256 hashes of known-good package archives from one or more trust sources:
+//! hashes added directly, or a manifest signed with a publisher's Ed25519 key. Set it as
+//! `ThermiteConfig::verifier` to have [`ModManager`](crate::manager::ModManager) check every archive before
+//! installing it.
+//!
+//! A signed manifest is a JSON object mapping `author-name-X.Y.Z` to the hex encoded SHA-256 of the package's
+//! archive, e.g. `{"packages": {"Foo-Bar-1.0.0": "9f86d0..."}}`, with a detached 64 byte signature over its
+//! exact bytes.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use ring::{
+    digest::{digest, Context, SHA256},
+    signature::{UnparsedPublicKey, ED25519},
+};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::error::{Result, ThermiteError};
+
+/// The outcome of a successful verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The archive matched a trusted hash
+    Verified,
+    /// No trusted hash is known for the package and the verifier allows unlisted packages
+    Unlisted,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verifier {
+    /// Trusted SHA-256 hashes keyed by `author-name-X.Y.Z`
+    hashes: BTreeMap<String, [u8; 32]>,
+    allow_unlisted: bool,
+}
+
+#[derive(Deserialize)]
+struct SignedManifest {
+    packages: BTreeMap<String, String>,
+}
+
+impl Verifier {
+    /// Creates a verifier that rejects every package without a trusted hash
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets packages without a trusted hash through instead of rejecting them
+    #[must_use]
+    pub const fn allow_unlisted(mut self, allow: bool) -> Self {
+        self.allow_unlisted = allow;
+        self
+    }
+
+    /// Trusts a hex encoded SHA-256 hash for a package
+    ///
+    /// # Errors
+    /// * `ThermiteError::InvalidHash` if `hash` isn't 64 hex characters
+    pub fn trust_hash(
+        &mut self,
+        full_name: impl Into<String>,
+        hash: impl AsRef<str>,
+    ) -> Result<()> {
+        let full_name = full_name.into();
+        let hash = decode_hash(hash.as_ref())
+            .ok_or_else(|| ThermiteError::InvalidHash(full_name.clone()))?;
+        self.hashes.insert(full_name, hash);
+        Ok(())
+    }
+
+    /// Trusts every hash in a manifest after checking its signature with `public_key`
+    ///
+    /// # Errors
+    /// * `ThermiteError::InvalidSignature` if the signature doesn't match the manifest and key
+    /// * The manifest isn't formatted properly
+    pub fn trust_signed_manifest(
+        &mut self,
+        manifest: &[u8],
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<()> {
+        UnparsedPublicKey::new(&ED25519, public_key)
+            .verify(manifest, signature)
+            .map_err(|_| ThermiteError::InvalidSignature)?;
+
+        let parsed: SignedManifest = serde_json::from_slice(manifest)?;
+        for (full_name, hash) in parsed.packages {
+            self.trust_hash(full_name, hash)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if a trusted hash is known for the package
+    #[must_use]
+    pub fn is_listed(&self, full_name: impl AsRef<str>) -> bool {
+        self.hashes.contains_key(full_name.as_ref())
+    }
+
+    /// Checks a package archive against the trusted hashes
+    ///
+    /// # Errors
+    /// * `ThermiteError::HashMismatch` if the archive doesn't match the trusted hash
+    /// * `ThermiteError::UntrustedPackage` if no hash is known and unlisted packages aren't allowed
+    pub fn verify(&self, full_name: impl AsRef<str>, archive: &[u8]) -> Result<Verification> {
+        let full_name = full_name.as_ref();
+        let Some(expected) = self.hashes.get(full_name) else {
+            if self.allow_unlisted {
+                warn!("No trusted hash for {full_name}, installing unverified");
+                return Ok(Verification::Unlisted);
+            }
+            return Err(ThermiteError::UntrustedPackage(full_name.into()));
+        };
+
+        let actual = sha256(archive);
+        if actual == *expected {
+            Ok(Verification::Verified)
+        } else {
+            Err(ThermiteError::HashMismatch {
+                package: full_name.into(),
+                expected: encode_hash(expected),
+                actual: encode_hash(&actual),
+            })
+        }
+    }
+}
+
+/// Hashes everything written through it with SHA-256
+pub(crate) struct HashingWriter<W> {
+    inner: W,
+    context: Context,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            context: Context::new(&SHA256),
+        }
+    }
+
+    /// The SHA-256 of everything written so far
+    pub(crate) fn finish(self) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(self.context.finish().as_ref());
+        hash
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.context.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the SHA-256 of `data`
+#[must_use]
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(digest(&SHA256, data).as_ref());
+    hash
+}
+
+/// Lowercase hex encoding of a hash
+#[must_use]
+pub fn encode_hash(hash: &[u8]) -> String {
+    hash.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hash(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut hash = [0; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod test {
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+
+    use super::{encode_hash, sha256, Verification, Verifier};
+    use crate::error::ThermiteError;
+
+    const ARCHIVE: &[u8] = b"not really a zip";
+
+    #[test]
+    fn known_hashes() {
+        let mut verifier = Verifier::new();
+        verifier
+            .trust_hash("Foo-Bar-1.0.0", encode_hash(&sha256(ARCHIVE)))
+            .unwrap();
+
+        assert_eq!(
+            verifier.verify("Foo-Bar-1.0.0", ARCHIVE).unwrap(),
+            Verification::Verified
+        );
+        assert!(matches!(
+            verifier.verify("Foo-Bar-1.0.0", b"tampered"),
+            Err(ThermiteError::HashMismatch { .. })
+        ));
+        assert!(matches!(
+            verifier.verify("Foo-Baz-1.0.0", ARCHIVE),
+            Err(ThermiteError::UntrustedPackage(_))
+        ));
+
+        let verifier = verifier.allow_unlisted(true);
+        assert_eq!(
+            verifier.verify("Foo-Baz-1.0.0", ARCHIVE).unwrap(),
+            Verification::Unlisted
+        );
+    }
+
+    #[test]
+    fn signed_manifest() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let manifest = format!(
+            r#"{{"packages": {{"Foo-Bar-1.0.0": "{}"}}}}"#,
+            encode_hash(&sha256(ARCHIVE))
+        );
+        let signature = key.sign(manifest.as_bytes());
+
+        let mut verifier = Verifier::new();
+        assert!(matches!(
+            verifier.trust_signed_manifest(
+                b"{\"packages\": {}}",
+                signature.as_ref(),
+                key.public_key().as_ref()
+            ),
+            Err(ThermiteError::InvalidSignature)
+        ));
+
+        verifier
+            .trust_signed_manifest(
+                manifest.as_bytes(),
+                signature.as_ref(),
+                key.public_key().as_ref(),
+            )
+            .expect("valid signature");
+        assert!(verifier.is_listed("Foo-Bar-1.0.0"));
+        assert_eq!(
+            verifier.verify("Foo-Bar-1.0.0", ARCHIVE).unwrap(),
+            Verification::Verified
+        );
+    }
+}